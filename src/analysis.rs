@@ -0,0 +1,543 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt::Write;
+
+use crate::bus::Bus;
+use crate::disassembler::format_instruction;
+use crate::instruction_info::ControlFlow;
+use crate::processor::{AddressingMode, Instruction};
+use crate::symbols::SymbolTable;
+
+// Interrupt vectors, in the order the entry points are listed
+const VECTORS: [(&str, u16); 3] = [("reset", 0xFFFC), ("nmi", 0xFFFA), ("irq", 0xFFFE)];
+
+/// Data bytes shown on one `.byte` line of the annotated disassembly
+const DATA_BYTES_PER_LINE: usize = 8;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ByteKind {
+    Data,
+    Opcode,
+    Operand,
+}
+
+/**
+ * Instructions always executed one after the other, entered only at the first one
+ */
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BasicBlock {
+    pub start: u16,
+    /// Address following the last instruction
+    pub end: u16,
+    /// Addresses of the instructions
+    pub instructions: Vec<u16>,
+    /// Blocks execution may continue at, branch target first
+    pub successors: Vec<u16>,
+    /// Subroutines called from the block
+    pub calls: Vec<u16>,
+}
+
+/**
+ * Code found by following the execution paths from the interrupt vectors
+ *
+ * Decoding is recursive descent: from each entry point, instructions are decoded and the branches,
+ * `JMP`s and `JSR`s are followed (subroutines are assumed to return). Paths stop at returns, `BRK`,
+ * indirect jumps, illegal opcodes and instructions overlapping already decoded ones.
+ * Everything never reached is considered data.
+ *
+ * The analysis works on a copy of the address space, taken from a `Bus` or given as a plain slice.
+ */
+pub struct Analysis {
+    /// Copy of the address space the analysis was made on
+    image: Vec<u8>,
+    kinds: Vec<ByteKind>,
+    entry_points: Vec<(String, u16)>,
+    blocks: BTreeMap<u16, BasicBlock>,
+    /// Entry points and subroutines along with the subroutines they call
+    call_graph: BTreeMap<u16, BTreeSet<u16>>,
+    /// Addresses where decoding stopped as the instruction would overlap another one
+    conflicts: BTreeSet<u16>,
+}
+
+// Constructor like implementation
+impl Analysis {
+    /**
+     * Analyses the code reachable from the reset, NMI and IRQ vectors of the bus
+     *
+     * The address space is peeked, so the devices on the bus aren't disturbed.
+     */
+    pub fn new(bus: &Bus) -> Self {
+        let memory: Vec<u8> = (0..=0xFFFF_u16).map(|address| bus.peek(address)).collect();
+        Self::of_address_space(&memory)
+    }
+
+    /**
+     * Analyses the code reachable from the reset, NMI and IRQ vectors of the address space
+     *
+     # Arguments
+
+     * `memory` - The whole address space, 0x10000 bytes
+     */
+    pub fn of_address_space(memory: &[u8]) -> Self {
+        let entry_points: Vec<(String, u16)> = VECTORS
+            .iter()
+            .map(|(name, vector)| {
+                let address = u16::from_le_bytes([memory[*vector as usize], memory[vector.wrapping_add(1) as usize]]);
+                (name.to_string(), address)
+            })
+            .collect();
+
+        Self::with_entry_points(memory, &entry_points)
+    }
+
+    /**
+     * Analyses a ROM image placed at `base`, the rest of the address space being zeros
+     *
+     * The image is expected to end with the vectors (i.e. to be mapped up to 0xFFFF).
+     */
+    pub fn of_image(image: &[u8], base: u16) -> Self {
+        let mut memory = vec![0_u8; 0x10000];
+        for (offset, byte) in image.iter().take(0x10000 - base as usize).enumerate() {
+            memory[base as usize + offset] = *byte;
+        }
+
+        Self::of_address_space(&memory)
+    }
+
+    /**
+     * Analyses the code reachable from the given named addresses
+     *
+     # Arguments
+
+     * `memory` - The whole address space, 0x10000 bytes
+     * `entry_points` - Names and addresses to start decoding from
+     */
+    pub fn with_entry_points(memory: &[u8], entry_points: &[(String, u16)]) -> Self {
+        let mut analysis = Self {
+            image: memory.to_vec(),
+            kinds: vec![ByteKind::Data; 0x10000],
+            entry_points: entry_points.to_vec(),
+            blocks: BTreeMap::new(),
+            call_graph: BTreeMap::new(),
+            conflicts: BTreeSet::new(),
+        };
+
+        let leaders = analysis.decode();
+        analysis.build_blocks(&leaders);
+        analysis.build_call_graph();
+        analysis
+    }
+}
+
+// Decoding implementation
+impl Analysis {
+    fn instruction_at(&self, address: u16) -> &'static Instruction {
+        Instruction::decode(self.image[address as usize])
+    }
+
+    fn operand_at(&self, address: u16) -> u16 {
+        u16::from_le_bytes([self.image[address.wrapping_add(1) as usize], self.image[address.wrapping_add(2) as usize]])
+    }
+
+    /**
+     * Addresses the instruction may continue at, other than the next one, along with whether it may continue
+     * with the next one
+     */
+    fn targets(&self, address: u16) -> (Option<u16>, bool) {
+        let instruction = self.instruction_at(address);
+        let operand = self.operand_at(address);
+
+        match instruction.control_flow() {
+            ControlFlow::Sequential => (None, true),
+            ControlFlow::Branch => (Some(address.wrapping_add(2).wrapping_add(operand as u8 as i8 as u16)), true),
+            ControlFlow::Jump if instruction.addressing_mode_enum == AddressingMode::ABS => (Some(operand), false),
+            ControlFlow::Call => (Some(operand), true),
+            ControlFlow::Jump | ControlFlow::Return | ControlFlow::Interrupt | ControlFlow::Halt => (None, false),
+        }
+    }
+
+    /**
+     * Whether or not an instruction can be decoded at the address, marking it as code if so
+     */
+    fn mark_instruction(&mut self, address: u16) -> bool {
+        let instruction = self.instruction_at(address);
+        if instruction.undocumented && instruction.control_flow() != ControlFlow::Halt {
+            return false;
+        }
+
+        let length = instruction.length as usize;
+        if address as usize + length > 0x10000 {
+            return false;
+        }
+        if self.kinds[address as usize..address as usize + length].iter().any(|kind| *kind != ByteKind::Data) {
+            self.conflicts.insert(address);
+            return false;
+        }
+
+        self.kinds[address as usize] = ByteKind::Opcode;
+        for kind in &mut self.kinds[address as usize + 1..address as usize + length] {
+            *kind = ByteKind::Operand;
+        }
+        true
+    }
+
+    /**
+     * Marks the code bytes, returns the addresses starting a block (entry points and targets)
+     */
+    fn decode(&mut self) -> BTreeSet<u16> {
+        let mut leaders = BTreeSet::new();
+        let mut pending: Vec<u16> = self.entry_points.iter().map(|(_, address)| *address).collect();
+        leaders.extend(pending.iter().copied());
+
+        while let Some(mut address) = pending.pop() {
+            loop {
+                if self.kinds[address as usize] == ByteKind::Opcode || !self.mark_instruction(address) {
+                    break;
+                }
+
+                let (target, continues) = self.targets(address);
+                if let Some(target) = target {
+                    leaders.insert(target);
+                    pending.push(target);
+                }
+
+                let next = address.wrapping_add(self.instruction_at(address).length as u16);
+                if !continues || next < address {
+                    break;
+                }
+                address = next;
+            }
+        }
+
+        leaders.retain(|address| self.is_code(*address));
+        leaders
+    }
+
+    fn build_blocks(&mut self, leaders: &BTreeSet<u16>) {
+        let mut current: Option<BasicBlock> = None;
+
+        for address in 0..=0xFFFF_u16 {
+            if !self.is_code(address) {
+                continue;
+            }
+
+            let starts_block = leaders.contains(&address) || current.as_ref().is_none_or(|block| block.end != address);
+            if starts_block {
+                if let Some(block) = current.take() {
+                    self.finish_block(block, Some(address));
+                }
+                current = Some(BasicBlock {
+                    start: address,
+                    end: address,
+                    instructions: Vec::new(),
+                    successors: Vec::new(),
+                    calls: Vec::new(),
+                });
+            }
+
+            let block = current.as_mut().unwrap();
+            let instruction = self.instruction_at(address);
+            let (target, continues) = self.targets(address);
+            block.instructions.push(address);
+            block.end = address.wrapping_add(instruction.length as u16);
+
+            match instruction.control_flow() {
+                ControlFlow::Call => block.calls.extend(target.filter(|target| self.is_code(*target))),
+                ControlFlow::Sequential => (),
+                _ => {
+                    block.successors.extend(target.filter(|target| self.is_code(*target)));
+                    let block = current.take().unwrap();
+                    self.finish_block(block, continues.then_some(address.wrapping_add(instruction.length as u16)));
+                }
+            }
+        }
+
+        if let Some(block) = current.take() {
+            self.finish_block(block, None);
+        }
+    }
+
+    /**
+     * Adds the fall through successor (if it is code) and stores the block
+     */
+    fn finish_block(&mut self, mut block: BasicBlock, next: Option<u16>) {
+        let falls_through = match self.instruction_at(*block.instructions.last().unwrap()).control_flow() {
+            ControlFlow::Sequential | ControlFlow::Call => next == Some(block.end),
+            ControlFlow::Branch => true,
+            _ => false,
+        };
+
+        if falls_through && block.end > block.start && self.is_code(block.end) && !block.successors.contains(&block.end) {
+            block.successors.push(block.end);
+        }
+        self.blocks.insert(block.start, block);
+    }
+
+    fn build_call_graph(&mut self) {
+        let mut roots: BTreeSet<u16> = self.entry_points.iter().map(|(_, address)| *address).collect();
+        roots.extend(self.blocks.values().flat_map(|block| block.calls.iter().copied()));
+
+        for root in roots.into_iter().filter(|root| self.blocks.contains_key(root)) {
+            let mut callees = BTreeSet::new();
+            let mut visited = BTreeSet::new();
+            let mut pending = vec![root];
+
+            while let Some(start) = pending.pop() {
+                if !visited.insert(start) {
+                    continue;
+                }
+                if let Some(block) = self.blocks.get(&start) {
+                    callees.extend(block.calls.iter().copied());
+                    pending.extend(block.successors.iter().copied());
+                }
+            }
+
+            self.call_graph.insert(root, callees);
+        }
+    }
+}
+
+impl Analysis {
+    /**
+     * Whether or not the byte is the opcode of a reachable instruction
+     */
+    pub fn is_code(&self, address: u16) -> bool {
+        self.kinds[address as usize] == ByteKind::Opcode
+    }
+
+    /**
+     * Whether or not the byte is part of a reachable instruction, opcode or operand
+     */
+    pub fn is_code_byte(&self, address: u16) -> bool {
+        self.kinds[address as usize] != ByteKind::Data
+    }
+
+    pub fn code_byte_count(&self) -> usize {
+        self.kinds.iter().filter(|kind| **kind != ByteKind::Data).count()
+    }
+
+    pub fn entry_points(&self) -> &[(String, u16)] {
+        &self.entry_points
+    }
+
+    /**
+     * The basic blocks, by start address
+     */
+    pub fn blocks(&self) -> &BTreeMap<u16, BasicBlock> {
+        &self.blocks
+    }
+
+    /**
+     * The entry points and subroutines, each along with the subroutines it calls
+     */
+    pub fn call_graph(&self) -> &BTreeMap<u16, BTreeSet<u16>> {
+        &self.call_graph
+    }
+
+    pub fn conflicts(&self) -> &BTreeSet<u16> {
+        &self.conflicts
+    }
+
+    /**
+     * The given labels completed with the entry point names, `sub_XXXX` for subroutines and `L_XXXX` for the other blocks
+     */
+    pub fn labels(&self, symbols: &SymbolTable) -> SymbolTable {
+        let mut labels = symbols.clone();
+        for (name, address) in &self.entry_points {
+            if labels.name_of(*address).is_none() && labels.address_of(name).is_none() {
+                labels.insert(name, *address);
+            }
+        }
+        for address in self.call_graph.keys() {
+            if labels.name_of(*address).is_none() {
+                labels.insert(&format!("sub_{:04X}", address), *address);
+            }
+        }
+        for address in self.blocks.keys() {
+            if labels.name_of(*address).is_none() {
+                labels.insert(&format!("L_{:04X}", address), *address);
+            }
+        }
+        labels
+    }
+
+    fn format_instruction(&self, address: u16, labels: &SymbolTable) -> String {
+        format_instruction(self.instruction_at(address), address, self.operand_at(address), labels)
+    }
+}
+
+// Exporting implementation
+impl Analysis {
+    /**
+     * Disassembles the code between the addresses (both included) and shows the rest as `.byte` lines
+     */
+    pub fn to_disassembly(&self, symbols: &SymbolTable, start: u16, end: u16) -> String {
+        let labels = self.labels(symbols);
+        let mut output = String::new();
+        let mut address = start as usize;
+
+        while address <= end as usize {
+            if let Some(name) = labels.name_of(address as u16) {
+                let _ = writeln!(output, "{}:", name);
+            }
+
+            if self.is_code(address as u16) {
+                let length = self.instruction_at(address as u16).length as usize;
+                let bytes: Vec<String> = self.image[address..address + length].iter().map(|byte| format!("{:02X}", byte)).collect();
+                let _ = writeln!(output, "{:04X}  {:9} {}", address, bytes.join(" "), self.format_instruction(address as u16, &labels));
+                address += length;
+            } else {
+                // data runs up to the next label or code byte
+                let mut length = 1;
+                while length < DATA_BYTES_PER_LINE
+                    && address + length <= end as usize
+                    && !self.is_code_byte((address + length) as u16)
+                    && labels.name_of((address + length) as u16).is_none()
+                {
+                    length += 1;
+                }
+
+                let bytes: Vec<String> = self.image[address..address + length].iter().map(|byte| format!("${:02X}", byte)).collect();
+                let _ = writeln!(output, "{:04X}  .byte {}", address, bytes.join(", "));
+                address += length;
+            }
+        }
+
+        output
+    }
+
+    /**
+     * Returns the control flow graph in Graphviz DOT, calls are the dashed edges
+     */
+    pub fn to_dot(&self, symbols: &SymbolTable) -> String {
+        let labels = self.labels(symbols);
+        let mut output = String::from("digraph cfg {\n    node [shape=box, fontname=\"monospace\"];\n");
+
+        for block in self.blocks.values() {
+            let mut label = format!("{}:\\l", escape_dot(&labels.format_address(block.start)));
+            for address in &block.instructions {
+                label += &format!("{:04X}  {}\\l", address, escape_dot(&self.format_instruction(*address, &labels)));
+            }
+            let _ = writeln!(output, "    \"{:04X}\" [label=\"{}\"];", block.start, label);
+        }
+
+        for block in self.blocks.values() {
+            for successor in &block.successors {
+                let _ = writeln!(output, "    \"{:04X}\" -> \"{:04X}\";", block.start, successor);
+            }
+            for callee in &block.calls {
+                let _ = writeln!(output, "    \"{:04X}\" -> \"{:04X}\" [style=dashed];", block.start, callee);
+            }
+        }
+
+        output.push_str("}\n");
+        output
+    }
+
+    /**
+     * Returns the call graph in Graphviz DOT
+     */
+    pub fn call_graph_dot(&self, symbols: &SymbolTable) -> String {
+        let labels = self.labels(symbols);
+        let mut output = String::from("digraph calls {\n    node [shape=box, fontname=\"monospace\"];\n");
+
+        for (caller, callees) in &self.call_graph {
+            let _ = writeln!(output, "    \"{:04X}\" [label=\"{}\"];", caller, escape_dot(&labels.format_address(*caller)));
+            for callee in callees {
+                let _ = writeln!(output, "    \"{:04X}\" -> \"{:04X}\";", caller, callee);
+            }
+        }
+
+        output.push_str("}\n");
+        output
+    }
+}
+
+fn escape_dot(text: &str) -> String {
+    text.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    /**
+     * Returns a 32 KB image for 0x8000 with the program at its start and the reset vector pointing to it
+     */
+    fn image_with_program(program: &[u8]) -> Vec<u8> {
+        let mut image = vec![0x00; 0x8000];
+        image[..program.len()].copy_from_slice(program);
+        image[0x7FFC] = 0x00; // reset
+        image[0x7FFD] = 0x80;
+        image[0x7FFA] = 0x0C; // nmi, the RTI
+        image[0x7FFB] = 0x80;
+        image[0x7FFE] = 0x0C; // irq
+        image[0x7FFF] = 0x80;
+        image
+    }
+
+    /**
+     * Whether or not code and data are told apart, with the blocks and calls of the program
+     */
+    #[test]
+    fn blocks_and_calls() {
+        let image = image_with_program(&[
+            0x20, 0x0D, 0x80, // 8000 JSR $800D
+            0xCA,             // 8003 DEX
+            0xD0, 0xFA,       // 8004 BNE $8000
+            0x4C, 0x06, 0x80, // 8006 JMP $8006
+            0xFF, 0xFF, 0xFF, // 8009 data
+            0x40,             // 800C RTI
+            0xE8,             // 800D INX
+            0x60,             // 800E RTS
+        ]);
+        let analysis = Analysis::of_image(&image, 0x8000);
+
+        assert!(analysis.is_code(0x8000) && analysis.is_code_byte(0x8001) && !analysis.is_code(0x8001));
+        assert!(!analysis.is_code_byte(0x8009));
+        assert!(analysis.is_code(0x800C) && analysis.is_code(0x800E));
+        assert_eq!(analysis.code_byte_count(), 12);
+
+        let starts: Vec<u16> = analysis.blocks().keys().copied().collect();
+        assert_eq!(starts, vec![0x8000, 0x8006, 0x800C, 0x800D]);
+        let first = &analysis.blocks()[&0x8000];
+        assert_eq!(first.instructions, vec![0x8000, 0x8003, 0x8004]);
+        assert_eq!(first.successors, vec![0x8000, 0x8006]);
+        assert_eq!(first.calls, vec![0x800D]);
+        assert_eq!(analysis.blocks()[&0x8006].successors, vec![0x8006]);
+
+        assert_eq!(analysis.call_graph()[&0x8000], BTreeSet::from([0x800D]));
+        assert!(analysis.call_graph()[&0x800D].is_empty());
+
+        // NOP, then an undocumented NOP that isn't taken for code
+        let analysis = Analysis::of_image(&image_with_program(&[0xEA, 0x1A]), 0x8000);
+        assert!(analysis.is_code(0x8000) && !analysis.is_code_byte(0x8001));
+    }
+
+    /**
+     * Whether or not the exports show the labels, the instructions and the edges
+     */
+    #[test]
+    fn exports() {
+        let image = image_with_program(&[
+            0x20, 0x05, 0x80, // 8000 JSR $8005
+            0x00,             // 8003 BRK
+            0xAB,             // 8004 data
+            0x60,             // 8005 RTS
+        ]);
+        let analysis = Analysis::of_image(&image, 0x8000);
+        let symbols = SymbolTable::new();
+
+        let disassembly = analysis.to_disassembly(&symbols, 0x8000, 0x8005);
+        assert_eq!(
+            disassembly,
+            "reset:\n8000  20 05 80  JSR sub_8005\n8003  00        BRK\n8004  .byte $AB\nsub_8005:\n8005  60        RTS\n"
+        );
+
+        let dot = analysis.to_dot(&symbols);
+        assert!(dot.contains("\"8000\" -> \"8005\" [style=dashed];"));
+        assert!(dot.contains("8000  JSR sub_8005\\l"));
+
+        assert!(analysis.call_graph_dot(&symbols).contains("\"8000\" -> \"8005\";"));
+    }
+}
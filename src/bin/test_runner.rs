@@ -0,0 +1,71 @@
+use emulator_6502::test_runner::{self, TestReport, TestScript};
+
+use std::env;
+use std::fs;
+use std::process;
+
+/**
+ * Runs 6502 test scripts (TOML, or JSON for `.json` files) headless
+ *
+ # Description
+ * Usage: `6502-test-runner <script>... [--junit <path>] [--tap <path>]`
+ * The results are printed as TAP and optionally written as JUnit XML or TAP. The exit code is 0 when every
+   test passed, 1 when some failed and 2 when a script couldn't be run.
+ */
+fn main() {
+    let args: Vec<String> = env::args().skip(1).collect();
+
+    let mut scripts = Vec::new();
+    let mut junit_path = None;
+    let mut tap_path = None;
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--junit" => junit_path = args.next(),
+            "--tap" => tap_path = args.next(),
+            _ => scripts.push(arg),
+        }
+    }
+
+    if scripts.is_empty() {
+        println!("Usage: 6502-test-runner <script>... [--junit <path>] [--tap <path>]");
+        process::exit(2);
+    }
+
+    let mut reports: Vec<TestReport> = Vec::new();
+    let mut script_failed = false;
+    for path in scripts {
+        match TestScript::load(path).and_then(|script| script.run()) {
+            Ok(report) => reports.push(report),
+            Err(error) => {
+                eprintln!("Failed to run {}: {}", path, error);
+                script_failed = true;
+            }
+        }
+    }
+
+    let tap = test_runner::to_tap(&reports);
+    print!("{}", tap);
+
+    if let Some(path) = tap_path {
+        if let Err(error) = fs::write(path, &tap) {
+            eprintln!("Failed to write the TAP report to {}: {}", path, error);
+        }
+    }
+    if let Some(path) = junit_path {
+        if let Err(error) = fs::write(path, test_runner::to_junit(&reports)) {
+            eprintln!("Failed to write the JUnit report to {}: {}", path, error);
+        }
+    }
+
+    let passed: usize = reports.iter().map(TestReport::passed).sum();
+    let total: usize = reports.iter().map(|report| report.results.len()).sum();
+    eprintln!("{} of {} tests passed", passed, total);
+
+    if script_failed {
+        process::exit(2);
+    }
+    if passed < total {
+        process::exit(1);
+    }
+}
@@ -1,48 +1,223 @@
-use num::traits::int::PrimInt;
+use std::cell::RefCell;
+use std::rc::Rc;
 
+use crate::devices::BusDevice;
+use crate::mapper::{MappedBank, Mapper};
+use crate::profiler::AccessCounters;
 use crate::memory::Memory;
 use crate::rom::Rom;
+use crate::watchpoints::Watchpoints;
+
+/**
+ * A device attached to the bus along with the first address it occupies
+ */
+pub struct MappedDevice {
+    pub start: u16,
+    pub device: Rc<RefCell<dyn BusDevice>>,
+}
+
+impl MappedDevice {
+    /**
+     * Returns the offset of the address inside the device, if the device occupies it
+     */
+    fn offset(&self, address: u16) -> Option<u16> {
+        let offset = address.wrapping_sub(self.start);
+        if address >= self.start && offset < self.device.borrow().size() {
+            Some(offset)
+        } else {
+            None
+        }
+    }
+}
+
+/**
+ * A change made by a write, with what undoing it takes
+ */
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum JournalEntry {
+    /// A storage byte along with its previous value
+    Storage { address: u16, data: u8 },
+    /// The mapper registers before a write changed them (e.g. a bank switch)
+    MapperRegisters(Vec<u8>),
+    /// A banked RAM byte of the mapper along with its previous value
+    MapperRam { address: u16, data: u8 },
+}
 
 /**
  * Emulating the actual bus
  * 
  * Read and write operation should take place from here
  */
-pub struct Bus<T: PrimInt + std::convert::From<u8>> {
-    pub memory: Memory<T>, // RAM
-    pub other: Vec<T>, // Other storages or devices
-    pub secondary_storage: Rom<T>, // ROM 
+pub struct Bus {
+    pub memory: Memory, // RAM
+    pub other: Vec<u8>, // Other storages or devices
+    pub secondary_storage: Rom, // ROM 
+    pub devices: Vec<MappedDevice>, // Memory mapped devices, these take precedence over the storages
+    pub mapper: Option<Box<dyn Mapper>>, // Bank switching, between the devices and the storages
+    pub access_counters: Option<AccessCounters>, // Per-address profiling, counted only while enabled
+    pub watchpoints: Option<Watchpoints>, // Debugger watchpoints, checked only while some are set
+    pub write_journal: Option<Vec<JournalEntry>>, // Changes made by the writes, kept only for the execution history
 }
 
 // Constructor like implementation
-impl<T: PrimInt + std::convert::From<u8>> Bus<T> {
+impl Bus {
     
-    pub fn new(memory: Memory<T>, other: Vec<T>, secondary_storage: Rom<T>) -> Self {
+    pub fn new(memory: Memory, other: Vec<u8>, secondary_storage: Rom) -> Self {
         Self {
             memory,
             other,
-            secondary_storage
+            secondary_storage,
+            devices: Vec::new(),
+            mapper: None,
+            access_counters: None,
+            watchpoints: None,
+            write_journal: None,
         }
     }
 }
 
-impl<T: PrimInt + std::convert::From<u8>> Bus<T> {
-    pub fn read(&self, address: u16) -> T {
-        if address < self.memory.len() as u16 {
-            return self.memory[address];
+impl Bus {
+    pub fn read(&self, address: u16) -> u8 {
+        if let Some(watchpoints) = &self.watchpoints {
+            watchpoints.record_read(address);
+        }
+
+        self.fetch(address)
+    }
+
+    /**
+     * Reads an opcode or an operand byte
+     *
+     * Same as `read()`, except for the read watchpoints that only watch the data accesses.
+     */
+    pub fn fetch(&self, address: u16) -> u8 {
+        if let Some(counters) = &self.access_counters {
+            counters.record_read(address);
+        }
+
+        if let Some((device, offset)) = self.device_at(address) {
+            device.device.borrow_mut().read(offset)
+        }
+        else if let Some(data) = self.mapper.as_ref().and_then(|mapper| mapper.read(address)) {
+            data
+        }
+        else if address < self.memory.len() as u16 {
+            self.memory[address]
         }
         else if address < self.memory.len() as u16 + self.other.len() as u16 {
-            return self.other[(address - self.memory.len() as u16) as usize];
+            self.other[(address - self.memory.len() as u16) as usize]
         }
         else if (address as u32) < self.memory.len() as u32 + self.other.len() as u32 + self.secondary_storage.len() as u32 {
-            return self.secondary_storage[address - self.memory.len() as u16 - self.other.len() as u16];
+            self.secondary_storage[address - self.memory.len() as u16 - self.other.len() as u16]
         }
         else {
-            return T::zero();
+            0x00
+        }
+    }
+
+    /**
+     * Reads without side effects, for debugger views
+     *
+     * Devices aren't read (reading their registers may acknowledge interrupts, pop input, ...),
+     * the storage underneath them is returned instead. Nothing is counted by the profiler.
+     */
+    pub fn peek(&self, address: u16) -> u8 {
+        if let Some(data) = self.mapper.as_ref().and_then(|mapper| mapper.read(address)) {
+            data
+        }
+        else if address < self.memory.len() as u16 {
+            self.memory[address]
+        }
+        else if address < self.memory.len() as u16 + self.other.len() as u16 {
+            self.other[(address - self.memory.len() as u16) as usize]
+        }
+        else if (address as u32) < self.memory.len() as u32 + self.other.len() as u32 + self.secondary_storage.len() as u32 {
+            self.secondary_storage[address - self.memory.len() as u16 - self.other.len() as u16]
+        }
+        else {
+            0x00
+        }
+    }
+
+    pub fn write(&mut self, address: u16, data: u8) {
+        if let Some(counters) = &self.access_counters {
+            counters.record_write(address);
+        }
+        if let Some(watchpoints) = &self.watchpoints {
+            watchpoints.record_write(address);
+        }
+
+        if let Some((device, offset)) = self.device_at(address) {
+            device.device.borrow_mut().write(offset, data);
+        }
+        else if self.write_to_mapper(address, data) {
+            // taken by the mapper (bank latch, banked RAM or read only bank)
+        }
+        else {
+            if self.write_journal.is_some() {
+                let old_data = self.peek(address);
+                if let Some(journal) = &mut self.write_journal {
+                    journal.push(JournalEntry::Storage { address, data: old_data });
+                }
+            }
+            self.poke(address, data);
+        }
+    }
+
+    /**
+     * Gives the write to the mapper, journaling the registers and the RAM byte it changes
+     *
+     # Returns
+     whether the mapper took the write, `false` without a mapper
+     */
+    fn write_to_mapper(&mut self, address: u16, data: u8) -> bool {
+        let Some(mapper) = &mut self.mapper else {
+            return false;
+        };
+        let Some(journal) = &mut self.write_journal else {
+            return mapper.write(address, data);
+        };
+
+        let registers = mapper.save_registers();
+        let old_data = mapper.read(address);
+        let taken = mapper.write(address, data);
+
+        // registers may change even when the write goes on to the storages (e.g. the C64 processor port)
+        if mapper.save_registers() != registers {
+            journal.push(JournalEntry::MapperRegisters(registers));
+        }
+        if let Some(old_data) = old_data.filter(|_| taken) {
+            journal.push(JournalEntry::MapperRam { address, data: old_data });
+        }
+        taken
+    }
+
+    /**
+     * Reverts a change recorded in the write journal, nothing is counted or journaled
+     */
+    pub fn undo(&mut self, entry: &JournalEntry) {
+        match entry {
+            JournalEntry::Storage { address, data } => self.poke(*address, *data),
+            JournalEntry::MapperRegisters(registers) => {
+                if let Some(mapper) = &mut self.mapper {
+                    mapper.load_registers(registers);
+                }
+            }
+            // writes to a read only bank were journaled too, they aren't RAM so nothing is poked
+            JournalEntry::MapperRam { address, data } => {
+                if let Some(mapper) = &mut self.mapper {
+                    mapper.poke(*address, *data);
+                }
+            }
         }
     }
 
-    pub fn write(&mut self, address: u16, data: T) -> () {
+    /**
+     * Writes to the storages directly, without going through the devices or the mapper
+     *
+     * Meant for restoring previous contents, nothing is counted or journaled.
+     */
+    pub fn poke(&mut self, address: u16, data: u8) {
         if address < self.memory.len() as u16 {
             self.memory[address] = data;
         }
@@ -56,12 +231,157 @@ impl<T: PrimInt + std::convert::From<u8>> Bus<T> {
             panic!("Invalid Write Address");
         }
     }
+
+    /**
+     * Writes like a debugger would, without side effects: the devices are skipped and the mapper registers aren't latched
+     *
+     * Nothing is counted, watched or journaled.
+     *
+     # Returns
+     `false` if nothing writable is mapped at the address (read only bank, device only or unmapped address)
+     */
+    pub fn debug_write(&mut self, address: u16, data: u8) -> bool {
+        if let Some(mapper) = &mut self.mapper {
+            if mapper.poke(address, data) {
+                return true;
+            }
+            // a read only bank hides the storages under it
+            if mapper.read(address).is_some() {
+                return false;
+            }
+        }
+
+        if (address as u32) < self.memory.len() as u32 + self.other.len() as u32 + self.secondary_storage.len() as u32 {
+            self.poke(address, data);
+            true
+        } else {
+            false
+        }
+    }
+}
+
+// Device implementation
+impl Bus {
+
+    /**
+     * Maps the device onto the bus starting from the given address
+     *
+     * The device keeps being shared with the caller, so that the host can feed it input or inspect it
+     */
+    pub fn attach_device(&mut self, start: u16, device: Rc<RefCell<dyn BusDevice>>) {
+        self.devices.push(MappedDevice { start, device });
+    }
+
+    fn device_at(&self, address: u16) -> Option<(&MappedDevice, u16)> {
+        self.devices
+            .iter()
+            .find_map(|device| device.offset(address).map(|offset| (device, offset)))
+    }
+
+    /**
+     * States of the attached devices, in attach order
+     */
+    pub fn save_device_states(&self) -> Vec<Vec<u8>> {
+        self.devices.iter().map(|device| device.device.borrow().save_state()).collect()
+    }
+
+    /**
+     * Restores states returned by `save_device_states()`, ignored when devices were attached since
+     */
+    pub fn load_device_states(&mut self, states: &[Vec<u8>]) {
+        if states.len() != self.devices.len() {
+            return;
+        }
+        for (device, state) in self.devices.iter().zip(states) {
+            device.device.borrow_mut().load_state(state);
+        }
+    }
+
+    /**
+     * Advances every attached device by one clock cycle
+     */
+    pub fn tick(&mut self) {
+        for device in self.devices.iter() {
+            device.device.borrow_mut().tick();
+        }
+    }
+
+    /**
+     * Returns `true` if any attached device is pulling the (wired-or) IRQ line low
+     */
+    pub fn irq(&self) -> bool {
+        self.devices.iter().any(|device| device.device.borrow().irq())
+    }
+
+    /**
+     * Gives an external input to the device mapped at the address
+     *
+     # Returns
+
+     * `false` if no device is mapped there
+     */
+    pub fn device_input(&mut self, address: u16, port: u8, value: u8) -> bool {
+        match self.device_at(address) {
+            Some((device, _)) => {
+                device.device.borrow_mut().input(port, value);
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+// Bank switching implementation
+impl Bus {
+
+    /**
+     * Puts the mapper in front of the storages, replacing the previous one
+     */
+    pub fn set_mapper(&mut self, mapper: Box<dyn Mapper>) {
+        self.mapper = Some(mapper);
+    }
+
+    pub fn remove_mapper(&mut self) {
+        self.mapper = None;
+    }
+
+    /**
+     * Returns the banks currently mapped in, empty without a mapper
+     */
+    pub fn mapped_banks(&self) -> Vec<MappedBank> {
+        self.mapper.as_ref().map_or_else(Vec::new, |mapper| mapper.banks())
+    }
 }
 
 // load ROM implementation
-impl<T: PrimInt + std::convert::From<u8>> Bus<T> {
+impl Bus {
 
     pub fn load_rom(&mut self, filepath: &str) -> bool {
         self.secondary_storage.load(filepath)
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use crate::devices::terminal::Terminal;
+
+    /**
+     * Whether or not peeking reads the storages and the devices without side effects
+     */
+    #[test]
+    fn peek_without_side_effects() {
+        let mut bus = Bus::new(Memory::new(0x4000), vec![0; 0x4000], Rom::new(0x8000));
+        bus.write(0x0200, 0x34);
+        assert_eq!(bus.peek(0x0200), 0x34);
+
+        // peeking doesn't read the key from the keyboard register
+        let terminal = Rc::new(RefCell::new(Terminal::new()));
+        bus.attach_device(0x3000, terminal.clone());
+        bus.write(0x3001, 0x04); // selecting the keyboard data register
+        terminal.borrow_mut().push_input(b'A');
+        bus.peek(0x3000);
+        assert!(terminal.borrow().has_pending_input());
+    }
+}
@@ -0,0 +1,328 @@
+use std::collections::VecDeque;
+use std::fmt;
+
+use serde::Serialize;
+
+/// Only the most recent mismatches are kept
+const MAX_MISMATCHES: usize = 64;
+
+/**
+ * What pushed a frame onto the stack
+ */
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub enum FrameKind {
+    /// `JSR`, returns with `RTS`
+    Subroutine,
+    /// `BRK`, returns with `RTI`
+    Break,
+    /// Maskable interrupt, returns with `RTI`
+    Irq,
+    /// Non maskable interrupt, returns with `RTI`
+    Nmi,
+}
+
+impl FrameKind {
+    fn returns_with_rts(&self) -> bool {
+        *self == FrameKind::Subroutine
+    }
+}
+
+/**
+ * A call the program hasn't returned from yet
+ */
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct CallFrame {
+    pub kind: FrameKind,
+    /// Address of the `JSR`/`BRK` instruction, or of the instruction an interrupt came before
+    pub call_site: u16,
+    /// Where the call went (subroutine or interrupt handler)
+    pub target: u16,
+    /// Where the program counter should go when the call returns
+    pub return_address: u16,
+    /// Stack pointer right after the return address (and status) were pushed
+    pub stack_pointer: u8,
+}
+
+/**
+ * A return that doesn't match the shadow call stack
+ */
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "mismatch")]
+pub enum StackMismatch {
+    /// `RTS`/`RTI` pulled a return address nothing pushed through a call (e.g. an `RTS` jump table)
+    ReturnWithoutCall { address: u16, return_address: u16 },
+    /// The return address on the stack was changed while the call was running
+    ModifiedReturnAddress { address: u16, expected: u16, actual: u16 },
+    /// `RTS` returned from an interrupt or `RTI` returned from a subroutine
+    WrongReturnInstruction { address: u16, kind: FrameKind },
+    /// The stack pointer moved above frames that never returned (e.g. `TXS` or `PLA` dropping them)
+    DiscardedFrames { address: u16, count: usize },
+}
+
+/**
+ * Shadow call stack rebuilt from JSR/RTS/BRK/IRQ/NMI/RTI
+ *
+ * Frames are matched to returns through the stack pointer, so frames dropped by the program
+ * are noticed instead of desynchronizing the whole stack.
+ */
+#[derive(Debug, Default)]
+pub struct CallStack {
+    frames: Vec<CallFrame>,
+    mismatches: VecDeque<StackMismatch>,
+}
+
+// Constructor like implementation
+impl CallStack {
+    pub fn new() -> Self {
+        Self {
+            ..Default::default()
+        }
+    }
+}
+
+impl CallStack {
+    /**
+     * Records a call, after the return address (and status) were pushed
+     */
+    pub fn push(&mut self, frame: CallFrame) {
+        // frames at or below the new one have been overwritten
+        self.discard_below(frame.stack_pointer, frame.call_site, true);
+        self.frames.push(frame);
+    }
+
+    /**
+     * Records a return
+     *
+     # Arguments
+
+     * `address` - Address of the `RTS`/`RTI` instruction
+     * `is_rts` - `false` for `RTI`
+     * `stack_pointer` - Stack pointer before the return address (and status) were pulled
+     * `return_address` - Program counter after the return
+     */
+    pub fn pop(&mut self, address: u16, is_rts: bool, stack_pointer: u8, return_address: u16) {
+        self.discard_below(stack_pointer, address, false);
+
+        let frame = match self.frames.last() {
+            Some(frame) if frame.stack_pointer == stack_pointer => self.frames.pop().unwrap(),
+            _ => {
+                self.flag(StackMismatch::ReturnWithoutCall { address, return_address });
+                return;
+            }
+        };
+
+        if frame.kind.returns_with_rts() != is_rts {
+            self.flag(StackMismatch::WrongReturnInstruction { address, kind: frame.kind });
+        } else if frame.return_address != return_address {
+            self.flag(StackMismatch::ModifiedReturnAddress { address, expected: frame.return_address, actual: return_address });
+        }
+    }
+
+    /**
+     * Drops the frames the stack pointer has moved above
+     */
+    fn discard_below(&mut self, stack_pointer: u8, address: u16, inclusive: bool) {
+        let count = self
+            .frames
+            .iter()
+            .rev()
+            .take_while(|frame| frame.stack_pointer < stack_pointer || (inclusive && frame.stack_pointer == stack_pointer))
+            .count();
+
+        if count > 0 {
+            self.frames.truncate(self.frames.len() - count);
+            self.flag(StackMismatch::DiscardedFrames { address, count });
+        }
+    }
+
+    fn flag(&mut self, mismatch: StackMismatch) {
+        if self.mismatches.len() == MAX_MISMATCHES {
+            self.mismatches.pop_front();
+        }
+        self.mismatches.push_back(mismatch);
+    }
+
+    /**
+     * The active calls, outermost first
+     */
+    pub fn frames(&self) -> &[CallFrame] {
+        &self.frames
+    }
+
+    pub fn depth(&self) -> usize {
+        self.frames.len()
+    }
+
+    /**
+     * Drops the innermost frames down to `depth` frames, for calls abandoned without returning
+     */
+    pub fn truncate(&mut self, depth: usize) {
+        self.frames.truncate(depth);
+    }
+
+    /**
+     * The most recent mismatches, oldest first
+     */
+    pub fn mismatches(&self) -> impl Iterator<Item = &StackMismatch> {
+        self.mismatches.iter()
+    }
+
+    /**
+     * Returns the mismatches flagged since the previous call
+     */
+    pub fn take_mismatches(&mut self) -> Vec<StackMismatch> {
+        self.mismatches.drain(..).collect()
+    }
+
+    pub fn clear(&mut self) {
+        self.frames.clear();
+        self.mismatches.clear();
+    }
+
+    /**
+     * Returns the backtrace, innermost call first
+     *
+     # Arguments
+
+     * `program_counter` - Where the innermost call currently is
+     * `symbol` - Names an address, if a symbol table knows it
+     */
+    pub fn backtrace(&self, program_counter: u16, symbol: impl Fn(u16) -> Option<String>) -> Backtrace {
+        let mut entries = vec![BacktraceEntry {
+            kind: None,
+            address: program_counter,
+            function: self.frames.last().map(|frame| frame.target),
+            symbol: None,
+        }];
+
+        // each frame's call site lies inside the function of the frame below it
+        for (depth, frame) in self.frames.iter().enumerate().rev() {
+            entries.push(BacktraceEntry {
+                kind: Some(frame.kind),
+                address: frame.call_site,
+                function: depth.checked_sub(1).map(|outer| self.frames[outer].target),
+                symbol: None,
+            });
+        }
+
+        for entry in entries.iter_mut() {
+            entry.symbol = entry.function.and_then(&symbol);
+        }
+
+        Backtrace { entries }
+    }
+}
+
+/**
+ * One line of a backtrace
+ */
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct BacktraceEntry {
+    /// How the function below was entered from here, `None` for the current position
+    pub kind: Option<FrameKind>,
+    /// Current position or call site
+    pub address: u16,
+    /// Entry point of the function the address is in, `None` outside of any known call
+    pub function: Option<u16>,
+    pub symbol: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Backtrace {
+    /// Innermost first
+    pub entries: Vec<BacktraceEntry>,
+}
+
+impl fmt::Display for Backtrace {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, entry) in self.entries.iter().enumerate() {
+            write!(f, "#{:<3} ${:04X}", i, entry.address)?;
+
+            match (&entry.symbol, entry.function) {
+                (Some(symbol), _) => write!(f, " in {}", symbol)?,
+                (None, Some(function)) => write!(f, " in ${:04X}", function)?,
+                (None, None) => (),
+            }
+
+            match entry.kind {
+                Some(FrameKind::Subroutine) | None => (),
+                Some(kind) => write!(f, " ({:?})", kind)?,
+            }
+
+            writeln!(f)?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    fn subroutine(call_site: u16, target: u16, stack_pointer: u8) -> CallFrame {
+        CallFrame {
+            kind: FrameKind::Subroutine,
+            call_site,
+            target,
+            return_address: call_site + 3,
+            stack_pointer,
+        }
+    }
+
+    /**
+     * Whether or not matching calls and returns leave no mismatch
+     */
+    #[test]
+    fn nested_calls() {
+        let mut stack = CallStack::new();
+
+        stack.push(subroutine(0x8000, 0x9000, 0xFD));
+        stack.push(subroutine(0x9005, 0xA000, 0xFB));
+        assert_eq!(stack.depth(), 2);
+
+        let backtrace = stack.backtrace(0xA002, |address| (address == 0x9000).then(|| String::from("print")));
+        assert_eq!(backtrace.to_string(), "#0   $A002 in $A000\n#1   $9005 in print\n#2   $8000\n");
+
+        stack.pop(0xA010, true, 0xFB, 0x9008);
+        stack.pop(0x9010, true, 0xFD, 0x8003);
+        assert_eq!(stack.depth(), 0);
+        assert_eq!(stack.mismatches().count(), 0);
+    }
+
+    /**
+     * Whether or not returns that don't match a call are flagged
+     */
+    #[test]
+    fn mismatches() {
+        let mut stack = CallStack::new();
+
+        // RTS jump table: push an address, RTS to it
+        stack.pop(0x8000, true, 0xFD, 0x9000);
+
+        // return address changed while in the subroutine
+        stack.push(subroutine(0x8000, 0x9000, 0xFD));
+        stack.pop(0x9010, true, 0xFD, 0x1234);
+
+        // RTI from a subroutine
+        stack.push(subroutine(0x8000, 0x9000, 0xFD));
+        stack.pop(0x9010, false, 0xFD, 0x8003);
+
+        // the inner frame is dropped by the stack pointer moving above it
+        stack.push(subroutine(0x8000, 0x9000, 0xFD));
+        stack.push(subroutine(0x9000, 0xA000, 0xFB));
+        stack.pop(0x9010, true, 0xFD, 0x8003);
+
+        assert_eq!(
+            stack.take_mismatches(),
+            vec![
+                StackMismatch::ReturnWithoutCall { address: 0x8000, return_address: 0x9000 },
+                StackMismatch::ModifiedReturnAddress { address: 0x9010, expected: 0x8003, actual: 0x1234 },
+                StackMismatch::WrongReturnInstruction { address: 0x9010, kind: FrameKind::Subroutine },
+                StackMismatch::DiscardedFrames { address: 0x9010, count: 1 },
+            ]
+        );
+        assert_eq!(stack.depth(), 0);
+    }
+}
@@ -0,0 +1,296 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt::Write;
+
+use crate::analysis::Analysis;
+use crate::disassembler::format_instruction;
+use crate::instruction_info::ControlFlow;
+use crate::processor::{Instruction, Processor};
+
+/**
+ * How many times a branch was taken and not taken
+ */
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct BranchCounts {
+    pub taken: u64,
+    pub not_taken: u64,
+}
+
+/**
+ * Executed instructions and branch outcomes, recorded by the processor at every instruction it executes
+ */
+#[derive(Debug, Clone)]
+pub struct Coverage {
+    /// Executions of the instruction starting at each address
+    hits: Vec<u64>,
+    branches: BTreeMap<u16, BranchCounts>,
+}
+
+// Constructor like implementation
+impl Coverage {
+    pub fn new() -> Self {
+        Self {
+            hits: vec![0; 0x10000],
+            branches: BTreeMap::new(),
+        }
+    }
+}
+
+impl Default for Coverage {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Coverage {
+    /**
+     * Counts an executed instruction
+     *
+     # Arguments
+
+     * `address` - Where the instruction is
+     * `opcode` - Its opcode
+     * `next_address` - The program counter once it has executed, a branch went on at its target when
+       it is anything but the next instruction (so a branch to the next instruction is never taken)
+     */
+    pub fn record(&mut self, address: u16, opcode: u8, next_address: u16) {
+        self.hits[address as usize] += 1;
+
+        if Instruction::decode(opcode).control_flow() == ControlFlow::Branch {
+            let counts = self.branches.entry(address).or_default();
+            if next_address == address.wrapping_add(2) {
+                counts.not_taken += 1;
+            } else {
+                counts.taken += 1;
+            }
+        }
+    }
+
+    pub fn hits(&self, address: u16) -> u64 {
+        self.hits[address as usize]
+    }
+
+    pub fn branch(&self, address: u16) -> Option<BranchCounts> {
+        self.branches.get(&address).copied()
+    }
+
+    /**
+     * Addresses of the executed instructions, in order
+     */
+    pub fn executed(&self) -> impl Iterator<Item = u16> + '_ {
+        (0..=0xFFFF_u16).filter(|address| self.hits[*address as usize] > 0)
+    }
+
+    /**
+     * Addresses of the instructions known to the report: the executed ones and the code reachable from the vectors
+     */
+    fn instructions(&self, analysis: &Analysis) -> BTreeSet<u16> {
+        (0..=0xFFFF_u16)
+            .filter(|address| self.hits[*address as usize] > 0 || analysis.is_code(*address))
+            .collect()
+    }
+
+    /**
+     * Per-address report of the code, executed or not, along with a summary
+     *
+     # Description
+     * Code that was never executed is found by following the paths from the vectors, code only reached
+       through indirect jumps is listed once it has been executed.
+     */
+    pub fn to_text(&self, processor: &Processor) -> String {
+        let analysis = Analysis::new(&processor.bus);
+        let labels = analysis.labels(&processor.symbols);
+        let instructions = self.instructions(&analysis);
+
+        let executed = instructions.iter().filter(|address| self.hits(**address) > 0).count();
+        let branches = instructions.iter().filter(|address| is_branch(processor, **address)).count();
+        let outcomes: usize = self.branches.values().map(|counts| (counts.taken > 0) as usize + (counts.not_taken > 0) as usize).sum();
+
+        let mut output = format!(
+            "; {} of {} instructions executed ({}), {} of {} branch outcomes ({})\n",
+            executed,
+            instructions.len(),
+            percent(executed, instructions.len()),
+            outcomes,
+            branches * 2,
+            percent(outcomes, branches * 2)
+        );
+
+        for address in instructions {
+            if let Some(name) = labels.name_of(address) {
+                let _ = writeln!(output, "{}:", name);
+            }
+
+            let operand = u16::from_le_bytes([processor.bus.peek(address.wrapping_add(1)), processor.bus.peek(address.wrapping_add(2))]);
+            let text = format_instruction(Instruction::decode(processor.bus.peek(address)), address, operand, &labels);
+            let _ = write!(output, "{:04X}  {:>10}  {:20}", address, self.hits(address), text);
+
+            if let Some(location) = processor.debug_info.location(address) {
+                let _ = write!(output, "  {}", location);
+            }
+            if is_branch(processor, address) {
+                let counts = self.branch(address).unwrap_or_default();
+                let _ = write!(output, "  taken {}, not taken {}", counts.taken, counts.not_taken);
+            }
+            output.truncate(output.trim_end().len());
+            output.push('\n');
+        }
+
+        output
+    }
+
+    /**
+     * Report in the lcov tracefile format (`genhtml`, editors and CI services read it)
+     *
+     # Description
+     * Only the instructions with a source line in the debug info are part of it, so it is empty without one.
+     * A line is hit as many times as its most executed instruction, functions are the entry points and
+     * subroutines found by following the paths from the vectors.
+     */
+    pub fn to_lcov(&self, processor: &Processor) -> String {
+        let analysis = Analysis::new(&processor.bus);
+        let labels = analysis.labels(&processor.symbols);
+
+        // hits and branches by file and line
+        let mut files: BTreeMap<String, BTreeMap<u32, SourceLineCoverage>> = BTreeMap::new();
+        for address in self.instructions(&analysis) {
+            let Some(location) = processor.debug_info.location(address) else {
+                continue;
+            };
+
+            let line = files.entry(location.file).or_default().entry(location.line).or_default();
+            line.hits = line.hits.max(self.hits(address));
+            if is_branch(processor, address) {
+                line.branches.push((self.hits(address) > 0).then(|| self.branch(address).unwrap_or_default()));
+            }
+        }
+
+        let mut functions: BTreeMap<String, Vec<(u32, String, u64)>> = BTreeMap::new();
+        for address in analysis.call_graph().keys() {
+            if let (Some(location), Some(name)) = (processor.debug_info.location(*address), labels.name_of(*address)) {
+                functions.entry(location.file).or_default().push((location.line, name.to_owned(), self.hits(*address)));
+            }
+        }
+
+        let mut output = String::new();
+        for (file, lines) in files {
+            let _ = writeln!(output, "TN:\nSF:{}", file);
+
+            let file_functions = functions.remove(&file).unwrap_or_default();
+            for (line, name, _) in &file_functions {
+                let _ = writeln!(output, "FN:{},{}", line, name);
+            }
+            for (_, name, hits) in &file_functions {
+                let _ = writeln!(output, "FNDA:{},{}", hits, name);
+            }
+            let _ = writeln!(output, "FNF:{}", file_functions.len());
+            let _ = writeln!(output, "FNH:{}", file_functions.iter().filter(|(_, _, hits)| *hits > 0).count());
+
+            let (mut branches_found, mut branches_hit) = (0, 0);
+            for (line, coverage) in &lines {
+                for (block, counts) in coverage.branches.iter().enumerate() {
+                    for (branch, count) in [counts.map(|counts| counts.taken), counts.map(|counts| counts.not_taken)].iter().enumerate() {
+                        let count = count.map_or_else(|| String::from("-"), |count| count.to_string());
+                        let _ = writeln!(output, "BRDA:{},{},{},{}", line, block, branch, count);
+                    }
+                    branches_found += 2;
+                    branches_hit += counts.map_or(0, |counts| (counts.taken > 0) as usize + (counts.not_taken > 0) as usize);
+                }
+            }
+            let _ = writeln!(output, "BRF:{}\nBRH:{}", branches_found, branches_hit);
+
+            for (line, coverage) in &lines {
+                let _ = writeln!(output, "DA:{},{}", line, coverage.hits);
+            }
+            let _ = writeln!(output, "LF:{}", lines.len());
+            let _ = writeln!(output, "LH:{}", lines.values().filter(|coverage| coverage.hits > 0).count());
+            output.push_str("end_of_record\n");
+        }
+
+        output
+    }
+}
+
+#[derive(Default)]
+struct SourceLineCoverage {
+    hits: u64,
+    /// Outcomes of the branches on the line, `None` for the ones never executed
+    branches: Vec<Option<BranchCounts>>,
+}
+
+fn is_branch(processor: &Processor, address: u16) -> bool {
+    Instruction::decode(processor.bus.peek(address)).control_flow() == ControlFlow::Branch
+}
+
+fn percent(part: usize, whole: usize) -> String {
+    if whole == 0 {
+        return String::from("-");
+    }
+    format!("{:.1}%", part as f64 * 100.0 / whole as f64)
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use crate::debug_info::DebugInfo;
+
+    const DBG: &str = "version\tmajor=2,minor=0\n\
+        file\tid=0,name=\"loop.s\",size=40,mtime=0x60000000,mod=0\n\
+        line\tid=0,file=0,line=2,span=0\n\
+        line\tid=1,file=0,line=3,span=1\n\
+        line\tid=2,file=0,line=4,span=2\n\
+        line\tid=3,file=0,line=5,span=3\n\
+        line\tid=4,file=0,line=7,span=4\n\
+        seg\tid=0,name=\"CODE\",start=0x008000,size=0x000A,addrsize=absolute,type=ro,oname=\"a.bin\",ooffs=0\n\
+        span\tid=0,seg=0,start=0,size=3\n\
+        span\tid=1,seg=0,start=3,size=1\n\
+        span\tid=2,seg=0,start=4,size=2\n\
+        span\tid=3,seg=0,start=6,size=3\n\
+        span\tid=4,seg=0,start=9,size=1\n";
+
+    /**
+     * Whether or not hits and branch outcomes are recorded and reported per address and per source line
+     */
+    #[test]
+    fn hits_and_branches() {
+        let mut proc = Processor::new();
+        let program = [
+            0xAE, 0x10, 0x80, // 8000 LDX $8010
+            0xCA,             // 8003 DEX
+            0xF0, 0x03,       // 8004 BEQ $8009
+            0x4C, 0x03, 0x80, // 8006 JMP $8003
+            0x02,             // 8009 JAM
+        ];
+        for (i, byte) in program.iter().enumerate() {
+            proc.bus.write(0x8000 + i as u16, *byte);
+        }
+        proc.bus.write(0x8010, 0x03);
+        for (vector, target) in [(0xFFFA, 0x8009_u16), (0xFFFC, 0x8000), (0xFFFE, 0x8009)] {
+            proc.bus.write(vector, target as u8);
+            proc.bus.write(vector + 1, (target >> 8) as u8);
+        }
+        proc.debug_info = DebugInfo::parse(DBG).unwrap();
+
+        proc.reset();
+        let mut registers = proc.registers();
+        registers.program_counter = 0x8000;
+        proc.set_registers(&registers);
+        proc.enable_coverage();
+        proc.run(1000);
+
+        let coverage = proc.coverage().unwrap();
+        assert_eq!((coverage.hits(0x8000), coverage.hits(0x8003), coverage.hits(0x8006), coverage.hits(0x8009)), (1, 3, 2, 1));
+        assert_eq!(coverage.branch(0x8004), Some(BranchCounts { taken: 1, not_taken: 2 }));
+        assert_eq!(coverage.executed().collect::<Vec<u16>>(), vec![0x8000, 0x8003, 0x8004, 0x8006, 0x8009]);
+
+        let text = coverage.to_text(&proc);
+        assert!(text.starts_with("; 5 of 5 instructions executed (100.0%), 2 of 2 branch outcomes (100.0%)\n"));
+        assert!(text.contains("8004           3  BEQ nmi               loop.s:4  taken 1, not taken 2\n"));
+
+        let lcov = coverage.to_lcov(&proc);
+        assert!(lcov.starts_with("TN:\nSF:loop.s\nFN:2,reset\n"));
+        assert!(lcov.contains("BRDA:4,0,0,1\nBRDA:4,0,1,2\nBRF:2\nBRH:2\n"));
+        assert!(lcov.contains("DA:3,3\nDA:4,3\nDA:5,2\n"));
+        assert!(lcov.ends_with("LF:5\nLH:5\nend_of_record\n"));
+    }
+}
@@ -0,0 +1,292 @@
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::fs;
+use std::path::Path;
+
+use serde::Serialize;
+
+use crate::symbols::{parse_dbg_record, parse_number};
+
+/// `type` of the `line` records expanded from a macro, the invoking line is preferred over them
+const LINE_TYPE_MACRO: u32 = 2;
+
+/**
+ * A line of a source file
+ */
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize)]
+pub struct SourceLocation {
+    pub file: String,
+    pub line: u32,
+}
+
+impl fmt::Display for SourceLocation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}", self.file, self.line)
+    }
+}
+
+/**
+ * Reads a `file:line` location typed by the user
+ */
+pub fn parse_location(text: &str) -> Option<(&str, u32)> {
+    let (file, line) = text.trim().rsplit_once(':')?;
+    Some((file, line.parse().ok()?))
+}
+
+/**
+ * Maps addresses to source lines and back, from the ld65 debug file (`ld65 --dbgfile`)
+ */
+#[derive(Debug, Default, Clone)]
+pub struct DebugInfo {
+    /// Source file names by file id
+    files: HashMap<u32, String>,
+    /// `(file id, line, type)` of every byte generated by a source line
+    lines_by_address: HashMap<u16, (u32, u32, u32)>,
+    /// First address of every span generated by a source line, by `(file id, line)`
+    addresses_by_line: BTreeMap<(u32, u32), Vec<u16>>,
+    /// Contents of the source files, when they were loaded
+    sources: HashMap<u32, Vec<String>>,
+}
+
+// Constructor like implementation
+impl DebugInfo {
+    pub fn new() -> Self {
+        Self {
+            ..Default::default()
+        }
+    }
+}
+
+// parsing implementation
+impl DebugInfo {
+    /**
+     * Parses the text of an ld65 debug file
+     */
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let records: Vec<(&str, HashMap<&str, &str>)> = text.lines().filter_map(parse_dbg_record).collect();
+
+        if records.first().map(|(kind, _)| *kind) != Some("version") {
+            return Err(String::from("not an ld65 debug file"));
+        }
+
+        let number = |fields: &HashMap<&str, &str>, key: &str| fields.get(key).and_then(|value| value.parse::<u32>().ok());
+
+        let mut debug_info = Self::new();
+        let mut segments: HashMap<u32, u16> = HashMap::new();
+        // span id to (start address, size)
+        let mut spans: HashMap<u32, (u16, u16)> = HashMap::new();
+
+        for (kind, fields) in records.iter() {
+            match *kind {
+                "file" => {
+                    if let (Some(id), Some(name)) = (number(fields, "id"), fields.get("name")) {
+                        debug_info.files.insert(id, (*name).to_owned());
+                    }
+                }
+                "seg" => {
+                    if let (Some(id), Some(start)) = (number(fields, "id"), fields.get("start").and_then(|start| parse_number(start))) {
+                        segments.insert(id, start);
+                    }
+                }
+                _ => (),
+            }
+        }
+
+        for (_, fields) in records.iter().filter(|(kind, _)| *kind == "span") {
+            let segment_start = number(fields, "seg").and_then(|segment| segments.get(&segment));
+            let start = fields.get("start").and_then(|start| parse_number(start));
+            let size = fields.get("size").and_then(|size| parse_number(size));
+
+            if let (Some(id), Some(segment_start), Some(start), Some(size)) = (number(fields, "id"), segment_start, start, size) {
+                spans.insert(id, (segment_start.wrapping_add(start), size));
+            }
+        }
+
+        for (_, fields) in records.iter().filter(|(kind, _)| *kind == "line") {
+            let (file, line) = match (number(fields, "file"), number(fields, "line")) {
+                (Some(file), Some(line)) => (file, line),
+                _ => continue,
+            };
+            let line_type = number(fields, "type").unwrap_or(0);
+
+            // lines without code have no span
+            let span_ids = fields.get("span").map_or(Vec::new(), |span_ids| span_ids.split('+').filter_map(|id| id.parse::<u32>().ok()).collect());
+
+            for (start, size) in span_ids.iter().filter_map(|id| spans.get(id)) {
+                debug_info.insert_span(file, line, line_type, *start, *size);
+            }
+        }
+
+        Ok(debug_info)
+    }
+
+    fn insert_span(&mut self, file: u32, line: u32, line_type: u32, start: u16, size: u16) {
+        for offset in 0..size {
+            let address = start.wrapping_add(offset);
+
+            // the invoking line wins over the macro body
+            match self.lines_by_address.get(&address) {
+                Some((_, _, old_type)) if line_type == LINE_TYPE_MACRO && *old_type != LINE_TYPE_MACRO => (),
+                _ => {
+                    self.lines_by_address.insert(address, (file, line, line_type));
+                }
+            }
+        }
+
+        if line_type != LINE_TYPE_MACRO {
+            self.addresses_by_line.entry((file, line)).or_default().push(start);
+        }
+    }
+
+    pub fn load_file(filepath: &str) -> Result<Self, String> {
+        let text = fs::read_to_string(filepath).map_err(|error| error.to_string())?;
+        Self::parse(&text)
+    }
+
+    /**
+     * Reads the source files from disk so their lines can be shown
+     *
+     # Arguments
+
+     * `base_directory` - Where relative file names are looked up, usually the directory of the debug file
+     *
+     # Returns
+     the number of files read
+     */
+    pub fn load_sources(&mut self, base_directory: &Path) -> usize {
+        let mut count = 0;
+
+        for (id, name) in self.files.iter() {
+            if let Ok(text) = fs::read_to_string(base_directory.join(name)) {
+                self.sources.insert(*id, text.lines().map(str::to_owned).collect());
+                count += 1;
+            }
+        }
+
+        count
+    }
+
+    /**
+     * Sets the contents of a source file, for front ends without a file system
+     */
+    pub fn set_source(&mut self, file: &str, text: &str) -> bool {
+        match self.file_id(file) {
+            Some(id) => {
+                self.sources.insert(id, text.lines().map(str::to_owned).collect());
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+// lookup implementation
+impl DebugInfo {
+    pub fn is_empty(&self) -> bool {
+        self.lines_by_address.is_empty()
+    }
+
+    /**
+     * The source file names
+     */
+    pub fn files(&self) -> Vec<&str> {
+        let mut files: Vec<&str> = self.files.values().map(String::as_str).collect();
+        files.sort_unstable();
+        files
+    }
+
+    /**
+     * Finds a file by its name, a path suffix or its base name
+     */
+    fn file_id(&self, file: &str) -> Option<u32> {
+        let base_name = |name: &str| Path::new(name).file_name().map(|name| name.to_owned());
+
+        self.files
+            .iter()
+            .find(|(_, name)| name.as_str() == file)
+            .or_else(|| self.files.iter().find(|(_, name)| name.ends_with(&format!("/{}", file))))
+            .or_else(|| self.files.iter().find(|(_, name)| base_name(name) == base_name(file)))
+            .map(|(id, _)| *id)
+    }
+
+    /**
+     * Returns the source line the byte at the address was generated by
+     */
+    pub fn location(&self, address: u16) -> Option<SourceLocation> {
+        let (file, line, _) = self.lines_by_address.get(&address)?;
+
+        Some(SourceLocation {
+            file: self.files.get(file)?.clone(),
+            line: *line,
+        })
+    }
+
+    /**
+     * Returns the text of a source line, if the sources were loaded
+     */
+    pub fn source_line(&self, location: &SourceLocation) -> Option<&str> {
+        let lines = self.sources.get(&self.file_id(&location.file)?)?;
+        lines.get((location.line as usize).checked_sub(1)?).map(String::as_str)
+    }
+
+    /**
+     * Returns the addresses of the code generated by a source line
+     *
+     * A line without code resolves to the next line of the file with code, like breakpoints on
+     * comments or labels do in other debuggers.
+     *
+     # Returns
+     the line that was resolved and the start of each of its spans
+     */
+    pub fn addresses_of(&self, file: &str, line: u32) -> Option<(u32, Vec<u16>)> {
+        let id = self.file_id(file)?;
+
+        self.addresses_by_line
+            .range((id, line)..=(id, u32::MAX))
+            .next()
+            .map(|((_, line), addresses)| (*line, addresses.clone()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    const DBG: &str = "version\tmajor=2,minor=0\n\
+        info\tcsym=0,file=2,lib=0,line=6,mod=1,scope=1,seg=1,span=4,sym=1,type=1\n\
+        file\tid=0,name=\"src/main.s\",size=120,mtime=0x60000000,mod=0\n\
+        file\tid=1,name=\"macros.inc\",size=40,mtime=0x60000000,mod=0\n\
+        line\tid=0,file=0,line=3,span=0\n\
+        line\tid=1,file=0,line=4,span=1\n\
+        line\tid=2,file=0,line=5\n\
+        line\tid=3,file=0,line=6,span=2+3\n\
+        line\tid=4,file=1,line=2,type=2,count=1,span=2\n\
+        seg\tid=0,name=\"CODE\",start=0x008000,size=0x0008,addrsize=absolute,type=ro,oname=\"a.bin\",ooffs=0\n\
+        span\tid=0,seg=0,start=0,size=2\n\
+        span\tid=1,seg=0,start=2,size=1\n\
+        span\tid=2,seg=0,start=3,size=3\n\
+        span\tid=3,seg=0,start=6,size=2\n";
+
+    /**
+     * Whether or not addresses map to source lines and back
+     */
+    #[test]
+    fn lines_and_addresses() {
+        let debug_info = DebugInfo::parse(DBG).unwrap();
+
+        assert_eq!(debug_info.location(0x8001).map(|location| location.to_string()), Some(String::from("src/main.s:3")));
+        assert_eq!(debug_info.location(0x8002).map(|location| location.line), Some(4));
+        // the macro body loses against the line invoking it
+        assert_eq!(debug_info.location(0x8004).map(|location| location.line), Some(6));
+        assert_eq!(debug_info.location(0x9000), None);
+
+        assert_eq!(debug_info.addresses_of("main.s", 4), Some((4, vec![0x8002])));
+        // line 5 has no code
+        assert_eq!(debug_info.addresses_of("src/main.s", 5), Some((6, vec![0x8003, 0x8006])));
+        assert_eq!(debug_info.addresses_of("main.s", 7), None);
+        assert_eq!(parse_location("main.s:12"), Some(("main.s", 12)));
+
+        assert!(DebugInfo::parse("al C:8000 .main").is_err());
+    }
+}
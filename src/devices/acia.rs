@@ -0,0 +1,387 @@
+use std::collections::VecDeque;
+
+use crate::devices::serial::SerialBridge;
+use crate::devices::BusDevice;
+
+/// Where the ACIA is mapped unless told otherwise
+pub const DEFAULT_ADDRESS: u16 = 0x5000;
+
+// Register offsets
+const DATA: u16 = 0; // transmit (write) / receive (read) data register
+const STATUS: u16 = 1; // status (read) / programmed reset (write)
+const COMMAND: u16 = 2;
+const CONTROL: u16 = 3;
+
+// Status register bits
+const STATUS_PARITY_ERROR: u8 = 1 << 0;
+const STATUS_FRAMING_ERROR: u8 = 1 << 1;
+const STATUS_OVERRUN: u8 = 1 << 2;
+const STATUS_RDRF: u8 = 1 << 3; // receive data register full
+const STATUS_TDRE: u8 = 1 << 4; // transmit data register empty
+const STATUS_IRQ: u8 = 1 << 7;
+
+// Command register bits
+const COMMAND_DTR: u8 = 1 << 0; // 1 enables the receiver and the transmitter
+const COMMAND_IRD: u8 = 1 << 1; // 1 disables the receiver interrupt
+const COMMAND_TIC_MASK: u8 = 0b11 << 2;
+const COMMAND_TIC_IRQ_ENABLED: u8 = 0b01 << 2; // transmit interrupt enabled
+const COMMAND_ECHO: u8 = 1 << 4;
+const COMMAND_PARITY_ENABLE: u8 = 1 << 5;
+
+// Control register bits
+const CONTROL_BAUD_MASK: u8 = 0x0F;
+const CONTROL_WORD_LENGTH_SHIFT: u8 = 5;
+const CONTROL_TWO_STOP_BITS: u8 = 1 << 7;
+
+/// Baud rates selected by the low nibble of the control register, 0 selects the external clock
+const BAUD_RATES: [f64; 16] = [
+    0.0, 50.0, 75.0, 109.92, 134.58, 150.0, 300.0, 600.0,
+    1200.0, 1800.0, 2400.0, 3600.0, 4800.0, 7200.0, 9600.0, 19200.0,
+];
+
+/**
+ * MOS 6551 Asynchronous Communications Interface Adapter
+ *
+ * Bytes are moved at the pace of the programmed baud rate, relative to the processor clock given to `new()`.
+ * The external receiver clock (baud rate 0) has no timing, bytes move as soon as possible.
+ * Transmitted bytes go to a `SerialBridge`, received bytes only come through `input()` so that the host
+ * gives them through `Processor::input()`, where they are recorded and replayed like any other input.
+ */
+pub struct Acia<B: SerialBridge> {
+    bridge: B,
+    /// Clock rate of the processor ticking the device
+    cpu_hz: u32,
+
+    status: u8,
+    command: u8,
+    control: u8,
+
+    receive_data: u8,
+    /// Written by the processor, waiting for the shift register to be free
+    transmit_data: Option<u8>,
+    /// Byte being shifted out along with the cycles left until it is on the line
+    transmit_shift: Option<(u8, u64)>,
+    /// Cycles left until the receiver can accept the next frame
+    receive_wait: u64,
+    /// Bytes given through `input()`, waiting for the receiver to be ready
+    host_input: VecDeque<u8>,
+
+    irq_pending: bool,
+}
+
+// Constructor like implementation
+impl<B: SerialBridge> Acia<B> {
+    /**
+     * Returns the ACIA in its hardware reset state
+     *
+     # Arguments
+
+     * `bridge` - The host end of the serial line
+     * `cpu_hz` - The processor clock rate, used to convert the baud rate to cycles
+     */
+    pub fn new(bridge: B, cpu_hz: u32) -> Self {
+        Self {
+            bridge,
+            cpu_hz,
+            status: STATUS_TDRE,
+            command: COMMAND_IRD,
+            control: 0x00,
+            receive_data: 0x00,
+            transmit_data: None,
+            transmit_shift: None,
+            receive_wait: 0,
+            host_input: VecDeque::new(),
+            irq_pending: false,
+        }
+    }
+}
+
+impl<B: SerialBridge> Acia<B> {
+    pub fn bridge(&self) -> &B {
+        &self.bridge
+    }
+
+    pub fn bridge_mut(&mut self) -> &mut B {
+        &mut self.bridge
+    }
+
+    /**
+     * Number of processor cycles needed to send or receive one frame (start, data, parity and stop bits)
+     */
+    fn cycles_per_frame(&self) -> u64 {
+        let baud = BAUD_RATES[(self.control & CONTROL_BAUD_MASK) as usize];
+        if baud == 0.0 {
+            return 0;
+        }
+
+        let data_bits = 8 - ((self.control >> CONTROL_WORD_LENGTH_SHIFT) & 0b11) as u32;
+        let parity_bits = ((self.command & COMMAND_PARITY_ENABLE) != 0) as u32;
+        let stop_bits = if (self.control & CONTROL_TWO_STOP_BITS) != 0 { 2 } else { 1 };
+
+        let bits = 1 + data_bits + parity_bits + stop_bits;
+        (self.cpu_hz as f64 * bits as f64 / baud).round() as u64
+    }
+
+    fn is_enabled(&self) -> bool {
+        (self.command & COMMAND_DTR) != 0
+    }
+
+    fn data_mask(&self) -> u8 {
+        0xFF >> ((self.control >> CONTROL_WORD_LENGTH_SHIFT) & 0b11)
+    }
+
+    fn transmit_irq_enabled(&self) -> bool {
+        (self.command & COMMAND_TIC_MASK) == COMMAND_TIC_IRQ_ENABLED
+    }
+
+    fn receive_irq_enabled(&self) -> bool {
+        (self.command & COMMAND_IRD) == 0
+    }
+
+    fn tick_transmitter(&mut self) {
+        if let Some((byte, cycles_left)) = self.transmit_shift {
+            if cycles_left > 1 {
+                self.transmit_shift = Some((byte, cycles_left - 1));
+                return;
+            }
+            self.bridge.transmit(byte);
+            self.transmit_shift = None;
+        }
+
+        // the data register is moved to the shift register as soon as it is free
+        if let Some(byte) = self.transmit_data.take() {
+            self.transmit_shift = Some((byte, self.cycles_per_frame()));
+            self.status |= STATUS_TDRE;
+            if self.transmit_irq_enabled() {
+                self.irq_pending = true;
+            }
+        }
+    }
+
+    fn tick_receiver(&mut self) {
+        if self.receive_wait > 0 {
+            self.receive_wait -= 1;
+            return;
+        }
+
+        if let Some(byte) = self.host_input.pop_front() {
+            // the previous byte hasn't been read yet, so it is lost
+            if (self.status & STATUS_RDRF) != 0 {
+                self.status |= STATUS_OVERRUN;
+            }
+
+            self.receive_data = byte & self.data_mask();
+            self.status |= STATUS_RDRF;
+            if self.receive_irq_enabled() {
+                self.irq_pending = true;
+            }
+
+            if (self.command & COMMAND_ECHO) != 0 {
+                self.bridge.transmit(self.receive_data);
+            }
+
+            self.receive_wait = self.cycles_per_frame();
+        }
+    }
+}
+
+impl<B: SerialBridge> BusDevice for Acia<B> {
+    fn size(&self) -> u16 {
+        4
+    }
+
+    fn read(&mut self, offset: u16) -> u8 {
+        match offset {
+            DATA => {
+                self.status &= !(STATUS_RDRF | STATUS_OVERRUN | STATUS_FRAMING_ERROR | STATUS_PARITY_ERROR);
+                self.receive_data
+            }
+            // reading the status acknowledges the interrupt
+            STATUS => {
+                let status = if self.irq_pending { self.status | STATUS_IRQ } else { self.status };
+                self.irq_pending = false;
+                status
+            }
+            COMMAND => self.command,
+            CONTROL => self.control,
+            _ => 0x00,
+        }
+    }
+
+    fn write(&mut self, offset: u16, data: u8) {
+        match offset {
+            DATA => {
+                self.transmit_data = Some(data & self.data_mask());
+                self.status &= !STATUS_TDRE;
+            }
+            // programmed reset, the control register is left alone
+            STATUS => {
+                self.command &= !0x1F;
+                self.status &= !STATUS_OVERRUN;
+                self.irq_pending = false;
+            }
+            COMMAND => self.command = data,
+            CONTROL => self.control = data,
+            _ => (),
+        }
+    }
+
+    fn tick(&mut self) {
+        if !self.is_enabled() {
+            return;
+        }
+
+        self.tick_transmitter();
+        self.tick_receiver();
+    }
+
+    fn irq(&self) -> bool {
+        self.irq_pending && self.is_enabled()
+    }
+
+    /// Port 0 is the receive line, the byte is received once the receiver is ready
+    fn input(&mut self, port: u8, value: u8) {
+        if port == 0 {
+            self.host_input.push_back(value);
+        }
+    }
+
+    /// The registers, the bytes in flight and the received bytes waiting, what was transmitted belongs to the host
+    fn save_state(&self) -> Vec<u8> {
+        let (shift_data, shift_cycles) = self.transmit_shift.unwrap_or_default();
+
+        let mut state = vec![
+            self.status, self.command, self.control, self.receive_data,
+            self.transmit_data.is_some() as u8, self.transmit_data.unwrap_or_default(),
+            self.transmit_shift.is_some() as u8, shift_data, self.irq_pending as u8,
+        ];
+        state.extend(shift_cycles.to_le_bytes());
+        state.extend(self.receive_wait.to_le_bytes());
+        state.extend(&self.host_input);
+        state
+    }
+
+    fn load_state(&mut self, state: &[u8]) {
+        let [status, command, control, receive_data, has_transmit_data, transmit_data, has_transmit_shift, shift_data, irq_pending, rest @ ..] = state else {
+            return;
+        };
+        if rest.len() < 16 {
+            return;
+        }
+        let (shift_cycles, rest) = rest.split_at(8);
+        let (receive_wait, host_input) = rest.split_at(8);
+
+        (self.status, self.command, self.control, self.receive_data) = (*status, *command, *control, *receive_data);
+        self.transmit_data = (*has_transmit_data != 0).then_some(*transmit_data);
+        self.transmit_shift = (*has_transmit_shift != 0).then(|| (*shift_data, u64::from_le_bytes(shift_cycles.try_into().unwrap())));
+        self.irq_pending = *irq_pending != 0;
+        self.receive_wait = u64::from_le_bytes(receive_wait.try_into().unwrap());
+        self.host_input = host_input.iter().copied().collect();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use crate::devices::serial::QueueBridge;
+
+    const CPU_HZ: u32 = 1_000_000;
+
+    // 8 data bits, 1 stop bit, 9600 baud
+    const CONTROL_8N1_9600: u8 = 0x0E;
+
+    fn tick_times(acia: &mut Acia<QueueBridge>, times: u64) {
+        for _ in 0..times {
+            acia.tick();
+        }
+    }
+
+    /**
+     * Whether or not transmitted bytes reach the host after a frame time
+     */
+    #[test]
+    fn transmit_timing() {
+        let mut acia = Acia::new(QueueBridge::new(), CPU_HZ);
+        acia.write(CONTROL, CONTROL_8N1_9600);
+        acia.write(COMMAND, COMMAND_DTR | COMMAND_IRD);
+
+        // 10 bits at 9600 baud take 1042 cycles at 1 MHz
+        assert_eq!(acia.cycles_per_frame(), 1042);
+
+        acia.write(DATA, b'H');
+        assert_eq!(acia.read(STATUS) & STATUS_TDRE, 0x00);
+
+        // the byte moves to the shift register, freeing the data register
+        acia.tick();
+        assert_eq!(acia.read(STATUS) & STATUS_TDRE, STATUS_TDRE);
+        acia.write(DATA, b'i');
+
+        tick_times(&mut acia, 1041);
+        assert!(acia.bridge_mut().take_output().is_empty());
+        acia.tick();
+        assert_eq!(acia.bridge_mut().take_output(), b"H".to_vec());
+
+        tick_times(&mut acia, 1042);
+        assert_eq!(acia.bridge_mut().take_output(), b"i".to_vec());
+    }
+
+    /**
+     * Whether or not received bytes raise the interrupt and overrun when not read in time
+     */
+    #[test]
+    fn receive_interrupt_and_overrun() {
+        let mut acia = Acia::new(QueueBridge::new(), CPU_HZ);
+        acia.write(CONTROL, CONTROL_8N1_9600);
+        acia.write(COMMAND, COMMAND_DTR);
+
+        acia.input(0, b'a');
+        acia.input(0, b'b');
+        acia.tick();
+        assert!(acia.irq());
+
+        let status = acia.read(STATUS);
+        assert_eq!(status & (STATUS_IRQ | STATUS_RDRF), STATUS_IRQ | STATUS_RDRF);
+        assert!(!acia.irq());
+
+        // the second byte arrives a frame later, the first one wasn't read
+        tick_times(&mut acia, 1042);
+        assert_eq!(acia.read(STATUS) & STATUS_OVERRUN, 0x00);
+        acia.tick();
+        assert_eq!(acia.read(STATUS) & STATUS_OVERRUN, STATUS_OVERRUN);
+
+        assert_eq!(acia.read(DATA), b'b');
+        assert_eq!(acia.read(STATUS) & (STATUS_RDRF | STATUS_OVERRUN), 0x00);
+    }
+
+    /**
+     * Whether or not the ACIA stays quiet until DTR is asserted
+     */
+    #[test]
+    fn disabled_until_dtr() {
+        let mut acia = Acia::new(QueueBridge::new(), CPU_HZ);
+        acia.input(0, b'x');
+
+        tick_times(&mut acia, 10);
+        assert_eq!(acia.read(STATUS) & STATUS_RDRF, 0x00);
+
+        acia.write(COMMAND, COMMAND_DTR | COMMAND_IRD | COMMAND_ECHO);
+        acia.tick();
+        assert_eq!(acia.read(DATA), b'x');
+        assert_eq!(acia.bridge_mut().take_output(), b"x".to_vec());
+    }
+
+    /**
+     * Whether or not bytes waiting in the bridge are left for the host to give through `input()`
+     */
+    #[test]
+    fn receives_through_input_only() {
+        let mut acia = Acia::new(QueueBridge::new(), CPU_HZ);
+        acia.write(COMMAND, COMMAND_DTR | COMMAND_IRD);
+        acia.bridge_mut().push_input(b"x");
+
+        tick_times(&mut acia, 10);
+        assert_eq!(acia.read(STATUS) & STATUS_RDRF, 0x00);
+        assert_eq!(acia.bridge_mut().receive(), Some(b'x'));
+    }
+}
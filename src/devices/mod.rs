@@ -0,0 +1,41 @@
+pub mod acia;
+pub mod riot;
+pub mod serial;
+pub mod terminal;
+pub mod via;
+
+/**
+ * A memory mapped peripheral
+ *
+ * Devices are attached to the `Bus` at a start address and occupy `size()` consecutive addresses.
+ * Offsets passed to `read()` and `write()` are relative to the start address.
+ */
+pub trait BusDevice {
+    /// Number of addresses (registers) the device occupies
+    fn size(&self) -> u16;
+
+    /// Reads a register, reading may have side effects (e.g. clearing a status flag)
+    fn read(&mut self, offset: u16) -> u8;
+
+    fn write(&mut self, offset: u16, data: u8);
+
+    /// Called once every processor clock cycle
+    fn tick(&mut self) {}
+
+    /// Whether or not the device is pulling the IRQ line low
+    fn irq(&self) -> bool {
+        false
+    }
+
+    /// Applies an input from outside the machine (a key, levels on a port, ...), `port` selects which one.
+    /// Hosts should go through `Processor::input()` so that the input can be recorded and replayed
+    fn input(&mut self, _port: u8, _value: u8) {}
+
+    /// The registers and internal state, for save states and the execution history (empty for stateless devices)
+    fn save_state(&self) -> Vec<u8> {
+        Vec::new()
+    }
+
+    /// Restores a state returned by `save_state()`, states of another size are ignored
+    fn load_state(&mut self, _state: &[u8]) {}
+}
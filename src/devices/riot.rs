@@ -0,0 +1,373 @@
+use crate::devices::BusDevice;
+
+/// Size of the on-chip RAM
+pub const RAM_SIZE: u16 = 128;
+
+/// Offsets at and above this one select the I/O and timer registers instead of the RAM (the RS pin)
+const REGISTERS: u16 = 0x80;
+
+// I/O register offsets, selected while A2 is low
+const ORA: u16 = 0x0; // port A data
+const DDRA: u16 = 0x1; // port A data direction, 1 means output
+const ORB: u16 = 0x2; // port B data
+const DDRB: u16 = 0x3; // port B data direction, 1 means output
+
+// Address lines decoding the timer and interrupt registers while A2 is high
+const A0: u16 = 1 << 0;
+const A1: u16 = 1 << 1;
+const A2: u16 = 1 << 2;
+const A3: u16 = 1 << 3;
+const A4: u16 = 1 << 4;
+
+// Interrupt flag bits
+pub const IRQ_PA7: u8 = 1 << 6;
+pub const IRQ_TIMER: u8 = 1 << 7;
+
+const PA7: u8 = 1 << 7;
+
+/// Timer prescaler intervals selected by A1-A0 when writing the timer
+const PRESCALER_INTERVALS: [u16; 4] = [1, 8, 64, 1024];
+
+/**
+ * MOS 6532 RAM-I/O-Timer
+ *
+ * The device occupies 256 addresses: the 128 bytes of RAM at offsets 0x00-0x7F and the registers from 0x80,
+ * which mirror the chip's own decoding (A2 low: ports, A2 high: timer and interrupt flags).
+ * Systems decoding the RAM and the registers apart can attach the device twice with the same handle.
+ *
+ * The interval timer decrements every 1, 8, 64 or 1024 cycles, after passing zero it sets its flag
+ * and keeps counting down every cycle until it is written again.
+ */
+pub struct Riot {
+    ram: [u8; RAM_SIZE as usize],
+
+    // ports
+    output_a: u8,
+    output_b: u8,
+    ddr_a: u8,
+    ddr_b: u8,
+    /// Levels driven onto the port pins from the outside
+    input_a: u8,
+    input_b: u8,
+
+    // interval timer
+    timer: u8,
+    prescaler_interval: u16,
+    /// Cycles left until the next decrement
+    prescaler_count: u16,
+    timer_irq_enabled: bool,
+
+    // PA7 edge detection
+    pa7_rising_edge: bool,
+    pa7_irq_enabled: bool,
+
+    flags: u8,
+}
+
+impl Default for Riot {
+    fn default() -> Self {
+        Self {
+            ram: [0x00; RAM_SIZE as usize],
+            output_a: 0x00,
+            output_b: 0x00,
+            ddr_a: 0x00,
+            ddr_b: 0x00,
+            input_a: 0xFF, // pins float high
+            input_b: 0xFF,
+            timer: 0xFF,
+            prescaler_interval: 1024,
+            prescaler_count: 1024,
+            timer_irq_enabled: false,
+            pa7_rising_edge: false,
+            pa7_irq_enabled: false,
+            flags: 0x00,
+        }
+    }
+}
+
+// Constructor like implementation
+impl Riot {
+    pub fn new() -> Self {
+        Self {
+            ..Default::default()
+        }
+    }
+}
+
+// host side of the ports
+impl Riot {
+    /**
+     * Sets the levels on port A, an edge on PA7 sets the PA7 interrupt flag
+     */
+    pub fn set_port_a_input(&mut self, levels: u8) {
+        let pa7 = self.port_a() & PA7;
+        self.input_a = levels;
+        self.detect_pa7_edge(pa7);
+    }
+
+    pub fn set_port_b_input(&mut self, levels: u8) {
+        self.input_b = levels;
+    }
+
+    /**
+     * Levels seen on the port A pins, output bits come from the output register
+     */
+    pub fn port_a(&self) -> u8 {
+        (self.output_a & self.ddr_a) | (self.input_a & !self.ddr_a)
+    }
+
+    pub fn port_b(&self) -> u8 {
+        (self.output_b & self.ddr_b) | (self.input_b & !self.ddr_b)
+    }
+
+    /**
+     * The RAM, e.g. for a monitor or a debugger view
+     */
+    pub fn ram(&self) -> &[u8] {
+        &self.ram
+    }
+
+    fn detect_pa7_edge(&mut self, old_pa7: u8) {
+        let new_pa7 = self.port_a() & PA7;
+        let edge = if self.pa7_rising_edge {
+            old_pa7 == 0 && new_pa7 != 0
+        } else {
+            old_pa7 != 0 && new_pa7 == 0
+        };
+
+        if edge {
+            self.flags |= IRQ_PA7;
+        }
+    }
+}
+
+// registers
+impl Riot {
+    fn read_register(&mut self, offset: u16) -> u8 {
+        if (offset & A2) == 0 {
+            return match offset & (A1 | A0) {
+                ORA => self.port_a(),
+                DDRA => self.ddr_a,
+                ORB => self.port_b(),
+                DDRB => self.ddr_b,
+                _ => unreachable!(),
+            };
+        }
+
+        if (offset & A0) == 0 {
+            // reading the timer acknowledges its interrupt, A3 enables it
+            self.timer_irq_enabled = (offset & A3) != 0;
+            self.flags &= !IRQ_TIMER;
+            self.timer
+        } else {
+            // reading the flags acknowledges the PA7 interrupt
+            let flags = self.flags;
+            self.flags &= !IRQ_PA7;
+            flags
+        }
+    }
+
+    fn write_register(&mut self, offset: u16, data: u8) {
+        if (offset & A2) == 0 {
+            let pa7 = self.port_a() & PA7;
+            match offset & (A1 | A0) {
+                ORA => self.output_a = data,
+                DDRA => self.ddr_a = data,
+                ORB => self.output_b = data,
+                DDRB => self.ddr_b = data,
+                _ => unreachable!(),
+            }
+            self.detect_pa7_edge(pa7);
+            return;
+        }
+
+        if (offset & A4) != 0 {
+            // A1-A0 select the prescaler, A3 enables the timer interrupt
+            self.timer = data;
+            self.prescaler_interval = PRESCALER_INTERVALS[(offset & (A1 | A0)) as usize];
+            self.prescaler_count = self.prescaler_interval;
+            self.timer_irq_enabled = (offset & A3) != 0;
+            self.flags &= !IRQ_TIMER;
+        } else {
+            // edge detect control, A0 selects the rising edge, A1 enables the PA7 interrupt
+            self.pa7_rising_edge = (offset & A0) != 0;
+            self.pa7_irq_enabled = (offset & A1) != 0;
+        }
+    }
+}
+
+impl BusDevice for Riot {
+    fn size(&self) -> u16 {
+        REGISTERS * 2
+    }
+
+    fn read(&mut self, offset: u16) -> u8 {
+        if offset < REGISTERS {
+            self.ram[offset as usize]
+        } else {
+            self.read_register(offset)
+        }
+    }
+
+    fn write(&mut self, offset: u16, data: u8) {
+        if offset < REGISTERS {
+            self.ram[offset as usize] = data;
+        } else {
+            self.write_register(offset, data);
+        }
+    }
+
+    fn tick(&mut self) {
+        self.prescaler_count -= 1;
+        if self.prescaler_count > 0 {
+            return;
+        }
+
+        let (timer, expired) = self.timer.overflowing_sub(1);
+        self.timer = timer;
+
+        // once expired the timer counts down every cycle
+        if expired {
+            self.flags |= IRQ_TIMER;
+            self.prescaler_interval = 1;
+        }
+        self.prescaler_count = self.prescaler_interval;
+    }
+
+    fn irq(&self) -> bool {
+        ((self.flags & IRQ_TIMER) != 0 && self.timer_irq_enabled) || ((self.flags & IRQ_PA7) != 0 && self.pa7_irq_enabled)
+    }
+
+    /// Ports 0 and 1 are the levels on the pins of port A and B
+    fn input(&mut self, port: u8, value: u8) {
+        match port {
+            0 => self.set_port_a_input(value),
+            1 => self.set_port_b_input(value),
+            _ => (),
+        }
+    }
+
+    fn save_state(&self) -> Vec<u8> {
+        let [interval_low, interval_high] = self.prescaler_interval.to_le_bytes();
+        let [count_low, count_high] = self.prescaler_count.to_le_bytes();
+
+        let mut state = self.ram.to_vec();
+        state.extend([
+            self.output_a, self.output_b, self.ddr_a, self.ddr_b, self.input_a, self.input_b,
+            self.timer, interval_low, interval_high, count_low, count_high, self.timer_irq_enabled as u8,
+            self.pa7_rising_edge as u8, self.pa7_irq_enabled as u8, self.flags,
+        ]);
+        state
+    }
+
+    fn load_state(&mut self, state: &[u8]) {
+        if state.len() < RAM_SIZE as usize {
+            return;
+        }
+        let (ram, registers) = state.split_at(RAM_SIZE as usize);
+        let &[
+            output_a, output_b, ddr_a, ddr_b, input_a, input_b,
+            timer, interval_low, interval_high, count_low, count_high, timer_irq_enabled,
+            pa7_rising_edge, pa7_irq_enabled, flags,
+        ] = registers else {
+            return;
+        };
+
+        self.ram.copy_from_slice(ram);
+        (self.output_a, self.output_b, self.ddr_a, self.ddr_b, self.input_a, self.input_b) = (output_a, output_b, ddr_a, ddr_b, input_a, input_b);
+        self.timer = timer;
+        self.prescaler_interval = u16::from_le_bytes([interval_low, interval_high]);
+        self.prescaler_count = u16::from_le_bytes([count_low, count_high]);
+        self.timer_irq_enabled = timer_irq_enabled != 0;
+        (self.pa7_rising_edge, self.pa7_irq_enabled, self.flags) = (pa7_rising_edge != 0, pa7_irq_enabled != 0, flags);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    // timer write offsets, with the interrupt enabled
+    const TIMER_1T: u16 = REGISTERS | A4 | A3 | A2;
+    const TIMER_64T: u16 = REGISTERS | A4 | A3 | A2 | A1;
+    const READ_TIMER: u16 = REGISTERS | A3 | A2;
+    const READ_FLAGS: u16 = REGISTERS | A2 | A0;
+
+    fn tick_times(riot: &mut Riot, times: usize) {
+        for _ in 0..times {
+            riot.tick();
+        }
+    }
+
+    /**
+     * Whether or not the RAM and the ports are decoded apart
+     */
+    #[test]
+    fn ram_and_ports() {
+        let mut riot = Riot::new();
+
+        riot.write(0x00, 0x12);
+        riot.write(0x7F, 0x34);
+        assert_eq!(riot.read(0x00), 0x12);
+        assert_eq!(riot.read(0x7F), 0x34);
+        assert_eq!(riot.ram()[0x7F], 0x34);
+
+        riot.write(REGISTERS | DDRA, 0x0F);
+        riot.write(REGISTERS | ORA, 0x5A);
+        riot.set_port_a_input(0x30);
+        assert_eq!(riot.read(REGISTERS | ORA), 0x3A);
+
+        // the I/O registers are mirrored while A2 is low
+        riot.write(REGISTERS | 0x08 | DDRB, 0xFF);
+        riot.write(REGISTERS | ORB, 0xC3);
+        assert_eq!(riot.port_b(), 0xC3);
+    }
+
+    /**
+     * Whether or not the timer follows its prescaler and counts every cycle after expiring
+     */
+    #[test]
+    fn interval_timer() {
+        let mut riot = Riot::new();
+
+        riot.write(TIMER_64T, 2);
+        tick_times(&mut riot, 64);
+        assert_eq!(riot.read(READ_TIMER), 1);
+        tick_times(&mut riot, 127);
+        assert!(!riot.irq());
+        tick_times(&mut riot, 1);
+        assert!(riot.irq());
+        assert_eq!(riot.read(READ_FLAGS) & IRQ_TIMER, IRQ_TIMER);
+
+        // reading the timer acknowledges, it keeps counting every cycle
+        assert_eq!(riot.read(READ_TIMER), 0xFF);
+        assert!(!riot.irq());
+        tick_times(&mut riot, 3);
+        assert_eq!(riot.read(READ_TIMER), 0xFC);
+
+        // the interrupt stays off without A3
+        riot.write(TIMER_1T & !A3, 0);
+        tick_times(&mut riot, 1);
+        assert!(!riot.irq());
+        assert_eq!(riot.read(READ_FLAGS) & IRQ_TIMER, IRQ_TIMER);
+    }
+
+    /**
+     * Whether or not edges on PA7 raise the interrupt
+     */
+    #[test]
+    fn pa7_edge_interrupt() {
+        let mut riot = Riot::new();
+
+        // rising edge, interrupt enabled
+        riot.write(REGISTERS | A2 | A1 | A0, 0x00);
+        riot.set_port_a_input(0x00);
+        assert!(!riot.irq());
+        riot.set_port_a_input(PA7);
+        assert!(riot.irq());
+
+        assert_eq!(riot.read(READ_FLAGS), IRQ_PA7);
+        assert!(!riot.irq());
+    }
+}
@@ -0,0 +1,281 @@
+use std::collections::VecDeque;
+
+/**
+ * The host end of a serial line
+ *
+ * Serial devices hand over every transmitted byte with `transmit()`. Received bytes are pulled with
+ * `receive()` by the host, which gives them to the device through `Processor::input()` so that they are
+ * recorded (and ignored while a recording is replayed).
+ */
+pub trait SerialBridge {
+    /// Returns the next byte sent by the host, if one is waiting
+    fn receive(&mut self) -> Option<u8>;
+
+    fn transmit(&mut self, byte: u8);
+}
+
+/**
+ * In-memory byte queues, mostly useful for tests and the wasm frontend
+ */
+#[derive(Default)]
+pub struct QueueBridge {
+    pub input: VecDeque<u8>,
+    pub output: VecDeque<u8>,
+}
+
+impl QueueBridge {
+    pub fn new() -> Self {
+        Self {
+            ..Default::default()
+        }
+    }
+
+    pub fn push_input(&mut self, bytes: &[u8]) {
+        self.input.extend(bytes);
+    }
+
+    /**
+     * Returns the bytes transmitted since the previous call
+     */
+    pub fn take_output(&mut self) -> Vec<u8> {
+        self.output.drain(..).collect()
+    }
+}
+
+impl SerialBridge for QueueBridge {
+    fn receive(&mut self) -> Option<u8> {
+        self.input.pop_front()
+    }
+
+    fn transmit(&mut self, byte: u8) {
+        self.output.push_back(byte);
+    }
+}
+
+/**
+ * Lets the bridge be picked at run time (e.g. from the command line)
+ */
+impl<B: SerialBridge + ?Sized> SerialBridge for Box<B> {
+    fn receive(&mut self) -> Option<u8> {
+        (**self).receive()
+    }
+
+    fn transmit(&mut self, byte: u8) {
+        (**self).transmit(byte)
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+pub use self::tcp::TcpBridge;
+
+#[cfg(not(target_arch = "wasm32"))]
+mod tcp {
+    use std::collections::VecDeque;
+    use std::io::{self, ErrorKind, Read, Write};
+    use std::net::{Ipv4Addr, SocketAddr, TcpListener, TcpStream};
+
+    use super::SerialBridge;
+
+    /**
+     * Serves the serial line on a TCP port on localhost (e.g. `telnet localhost <port>`)
+     *
+     * A single client is served at a time, a new one can connect once the previous one has disconnected.
+     * Bytes transmitted while nobody is connected are dropped, like on an unplugged cable.
+     */
+    pub struct TcpBridge {
+        listener: TcpListener,
+        stream: Option<TcpStream>,
+        received: VecDeque<u8>,
+    }
+
+    impl TcpBridge {
+        /**
+         * Starts listening on `127.0.0.1:port`, port 0 picks a free one (see `local_address()`)
+         */
+        pub fn new(port: u16) -> io::Result<Self> {
+            let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, port))?;
+            listener.set_nonblocking(true)?;
+
+            Ok(Self {
+                listener,
+                stream: None,
+                received: VecDeque::new(),
+            })
+        }
+
+        pub fn local_address(&self) -> io::Result<SocketAddr> {
+            self.listener.local_addr()
+        }
+
+        pub fn is_connected(&self) -> bool {
+            self.stream.is_some()
+        }
+
+        fn accept(&mut self) {
+            if self.stream.is_none() {
+                if let Ok((stream, _)) = self.listener.accept() {
+                    if stream.set_nonblocking(true).is_ok() {
+                        let _ = stream.set_nodelay(true);
+                        self.stream = Some(stream);
+                    }
+                }
+            }
+        }
+    }
+
+    impl SerialBridge for TcpBridge {
+        fn receive(&mut self) -> Option<u8> {
+            self.accept();
+
+            if self.received.is_empty() {
+                if let Some(stream) = &mut self.stream {
+                    let mut buffer = [0u8; 256];
+                    match stream.read(&mut buffer) {
+                        Ok(0) => self.stream = None,
+                        Ok(length) => self.received.extend(&buffer[..length]),
+                        Err(error) if error.kind() == ErrorKind::WouldBlock => (),
+                        Err(_) => self.stream = None,
+                    }
+                }
+            }
+
+            self.received.pop_front()
+        }
+
+        fn transmit(&mut self, byte: u8) {
+            self.accept();
+
+            if let Some(stream) = &mut self.stream {
+                if stream.write_all(&[byte]).is_err() {
+                    self.stream = None;
+                }
+            }
+        }
+    }
+}
+
+#[cfg(unix)]
+pub use self::pty::PtyBridge;
+
+#[cfg(unix)]
+mod pty {
+    use std::collections::VecDeque;
+    use std::ffi::CStr;
+    use std::fs::File;
+    use std::io::{self, ErrorKind, Read, Write};
+    use std::os::unix::io::FromRawFd;
+
+    use super::SerialBridge;
+
+    /**
+     * Exposes the serial line as a Unix pseudo terminal
+     *
+     * Terminal programs connect to the slave side, whose path is returned by `path()`
+     * (e.g. `screen /dev/pts/3 9600`).
+     */
+    pub struct PtyBridge {
+        master: File,
+        path: String,
+        received: VecDeque<u8>,
+    }
+
+    impl PtyBridge {
+        pub fn new() -> io::Result<Self> {
+            // SAFETY: plain libc calls on a file descriptor we own, ptsname's buffer is copied right away
+            unsafe {
+                let fd = libc::posix_openpt(libc::O_RDWR | libc::O_NOCTTY);
+                if fd < 0 {
+                    return Err(io::Error::last_os_error());
+                }
+                let master = File::from_raw_fd(fd);
+
+                if libc::grantpt(fd) != 0 || libc::unlockpt(fd) != 0 {
+                    return Err(io::Error::last_os_error());
+                }
+
+                let name = libc::ptsname(fd);
+                if name.is_null() {
+                    return Err(io::Error::last_os_error());
+                }
+                let path = CStr::from_ptr(name).to_string_lossy().into_owned();
+
+                let flags = libc::fcntl(fd, libc::F_GETFL);
+                if flags < 0 || libc::fcntl(fd, libc::F_SETFL, flags | libc::O_NONBLOCK) < 0 {
+                    return Err(io::Error::last_os_error());
+                }
+
+                Ok(Self {
+                    master,
+                    path,
+                    received: VecDeque::new(),
+                })
+            }
+        }
+
+        /**
+         * Path of the slave side of the pseudo terminal
+         */
+        pub fn path(&self) -> &str {
+            &self.path
+        }
+    }
+
+    impl SerialBridge for PtyBridge {
+        fn receive(&mut self) -> Option<u8> {
+            if self.received.is_empty() {
+                let mut buffer = [0u8; 256];
+                match self.master.read(&mut buffer) {
+                    Ok(length) => self.received.extend(&buffer[..length]),
+                    // nothing to read, or nobody has the slave side open (EIO)
+                    Err(error) if error.kind() == ErrorKind::WouldBlock => (),
+                    Err(_) => (),
+                }
+            }
+
+            self.received.pop_front()
+        }
+
+        fn transmit(&mut self, byte: u8) {
+            // dropped while nobody reads the slave side and the buffer is full
+            let _ = self.master.write_all(&[byte]);
+        }
+    }
+}
+
+#[cfg(all(test, not(target_arch = "wasm32")))]
+mod tests {
+
+    use super::*;
+    use std::io::{Read, Write};
+    use std::net::TcpStream;
+    use std::thread;
+    use std::time::Duration;
+
+    /**
+     * Whether or not bytes go both ways through a localhost TCP client
+     */
+    #[test]
+    fn tcp_bridge_round_trip() {
+        let mut bridge = TcpBridge::new(0).unwrap();
+        let mut client = TcpStream::connect(bridge.local_address().unwrap()).unwrap();
+        client.write_all(b"ok").unwrap();
+
+        let mut received = Vec::new();
+        for _ in 0..100 {
+            if let Some(byte) = bridge.receive() {
+                received.push(byte);
+            }
+            if received.len() == 2 {
+                break;
+            }
+            thread::sleep(Duration::from_millis(10));
+        }
+        assert_eq!(received, b"ok".to_vec());
+        assert!(bridge.is_connected());
+
+        bridge.transmit(b'!');
+        let mut buffer = [0u8; 1];
+        client.read_exact(&mut buffer).unwrap();
+        assert_eq!(&buffer, b"!");
+    }
+}
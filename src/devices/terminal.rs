@@ -0,0 +1,285 @@
+use std::collections::VecDeque;
+
+use crate::devices::BusDevice;
+
+/// Where the Apple-1 maps its PIA, Wozmon expects the terminal here
+pub const APPLE_1_ADDRESS: u16 = 0xD010;
+
+// Register offsets (same layout as the Apple-1 PIA)
+const KBD: u16 = 0; // keyboard data (or data direction)
+const KBDCR: u16 = 1; // keyboard control, bit 7 is set when a key is available
+const DSP: u16 = 2; // display data (or data direction), bit 7 is set while the display is busy
+const DSPCR: u16 = 3; // display control
+
+const BIT_7: u8 = 0x80;
+/// Control register bit selecting the data register, the data direction register is selected while it's clear
+const DATA_REGISTER: u8 = 0x04;
+
+const CARRIAGE_RETURN: u8 = 0x0D;
+
+/**
+ * Character terminal in the style of the Apple-1 PIA
+ *
+ * The keyboard and display are plain byte queues, the host pushes keys with `push_input()`
+ * and collects printed characters with `take_output()`.
+ * The display is never busy, every character written to `DSP` ends up in the output queue immediately.
+ * Like on the PIA, `KBD` and `DSP` are the data direction registers until bit 2 of their control register is set,
+ * Wozmon writes the display direction (0x7F) before that and it isn't printed.
+ */
+pub struct Terminal {
+    input: VecDeque<u8>,
+    output: VecDeque<u8>,
+
+    /// The Apple-1 only knows upper case characters
+    uppercase_input: bool,
+
+    keyboard_control: u8,
+    display_control: u8,
+    keyboard_direction: u8,
+    display_direction: u8,
+}
+
+impl Default for Terminal {
+    fn default() -> Self {
+        Self {
+            input: VecDeque::new(),
+            output: VecDeque::new(),
+            uppercase_input: true,
+            keyboard_control: 0x00,
+            display_control: 0x00,
+            keyboard_direction: 0x00,
+            display_direction: 0x00,
+        }
+    }
+}
+
+// Constructor like implementation
+impl Terminal {
+    pub fn new() -> Self {
+        Self {
+            ..Default::default()
+        }
+    }
+}
+
+// host side of the terminal
+impl Terminal {
+    pub fn set_uppercase_input(&mut self, uppercase_input: bool) {
+        self.uppercase_input = uppercase_input;
+    }
+
+    /**
+     * Queues a key press
+     *
+     * Line feeds are turned into carriage returns as the Apple-1 expects
+     */
+    pub fn push_input(&mut self, key: u8) {
+        let key = match key {
+            b'\n' => CARRIAGE_RETURN,
+            key if self.uppercase_input => key.to_ascii_uppercase(),
+            key => key,
+        };
+
+        self.input.push_back(key & !BIT_7);
+    }
+
+    pub fn push_input_str(&mut self, keys: &str) {
+        for key in keys.bytes() {
+            self.push_input(key);
+        }
+    }
+
+    pub fn has_pending_input(&self) -> bool {
+        !self.input.is_empty()
+    }
+
+    /**
+     * Returns the characters printed since the previous call
+     *
+     * Carriage returns are turned into line feeds for the host
+     */
+    pub fn take_output(&mut self) -> Vec<u8> {
+        self.output
+            .drain(..)
+            .map(|character| match character {
+                CARRIAGE_RETURN => b'\n',
+                character => character,
+            })
+            .collect()
+    }
+}
+
+impl BusDevice for Terminal {
+    fn size(&self) -> u16 {
+        4
+    }
+
+    fn read(&mut self, offset: u16) -> u8 {
+        match offset {
+            KBD if self.keyboard_control & DATA_REGISTER == 0 => self.keyboard_direction,
+            // reading the key acknowledges it
+            KBD => match self.input.pop_front() {
+                Some(key) => key | BIT_7,
+                None => 0x00,
+            },
+            KBDCR => {
+                if self.input.is_empty() {
+                    self.keyboard_control & !BIT_7
+                } else {
+                    self.keyboard_control | BIT_7
+                }
+            }
+            DSP if self.display_control & DATA_REGISTER == 0 => self.display_direction,
+            // bit 7 clear, the display is always ready
+            DSP => 0x00,
+            DSPCR => self.display_control,
+            _ => 0x00,
+        }
+    }
+
+    fn write(&mut self, offset: u16, data: u8) {
+        match offset {
+            KBD if self.keyboard_control & DATA_REGISTER == 0 => self.keyboard_direction = data,
+            KBDCR => self.keyboard_control = data & !BIT_7,
+            DSP if self.display_control & DATA_REGISTER == 0 => self.display_direction = data,
+            DSP => self.output.push_back(data & !BIT_7),
+            DSPCR => self.display_control = data,
+            _ => (),
+        }
+    }
+
+    /// Port 0 is the keyboard
+    fn input(&mut self, port: u8, value: u8) {
+        if port == 0 {
+            self.push_input(value);
+        }
+    }
+
+    /// The control registers and the keys not read yet, the output already belongs to the host
+    fn save_state(&self) -> Vec<u8> {
+        let mut state = vec![
+            self.uppercase_input as u8,
+            self.keyboard_control,
+            self.display_control,
+            self.keyboard_direction,
+            self.display_direction,
+        ];
+        state.extend(&self.input);
+        state
+    }
+
+    fn load_state(&mut self, state: &[u8]) {
+        if let [uppercase_input, keyboard_control, display_control, keyboard_direction, display_direction, input @ ..] = state {
+            self.uppercase_input = *uppercase_input != 0;
+            self.keyboard_control = *keyboard_control;
+            self.display_control = *display_control;
+            self.keyboard_direction = *keyboard_direction;
+            self.display_direction = *display_direction;
+            self.input = input.iter().copied().collect();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    use super::*;
+    use crate::processor::Processor;
+
+    /// Control register value Wozmon sets, data register selected and CA1 on the rising edge
+    const WOZMON_CONTROL: u8 = 0xA7;
+
+    /**
+     * Whether or not keys go through the keyboard registers like on an Apple-1
+     */
+    #[test]
+    fn keyboard_registers() {
+        let mut terminal = Terminal::new();
+        terminal.write(KBDCR, WOZMON_CONTROL);
+        assert_eq!(terminal.read(KBDCR) & BIT_7, 0x00);
+
+        terminal.push_input_str("a\n");
+
+        assert_eq!(terminal.read(KBDCR) & BIT_7, BIT_7);
+        assert_eq!(terminal.read(KBD), b'A' | BIT_7);
+        assert_eq!(terminal.read(KBD), CARRIAGE_RETURN | BIT_7);
+        assert_eq!(terminal.read(KBDCR) & BIT_7, 0x00);
+    }
+
+    /**
+     * Whether or not characters written to the display end up in the output queue
+     */
+    #[test]
+    fn display_register() {
+        let mut terminal = Terminal::new();
+        terminal.write(DSPCR, WOZMON_CONTROL);
+
+        assert_eq!(terminal.read(DSP) & BIT_7, 0x00);
+        for character in [b'O' | BIT_7, b'K' | BIT_7, CARRIAGE_RETURN | BIT_7] {
+            terminal.write(DSP, character);
+        }
+
+        assert_eq!(terminal.take_output(), b"OK\n".to_vec());
+        assert!(terminal.take_output().is_empty());
+    }
+
+    /**
+     * Whether or not the direction registers are selected until bit 2 of the control registers is set
+     */
+    #[test]
+    fn data_direction_registers() {
+        let mut terminal = Terminal::new();
+        terminal.push_input(b'A');
+
+        terminal.write(KBD, 0x00);
+        terminal.write(DSP, 0x7F);
+        assert_eq!(terminal.read(KBD), 0x00);
+        assert_eq!(terminal.read(DSP), 0x7F);
+        assert!(terminal.has_pending_input());
+        assert!(terminal.take_output().is_empty());
+
+        terminal.write(KBDCR, WOZMON_CONTROL);
+        terminal.write(DSPCR, WOZMON_CONTROL);
+        assert_eq!(terminal.read(KBD), b'A' | BIT_7);
+        assert_eq!(terminal.read(DSP), 0x00);
+    }
+
+    /**
+     * Whether or not the Wozmon reset code sets the terminal up without printing anything
+     */
+    #[test]
+    fn wozmon_init() {
+        let program = [
+            0xD8,             // CLD
+            0x58,             // CLI
+            0xA0, 0x7F,       // LDY #$7F
+            0x8C, 0x12, 0xD0, // STY DSP
+            0xA9, 0xA7,       // LDA #$A7
+            0x8D, 0x11, 0xD0, // STA KBDCR
+            0x8D, 0x13, 0xD0, // STA DSPCR
+            0xA9, 0xDC,       // LDA #$DC (backslash)
+            0x2C, 0x12, 0xD0, // BIT DSP
+            0x30, 0xFB,       // BMI (back to BIT DSP)
+            0x8D, 0x12, 0xD0, // STA DSP
+            0x02,             // JAM
+        ];
+        let mut processor = Processor::new();
+        for (offset, byte) in program.iter().enumerate() {
+            processor.bus.write(0x8000 + offset as u16, *byte);
+        }
+        processor.bus.write(0xFFFC, 0x00);
+        processor.bus.write(0xFFFD, 0x80);
+        processor.reset();
+
+        let terminal = Rc::new(RefCell::new(Terminal::new()));
+        processor.bus.attach_device(APPLE_1_ADDRESS, terminal.clone());
+        while !processor.is_halted() {
+            processor.step();
+        }
+
+        assert_eq!(terminal.borrow_mut().take_output(), b"\\".to_vec());
+    }
+}
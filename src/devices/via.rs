@@ -0,0 +1,636 @@
+use crate::devices::BusDevice;
+
+/// Where most hobby boards decode the VIA, inside the "other" region of the bus
+pub const DEFAULT_ADDRESS: u16 = 0x6000;
+
+// Register offsets
+const ORB: u16 = 0x0; // output / input register B
+const ORA: u16 = 0x1; // output / input register A, with handshake
+const DDRB: u16 = 0x2; // data direction register B, 1 means output
+const DDRA: u16 = 0x3; // data direction register A, 1 means output
+const T1C_L: u16 = 0x4; // timer 1 counter low byte (write goes to the latch)
+const T1C_H: u16 = 0x5; // timer 1 counter high byte (write starts the timer)
+const T1L_L: u16 = 0x6; // timer 1 latch low byte
+const T1L_H: u16 = 0x7; // timer 1 latch high byte
+const T2C_L: u16 = 0x8; // timer 2 counter low byte (write goes to the latch)
+const T2C_H: u16 = 0x9; // timer 2 counter high byte (write starts the timer)
+const SR: u16 = 0xA; // shift register
+const ACR: u16 = 0xB; // auxiliary control register
+const PCR: u16 = 0xC; // peripheral control register
+const IFR: u16 = 0xD; // interrupt flag register
+const IER: u16 = 0xE; // interrupt enable register
+const ORA_NO_HANDSHAKE: u16 = 0xF; // output / input register A, without handshake
+
+// Interrupt flag bits (same positions in IFR and IER)
+pub const IRQ_CA2: u8 = 1 << 0;
+pub const IRQ_CA1: u8 = 1 << 1;
+pub const IRQ_SR: u8 = 1 << 2;
+pub const IRQ_CB2: u8 = 1 << 3;
+pub const IRQ_CB1: u8 = 1 << 4;
+pub const IRQ_T2: u8 = 1 << 5;
+pub const IRQ_T1: u8 = 1 << 6;
+const IRQ_ANY: u8 = 1 << 7;
+
+// Auxiliary control register bits
+const ACR_T1_PB7_OUTPUT: u8 = 1 << 7;
+const ACR_T1_FREE_RUN: u8 = 1 << 6;
+const ACR_T2_COUNT_PB6: u8 = 1 << 5;
+const ACR_SR_MODE_SHIFT: u8 = 2;
+const ACR_SR_MODE_MASK: u8 = 0b111 << ACR_SR_MODE_SHIFT;
+
+const PB6: u8 = 1 << 6;
+const PB7: u8 = 1 << 7;
+
+/**
+ * How the shift register is clocked, bits 4-2 of the auxiliary control register
+ */
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ShiftMode {
+    Disabled,
+    InUnderT2,
+    InUnderPhi2,
+    InUnderCb1,
+    OutFreeRunningT2,
+    OutUnderT2,
+    OutUnderPhi2,
+    OutUnderCb1,
+}
+
+impl ShiftMode {
+    fn from_acr(acr: u8) -> Self {
+        match (acr & ACR_SR_MODE_MASK) >> ACR_SR_MODE_SHIFT {
+            0 => ShiftMode::Disabled,
+            1 => ShiftMode::InUnderT2,
+            2 => ShiftMode::InUnderPhi2,
+            3 => ShiftMode::InUnderCb1,
+            4 => ShiftMode::OutFreeRunningT2,
+            5 => ShiftMode::OutUnderT2,
+            6 => ShiftMode::OutUnderPhi2,
+            _ => ShiftMode::OutUnderCb1,
+        }
+    }
+
+    fn is_output(&self) -> bool {
+        matches!(
+            self,
+            ShiftMode::OutFreeRunningT2 | ShiftMode::OutUnderT2 | ShiftMode::OutUnderPhi2 | ShiftMode::OutUnderCb1
+        )
+    }
+}
+
+/**
+ * MOS 6522 Versatile Interface Adapter
+ *
+ * Covers both I/O ports with their data direction registers, timer 1 (one-shot and free-run, with PB7 output),
+ * timer 2 (one-shot and PB6 pulse counting), the shift register and the interrupt flag/enable registers.
+ * CA1/CB1 and CA2/CB2 are modelled as interrupt inputs, the CA2/CB2 handshake output modes aren't.
+ * The timers run from the processor clock through `tick()`.
+ */
+pub struct Via {
+    // ports
+    output_a: u8,
+    output_b: u8,
+    ddr_a: u8,
+    ddr_b: u8,
+    /// Levels driven onto the port pins from the outside
+    input_a: u8,
+    input_b: u8,
+
+    // timer 1
+    t1_counter: u16,
+    t1_latch: u16,
+    /// In one-shot mode only the first time-out after loading raises the interrupt
+    t1_armed: bool,
+    t1_pb7: bool,
+
+    // timer 2
+    t2_counter: u16,
+    t2_latch_low: u8,
+    t2_armed: bool,
+
+    // shift register
+    shift_register: u8,
+    /// Number of bits shifted since the shift register was last accessed
+    shift_count: u8,
+    /// Level of the CB2 line while shifting in
+    cb2_input: bool,
+    /// Level last shifted out onto CB2
+    cb2_output: bool,
+
+    // control lines
+    ca1: bool,
+    ca2: bool,
+    cb1: bool,
+    cb2: bool,
+
+    acr: u8,
+    pcr: u8,
+    ifr: u8,
+    ier: u8,
+}
+
+impl Default for Via {
+    fn default() -> Self {
+        Self {
+            output_a: 0x00,
+            output_b: 0x00,
+            ddr_a: 0x00,
+            ddr_b: 0x00,
+            input_a: 0xFF, // pins float high
+            input_b: 0xFF,
+            t1_counter: 0xFFFF,
+            t1_latch: 0xFFFF,
+            t1_armed: false,
+            t1_pb7: true,
+            t2_counter: 0xFFFF,
+            t2_latch_low: 0xFF,
+            t2_armed: false,
+            shift_register: 0x00,
+            shift_count: 0,
+            cb2_input: false,
+            cb2_output: false,
+            ca1: false,
+            ca2: false,
+            cb1: false,
+            cb2: false,
+            acr: 0x00,
+            pcr: 0x00,
+            ifr: 0x00,
+            ier: 0x00,
+        }
+    }
+}
+
+// Constructor like implementation
+impl Via {
+    pub fn new() -> Self {
+        Self {
+            ..Default::default()
+        }
+    }
+}
+
+// host side of the ports and control lines
+impl Via {
+    pub fn set_port_a_input(&mut self, levels: u8) {
+        self.input_a = levels;
+    }
+
+    /**
+     * Sets the levels on port B, a falling edge on PB6 counts down timer 2 in pulse counting mode
+     */
+    pub fn set_port_b_input(&mut self, levels: u8) {
+        let pb6_falling = (self.input_b & PB6) != 0 && (levels & PB6) == 0;
+        self.input_b = levels;
+
+        if pb6_falling && (self.acr & ACR_T2_COUNT_PB6) != 0 {
+            self.count_down_t2();
+        }
+    }
+
+    /**
+     * Levels seen on the port A pins, output bits come from the output register
+     */
+    pub fn port_a(&self) -> u8 {
+        (self.output_a & self.ddr_a) | (self.input_a & !self.ddr_a)
+    }
+
+    /**
+     * Levels seen on the port B pins, PB7 is driven by timer 1 when enabled in ACR
+     */
+    pub fn port_b(&self) -> u8 {
+        let levels = (self.output_b & self.ddr_b) | (self.input_b & !self.ddr_b);
+
+        if (self.acr & ACR_T1_PB7_OUTPUT) != 0 {
+            (levels & !PB7) | if self.t1_pb7 { PB7 } else { 0x00 }
+        } else {
+            levels
+        }
+    }
+
+    pub fn set_ca1(&mut self, level: bool) {
+        // PCR bit 0 selects the active edge, 1 means rising
+        if Self::is_active_edge(self.ca1, level, (self.pcr & 0x01) != 0) {
+            self.ifr |= IRQ_CA1;
+        }
+        self.ca1 = level;
+    }
+
+    pub fn set_ca2(&mut self, level: bool) {
+        // PCR bits 3-1, only the input modes (bit 3 clear) are modelled, bit 2 selects the active edge
+        if (self.pcr & 0x08) == 0 && Self::is_active_edge(self.ca2, level, (self.pcr & 0x04) != 0) {
+            self.ifr |= IRQ_CA2;
+        }
+        self.ca2 = level;
+    }
+
+    /**
+     * Sets the CB1 level, CB1 also clocks the shift register in the external clock modes
+     */
+    pub fn set_cb1(&mut self, level: bool) {
+        if Self::is_active_edge(self.cb1, level, (self.pcr & 0x10) != 0) {
+            self.ifr |= IRQ_CB1;
+        }
+
+        let rising = !self.cb1 && level;
+        self.cb1 = level;
+
+        if rising && matches!(ShiftMode::from_acr(self.acr), ShiftMode::InUnderCb1 | ShiftMode::OutUnderCb1) {
+            self.shift();
+        }
+    }
+
+    /**
+     * Sets the CB2 level, it is also the data input of the shift register
+     */
+    pub fn set_cb2(&mut self, level: bool) {
+        if (self.pcr & 0x80) == 0 && Self::is_active_edge(self.cb2, level, (self.pcr & 0x40) != 0) {
+            self.ifr |= IRQ_CB2;
+        }
+        self.cb2 = level;
+        self.cb2_input = level;
+    }
+
+    /**
+     * Level of CB2 while the shift register shifts out
+     */
+    pub fn cb2_output(&self) -> bool {
+        self.cb2_output
+    }
+
+    fn is_active_edge(old: bool, new: bool, rising: bool) -> bool {
+        if rising {
+            !old && new
+        } else {
+            old && !new
+        }
+    }
+}
+
+// timers and shift register
+impl Via {
+    fn count_down_t2(&mut self) {
+        self.t2_counter = self.t2_counter.wrapping_sub(1);
+
+        if self.t2_counter == 0xFFFF && self.t2_armed {
+            self.t2_armed = false;
+            self.ifr |= IRQ_T2;
+        }
+    }
+
+    /**
+     * Shifts one bit, MSB first
+     */
+    fn shift(&mut self) {
+        let mode = ShiftMode::from_acr(self.acr);
+
+        // the free running mode never stops, the other modes stop after 8 bits
+        if mode == ShiftMode::Disabled || (mode != ShiftMode::OutFreeRunningT2 && self.shift_count >= 8) {
+            return;
+        }
+
+        if mode.is_output() {
+            // the bit shifted out is recirculated into bit 0
+            self.cb2_output = (self.shift_register & 0x80) != 0;
+            self.shift_register = self.shift_register.rotate_left(1);
+        } else {
+            self.shift_register = (self.shift_register << 1) | self.cb2_input as u8;
+        }
+
+        if mode != ShiftMode::OutFreeRunningT2 {
+            self.shift_count += 1;
+            if self.shift_count == 8 {
+                self.ifr |= IRQ_SR;
+            }
+        }
+    }
+}
+
+impl BusDevice for Via {
+    fn size(&self) -> u16 {
+        16
+    }
+
+    fn read(&mut self, offset: u16) -> u8 {
+        match offset {
+            ORB => {
+                self.ifr &= !(IRQ_CB1 | IRQ_CB2);
+                // output bits read back the output register, not the pins
+                (self.output_b & self.ddr_b) | (self.port_b() & !self.ddr_b)
+            }
+            ORA => {
+                self.ifr &= !(IRQ_CA1 | IRQ_CA2);
+                self.port_a()
+            }
+            ORA_NO_HANDSHAKE => self.port_a(),
+            DDRB => self.ddr_b,
+            DDRA => self.ddr_a,
+            T1C_L => {
+                self.ifr &= !IRQ_T1;
+                (self.t1_counter & 0x00FF) as u8
+            }
+            T1C_H => (self.t1_counter >> 8) as u8,
+            T1L_L => (self.t1_latch & 0x00FF) as u8,
+            T1L_H => (self.t1_latch >> 8) as u8,
+            T2C_L => {
+                self.ifr &= !IRQ_T2;
+                (self.t2_counter & 0x00FF) as u8
+            }
+            T2C_H => (self.t2_counter >> 8) as u8,
+            SR => {
+                self.ifr &= !IRQ_SR;
+                self.shift_count = 0;
+                self.shift_register
+            }
+            ACR => self.acr,
+            PCR => self.pcr,
+            IFR => {
+                if (self.ifr & self.ier & !IRQ_ANY) != 0 {
+                    self.ifr | IRQ_ANY
+                } else {
+                    self.ifr
+                }
+            }
+            IER => self.ier | IRQ_ANY,
+            _ => 0x00,
+        }
+    }
+
+    fn write(&mut self, offset: u16, data: u8) {
+        match offset {
+            ORB => {
+                self.ifr &= !(IRQ_CB1 | IRQ_CB2);
+                self.output_b = data;
+            }
+            ORA => {
+                self.ifr &= !(IRQ_CA1 | IRQ_CA2);
+                self.output_a = data;
+            }
+            ORA_NO_HANDSHAKE => self.output_a = data,
+            DDRB => self.ddr_b = data,
+            DDRA => self.ddr_a = data,
+            T1C_L | T1L_L => self.t1_latch = (self.t1_latch & 0xFF00) | data as u16,
+            T1C_H => {
+                self.t1_latch = (self.t1_latch & 0x00FF) | (data as u16) << 8;
+                self.t1_counter = self.t1_latch;
+                self.ifr &= !IRQ_T1;
+                self.t1_armed = true;
+                // PB7 goes low for the duration of a one-shot
+                self.t1_pb7 = false;
+            }
+            T1L_H => {
+                self.t1_latch = (self.t1_latch & 0x00FF) | (data as u16) << 8;
+                self.ifr &= !IRQ_T1;
+            }
+            T2C_L => self.t2_latch_low = data,
+            T2C_H => {
+                self.t2_counter = (data as u16) << 8 | self.t2_latch_low as u16;
+                self.ifr &= !IRQ_T2;
+                self.t2_armed = true;
+            }
+            SR => {
+                self.ifr &= !IRQ_SR;
+                self.shift_count = 0;
+                self.shift_register = data;
+            }
+            ACR => self.acr = data,
+            PCR => self.pcr = data,
+            // writing 1s clears the corresponding flags
+            IFR => self.ifr &= !(data & !IRQ_ANY),
+            // bit 7 selects between setting and clearing the given bits
+            IER => {
+                if (data & IRQ_ANY) != 0 {
+                    self.ier |= data & !IRQ_ANY;
+                } else {
+                    self.ier &= !data;
+                }
+            }
+            _ => (),
+        }
+    }
+
+    fn tick(&mut self) {
+        // timer 1
+        self.t1_counter = self.t1_counter.wrapping_sub(1);
+        if self.t1_counter == 0xFFFF {
+            if (self.acr & ACR_T1_FREE_RUN) != 0 {
+                self.ifr |= IRQ_T1;
+                self.t1_pb7 = !self.t1_pb7;
+                self.t1_counter = self.t1_latch;
+            } else if self.t1_armed {
+                self.t1_armed = false;
+                self.ifr |= IRQ_T1;
+                self.t1_pb7 = true;
+            }
+        }
+
+        // timer 2, in pulse counting mode it only counts PB6 edges
+        let shift_mode = ShiftMode::from_acr(self.acr);
+        if (self.acr & ACR_T2_COUNT_PB6) == 0 {
+            let low_byte_expired = (self.t2_counter & 0x00FF) == 0x0000;
+            self.count_down_t2();
+
+            // the shift register modes under T2 use the low byte of the counter as a free running divider
+            if low_byte_expired && matches!(shift_mode, ShiftMode::InUnderT2 | ShiftMode::OutUnderT2 | ShiftMode::OutFreeRunningT2) {
+                self.t2_counter = (self.t2_counter & 0xFF00) | self.t2_latch_low as u16;
+                self.shift();
+            }
+        }
+
+        if matches!(shift_mode, ShiftMode::InUnderPhi2 | ShiftMode::OutUnderPhi2) {
+            self.shift();
+        }
+    }
+
+    fn irq(&self) -> bool {
+        (self.ifr & self.ier & !IRQ_ANY) != 0
+    }
+
+    /// Ports 0 and 1 are the levels on the pins of port A and B, 2 to 5 the levels of CA1, CA2, CB1 and CB2
+    fn input(&mut self, port: u8, value: u8) {
+        match port {
+            0 => self.set_port_a_input(value),
+            1 => self.set_port_b_input(value),
+            2 => self.set_ca1(value != 0),
+            3 => self.set_ca2(value != 0),
+            4 => self.set_cb1(value != 0),
+            5 => self.set_cb2(value != 0),
+            _ => (),
+        }
+    }
+
+    fn save_state(&self) -> Vec<u8> {
+        let [t1_counter_low, t1_counter_high] = self.t1_counter.to_le_bytes();
+        let [t1_latch_low, t1_latch_high] = self.t1_latch.to_le_bytes();
+        let [t2_counter_low, t2_counter_high] = self.t2_counter.to_le_bytes();
+
+        vec![
+            self.output_a, self.output_b, self.ddr_a, self.ddr_b, self.input_a, self.input_b,
+            t1_counter_low, t1_counter_high, t1_latch_low, t1_latch_high, self.t1_armed as u8, self.t1_pb7 as u8,
+            t2_counter_low, t2_counter_high, self.t2_latch_low, self.t2_armed as u8,
+            self.shift_register, self.shift_count, self.cb2_input as u8, self.cb2_output as u8,
+            self.ca1 as u8, self.ca2 as u8, self.cb1 as u8, self.cb2 as u8,
+            self.acr, self.pcr, self.ifr, self.ier,
+        ]
+    }
+
+    fn load_state(&mut self, state: &[u8]) {
+        let &[
+            output_a, output_b, ddr_a, ddr_b, input_a, input_b,
+            t1_counter_low, t1_counter_high, t1_latch_low, t1_latch_high, t1_armed, t1_pb7,
+            t2_counter_low, t2_counter_high, t2_latch_low, t2_armed,
+            shift_register, shift_count, cb2_input, cb2_output,
+            ca1, ca2, cb1, cb2,
+            acr, pcr, ifr, ier,
+        ] = state else {
+            return;
+        };
+
+        (self.output_a, self.output_b, self.ddr_a, self.ddr_b, self.input_a, self.input_b) = (output_a, output_b, ddr_a, ddr_b, input_a, input_b);
+        self.t1_counter = u16::from_le_bytes([t1_counter_low, t1_counter_high]);
+        self.t1_latch = u16::from_le_bytes([t1_latch_low, t1_latch_high]);
+        (self.t1_armed, self.t1_pb7) = (t1_armed != 0, t1_pb7 != 0);
+        self.t2_counter = u16::from_le_bytes([t2_counter_low, t2_counter_high]);
+        (self.t2_latch_low, self.t2_armed) = (t2_latch_low, t2_armed != 0);
+        (self.shift_register, self.shift_count, self.cb2_input, self.cb2_output) = (shift_register, shift_count, cb2_input != 0, cb2_output != 0);
+        (self.ca1, self.ca2, self.cb1, self.cb2) = (ca1 != 0, ca2 != 0, cb1 != 0, cb2 != 0);
+        (self.acr, self.pcr, self.ifr, self.ier) = (acr, pcr, ifr, ier);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    fn tick_times(via: &mut Via, times: usize) {
+        for _ in 0..times {
+            via.tick();
+        }
+    }
+
+    /**
+     * Whether or not the port pins follow the data direction registers
+     */
+    #[test]
+    fn ports_and_data_direction() {
+        let mut via = Via::new();
+
+        via.write(DDRA, 0xF0);
+        via.write(ORA, 0xAA);
+        via.set_port_a_input(0x05);
+        assert_eq!(via.port_a(), 0xA5);
+        assert_eq!(via.read(ORA), 0xA5);
+
+        via.write(DDRB, 0x0F);
+        via.write(ORB, 0x3C);
+        via.set_port_b_input(0x00);
+        assert_eq!(via.read(ORB), 0x0C);
+    }
+
+    /**
+     * Whether or not timer 1 interrupts once in one-shot mode and repeatedly in free-run mode
+     */
+    #[test]
+    fn timer_1_modes() {
+        let mut via = Via::new();
+        via.write(IER, IRQ_ANY | IRQ_T1);
+
+        // one-shot
+        via.write(T1C_L, 0x03);
+        via.write(T1C_H, 0x00);
+        tick_times(&mut via, 3);
+        assert!(!via.irq());
+        tick_times(&mut via, 1);
+        assert!(via.irq());
+        assert_eq!(via.read(IFR), IRQ_ANY | IRQ_T1);
+
+        // reading the low counter acknowledges, one-shot doesn't fire again
+        via.read(T1C_L);
+        assert!(!via.irq());
+        tick_times(&mut via, 0x20000);
+        assert!(!via.irq());
+
+        // free-run reloads from the latch
+        via.write(ACR, ACR_T1_FREE_RUN | ACR_T1_PB7_OUTPUT);
+        via.write(T1C_L, 0x01);
+        via.write(T1C_H, 0x00);
+        tick_times(&mut via, 2);
+        assert!(via.irq());
+        let pb7 = via.port_b() & PB7;
+        via.write(IFR, IRQ_T1);
+        tick_times(&mut via, 2);
+        assert!(via.irq());
+        assert_ne!(via.port_b() & PB7, pb7);
+    }
+
+    /**
+     * Whether or not timer 2 works as a one-shot and as a PB6 pulse counter
+     */
+    #[test]
+    fn timer_2_modes() {
+        let mut via = Via::new();
+        via.write(IER, IRQ_ANY | IRQ_T2);
+
+        via.write(T2C_L, 0x02);
+        via.write(T2C_H, 0x00);
+        tick_times(&mut via, 3);
+        assert!(via.irq());
+        via.read(T2C_L);
+        assert!(!via.irq());
+
+        via.write(ACR, ACR_T2_COUNT_PB6);
+        via.write(T2C_L, 0x01);
+        via.write(T2C_H, 0x00);
+        tick_times(&mut via, 100);
+        assert!(!via.irq());
+        for _ in 0..2 {
+            via.set_port_b_input(0xFF);
+            via.set_port_b_input(!PB6);
+        }
+        assert!(via.irq());
+    }
+
+    /**
+     * Whether or not the shift register shifts 8 bits in and flags it
+     */
+    #[test]
+    fn shift_register_in_under_phi2() {
+        let mut via = Via::new();
+        via.write(IER, IRQ_ANY | IRQ_SR);
+        via.write(ACR, 0b010 << ACR_SR_MODE_SHIFT);
+        via.write(SR, 0x00);
+
+        for bit in [true, false, true, false, false, true, false, true] {
+            via.set_cb2(bit);
+            via.tick();
+        }
+        assert!(via.irq());
+        assert_eq!(via.read(SR), 0xA5);
+        assert!(!via.irq());
+    }
+
+    /**
+     * Whether or not the interrupt enable register masks the flags
+     */
+    #[test]
+    fn interrupt_enable() {
+        let mut via = Via::new();
+
+        via.set_ca1(true);
+        via.set_ca1(false);
+        assert_eq!(via.read(IFR), IRQ_CA1);
+        assert!(!via.irq());
+
+        via.write(IER, IRQ_ANY | IRQ_CA1 | IRQ_T1);
+        assert_eq!(via.read(IER), IRQ_ANY | IRQ_CA1 | IRQ_T1);
+        assert!(via.irq());
+
+        via.write(IER, IRQ_CA1);
+        assert!(!via.irq());
+
+        via.write(IER, IRQ_ANY | IRQ_CA1);
+        via.read(ORA);
+        assert!(!via.irq());
+    }
+}
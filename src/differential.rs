@@ -0,0 +1,853 @@
+use std::env;
+use std::fmt;
+
+use crate::disassembler::format_instruction;
+use crate::processor::{Instruction, Processor, Registers};
+use crate::symbols::SymbolTable;
+
+/*
+ * Differential testing of `Processor` against a reference model
+ *
+ * Random programs are run from random states on both, the registers and the cycles are compared after
+ * every instruction and the memory once the program is over. A diverging case is shrunk to a small
+ * reproducer before being reported.
+ *
+ * The reference is a plain interpreter of the documented opcodes, written from the data sheet with
+ * nothing shared with `Processor`. A run ends on an undocumented opcode.
+ */
+
+/// Where the generated programs are placed, the zero page and the stack stay free for them to use
+const PROGRAM_START: u16 = 0x0200;
+
+/// Instructions in a generated program
+const PROGRAM_LENGTH: usize = 48;
+
+/// Cases run by `matches_reference()`, `DIFFERENTIAL_CASES` overrides it to fuzz for longer
+const DEFAULT_CASES: u64 = 300;
+
+/// Seed of the first case, `DIFFERENTIAL_SEED` overrides it
+const DEFAULT_SEED: u64 = 0x6502;
+
+const CARRY: u8 = 0x01;
+const ZERO: u8 = 0x02;
+const INTERRUPT_DISABLE: u8 = 0x04;
+const DECIMAL: u8 = 0x08;
+const BREAK: u8 = 0x10;
+const UNUSED: u8 = 0x20;
+const OVERFLOW: u8 = 0x40;
+const NEGATIVE: u8 = 0x80;
+
+/// The B and unused bits only exist on the stack, they aren't compared
+const COMPARED_FLAGS: u8 = !(BREAK | UNUSED);
+
+/**
+ * Seeded pseudo random numbers (splitmix64), so a failing case can be run again from its seed
+ */
+struct Rng(u64);
+
+impl Rng {
+    fn next(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+
+    fn byte(&mut self) -> u8 {
+        self.next() as u8
+    }
+
+    fn below(&mut self, bound: usize) -> usize {
+        (self.next() % bound as u64) as usize
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Mode {
+    Implied,
+    Accumulator,
+    Immediate,
+    ZeroPage,
+    ZeroPageX,
+    ZeroPageY,
+    Absolute,
+    AbsoluteX,
+    AbsoluteY,
+    Indirect,
+    IndirectX,
+    IndirectY,
+    Relative,
+}
+
+impl Mode {
+    /// Bytes following the opcode
+    fn operand_length(self) -> usize {
+        match self {
+            Mode::Implied | Mode::Accumulator => 0,
+            Mode::Absolute | Mode::AbsoluteX | Mode::AbsoluteY | Mode::Indirect => 2,
+            _ => 1,
+        }
+    }
+}
+
+use Mode::*;
+
+/// The documented opcodes: opcode, mnemonic, addressing mode, cycles and whether crossing a page costs one more
+#[rustfmt::skip]
+const OPCODES: [(u8, &str, Mode, u8, bool); 151] = [
+    (0x69, "ADC", Immediate, 2, false), (0x65, "ADC", ZeroPage, 3, false), (0x75, "ADC", ZeroPageX, 4, false),
+    (0x6D, "ADC", Absolute, 4, false), (0x7D, "ADC", AbsoluteX, 4, true), (0x79, "ADC", AbsoluteY, 4, true),
+    (0x61, "ADC", IndirectX, 6, false), (0x71, "ADC", IndirectY, 5, true),
+    (0x29, "AND", Immediate, 2, false), (0x25, "AND", ZeroPage, 3, false), (0x35, "AND", ZeroPageX, 4, false),
+    (0x2D, "AND", Absolute, 4, false), (0x3D, "AND", AbsoluteX, 4, true), (0x39, "AND", AbsoluteY, 4, true),
+    (0x21, "AND", IndirectX, 6, false), (0x31, "AND", IndirectY, 5, true),
+    (0x0A, "ASL", Accumulator, 2, false), (0x06, "ASL", ZeroPage, 5, false), (0x16, "ASL", ZeroPageX, 6, false),
+    (0x0E, "ASL", Absolute, 6, false), (0x1E, "ASL", AbsoluteX, 7, false),
+    (0x90, "BCC", Relative, 2, false), (0xB0, "BCS", Relative, 2, false), (0xF0, "BEQ", Relative, 2, false),
+    (0x30, "BMI", Relative, 2, false), (0xD0, "BNE", Relative, 2, false), (0x10, "BPL", Relative, 2, false),
+    (0x50, "BVC", Relative, 2, false), (0x70, "BVS", Relative, 2, false),
+    (0x24, "BIT", ZeroPage, 3, false), (0x2C, "BIT", Absolute, 4, false),
+    (0x00, "BRK", Implied, 7, false),
+    (0x18, "CLC", Implied, 2, false), (0xD8, "CLD", Implied, 2, false), (0x58, "CLI", Implied, 2, false),
+    (0xB8, "CLV", Implied, 2, false),
+    (0xC9, "CMP", Immediate, 2, false), (0xC5, "CMP", ZeroPage, 3, false), (0xD5, "CMP", ZeroPageX, 4, false),
+    (0xCD, "CMP", Absolute, 4, false), (0xDD, "CMP", AbsoluteX, 4, true), (0xD9, "CMP", AbsoluteY, 4, true),
+    (0xC1, "CMP", IndirectX, 6, false), (0xD1, "CMP", IndirectY, 5, true),
+    (0xE0, "CPX", Immediate, 2, false), (0xE4, "CPX", ZeroPage, 3, false), (0xEC, "CPX", Absolute, 4, false),
+    (0xC0, "CPY", Immediate, 2, false), (0xC4, "CPY", ZeroPage, 3, false), (0xCC, "CPY", Absolute, 4, false),
+    (0xC6, "DEC", ZeroPage, 5, false), (0xD6, "DEC", ZeroPageX, 6, false), (0xCE, "DEC", Absolute, 6, false),
+    (0xDE, "DEC", AbsoluteX, 7, false),
+    (0xCA, "DEX", Implied, 2, false), (0x88, "DEY", Implied, 2, false),
+    (0x49, "EOR", Immediate, 2, false), (0x45, "EOR", ZeroPage, 3, false), (0x55, "EOR", ZeroPageX, 4, false),
+    (0x4D, "EOR", Absolute, 4, false), (0x5D, "EOR", AbsoluteX, 4, true), (0x59, "EOR", AbsoluteY, 4, true),
+    (0x41, "EOR", IndirectX, 6, false), (0x51, "EOR", IndirectY, 5, true),
+    (0xE6, "INC", ZeroPage, 5, false), (0xF6, "INC", ZeroPageX, 6, false), (0xEE, "INC", Absolute, 6, false),
+    (0xFE, "INC", AbsoluteX, 7, false),
+    (0xE8, "INX", Implied, 2, false), (0xC8, "INY", Implied, 2, false),
+    (0x4C, "JMP", Absolute, 3, false), (0x6C, "JMP", Indirect, 5, false),
+    (0x20, "JSR", Absolute, 6, false),
+    (0xA9, "LDA", Immediate, 2, false), (0xA5, "LDA", ZeroPage, 3, false), (0xB5, "LDA", ZeroPageX, 4, false),
+    (0xAD, "LDA", Absolute, 4, false), (0xBD, "LDA", AbsoluteX, 4, true), (0xB9, "LDA", AbsoluteY, 4, true),
+    (0xA1, "LDA", IndirectX, 6, false), (0xB1, "LDA", IndirectY, 5, true),
+    (0xA2, "LDX", Immediate, 2, false), (0xA6, "LDX", ZeroPage, 3, false), (0xB6, "LDX", ZeroPageY, 4, false),
+    (0xAE, "LDX", Absolute, 4, false), (0xBE, "LDX", AbsoluteY, 4, true),
+    (0xA0, "LDY", Immediate, 2, false), (0xA4, "LDY", ZeroPage, 3, false), (0xB4, "LDY", ZeroPageX, 4, false),
+    (0xAC, "LDY", Absolute, 4, false), (0xBC, "LDY", AbsoluteX, 4, true),
+    (0x4A, "LSR", Accumulator, 2, false), (0x46, "LSR", ZeroPage, 5, false), (0x56, "LSR", ZeroPageX, 6, false),
+    (0x4E, "LSR", Absolute, 6, false), (0x5E, "LSR", AbsoluteX, 7, false),
+    (0xEA, "NOP", Implied, 2, false),
+    (0x09, "ORA", Immediate, 2, false), (0x05, "ORA", ZeroPage, 3, false), (0x15, "ORA", ZeroPageX, 4, false),
+    (0x0D, "ORA", Absolute, 4, false), (0x1D, "ORA", AbsoluteX, 4, true), (0x19, "ORA", AbsoluteY, 4, true),
+    (0x01, "ORA", IndirectX, 6, false), (0x11, "ORA", IndirectY, 5, true),
+    (0x48, "PHA", Implied, 3, false), (0x08, "PHP", Implied, 3, false), (0x68, "PLA", Implied, 4, false),
+    (0x28, "PLP", Implied, 4, false),
+    (0x2A, "ROL", Accumulator, 2, false), (0x26, "ROL", ZeroPage, 5, false), (0x36, "ROL", ZeroPageX, 6, false),
+    (0x2E, "ROL", Absolute, 6, false), (0x3E, "ROL", AbsoluteX, 7, false),
+    (0x6A, "ROR", Accumulator, 2, false), (0x66, "ROR", ZeroPage, 5, false), (0x76, "ROR", ZeroPageX, 6, false),
+    (0x6E, "ROR", Absolute, 6, false), (0x7E, "ROR", AbsoluteX, 7, false),
+    (0x40, "RTI", Implied, 6, false), (0x60, "RTS", Implied, 6, false),
+    (0xE9, "SBC", Immediate, 2, false), (0xE5, "SBC", ZeroPage, 3, false), (0xF5, "SBC", ZeroPageX, 4, false),
+    (0xED, "SBC", Absolute, 4, false), (0xFD, "SBC", AbsoluteX, 4, true), (0xF9, "SBC", AbsoluteY, 4, true),
+    (0xE1, "SBC", IndirectX, 6, false), (0xF1, "SBC", IndirectY, 5, true),
+    (0x38, "SEC", Implied, 2, false), (0xF8, "SED", Implied, 2, false), (0x78, "SEI", Implied, 2, false),
+    (0x85, "STA", ZeroPage, 3, false), (0x95, "STA", ZeroPageX, 4, false), (0x8D, "STA", Absolute, 4, false),
+    (0x9D, "STA", AbsoluteX, 5, false), (0x99, "STA", AbsoluteY, 5, false), (0x81, "STA", IndirectX, 6, false),
+    (0x91, "STA", IndirectY, 6, false),
+    (0x86, "STX", ZeroPage, 3, false), (0x96, "STX", ZeroPageY, 4, false), (0x8E, "STX", Absolute, 4, false),
+    (0x84, "STY", ZeroPage, 3, false), (0x94, "STY", ZeroPageX, 4, false), (0x8C, "STY", Absolute, 4, false),
+    (0xAA, "TAX", Implied, 2, false), (0xA8, "TAY", Implied, 2, false), (0xBA, "TSX", Implied, 2, false),
+    (0x8A, "TXA", Implied, 2, false), (0x9A, "TXS", Implied, 2, false), (0x98, "TYA", Implied, 2, false),
+];
+
+fn lookup(opcode: u8) -> Option<(&'static str, Mode, u8, bool)> {
+    OPCODES
+        .iter()
+        .find(|entry| entry.0 == opcode)
+        .map(|(_, name, mode, cycles, page_penalty)| (*name, *mode, *cycles, *page_penalty))
+}
+
+fn page(address: u16) -> u16 {
+    address & 0xFF00
+}
+
+/**
+ * The reference model: the registers and a flat 64 KiB memory
+ */
+struct Reference {
+    a: u8,
+    x: u8,
+    y: u8,
+    sp: u8,
+    /// Kept with the unused bit set and the B bit clear
+    p: u8,
+    pc: u16,
+    memory: Vec<u8>,
+}
+
+impl Reference {
+    fn new(registers: &Registers, memory: Vec<u8>) -> Self {
+        Self {
+            a: registers.accumulator,
+            x: registers.index_register_x,
+            y: registers.index_register_y,
+            sp: registers.stack_pointer,
+            p: (registers.status | UNUSED) & !BREAK,
+            pc: registers.program_counter,
+            memory,
+        }
+    }
+
+    fn registers(&self) -> Registers {
+        Registers {
+            accumulator: self.a,
+            index_register_x: self.x,
+            index_register_y: self.y,
+            status: self.p,
+            stack_pointer: self.sp,
+            program_counter: self.pc,
+        }
+    }
+
+    fn read(&self, address: u16) -> u8 {
+        self.memory[address as usize]
+    }
+
+    fn write(&mut self, address: u16, data: u8) {
+        self.memory[address as usize] = data;
+    }
+
+    fn read_word(&self, address: u16) -> u16 {
+        u16::from_le_bytes([self.read(address), self.read(address.wrapping_add(1))])
+    }
+
+    /// The high byte of a pointer in the zero page comes from $00 when the low one is at $FF
+    fn read_zero_page_word(&self, address: u8) -> u16 {
+        u16::from_le_bytes([self.read(address as u16), self.read(address.wrapping_add(1) as u16)])
+    }
+
+    fn push(&mut self, data: u8) {
+        self.write(0x0100 | self.sp as u16, data);
+        self.sp = self.sp.wrapping_sub(1);
+    }
+
+    fn pull(&mut self) -> u8 {
+        self.sp = self.sp.wrapping_add(1);
+        self.read(0x0100 | self.sp as u16)
+    }
+
+    fn flag(&self, flag: u8) -> bool {
+        self.p & flag != 0
+    }
+
+    fn set_flag(&mut self, flag: u8, value: bool) {
+        if value {
+            self.p |= flag;
+        } else {
+            self.p &= !flag;
+        }
+    }
+
+    fn set_zn(&mut self, value: u8) {
+        self.set_flag(ZERO, value == 0);
+        self.set_flag(NEGATIVE, value & 0x80 != 0);
+    }
+
+    fn add(&mut self, value: u8) {
+        let sum = self.a as u16 + value as u16 + self.flag(CARRY) as u16;
+        let result = sum as u8;
+        self.set_flag(CARRY, sum > 0xFF);
+        // overflow when both operands have the same sign and the result has the other one
+        self.set_flag(OVERFLOW, (self.a ^ result) & (value ^ result) & 0x80 != 0);
+        self.a = result;
+        self.set_zn(result);
+    }
+
+    /**
+     * NMOS decimal addition: the digits are adjusted one at a time, N and V come from the sum before the high
+       digit is adjusted and Z from the binary sum
+     */
+    fn add_decimal(&mut self, value: u8) {
+        let carry = self.flag(CARRY) as i16;
+        let binary = self.a.wrapping_add(value).wrapping_add(carry as u8);
+
+        let mut low = (self.a & 0x0F) as i16 + (value & 0x0F) as i16 + carry;
+        if low >= 0x0A {
+            low = ((low + 0x06) & 0x0F) + 0x10;
+        }
+        let mut sum = (self.a & 0xF0) as i16 + (value & 0xF0) as i16 + low;
+        let signed_sum = (self.a & 0xF0) as i8 as i16 + (value & 0xF0) as i8 as i16 + low;
+
+        self.set_flag(NEGATIVE, sum & 0x80 != 0);
+        self.set_flag(OVERFLOW, !(-128..=127).contains(&signed_sum));
+        self.set_flag(ZERO, binary == 0);
+
+        if sum >= 0xA0 {
+            sum += 0x60;
+        }
+        self.set_flag(CARRY, sum >= 0x100);
+        self.a = sum as u8;
+    }
+
+    /**
+     * NMOS decimal subtraction: the digits are adjusted one at a time, the flags are the ones of the binary subtraction
+     */
+    fn subtract_decimal(&mut self, value: u8) {
+        let borrow = !self.flag(CARRY) as i16;
+
+        let mut low = (self.a & 0x0F) as i16 - (value & 0x0F) as i16 - borrow;
+        if low < 0 {
+            low = ((low - 0x06) & 0x0F) - 0x10;
+        }
+        let mut difference = (self.a & 0xF0) as i16 - (value & 0xF0) as i16 + low;
+        if difference < 0 {
+            difference -= 0x60;
+        }
+        let result = difference as u8;
+
+        self.add(!value);
+        self.a = result;
+    }
+
+    fn compare(&mut self, register: u8, value: u8) {
+        self.set_flag(CARRY, register >= value);
+        self.set_zn(register.wrapping_sub(value));
+    }
+
+    /**
+     * Executes the instruction at the program counter
+     *
+     # Returns
+     its cycles, `None` when it isn't modeled (undocumented opcodes)
+     */
+    fn step(&mut self) -> Option<u8> {
+        let opcode = self.read(self.pc);
+        let (name, mode, mut cycles, page_penalty) = lookup(opcode)?;
+
+        let operand_byte = self.read(self.pc.wrapping_add(1));
+        let operand_word = self.read_word(self.pc.wrapping_add(1));
+        let next = self.pc.wrapping_add(1 + mode.operand_length() as u16);
+
+        let mut indexed = |base: u16, index: u8| {
+            let address = base.wrapping_add(index as u16);
+            if page_penalty && page(address) != page(base) {
+                cycles += 1;
+            }
+            address
+        };
+        let address = match mode {
+            Implied | Accumulator => 0,
+            Immediate => self.pc.wrapping_add(1),
+            ZeroPage => operand_byte as u16,
+            ZeroPageX => operand_byte.wrapping_add(self.x) as u16,
+            ZeroPageY => operand_byte.wrapping_add(self.y) as u16,
+            Absolute => operand_word,
+            AbsoluteX => indexed(operand_word, self.x),
+            AbsoluteY => indexed(operand_word, self.y),
+            // the high byte of the target is read from the start of the page when the pointer is at its end
+            Indirect => u16::from_le_bytes([
+                self.read(operand_word),
+                self.read(page(operand_word) | (operand_word.wrapping_add(1) & 0x00FF)),
+            ]),
+            IndirectX => self.read_zero_page_word(operand_byte.wrapping_add(self.x)),
+            IndirectY => indexed(self.read_zero_page_word(operand_byte), self.y),
+            Relative => next.wrapping_add(operand_byte as i8 as u16),
+        };
+        let value = if mode == Accumulator { self.a } else { self.read(address) };
+        self.pc = next;
+
+        match name {
+            "ADC" if self.flag(DECIMAL) => self.add_decimal(value),
+            "SBC" if self.flag(DECIMAL) => self.subtract_decimal(value),
+            "ADC" => self.add(value),
+            "SBC" => self.add(!value),
+            "AND" => {
+                self.a &= value;
+                self.set_zn(self.a);
+            }
+            "ORA" => {
+                self.a |= value;
+                self.set_zn(self.a);
+            }
+            "EOR" => {
+                self.a ^= value;
+                self.set_zn(self.a);
+            }
+            "ASL" | "LSR" | "ROL" | "ROR" => {
+                let carry = self.flag(CARRY) as u8;
+                let (result, carry_out) = match name {
+                    "ASL" => (value << 1, value & 0x80 != 0),
+                    "LSR" => (value >> 1, value & 0x01 != 0),
+                    "ROL" => (value << 1 | carry, value & 0x80 != 0),
+                    _ => (value >> 1 | carry << 7, value & 0x01 != 0),
+                };
+                self.set_flag(CARRY, carry_out);
+                self.set_zn(result);
+                if mode == Accumulator {
+                    self.a = result;
+                } else {
+                    self.write(address, result);
+                }
+            }
+            "INC" | "DEC" => {
+                let result = if name == "INC" { value.wrapping_add(1) } else { value.wrapping_sub(1) };
+                self.set_zn(result);
+                self.write(address, result);
+            }
+            "INX" => {
+                self.x = self.x.wrapping_add(1);
+                self.set_zn(self.x);
+            }
+            "INY" => {
+                self.y = self.y.wrapping_add(1);
+                self.set_zn(self.y);
+            }
+            "DEX" => {
+                self.x = self.x.wrapping_sub(1);
+                self.set_zn(self.x);
+            }
+            "DEY" => {
+                self.y = self.y.wrapping_sub(1);
+                self.set_zn(self.y);
+            }
+            "CMP" => self.compare(self.a, value),
+            "CPX" => self.compare(self.x, value),
+            "CPY" => self.compare(self.y, value),
+            "BIT" => {
+                self.set_flag(ZERO, self.a & value == 0);
+                self.set_flag(NEGATIVE, value & 0x80 != 0);
+                self.set_flag(OVERFLOW, value & 0x40 != 0);
+            }
+            "BCC" | "BCS" | "BEQ" | "BMI" | "BNE" | "BPL" | "BVC" | "BVS" => {
+                let taken = match name {
+                    "BCC" => !self.flag(CARRY),
+                    "BCS" => self.flag(CARRY),
+                    "BEQ" => self.flag(ZERO),
+                    "BNE" => !self.flag(ZERO),
+                    "BMI" => self.flag(NEGATIVE),
+                    "BPL" => !self.flag(NEGATIVE),
+                    "BVC" => !self.flag(OVERFLOW),
+                    _ => self.flag(OVERFLOW),
+                };
+                if taken {
+                    cycles += 1 + (page(next) != page(address)) as u8;
+                    self.pc = address;
+                }
+            }
+            "BRK" => {
+                // the byte after BRK is skipped
+                let [low, high] = next.wrapping_add(1).to_le_bytes();
+                self.push(high);
+                self.push(low);
+                self.push(self.p | BREAK | UNUSED);
+                self.set_flag(INTERRUPT_DISABLE, true);
+                self.pc = self.read_word(0xFFFE);
+            }
+            "JMP" => self.pc = address,
+            "JSR" => {
+                let [low, high] = next.wrapping_sub(1).to_le_bytes();
+                self.push(high);
+                self.push(low);
+                self.pc = address;
+            }
+            "RTS" => {
+                let low = self.pull();
+                let high = self.pull();
+                self.pc = u16::from_le_bytes([low, high]).wrapping_add(1);
+            }
+            "RTI" => {
+                self.p = (self.pull() | UNUSED) & !BREAK;
+                let low = self.pull();
+                let high = self.pull();
+                self.pc = u16::from_le_bytes([low, high]);
+            }
+            "PHA" => self.push(self.a),
+            "PHP" => self.push(self.p | BREAK | UNUSED),
+            "PLA" => {
+                self.a = self.pull();
+                self.set_zn(self.a);
+            }
+            "PLP" => self.p = (self.pull() | UNUSED) & !BREAK,
+            "LDA" => {
+                self.a = value;
+                self.set_zn(value);
+            }
+            "LDX" => {
+                self.x = value;
+                self.set_zn(value);
+            }
+            "LDY" => {
+                self.y = value;
+                self.set_zn(value);
+            }
+            "STA" => self.write(address, self.a),
+            "STX" => self.write(address, self.x),
+            "STY" => self.write(address, self.y),
+            "CLC" => self.set_flag(CARRY, false),
+            "CLD" => self.set_flag(DECIMAL, false),
+            "CLI" => self.set_flag(INTERRUPT_DISABLE, false),
+            "CLV" => self.set_flag(OVERFLOW, false),
+            "SEC" => self.set_flag(CARRY, true),
+            "SED" => self.set_flag(DECIMAL, true),
+            "SEI" => self.set_flag(INTERRUPT_DISABLE, true),
+            "TAX" => {
+                self.x = self.a;
+                self.set_zn(self.x);
+            }
+            "TAY" => {
+                self.y = self.a;
+                self.set_zn(self.y);
+            }
+            "TSX" => {
+                self.x = self.sp;
+                self.set_zn(self.x);
+            }
+            "TXA" => {
+                self.a = self.x;
+                self.set_zn(self.a);
+            }
+            "TYA" => {
+                self.a = self.y;
+                self.set_zn(self.a);
+            }
+            "TXS" => self.sp = self.x,
+            _ => (),
+        }
+
+        Some(cycles)
+    }
+}
+
+/**
+ * A program with the state it starts from
+ */
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Case {
+    registers: Registers,
+    /// Seed of the random memory contents, `None` for zeroed memory
+    memory_seed: Option<u64>,
+    /// Instructions placed from `PROGRAM_START`, opcode followed by the operand
+    program: Vec<Vec<u8>>,
+}
+
+impl Case {
+    fn generate(rng: &mut Rng, length: usize) -> Self {
+        let registers = Registers {
+            accumulator: rng.byte(),
+            index_register_x: rng.byte(),
+            index_register_y: rng.byte(),
+            status: (rng.byte() | UNUSED) & !BREAK,
+            stack_pointer: rng.byte(),
+            program_counter: PROGRAM_START,
+        };
+        let memory_seed = Some(rng.next());
+
+        let program = (0..length)
+            .map(|_| {
+                let (opcode, _, mode, _, _) = &OPCODES[rng.below(OPCODES.len())];
+                let mut instruction = vec![*opcode];
+                instruction.extend((0..mode.operand_length()).map(|_| rng.byte()));
+                instruction
+            })
+            .collect();
+
+        Self { registers, memory_seed, program }
+    }
+
+    fn memory(&self) -> Vec<u8> {
+        let mut memory = vec![0; 0x10000];
+        if let Some(seed) = self.memory_seed {
+            let mut rng = Rng(seed);
+            memory.iter_mut().for_each(|byte| *byte = rng.byte());
+        }
+
+        for (address, byte) in (PROGRAM_START as usize..).zip(self.program.iter().flatten()) {
+            memory[address] = *byte;
+        }
+        memory
+    }
+
+    /**
+     * Runs the case on the reference and on `Processor`
+     *
+     # Returns
+     the number of instructions executed, or where the two first disagree
+     */
+    fn run(&self) -> Result<usize, Divergence> {
+        let memory = self.memory();
+        let mut processor = Processor::new();
+        for (address, byte) in memory.iter().enumerate() {
+            processor.bus.poke(address as u16, *byte);
+        }
+        processor.set_registers(&self.registers);
+        let mut reference = Reference::new(&self.registers, memory);
+
+        // branches may loop, so the run is bounded
+        let max_instructions = self.program.len() * 4;
+        let mut instructions = 0;
+        while instructions < max_instructions {
+            let address = reference.pc;
+            let Some(expected_cycles) = reference.step() else {
+                break;
+            };
+            let cycles = processor.step();
+            instructions += 1;
+
+            let expected = reference.registers();
+            let mut actual = processor.registers();
+            actual.status = (actual.status & COMPARED_FLAGS) | (expected.status & !COMPARED_FLAGS);
+            if actual != expected {
+                return Err(Divergence { instructions, address, kind: DivergenceKind::Registers { expected, actual } });
+            }
+            if cycles != expected_cycles as u64 {
+                return Err(Divergence { instructions, address, kind: DivergenceKind::Cycles { expected: expected_cycles as u64, actual: cycles } });
+            }
+        }
+
+        for address in 0..=0xFFFF_u16 {
+            let (expected, actual) = (reference.read(address), processor.bus.peek(address));
+            if expected != actual {
+                let kind = DivergenceKind::Memory { address, expected, actual };
+                return Err(Divergence { instructions, address: reference.pc, kind });
+            }
+        }
+        Ok(instructions)
+    }
+
+    /**
+     * Simpler variants of the case: one instruction less, a zero operand, plainer registers or zeroed memory
+     */
+    fn simplifications(&self) -> Vec<Case> {
+        let mut candidates = Vec::new();
+
+        for index in (0..self.program.len()).rev() {
+            let mut candidate = self.clone();
+            candidate.program.remove(index);
+            candidates.push(candidate);
+        }
+        if self.memory_seed.is_some() {
+            candidates.push(Case { memory_seed: None, ..self.clone() });
+        }
+
+        let plain = Registers { status: UNUSED, stack_pointer: 0xFF, program_counter: PROGRAM_START, ..Default::default() };
+        let fields: [fn(&mut Registers) -> &mut u8; 5] = [
+            |registers| &mut registers.accumulator,
+            |registers| &mut registers.index_register_x,
+            |registers| &mut registers.index_register_y,
+            |registers| &mut registers.status,
+            |registers| &mut registers.stack_pointer,
+        ];
+        for field in fields {
+            let mut candidate = self.clone();
+            *field(&mut candidate.registers) = *field(&mut plain.clone());
+            if candidate != *self {
+                candidates.push(candidate);
+            }
+        }
+
+        for (index, instruction) in self.program.iter().enumerate() {
+            for (operand, byte) in instruction.iter().enumerate().skip(1) {
+                if *byte != 0 {
+                    let mut candidate = self.clone();
+                    candidate.program[index][operand] = 0;
+                    candidates.push(candidate);
+                }
+            }
+        }
+
+        candidates
+    }
+
+    /**
+     * Simplifies the case for as long as it keeps failing
+     */
+    fn shrink(self, fails: impl Fn(&Case) -> bool) -> Case {
+        let mut case = self;
+        while let Some(simpler) = case.simplifications().into_iter().find(|candidate| fails(candidate)) {
+            case = simpler;
+        }
+        case
+    }
+}
+
+impl fmt::Display for Case {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "registers: {}", self.registers)?;
+        match self.memory_seed {
+            Some(seed) => writeln!(f, "memory: random, seed {:#x}", seed)?,
+            None => writeln!(f, "memory: zeroed")?,
+        }
+
+        let symbols = SymbolTable::default();
+        let mut address = PROGRAM_START;
+        for instruction in &self.program {
+            let bytes: Vec<String> = instruction.iter().map(|byte| format!("{:02X}", byte)).collect();
+            let operand = u16::from_le_bytes([instruction.get(1).copied().unwrap_or(0), instruction.get(2).copied().unwrap_or(0)]);
+            let text = format_instruction(Instruction::decode(instruction[0]), address, operand, &symbols);
+            writeln!(f, "{:04X}  {:8}  {}", address, bytes.join(" "), text)?;
+            address = address.wrapping_add(instruction.len() as u16);
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum DivergenceKind {
+    Registers { expected: Registers, actual: Registers },
+    Cycles { expected: u64, actual: u64 },
+    Memory { address: u16, expected: u8, actual: u8 },
+}
+
+/**
+ * The first disagreement between the reference and `Processor`
+ */
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Divergence {
+    /// Instructions executed, the diverging one included
+    instructions: usize,
+    /// Where the diverging instruction is (where the run ended for the memory)
+    address: u16,
+    kind: DivergenceKind,
+}
+
+impl fmt::Display for Divergence {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.kind {
+            DivergenceKind::Registers { expected, actual } => write!(
+                f,
+                "registers after instruction {} at ${:04X}\n  expected {}\n  actual   {}",
+                self.instructions, self.address, expected, actual
+            ),
+            DivergenceKind::Cycles { expected, actual } => write!(
+                f,
+                "cycles of instruction {} at ${:04X}: expected {}, actual {}",
+                self.instructions, self.address, expected, actual
+            ),
+            DivergenceKind::Memory { address, expected, actual } => write!(
+                f,
+                "memory at ${:04X} after {} instructions: expected ${:02X}, actual ${:02X}",
+                address, self.instructions, expected, actual
+            ),
+        }
+    }
+}
+
+fn env_or(name: &str, default: u64) -> u64 {
+    env::var(name)
+        .ok()
+        .and_then(|value| match value.strip_prefix("0x") {
+            Some(hex) => u64::from_str_radix(hex, 16).ok(),
+            None => value.parse().ok(),
+        })
+        .unwrap_or(default)
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    /**
+     * Whether or not random programs behave the same on `Processor` and on the reference
+     *
+     * A divergence is shrunk and reported with the seed of its case, set `DIFFERENTIAL_SEED` to it to run it again.
+     */
+    #[test]
+    fn matches_reference() {
+        let seed = env_or("DIFFERENTIAL_SEED", DEFAULT_SEED);
+        let cases = env_or("DIFFERENTIAL_CASES", DEFAULT_CASES);
+
+        for case_seed in (0..cases).map(|index| seed.wrapping_add(index)) {
+            let case = Case::generate(&mut Rng(case_seed), PROGRAM_LENGTH);
+            if let Err(divergence) = case.run() {
+                let shrunk = case.shrink(|candidate| candidate.run().is_err());
+                panic!(
+                    "case {:#x} diverges: {}\n\nshrunk to: {}\n{}",
+                    case_seed,
+                    divergence,
+                    shrunk.run().unwrap_err(),
+                    shrunk
+                );
+            }
+        }
+    }
+
+    /**
+     * Whether or not a failing case is shrunk to the single instruction and the plain state it fails with
+     */
+    #[test]
+    fn shrinks_to_minimal_case() {
+        let case = Case::generate(&mut Rng(DEFAULT_SEED), PROGRAM_LENGTH);
+        let mut program = case.program.clone();
+        program.insert(PROGRAM_LENGTH / 2, vec![0xE8]);
+        let case = Case { program, ..case };
+
+        // an INX is the artificial failure
+        let shrunk = case.shrink(|candidate| candidate.program.contains(&vec![0xE8]));
+
+        assert_eq!(shrunk.program, vec![vec![0xE8]]);
+        assert_eq!(shrunk.memory_seed, None);
+        assert_eq!(shrunk.registers, Registers { status: UNUSED, stack_pointer: 0xFF, program_counter: PROGRAM_START, ..Default::default() });
+        assert!(shrunk.to_string().ends_with("0200  E8        INX\n"));
+    }
+
+    /**
+     * Whether or not the reference itself follows the data sheet on a few hand checked instructions
+     */
+    #[test]
+    fn reference_model() {
+        let mut memory = vec![0; 0x10000];
+        let program = [
+            0xA9, 0x7F, //       LDA #$7F
+            0x69, 0x01, //       ADC #$01 (overflow into the sign)
+            0x6C, 0xFF, 0x02, // JMP ($02FF) (high byte from $0200)
+        ];
+        memory[0x0200..0x0200 + program.len()].copy_from_slice(&program);
+        memory[0x02FF] = 0x34;
+        let registers = Registers { status: UNUSED, stack_pointer: 0xFF, program_counter: 0x0200, ..Default::default() };
+        let mut reference = Reference::new(&registers, memory);
+
+        assert_eq!(reference.step(), Some(2));
+        assert_eq!(reference.step(), Some(2));
+        assert_eq!((reference.a, reference.p & (OVERFLOW | NEGATIVE | CARRY)), (0x80, OVERFLOW | NEGATIVE));
+        assert_eq!(reference.step(), Some(5));
+        assert_eq!(reference.pc, 0xA934);
+    }
+
+    /**
+     * Whether or not decimal mode follows the NMOS 6502 on both the reference and `Processor`
+     */
+    #[test]
+    fn decimal_mode() {
+        let program = vec![
+            vec![0xF8],       // SED
+            vec![0x18],       // CLC
+            vec![0xA9, 0x58], // LDA #$58
+            vec![0x69, 0x46], // ADC #$46 (104, carry out)
+            vec![0xA9, 0x46], // LDA #$46
+            vec![0xE9, 0x12], // SBC #$12 (with the carry set, 34)
+            vec![0x38],       // SEC
+            vec![0xA9, 0x10], // LDA #$10
+            vec![0xE9, 0x20], // SBC #$20 (borrow, 90)
+        ];
+        let case = Case {
+            registers: Registers { status: UNUSED, stack_pointer: 0xFF, program_counter: PROGRAM_START, ..Default::default() },
+            memory_seed: None,
+            program,
+        };
+        let mut reference = Reference::new(&case.registers, case.memory());
+
+        let results: Vec<(u8, bool)> = [4, 6, 10, 14]
+            .iter()
+            .map(|&instructions| {
+                while reference.pc < PROGRAM_START + instructions {
+                    reference.step();
+                }
+                (reference.a, reference.flag(CARRY))
+            })
+            .collect();
+        assert_eq!(results, [(0x58, false), (0x04, true), (0x34, true), (0x90, false)]);
+
+        assert!(case.run().is_ok());
+    }
+}
@@ -0,0 +1,145 @@
+use std::fmt;
+
+use serde::Serialize;
+
+use crate::processor::{AddressingMode, Instruction, Processor, TraceEntry};
+use crate::symbols::SymbolTable;
+
+/**
+ * A disassembled instruction
+ */
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct DisassembledLine {
+    pub address: u16,
+    /// Opcode followed by the operand
+    pub bytes: Vec<u8>,
+    /// Label of the address, if the symbol table has one
+    pub label: Option<String>,
+    /// Mnemonic and operand, e.g. `LDA ($10),Y`
+    pub text: String,
+    /// Whether or not the opcode is missing from the data sheet
+    pub undocumented: bool,
+}
+
+impl fmt::Display for DisassembledLine {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let bytes: Vec<String> = self.bytes.iter().map(|byte| format!("{:02X}", byte)).collect();
+        let label = self.label.as_ref().map(|label| format!("{}:", label)).unwrap_or_default();
+
+        write!(f, "{:04X}  {:<8}  {:<12} {}", self.address, bytes.join(" "), label, self.text)
+    }
+}
+
+/**
+ * Formats an instruction in the usual assembler syntax, addresses are replaced by their labels
+ *
+ # Arguments
+
+ * `instruction` - The instruction of the opcode
+ * `address` - Where the opcode is, needed for the branch targets
+ * `operand` - The bytes following the opcode (little endian), unused bytes are ignored
+ * `symbols` - Labels to show instead of addresses
+ */
+pub fn format_instruction(instruction: &Instruction, address: u16, operand: u16, symbols: &SymbolTable) -> String {
+    let byte = operand as u8;
+    let zero_page = || symbols.name_of(byte as u16).map(str::to_owned).unwrap_or_else(|| format!("${:02X}", byte));
+
+    let operand = match instruction.addressing_mode_enum {
+        AddressingMode::IMPL => return instruction.name.to_string(),
+        AddressingMode::IMM => format!("#${:02X}", byte),
+        AddressingMode::ZPG => zero_page(),
+        AddressingMode::ZPGX => format!("{},X", zero_page()),
+        AddressingMode::ZPGY => format!("{},Y", zero_page()),
+        AddressingMode::INDX => format!("({},X)", zero_page()),
+        AddressingMode::INDY => format!("({}),Y", zero_page()),
+        AddressingMode::ABS => symbols.format_address(operand),
+        AddressingMode::ABSX => format!("{},X", symbols.format_address(operand)),
+        AddressingMode::ABSY => format!("{},Y", symbols.format_address(operand)),
+        AddressingMode::IND => format!("({})", symbols.format_address(operand)),
+        // branches are shown with their target, not the offset
+        AddressingMode::REL => symbols.format_address(address.wrapping_add(2).wrapping_add(byte as i8 as u16)),
+    };
+
+    format!("{} {}", instruction.name, operand)
+}
+
+/**
+ * Disassembles `count` instructions starting at `address`
+ *
+ * Memory is peeked, so disassembling doesn't disturb the devices on the bus.
+ */
+pub fn disassemble(processor: &Processor, address: u16, count: usize) -> Vec<DisassembledLine> {
+    let mut lines = Vec::with_capacity(count);
+    let mut address = address;
+
+    for _ in 0..count {
+        let opcode = processor.bus.peek(address);
+        let instruction = Instruction::decode(opcode);
+        let length = instruction.length as u16;
+
+        let bytes: Vec<u8> = (0..length).map(|i| processor.bus.peek(address.wrapping_add(i))).collect();
+        let operand = bytes.iter().skip(1).rev().fold(0u16, |operand, byte| (operand << 8) | *byte as u16);
+
+        lines.push(DisassembledLine {
+            address,
+            label: processor.symbols.name_of(address).map(str::to_owned),
+            text: format_instruction(instruction, address, operand, &processor.symbols),
+            bytes,
+            undocumented: instruction.undocumented,
+        });
+
+        address = address.wrapping_add(length);
+    }
+
+    lines
+}
+
+/**
+ * Formats a traced instruction along with the registers it was executed with
+ */
+pub fn format_trace_entry(processor: &Processor, entry: &TraceEntry) -> String {
+    let instruction = Instruction::decode(entry.bytes[0]);
+    let length = instruction.length as usize;
+    let operand = u16::from_le_bytes([entry.bytes[1], entry.bytes[2]]);
+
+    let line = DisassembledLine {
+        address: entry.registers.program_counter,
+        bytes: entry.bytes[..length].to_vec(),
+        label: processor.symbols.name_of(entry.registers.program_counter).map(str::to_owned),
+        text: format_instruction(instruction, entry.registers.program_counter, operand, &processor.symbols),
+        undocumented: instruction.undocumented,
+    };
+
+    format!("{:<44} {}", line.to_string(), entry.registers)
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    /**
+     * Whether or not operands are formatted per addressing mode with labels substituted
+     */
+    #[test]
+    fn labels_in_operands() {
+        let mut processor = Processor::new();
+        // LDA #$01; STA $0200,X; LDA ($10),Y; JSR $FFD2; BNE $8000
+        let program = [0xA9, 0x01, 0x9D, 0x00, 0x02, 0xB1, 0x10, 0x20, 0xD2, 0xFF, 0xD0, 0xF4];
+        for (i, byte) in program.iter().enumerate() {
+            processor.bus.write(0x8000 + i as u16, *byte);
+        }
+
+        processor.symbols.insert("main", 0x8000);
+        processor.symbols.insert("CHROUT", 0xFFD2);
+        processor.symbols.insert("pointer", 0x0010);
+
+        let lines = disassemble(&processor, 0x8000, 5);
+        let text: Vec<&str> = lines.iter().map(|line| line.text.as_str()).collect();
+
+        assert_eq!(text, vec!["LDA #$01", "STA $0200,X", "LDA (pointer),Y", "JSR CHROUT", "BNE main"]);
+        assert_eq!(lines[0].label.as_deref(), Some("main"));
+        assert_eq!(lines[3].bytes, vec![0x20, 0xD2, 0xFF]);
+        assert_eq!(lines[0].to_string(), "8000  A9 01     main:        LDA #$01");
+    }
+}
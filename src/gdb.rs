@@ -0,0 +1,610 @@
+use std::io::{self, BufReader, Read, Write};
+use std::net::{TcpListener, TcpStream};
+
+use crate::monitor::Monitor;
+use crate::processor::{Processor, Registers, StopReason};
+use crate::watchpoints::WatchKind;
+
+/// Largest packet we accept, advertised to the debugger
+const PACKET_SIZE: usize = 0x1000;
+
+/// Cycles `continue` runs between checks for an interrupt from the debugger
+const CONTINUE_SLICE: u64 = 100_000;
+
+/// The byte the debugger sends to interrupt a running target (Ctrl-C)
+const INTERRUPT: u8 = 0x03;
+
+// Stop signals
+const SIGINT: u8 = 2;
+const SIGILL: u8 = 4;
+const SIGTRAP: u8 = 5;
+const SIGSEGV: u8 = 11;
+
+/**
+ * The 6502 register set, sent to the debugger on `qXfer:features:read:target.xml`
+ */
+const TARGET_XML: &str = r#"<?xml version="1.0"?>
+<!DOCTYPE target SYSTEM "gdb-target.dtd">
+<target version="1.0">
+  <feature name="org.gnu.gdb.m6502.core">
+    <flags id="status_flags" size="1">
+      <field name="C" start="0" end="0"/>
+      <field name="Z" start="1" end="1"/>
+      <field name="I" start="2" end="2"/>
+      <field name="D" start="3" end="3"/>
+      <field name="B" start="4" end="4"/>
+      <field name="U" start="5" end="5"/>
+      <field name="V" start="6" end="6"/>
+      <field name="N" start="7" end="7"/>
+    </flags>
+    <reg name="a" bitsize="8" regnum="0" type="uint8" group="general"/>
+    <reg name="x" bitsize="8" regnum="1" type="uint8" group="general"/>
+    <reg name="y" bitsize="8" regnum="2" type="uint8" group="general"/>
+    <reg name="sp" bitsize="8" regnum="3" type="uint8" group="general"/>
+    <reg name="p" bitsize="8" regnum="4" type="status_flags" group="general"/>
+    <reg name="pc" bitsize="16" regnum="5" type="code_ptr" group="general"/>
+  </feature>
+</target>
+"#;
+
+/**
+ * What the connection should do after a packet has been handled
+ */
+#[derive(Debug, PartialEq, Eq)]
+pub enum Response {
+    Reply(String),
+    /// Reply `OK` and close the connection, the processor is left as it is
+    Detach,
+    /// Close the connection without replying
+    Kill,
+}
+
+/**
+ * GDB remote serial protocol stub, driving a `Processor` for gdb or any RSP speaking front end
+ *
+ * Registers are `a`, `x`, `y`, `sp`, `p` and `pc`, described to the debugger through `target.xml`.
+ * Software and hardware breakpoints both map to the processor breakpoints, watchpoints to the bus ones.
+ * `monitor <command>` runs a command of the emulator's own monitor.
+ * `reverse-stepi` and `reverse-continue` work once the processor's execution history is enabled.
+ */
+#[derive(Debug)]
+pub struct GdbStub {
+    no_ack_mode: bool,
+    /// Reply to `?`
+    last_stop: String,
+    monitor: Monitor,
+}
+
+impl Default for GdbStub {
+    fn default() -> Self {
+        Self {
+            no_ack_mode: false,
+            last_stop: format!("S{:02x}", SIGTRAP),
+            monitor: Monitor::new(),
+        }
+    }
+}
+
+// Constructor like implementation
+impl GdbStub {
+    pub fn new() -> Self {
+        Self {
+            ..Default::default()
+        }
+    }
+}
+
+fn encode_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+fn decode_hex(text: &str) -> Option<Vec<u8>> {
+    if !text.len().is_multiple_of(2) {
+        return None;
+    }
+
+    (0..text.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(text.get(i..i + 2)?, 16).ok())
+        .collect()
+}
+
+/**
+ * Reads `address,length` with both numbers in hexadecimal
+ */
+fn parse_range(text: &str) -> Option<(u16, u16)> {
+    let (address, length) = text.split_once(',')?;
+    Some((u16::from_str_radix(address, 16).ok()?, u16::from_str_radix(length, 16).ok()?))
+}
+
+fn checksum(data: &str) -> u8 {
+    data.bytes().fold(0u8, |sum, byte| sum.wrapping_add(byte))
+}
+
+/**
+ * Frames a reply as `$data#checksum`, escaping the characters the protocol reserves
+ */
+fn frame(data: &str) -> String {
+    let mut escaped = String::with_capacity(data.len());
+    for character in data.chars() {
+        match character {
+            '#' | '$' | '}' | '*' => {
+                escaped.push('}');
+                escaped.push((character as u8 ^ 0x20) as char);
+            }
+            _ => escaped.push(character),
+        }
+    }
+
+    format!("${}#{:02x}", escaped, checksum(&escaped))
+}
+
+// Register implementation
+impl GdbStub {
+    /**
+     * The registers as sent by `g`, in the order of `target.xml` (pc is little endian)
+     */
+    fn register_bytes(registers: &Registers) -> Vec<u8> {
+        let [pc_low, pc_high] = registers.program_counter.to_le_bytes();
+        vec![registers.accumulator, registers.index_register_x, registers.index_register_y, registers.stack_pointer, registers.status, pc_low, pc_high]
+    }
+
+    /**
+     * Returns the offset and size of a register in the `g` packet
+     */
+    fn register_span(number: usize) -> Option<(usize, usize)> {
+        match number {
+            0..=4 => Some((number, 1)),
+            5 => Some((5, 2)),
+            _ => None,
+        }
+    }
+
+    fn set_register_bytes(processor: &mut Processor, bytes: &[u8]) {
+        let registers = Registers {
+            accumulator: bytes[0],
+            index_register_x: bytes[1],
+            index_register_y: bytes[2],
+            stack_pointer: bytes[3],
+            status: bytes[4],
+            program_counter: u16::from_le_bytes([bytes[5], bytes[6]]),
+        };
+        processor.set_registers(&registers);
+    }
+}
+
+// Packet handling implementation
+impl GdbStub {
+    /**
+     * Handles the data of a packet (without the framing)
+     *
+     # Arguments
+
+     * `processor` - The target
+     * `packet` - The packet data
+     * `interrupted` - Polled while continuing, returns true once the debugger asked to stop
+     */
+    pub fn handle_packet(&mut self, processor: &mut Processor, packet: &str, interrupted: &mut dyn FnMut() -> bool) -> Response {
+        let reply = match packet.as_bytes().first() {
+            Some(b'?') => self.last_stop.clone(),
+            Some(b'g') => encode_hex(&Self::register_bytes(&processor.registers())),
+            Some(b'G') => match decode_hex(&packet[1..]) {
+                Some(bytes) if bytes.len() == 7 => {
+                    Self::set_register_bytes(processor, &bytes);
+                    String::from("OK")
+                }
+                _ => String::from("E01"),
+            },
+            Some(b'p') => {
+                let bytes = Self::register_bytes(&processor.registers());
+                match usize::from_str_radix(&packet[1..], 16).ok().and_then(Self::register_span) {
+                    Some((offset, size)) => encode_hex(&bytes[offset..offset + size]),
+                    None => String::from("E01"),
+                }
+            }
+            Some(b'P') => self.write_register(processor, &packet[1..]),
+            Some(b'm') => match parse_range(&packet[1..]) {
+                Some((address, length)) => {
+                    let length = (length as usize).min(PACKET_SIZE / 2) as u16;
+                    let bytes: Vec<u8> = (0..length).map(|i| processor.bus.peek(address.wrapping_add(i))).collect();
+                    encode_hex(&bytes)
+                }
+                None => String::from("E01"),
+            },
+            Some(b'M') => {
+                let write = packet[1..].split_once(':').and_then(|(range, data)| Some((parse_range(range)?, decode_hex(data)?)));
+                match write {
+                    Some(((address, length), bytes)) if bytes.len() == length as usize => {
+                        // without side effects, writing a device register or a bank latch would change the machine
+                        let written = bytes
+                            .iter()
+                            .enumerate()
+                            .fold(true, |written, (i, byte)| processor.bus.debug_write(address.wrapping_add(i as u16), *byte) && written);
+                        if written { String::from("OK") } else { String::from("E01") }
+                    }
+                    _ => String::from("E01"),
+                }
+            }
+            Some(b'Z') | Some(b'z') => Self::breakpoint(processor, packet),
+            Some(b's') => {
+                self.resume_at(processor, &packet[1..]);
+                processor.step();
+                // stepping doesn't stop on watchpoints, their hits are dropped
+                processor.take_watch_hit();
+
+                self.last_stop = if processor.is_halted() { format!("S{:02x}", SIGILL) } else { format!("S{:02x}", SIGTRAP) };
+                self.last_stop.clone()
+            }
+            Some(b'c') => {
+                self.resume_at(processor, &packet[1..]);
+                self.last_stop = Self::continue_execution(processor, interrupted);
+                self.last_stop.clone()
+            }
+            // reverse execution, through the execution history
+            Some(b'b') if processor.history().is_none() => String::from("E01"),
+            Some(b'b') if packet == "bs" => {
+                self.last_stop = if processor.step_back() { format!("S{:02x}", SIGTRAP) } else { format!("T{:02x}replaylog:begin;", SIGTRAP) };
+                self.last_stop.clone()
+            }
+            Some(b'b') if packet == "bc" => {
+                self.last_stop = match processor.reverse_continue().stop_reason {
+                    StopReason::Breakpoint { .. } => format!("T{:02x}swbreak:;", SIGTRAP),
+                    _ => format!("T{:02x}replaylog:begin;", SIGTRAP),
+                };
+                self.last_stop.clone()
+            }
+            Some(b'H') | Some(b'T') => String::from("OK"),
+            Some(b'D') => return Response::Detach,
+            Some(b'k') => return Response::Kill,
+            Some(b'q') | Some(b'Q') => self.query(processor, packet),
+            // everything else (vCont, X, extended mode, ...) is unsupported, an empty reply says so
+            _ => String::new(),
+        };
+
+        Response::Reply(reply)
+    }
+
+    fn write_register(&mut self, processor: &mut Processor, arguments: &str) -> String {
+        let write = arguments.split_once('=').and_then(|(number, value)| {
+            let span = usize::from_str_radix(number, 16).ok().and_then(Self::register_span)?;
+            Some((span, decode_hex(value)?))
+        });
+
+        match write {
+            Some(((offset, size), value)) if value.len() == size => {
+                let mut bytes = Self::register_bytes(&processor.registers());
+                bytes[offset..offset + size].copy_from_slice(&value);
+                Self::set_register_bytes(processor, &bytes);
+                String::from("OK")
+            }
+            _ => String::from("E01"),
+        }
+    }
+
+    /**
+     * Moves the program counter for `c addr` and `s addr`
+     */
+    fn resume_at(&mut self, processor: &mut Processor, address: &str) {
+        if let Ok(address) = u16::from_str_radix(address, 16) {
+            let mut registers = processor.registers();
+            registers.program_counter = address;
+            processor.set_registers(&registers);
+        }
+    }
+
+    /**
+     * Handles `Z`/`z` packets, `type,address,kind`
+     */
+    fn breakpoint(processor: &mut Processor, packet: &str) -> String {
+        let insert = packet.starts_with('Z');
+        let mut fields = packet[1..].split(',');
+
+        let (kind, address, length) = match (fields.next(), fields.next(), fields.next()) {
+            (Some(kind), Some(address), Some(length)) => (kind, u16::from_str_radix(address, 16), u16::from_str_radix(length, 16)),
+            _ => return String::from("E01"),
+        };
+        let (address, length) = match (address, length) {
+            (Ok(address), Ok(length)) => (address, length),
+            _ => return String::from("E01"),
+        };
+
+        let watch_kind = match kind {
+            // software and hardware breakpoints are the same thing here
+            "0" | "1" => {
+                if insert {
+                    processor.add_breakpoint(address);
+                } else {
+                    processor.remove_breakpoint(address);
+                }
+                return String::from("OK");
+            }
+            "2" => WatchKind::Write,
+            "3" => WatchKind::Read,
+            "4" => WatchKind::Access,
+            _ => return String::new(),
+        };
+
+        if insert {
+            processor.add_watchpoint(address, length, watch_kind);
+        } else {
+            processor.remove_watchpoint(address, length, watch_kind);
+        }
+        String::from("OK")
+    }
+
+    /**
+     * Runs until something stops the processor or the debugger interrupts it
+     *
+     # Returns
+     the stop reply
+     */
+    fn continue_execution(processor: &mut Processor, interrupted: &mut dyn FnMut() -> bool) -> String {
+        loop {
+            let result = processor.run(CONTINUE_SLICE);
+
+            return match result.stop_reason {
+                StopReason::BudgetExhausted if interrupted() => format!("S{:02x}", SIGINT),
+                StopReason::BudgetExhausted => continue,
+                StopReason::Breakpoint { .. } => format!("T{:02x}swbreak:;", SIGTRAP),
+                StopReason::Watchpoint { hit } => {
+                    let kind = match hit.kind {
+                        WatchKind::Read => "rwatch",
+                        WatchKind::Write => "watch",
+                        WatchKind::Access => "awatch",
+                    };
+                    format!("T{:02x}{}:{:x};", SIGTRAP, kind, hit.address)
+                }
+                StopReason::Halted | StopReason::IllegalOpcode { .. } => format!("S{:02x}", SIGILL),
+                StopReason::StackGuard { .. } => format!("S{:02x}", SIGSEGV),
+                StopReason::HistoryStart => format!("T{:02x}replaylog:begin;", SIGTRAP),
+            };
+        }
+    }
+
+    fn query(&mut self, processor: &mut Processor, packet: &str) -> String {
+        let (name, arguments) = packet.split_once([':', ',']).unwrap_or((packet, ""));
+
+        match name {
+            "qSupported" => format!(
+                "PacketSize={:x};qXfer:features:read+;swbreak+;hwbreak+;QStartNoAckMode+;ReverseStep+;ReverseContinue+",
+                PACKET_SIZE
+            ),
+            "QStartNoAckMode" => {
+                self.no_ack_mode = true;
+                String::from("OK")
+            }
+            "qXfer" => match arguments.strip_prefix("features:read:target.xml:").and_then(parse_range) {
+                Some((offset, length)) => {
+                    let offset = (offset as usize).min(TARGET_XML.len());
+                    let end = (offset + length as usize).min(TARGET_XML.len());
+                    let marker = if end == TARGET_XML.len() { 'l' } else { 'm' };
+                    format!("{}{}", marker, &TARGET_XML[offset..end])
+                }
+                None => String::from("E00"),
+            },
+            "qAttached" => String::from("1"),
+            "qfThreadInfo" => String::from("m1"),
+            "qsThreadInfo" => String::from("l"),
+            "qC" => String::from("QC1"),
+            // `monitor <command>` in gdb
+            "qRcmd" => match decode_hex(arguments).and_then(|command| String::from_utf8(command).ok()) {
+                Some(command) => {
+                    let mut output = self.monitor.execute(processor, &command);
+                    output.push('\n');
+                    encode_hex(output.as_bytes())
+                }
+                None => String::from("E01"),
+            },
+            _ => String::new(),
+        }
+    }
+}
+
+// Connection implementation
+impl GdbStub {
+    /**
+     * Serves a connected debugger until it detaches or kills the target
+     */
+    pub fn handle_connection(&mut self, processor: &mut Processor, stream: TcpStream) -> io::Result<()> {
+        let mut writer = stream.try_clone()?;
+        let poller = stream.try_clone()?;
+        let mut reader = BufReader::new(stream).bytes();
+
+        // the debugger only sends Ctrl-C while the target runs, anything else read meanwhile is an ack
+        let mut interrupted = || {
+            let mut byte = [0u8];
+            let interrupted = poller.set_nonblocking(true).is_ok() && matches!((&poller).read(&mut byte), Ok(1) if byte[0] == INTERRUPT);
+            let _ = poller.set_nonblocking(false);
+            interrupted
+        };
+
+        loop {
+            let byte = match reader.next() {
+                Some(byte) => byte?,
+                None => return Ok(()),
+            };
+
+            let packet = match byte {
+                b'$' => {
+                    let mut data = Vec::new();
+                    loop {
+                        match reader.next() {
+                            Some(byte) if byte.as_ref().is_ok_and(|byte| *byte == b'#') => break,
+                            Some(byte) => data.push(byte?),
+                            None => return Ok(()),
+                        }
+                    }
+
+                    let mut sent_checksum = [0u8; 2];
+                    for digit in sent_checksum.iter_mut() {
+                        *digit = match reader.next() {
+                            Some(byte) => byte?,
+                            None => return Ok(()),
+                        };
+                    }
+
+                    let data = String::from_utf8_lossy(&data).into_owned();
+                    let valid = std::str::from_utf8(&sent_checksum)
+                        .ok()
+                        .and_then(|sent| u8::from_str_radix(sent, 16).ok())
+                        .is_some_and(|sent| sent == checksum(&data));
+
+                    if !self.no_ack_mode {
+                        writer.write_all(if valid { b"+" } else { b"-" })?;
+                    }
+                    if !valid {
+                        continue;
+                    }
+                    data
+                }
+                // interrupting a target that isn't running
+                INTERRUPT => {
+                    writer.write_all(frame(&format!("S{:02x}", SIGINT)).as_bytes())?;
+                    continue;
+                }
+                // acks and anything outside of a packet
+                _ => continue,
+            };
+
+            match self.handle_packet(processor, &packet, &mut interrupted) {
+                Response::Reply(reply) => writer.write_all(frame(&reply).as_bytes())?,
+                Response::Detach => {
+                    writer.write_all(frame("OK").as_bytes())?;
+                    return Ok(());
+                }
+                Response::Kill => return Ok(()),
+            }
+            writer.flush()?;
+        }
+    }
+}
+
+/**
+ * Listens for a debugger on `127.0.0.1:port`
+ *
+ * The connection accepted from the listener is meant for `GdbStub::handle_connection()`,
+ * port 0 picks a free port (see `TcpListener::local_addr()`).
+ */
+pub fn listen(port: u16) -> io::Result<TcpListener> {
+    TcpListener::bind(("127.0.0.1", port))
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    fn reply(stub: &mut GdbStub, processor: &mut Processor, packet: &str) -> String {
+        match stub.handle_packet(processor, packet, &mut || false) {
+            Response::Reply(reply) => reply,
+            response => panic!("unexpected response {:?}", response),
+        }
+    }
+
+    /**
+     * Whether or not registers, memory, breakpoints, watchpoints and stepping work through packets
+     */
+    #[test]
+    fn packets() {
+        let mut processor = Processor::new();
+        // INX, STX $0200, INX, JAM
+        for (i, byte) in [0xE8, 0x8E, 0x00, 0x02, 0xE8, 0x02].iter().enumerate() {
+            processor.bus.write(0x8000 + i as u16, *byte);
+        }
+        processor.reset();
+        processor.step();
+
+        let mut stub = GdbStub::new();
+
+        assert_eq!(reply(&mut stub, &mut processor, "P5=0080"), "OK");
+        assert_eq!(reply(&mut stub, &mut processor, "p5"), "0080");
+        assert_eq!(reply(&mut stub, &mut processor, "m8000,2"), "e88e");
+        assert_eq!(reply(&mut stub, &mut processor, "M0300,2:abcd"), "OK");
+        assert_eq!(processor.bus.peek(0x0301), 0xCD);
+
+        assert_eq!(reply(&mut stub, &mut processor, "s"), "S05");
+        assert_eq!(reply(&mut stub, &mut processor, "g")[..2], *"00");
+        assert_eq!(&reply(&mut stub, &mut processor, "g")[2..4], "01");
+
+        assert_eq!(reply(&mut stub, &mut processor, "Z2,200,1"), "OK");
+        assert_eq!(reply(&mut stub, &mut processor, "c"), "T05watch:200;");
+        assert_eq!(processor.bus.peek(0x0200), 0x01);
+        assert_eq!(reply(&mut stub, &mut processor, "z2,200,1"), "OK");
+
+        assert_eq!(reply(&mut stub, &mut processor, "c"), "S04");
+        assert_eq!(reply(&mut stub, &mut processor, "?"), "S04");
+
+        assert!(reply(&mut stub, &mut processor, "qXfer:features:read:target.xml:0,ffff").starts_with("l<?xml"));
+        assert_eq!(stub.handle_packet(&mut processor, "D", &mut || false), Response::Detach);
+    }
+
+    /**
+     * Whether or not memory writes don't latch the mapper and access watchpoints are reported as awatch
+     */
+    #[test]
+    fn debugger_writes_and_access_watchpoints() {
+        use crate::mapper::{UxRom, KB_16};
+
+        // 4 banks starting with their number
+        let mut prg = vec![0x00; 4 * KB_16];
+        for bank in 0..4 {
+            prg[bank * KB_16] = bank as u8;
+        }
+
+        let mut processor = Processor::new();
+        processor.bus.set_mapper(Box::new(UxRom::new(&prg)));
+        // LDA $0300, JAM
+        for (i, byte) in [0xAD, 0x00, 0x03, 0x02].iter().enumerate() {
+            processor.bus.write(0x0400 + i as u16, *byte);
+        }
+        let mut registers = processor.registers();
+        registers.program_counter = 0x0400;
+        processor.set_registers(&registers);
+
+        let mut stub = GdbStub::new();
+
+        // the bank stays selected and the read only bank is left as it is
+        assert_eq!(reply(&mut stub, &mut processor, "M8000,1:02"), "E01");
+        assert_eq!(processor.bus.peek(0x8000), 0x00);
+        assert_eq!(reply(&mut stub, &mut processor, "M0300,1:aa"), "OK");
+
+        assert_eq!(reply(&mut stub, &mut processor, "Z4,300,1"), "OK");
+        assert_eq!(reply(&mut stub, &mut processor, "c"), "T05awatch:300;");
+        assert_eq!(processor.registers().accumulator, 0xAA);
+    }
+
+    /**
+     * Whether or not the stub talks the protocol over TCP, acks and checksums included
+     */
+    #[test]
+    fn connection() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+
+        let client = std::thread::spawn(move || {
+            let mut stream = TcpStream::connect(address).unwrap();
+            stream.write_all(frame("Z0,8001,1").as_bytes()).unwrap();
+            stream.write_all(frame("c").as_bytes()).unwrap();
+            stream.write_all(frame("D").as_bytes()).unwrap();
+
+            let mut received = String::new();
+            stream.read_to_string(&mut received).unwrap();
+            received
+        });
+
+        let mut processor = Processor::new();
+        // INX, INX
+        processor.bus.write(0x8000, 0xE8);
+        processor.bus.write(0x8001, 0xE8);
+        processor.reset();
+        processor.step();
+        let mut registers = processor.registers();
+        registers.program_counter = 0x8000;
+        processor.set_registers(&registers);
+
+        let (stream, _) = listener.accept().unwrap();
+        GdbStub::new().handle_connection(&mut processor, stream).unwrap();
+
+        assert_eq!(client.join().unwrap(), format!("+{}+{}+{}", frame("OK"), frame("T05swbreak:;"), frame("OK")));
+    }
+}
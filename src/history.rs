@@ -0,0 +1,260 @@
+use std::collections::VecDeque;
+use std::mem::size_of;
+
+use crate::bus::JournalEntry;
+use crate::save_state::{CpuState, MachineState};
+
+/// Default cycles between two snapshots
+pub const DEFAULT_SNAPSHOT_INTERVAL: u64 = 1_000_000;
+
+/// Default memory the history may take, in bytes
+pub const DEFAULT_MEMORY_CAP: usize = 64 * 1024 * 1024;
+
+/**
+ * What undoing an instruction (or an interrupt) takes
+ */
+#[derive(Debug, Clone)]
+pub struct UndoEntry {
+    /// Cycle the instruction started at
+    pub cycle: u64,
+    /// The processor before the instruction
+    pub cpu: CpuState,
+    /// States of the devices before the instruction, empty without devices
+    pub devices: Vec<Vec<u8>>,
+    /// Changes made by the writes of the instruction, in write order
+    pub writes: Vec<JournalEntry>,
+}
+
+impl UndoEntry {
+    fn size(&self) -> usize {
+        let mapper_registers: usize = self
+            .writes
+            .iter()
+            .map(|write| match write {
+                JournalEntry::MapperRegisters(registers) => registers.capacity(),
+                _ => 0,
+            })
+            .sum();
+
+        size_of::<Self>()
+            + self.writes.capacity() * size_of::<JournalEntry>()
+            + mapper_registers
+            + self.devices.iter().map(|state| size_of::<Vec<u8>>() + state.capacity()).sum::<usize>()
+    }
+}
+
+/**
+ * A save state taken on an instruction boundary
+ */
+#[derive(Clone)]
+pub struct Snapshot {
+    pub cycle: u64,
+    pub state: MachineState,
+}
+
+/**
+ * Execution history for reverse debugging
+ *
+ * Every instruction leaves an undo entry (registers and device states before it, the bytes it overwrote
+ * and the mapper registers it changed), which is enough to step back one instruction at a time. Snapshots are taken periodically
+ * so that cycles older than the undo log can still be reached by replaying from them.
+ * The oldest entries and snapshots are dropped to stay under the memory cap.
+ */
+#[derive(Clone)]
+pub struct History {
+    snapshot_interval: u64,
+    memory_cap: usize,
+    /// Cycles clocked since the history was enabled
+    cycle: u64,
+    next_snapshot: u64,
+    entries: VecDeque<UndoEntry>,
+    snapshots: VecDeque<Snapshot>,
+    /// Bytes taken by the entries
+    entries_usage: usize,
+    /// Bytes taken by the snapshots
+    snapshots_usage: usize,
+}
+
+// Constructor like implementation
+impl History {
+    /**
+     * Returns an empty history
+     *
+     # Arguments
+
+     * `snapshot_interval` - Cycles between two snapshots
+     * `memory_cap` - Bytes the entries and snapshots may take
+     */
+    pub fn new(snapshot_interval: u64, memory_cap: usize) -> Self {
+        Self {
+            snapshot_interval: snapshot_interval.max(1),
+            memory_cap,
+            cycle: 0,
+            next_snapshot: 0,
+            entries: VecDeque::new(),
+            snapshots: VecDeque::new(),
+            entries_usage: 0,
+            snapshots_usage: 0,
+        }
+    }
+}
+
+impl Default for History {
+    fn default() -> Self {
+        Self::new(DEFAULT_SNAPSHOT_INTERVAL, DEFAULT_MEMORY_CAP)
+    }
+}
+
+impl History {
+    /**
+     * The current cycle, counted from when the history was enabled
+     */
+    pub fn cycle(&self) -> u64 {
+        self.cycle
+    }
+
+    /**
+     * The oldest cycle that can still be reached
+     */
+    pub fn oldest_cycle(&self) -> u64 {
+        let oldest_snapshot = self.snapshots.front().map_or(self.cycle, |snapshot| snapshot.cycle);
+        self.oldest_entry_cycle().min(oldest_snapshot)
+    }
+
+    /**
+     * Cycle of the oldest instruction that can be undone, the current cycle when there is none
+     */
+    pub fn oldest_entry_cycle(&self) -> u64 {
+        self.entries.front().map_or(self.cycle, |entry| entry.cycle)
+    }
+
+    /**
+     * Number of instructions that can be undone one by one
+     */
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn snapshot_count(&self) -> usize {
+        self.snapshots.len()
+    }
+
+    pub fn memory_usage(&self) -> usize {
+        self.entries_usage + self.snapshots_usage
+    }
+
+    pub fn snapshot_interval(&self) -> u64 {
+        self.snapshot_interval
+    }
+
+    pub fn memory_cap(&self) -> usize {
+        self.memory_cap
+    }
+
+    pub(crate) fn tick(&mut self) {
+        self.cycle += 1;
+    }
+
+    pub(crate) fn wants_snapshot(&self) -> bool {
+        self.cycle >= self.next_snapshot
+    }
+
+    /**
+     * Records the start of an instruction, `writes` are the ones of the previous instruction
+     */
+    pub(crate) fn push(&mut self, cpu: CpuState, devices: Vec<Vec<u8>>, writes: Vec<JournalEntry>) {
+        self.attach_writes(writes);
+
+        let entry = UndoEntry { cycle: self.cycle, cpu, devices, writes: Vec::new() };
+        self.entries_usage += entry.size();
+        self.entries.push_back(entry);
+
+        self.enforce_cap();
+    }
+
+    /**
+     * Gives the writes made since the last entry to it
+     */
+    pub(crate) fn attach_writes(&mut self, writes: Vec<JournalEntry>) {
+        if let Some(entry) = self.entries.back_mut() {
+            self.entries_usage -= entry.size();
+            entry.writes.extend(writes);
+            self.entries_usage += entry.size();
+        }
+    }
+
+    pub(crate) fn push_snapshot(&mut self, state: MachineState) {
+        self.snapshots_usage += state.size();
+        self.snapshots.push_back(Snapshot { cycle: self.cycle, state });
+        self.next_snapshot = self.cycle + self.snapshot_interval;
+
+        self.enforce_cap();
+    }
+
+    /**
+     * Removes the newest entry, the caller reverts it
+     */
+    pub(crate) fn pop(&mut self) -> Option<UndoEntry> {
+        let entry = self.entries.pop_back()?;
+        self.entries_usage -= entry.size();
+        self.cycle = entry.cycle;
+        self.drop_snapshots_after(entry.cycle);
+        Some(entry)
+    }
+
+    /**
+     * Returns the newest snapshot at or before the cycle, forgetting everything after it
+     */
+    pub(crate) fn rewind_to_snapshot(&mut self, cycle: u64) -> Option<MachineState> {
+        self.drop_snapshots_after(cycle);
+        let snapshot = self.snapshots.back()?.clone();
+
+        // the undo log can't be used across a restored snapshot
+        while let Some(entry) = self.entries.pop_back() {
+            self.entries_usage -= entry.size();
+        }
+
+        self.cycle = snapshot.cycle;
+        Some(snapshot.state)
+    }
+
+    fn drop_snapshots_after(&mut self, cycle: u64) {
+        while self.snapshots.back().is_some_and(|snapshot| snapshot.cycle > cycle) {
+            let snapshot = self.snapshots.pop_back().unwrap();
+            self.snapshots_usage -= snapshot.state.size();
+        }
+
+        self.next_snapshot = self.snapshots.back().map_or(0, |snapshot| snapshot.cycle + self.snapshot_interval);
+    }
+
+    /**
+     * Drops the oldest entries and snapshots until the history fits under the cap
+     *
+     * The undo log may take half of the cap, so that the snapshots keep reaching further back
+     * than it does. The newest snapshot is never dropped.
+     */
+    fn enforce_cap(&mut self) {
+        while self.entries_usage > self.memory_cap / 2 {
+            match self.entries.pop_front() {
+                Some(entry) => self.entries_usage -= entry.size(),
+                None => break,
+            }
+        }
+
+        while self.memory_usage() > self.memory_cap && self.snapshots.len() > 1 {
+            let snapshot = self.snapshots.pop_front().unwrap();
+            self.snapshots_usage -= snapshot.state.size();
+        }
+
+        while self.memory_usage() > self.memory_cap {
+            match self.entries.pop_front() {
+                Some(entry) => self.entries_usage -= entry.size(),
+                None => break,
+            }
+        }
+    }
+}
@@ -0,0 +1,233 @@
+use serde::Serialize;
+
+use crate::processor::{AddressingMode, Instruction, Operation};
+
+// Status register masks, for the flags an instruction reads or writes
+pub const FLAG_CARRY: u8 = 1 << 0;
+pub const FLAG_ZERO: u8 = 1 << 1;
+pub const FLAG_INTERRUPT_DISABLE: u8 = 1 << 2;
+pub const FLAG_DECIMAL: u8 = 1 << 3;
+pub const FLAG_OVERFLOW: u8 = 1 << 6;
+pub const FLAG_NEGATIVE: u8 = 1 << 7;
+
+/// Every flag that exists in the status register (B and the unused bit only exist once pushed)
+pub const ALL_FLAGS: u8 = FLAG_CARRY | FLAG_ZERO | FLAG_INTERRUPT_DISABLE | FLAG_DECIMAL | FLAG_OVERFLOW | FLAG_NEGATIVE;
+
+const NZ: u8 = FLAG_NEGATIVE | FLAG_ZERO;
+const NZC: u8 = NZ | FLAG_CARRY;
+const NVZC: u8 = NZC | FLAG_OVERFLOW;
+
+/**
+ * What the bytes following the opcode are
+ */
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub enum OperandType {
+    /// No operand
+    None,
+    /// No operand, the instruction works on the accumulator (e.g. `ASL A`)
+    Accumulator,
+    /// One byte of data
+    Immediate,
+    /// One byte addressing the zero page (indexed and indirect modes included)
+    ZeroPage,
+    /// Two bytes address, little endian (indexed and indirect modes included)
+    Absolute,
+    /// Signed branch offset from the next instruction
+    Relative,
+}
+
+/**
+ * How an instruction affects the flow of execution
+ */
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub enum ControlFlow {
+    /// Continues with the next instruction
+    Sequential,
+    /// Conditionally continues at the relative target
+    Branch,
+    /// Continues at the target (`JMP`), indirect jumps only know it at run time
+    Jump,
+    /// Continues at the target and returns to the next instruction (`JSR`)
+    Call,
+    /// Continues at an address pulled from the stack (`RTS`, `RTI`)
+    Return,
+    /// Continues at the IRQ/BRK vector (`BRK`)
+    Interrupt,
+    /// Freezes the processor (`JAM`)
+    Halt,
+}
+
+/**
+ * Everything known about an instruction before running it
+ */
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct InstructionInfo {
+    pub name: &'static str,
+    pub length: u8,
+    pub cycles: u8,
+    pub page_cross_penalty: bool,
+    pub operand_type: OperandType,
+    pub flags_read: u8,
+    pub flags_written: u8,
+    pub reads_memory: bool,
+    pub writes_memory: bool,
+    pub control_flow: ControlFlow,
+    pub undocumented: bool,
+}
+
+// Instruction metadata implementation
+impl Instruction {
+    pub fn operand_type(&self) -> OperandType {
+        match self.addressing_mode_enum {
+            AddressingMode::IMPL => match self.operation_enum {
+                Operation::ASL | Operation::LSR | Operation::ROL | Operation::ROR => OperandType::Accumulator,
+                _ => OperandType::None,
+            },
+            AddressingMode::IMM => OperandType::Immediate,
+            AddressingMode::REL => OperandType::Relative,
+            AddressingMode::ZPG | AddressingMode::ZPGX | AddressingMode::ZPGY | AddressingMode::INDX | AddressingMode::INDY => {
+                OperandType::ZeroPage
+            }
+            AddressingMode::ABS | AddressingMode::ABSX | AddressingMode::ABSY | AddressingMode::IND => OperandType::Absolute,
+        }
+    }
+
+    /**
+     * Mask of the status flags the result depends on (see the `FLAG_` constants)
+     */
+    pub fn flags_read(&self) -> u8 {
+        match self.operation_enum {
+            Operation::BCC | Operation::BCS => FLAG_CARRY,
+            Operation::BEQ | Operation::BNE => FLAG_ZERO,
+            Operation::BMI | Operation::BPL => FLAG_NEGATIVE,
+            Operation::BVC | Operation::BVS => FLAG_OVERFLOW,
+            Operation::ADC | Operation::SBC | Operation::USBC | Operation::RRA | Operation::ISC | Operation::ARR => {
+                FLAG_CARRY | FLAG_DECIMAL
+            }
+            Operation::ROL | Operation::ROR | Operation::RLA => FLAG_CARRY,
+            // the whole register is pushed
+            Operation::PHP | Operation::BRK => ALL_FLAGS,
+            _ => 0,
+        }
+    }
+
+    /**
+     * Mask of the status flags the instruction may change (see the `FLAG_` constants)
+     */
+    pub fn flags_written(&self) -> u8 {
+        match self.operation_enum {
+            Operation::ADC | Operation::SBC | Operation::USBC | Operation::RRA | Operation::ISC | Operation::ARR => NVZC,
+            Operation::BIT => FLAG_NEGATIVE | FLAG_OVERFLOW | FLAG_ZERO,
+            Operation::ASL | Operation::LSR | Operation::ROL | Operation::ROR | Operation::CMP | Operation::CPX
+            | Operation::CPY | Operation::SLO | Operation::RLA | Operation::SRE | Operation::ALR | Operation::ANC
+            | Operation::DCP | Operation::SBX => NZC,
+            Operation::AND | Operation::EOR | Operation::ORA | Operation::LDA | Operation::LDX | Operation::LDY
+            | Operation::DEC | Operation::DEX | Operation::DEY | Operation::INC | Operation::INX | Operation::INY
+            | Operation::TAX | Operation::TAY | Operation::TSX | Operation::TXA | Operation::TYA | Operation::PLA
+            | Operation::ANE | Operation::LAS | Operation::LAX | Operation::LXA => NZ,
+            Operation::CLC | Operation::SEC => FLAG_CARRY,
+            Operation::CLD | Operation::SED => FLAG_DECIMAL,
+            Operation::CLI | Operation::SEI | Operation::BRK => FLAG_INTERRUPT_DISABLE,
+            Operation::CLV => FLAG_OVERFLOW,
+            Operation::PLP | Operation::RTI => ALL_FLAGS,
+            _ => 0,
+        }
+    }
+
+    /**
+     * Whether or not the memory addressed by the operand is read, stack accesses aside
+     */
+    pub fn reads_memory(&self) -> bool {
+        self.accesses_memory()
+            && !matches!(
+                self.operation_enum,
+                Operation::JMP | Operation::JSR | Operation::STA | Operation::STX | Operation::STY | Operation::SAX
+                    | Operation::SHA | Operation::SHX | Operation::SHY | Operation::TAS | Operation::JAM
+            )
+    }
+
+    /**
+     * Whether or not the memory addressed by the operand is written, stack accesses aside
+     */
+    pub fn writes_memory(&self) -> bool {
+        self.accesses_memory()
+            && matches!(
+                self.operation_enum,
+                Operation::STA | Operation::STX | Operation::STY | Operation::ASL | Operation::LSR | Operation::ROL
+                    | Operation::ROR | Operation::DEC | Operation::INC | Operation::SLO | Operation::RLA | Operation::SRE
+                    | Operation::RRA | Operation::SAX | Operation::SHA | Operation::SHX | Operation::SHY | Operation::TAS
+                    | Operation::DCP | Operation::ISC
+            )
+    }
+
+    fn accesses_memory(&self) -> bool {
+        !matches!(self.addressing_mode_enum, AddressingMode::IMPL | AddressingMode::IMM | AddressingMode::REL)
+    }
+
+    pub fn control_flow(&self) -> ControlFlow {
+        match self.operation_enum {
+            Operation::BCC | Operation::BCS | Operation::BEQ | Operation::BNE | Operation::BMI | Operation::BPL
+            | Operation::BVC | Operation::BVS => ControlFlow::Branch,
+            Operation::JMP => ControlFlow::Jump,
+            Operation::JSR => ControlFlow::Call,
+            Operation::RTS | Operation::RTI => ControlFlow::Return,
+            Operation::BRK => ControlFlow::Interrupt,
+            Operation::JAM => ControlFlow::Halt,
+            _ => ControlFlow::Sequential,
+        }
+    }
+
+    pub fn info(&self) -> InstructionInfo {
+        InstructionInfo {
+            name: self.name,
+            length: self.length,
+            cycles: self.cycles,
+            page_cross_penalty: self.page_cross_penalty,
+            operand_type: self.operand_type(),
+            flags_read: self.flags_read(),
+            flags_written: self.flags_written(),
+            reads_memory: self.reads_memory(),
+            writes_memory: self.writes_memory(),
+            control_flow: self.control_flow(),
+            undocumented: self.undocumented,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    /**
+     * Whether or not the metadata of a few representative opcodes is right
+     */
+    #[test]
+    fn metadata() {
+        let inc = Instruction::decode(0xFE).info(); // INC abs,X
+        assert_eq!(inc.operand_type, OperandType::Absolute);
+        assert!(inc.reads_memory && inc.writes_memory);
+        assert_eq!((inc.flags_read, inc.flags_written), (0, NZ));
+
+        let sta = Instruction::decode(0x85).info(); // STA zpg
+        assert_eq!(sta.operand_type, OperandType::ZeroPage);
+        assert!(!sta.reads_memory && sta.writes_memory);
+
+        let asl = Instruction::decode(0x0A).info(); // ASL A
+        assert_eq!(asl.operand_type, OperandType::Accumulator);
+        assert!(!asl.reads_memory && !asl.writes_memory);
+
+        let bne = Instruction::decode(0xD0).info();
+        assert_eq!((bne.control_flow, bne.flags_read), (ControlFlow::Branch, FLAG_ZERO));
+
+        assert_eq!(Instruction::decode(0x20).control_flow(), ControlFlow::Call);
+        assert_eq!(Instruction::decode(0x6C).control_flow(), ControlFlow::Jump);
+        assert_eq!(Instruction::decode(0x40).control_flow(), ControlFlow::Return);
+        assert!(!Instruction::decode(0x4C).reads_memory());
+
+        // NOP, the undocumented NOPs share the operation
+        assert!(!Instruction::decode(0xEA).info().undocumented);
+        assert!(Instruction::decode(0x1A).info().undocumented && Instruction::decode(0x04).info().undocumented);
+        assert!(Instruction::decode(0x03).info().undocumented); // SLO (zpg,X)
+    }
+}
@@ -1,25 +1,293 @@
+use emulator_6502::analysis::Analysis;
+use emulator_6502::debug_info::DebugInfo;
+use emulator_6502::devices::acia::{self, Acia};
+#[cfg(unix)]
+use emulator_6502::devices::serial::PtyBridge;
+use emulator_6502::devices::serial::{SerialBridge, TcpBridge};
+use emulator_6502::devices::terminal::{self, Terminal};
+use emulator_6502::gdb::{self, GdbStub};
+use emulator_6502::history;
+use emulator_6502::memory::PowerOnPattern;
+use emulator_6502::monitor::Monitor;
+use emulator_6502::processor::StopReason;
+use emulator_6502::profiler::ProfileReport;
+use emulator_6502::replay::{InputEvent, InputLog};
+use emulator_6502::scheduler::{self, ClockRate, Scheduler};
 use emulator_6502::Processor;
 
-pub mod bus;
-pub mod memory;
-pub mod processor;
-pub mod rom;
-
+use std::cell::RefCell;
 use std::env;
+use std::fs;
+use std::io::{self, BufRead, Read, Write};
+use std::path::Path;
+use std::rc::Rc;
+use std::sync::mpsc::{self, Receiver};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// How long the throttled run loop sleeps between scheduler updates
+const THROTTLE_SLICE: Duration = Duration::from_millis(10);
+
+/// How often the serial bridge is polled for received bytes (once per 60 Hz frame)
+const SERIAL_POLL_INTERVAL: Duration = Duration::from_micros(16_667);
+
+/// Number of instructions the monitor's `t` command can show
+const MONITOR_TRACE_LENGTH: usize = 256;
+
+/**
+ * Returns the value following the given option on the command line, if any
+ */
+fn arg_value<'a>(args: &'a [String], option: &str) -> Option<&'a String> {
+    args.iter()
+        .position(|arg| arg == option)
+        .and_then(|index| args.get(index + 1))
+}
+
+/**
+ * Reads `--clock <rate>` from the command line, defaults to unlimited
+ */
+fn clock_rate_from_args(args: &[String]) -> ClockRate {
+    match arg_value(args, "--clock").map(|rate| rate.parse::<ClockRate>()) {
+        Some(Ok(clock_rate)) => clock_rate,
+        Some(Err(error)) => {
+            println!("{}, running unlimited", error);
+            ClockRate::Unlimited
+        }
+        None => ClockRate::Unlimited,
+    }
+}
+
+/**
+ * Reads `--terminal` and `--terminal-address <hex>` from the command line
+ *
+ * Returns the address to map the terminal at, if it was asked for
+ */
+fn terminal_address_from_args(args: &[String]) -> Option<u16> {
+    let address = arg_value(args, "--terminal-address").map(|address| {
+        u16::from_str_radix(address.trim_start_matches('$').trim_start_matches("0x"), 16)
+            .unwrap_or_else(|_| panic!("Invalid terminal address {}", address))
+    });
+
+    if args.iter().any(|arg| arg == "--terminal") || address.is_some() {
+        Some(address.unwrap_or(terminal::APPLE_1_ADDRESS))
+    } else {
+        None
+    }
+}
+
+#[cfg(unix)]
+fn pty_bridge() -> Box<dyn SerialBridge> {
+    let bridge = PtyBridge::new().expect("Failed to open a pseudo terminal for the ACIA");
+    println!("ACIA connected to {}", bridge.path());
+    Box::new(bridge)
+}
+
+#[cfg(not(unix))]
+fn pty_bridge() -> Box<dyn SerialBridge> {
+    panic!("Pseudo terminals are only available on Unix, use --acia-tcp <port> instead");
+}
+
+/**
+ * Reads `--acia-tcp <port>` or `--acia-pty` and `--acia-address <hex>` from the command line
+ *
+ * Returns the address to map the ACIA at along with the host end of its serial line, if it was asked for
+ */
+fn acia_from_args(args: &[String]) -> Option<(u16, Box<dyn SerialBridge>)> {
+    let bridge: Box<dyn SerialBridge> = if let Some(port) = arg_value(args, "--acia-tcp") {
+        let port = port.parse::<u16>().unwrap_or_else(|_| panic!("Invalid port {}", port));
+        let bridge = TcpBridge::new(port).expect("Failed to listen for the ACIA");
+        println!("ACIA listening on {}", bridge.local_address().expect("Failed to read the ACIA address"));
+        Box::new(bridge)
+    } else if args.iter().any(|arg| arg == "--acia-pty") {
+        pty_bridge()
+    } else {
+        return None;
+    };
+
+    let address = arg_value(args, "--acia-address").map_or(acia::DEFAULT_ADDRESS, |address| {
+        u16::from_str_radix(address.trim_start_matches('$').trim_start_matches("0x"), 16)
+            .unwrap_or_else(|_| panic!("Invalid ACIA address {}", address))
+    });
+
+    Some((address, bridge))
+}
+
+/**
+ * Forwards stdin to the returned channel byte by byte from a background thread
+ */
+fn spawn_stdin_reader() -> Receiver<u8> {
+    let (sender, receiver) = mpsc::channel();
+
+    thread::spawn(move || {
+        for byte in io::stdin().lock().bytes() {
+            match byte {
+                Ok(byte) if sender.send(byte).is_ok() => (),
+                _ => break,
+            }
+        }
+    });
+
+    receiver
+}
+
+/**
+ * Analyses the code reachable from the vectors and writes the result in the format given by the extension
+ */
+fn write_analysis(path: &str, proc: &Processor) {
+    let analysis = Analysis::new(&proc.bus);
+
+    let output = if path.ends_with(".calls.dot") {
+        analysis.call_graph_dot(&proc.symbols)
+    } else if path.ends_with(".dot") {
+        analysis.to_dot(&proc.symbols)
+    } else {
+        let rom_start = (proc.bus.memory.len() + proc.bus.other.len()) as u16;
+        analysis.to_disassembly(&proc.symbols, rom_start, 0xFFFF)
+    };
+
+    match fs::write(path, output) {
+        Ok(()) => println!(
+            "Found {} code bytes in {} blocks and {} subroutines, written to {}",
+            analysis.code_byte_count(),
+            analysis.blocks().len(),
+            analysis.call_graph().len(),
+            path
+        ),
+        Err(error) => println!("Failed to write the analysis to {}: {}", path, error),
+    }
+}
+
+/**
+ * Stops recording the external inputs and writes them as JSON
+ */
+fn write_recording(path: &str, proc: &mut Processor) {
+    if let Some(log) = proc.stop_recording() {
+        match log.save(path) {
+            Ok(()) => println!("Recorded {} inputs to {}", log.len(), path),
+            Err(error) => println!("Failed to write the recording to {}: {}", path, error),
+        }
+    }
+}
+
+/**
+ * Writes the coverage as an lcov tracefile for a `.info` path, as the per-address text report otherwise
+ */
+fn write_coverage(path: &str, proc: &Processor) {
+    let Some(coverage) = proc.coverage() else {
+        return;
+    };
+
+    let output = match path.ends_with(".info") {
+        true => coverage.to_lcov(proc),
+        false => coverage.to_text(proc),
+    };
+
+    match fs::write(path, output) {
+        Ok(()) => println!("Coverage of {} instructions written to {}", coverage.executed().count(), path),
+        Err(error) => println!("Failed to write the coverage to {}: {}", path, error),
+    }
+}
+
+/**
+ * Writes the profile as CSV or JSON depending on the extension and prints the hottest subroutines
+ *
+ * A `.csv` path gets one file per table, named after it (e.g. `profile.memory.csv`)
+ */
+fn write_profile(path: &str, report: &ProfileReport) {
+    let result = match path.strip_suffix(".csv") {
+        Some(stem) => [
+            ("memory", report.memory_csv()),
+            ("opcodes", report.opcodes_csv()),
+            ("program_counters", report.program_counters_csv()),
+            ("hot_subroutines", report.hot_subroutines_csv()),
+        ]
+        .iter()
+        .try_for_each(|(table, csv)| fs::write(format!("{}.{}.csv", stem, table), csv)),
+        None => fs::write(path, report.to_json()),
+    };
+
+    match result {
+        Ok(()) => println!("Profile written to {}", path),
+        Err(error) => println!("Failed to write the profile to {}: {}", path, error),
+    }
+
+    println!("Hot subroutines:");
+    for subroutine in report.hot_subroutines.iter().take(10) {
+        println!(
+            "  ${:04X}  {:>8} calls  {:>12} cycles  ({} in its own code)",
+            subroutine.address, subroutine.calls, subroutine.inclusive_cycles, subroutine.exclusive_cycles
+        );
+    }
+}
+
+/**
+ * Writes what was asked for on the command line once the program stops: the recording, the profile and the coverage
+ */
+fn finish_session(proc: &mut Processor, args: &[String]) {
+    if let Some(path) = arg_value(args, "--record") {
+        write_recording(path, proc);
+    }
+
+    if let (Some(path), Some(report)) = (arg_value(args, "--profile"), proc.profile_report()) {
+        write_profile(path, &report);
+    }
+
+    if let Some(path) = arg_value(args, "--coverage") {
+        write_coverage(path, proc);
+    }
+}
+
+/**
+ * Waits for a debugger on the port and serves it until it detaches
+ */
+fn run_debugger(proc: &mut Processor, port: u16) -> io::Result<()> {
+    let listener = gdb::listen(port)?;
+    println!("Waiting for a debugger on {}", listener.local_addr()?);
+
+    let (stream, peer) = listener.accept()?;
+    println!("Debugger connected from {}", peer);
+
+    GdbStub::new().handle_connection(proc, stream)
+}
+
+/**
+ * Reads monitor commands from stdin until `q` or the end of input
+ */
+fn run_monitor(proc: &mut Processor) {
+    let mut monitor = Monitor::new();
+    proc.enable_trace(MONITOR_TRACE_LENGTH);
+
+    println!("Monitor, h for help, q to quit");
+    print!("> ");
+    io::stdout().flush().expect("Failed to write to stdout");
+
+    for line in io::stdin().lock().lines() {
+        let line = line.expect("Failed to read from stdin");
+        if line.trim() == "q" {
+            break;
+        }
+
+        let output = monitor.execute(proc, &line);
+        if !output.is_empty() {
+            println!("{}", output);
+        }
+        print!("> ");
+        io::stdout().flush().expect("Failed to write to stdout");
+    }
+}
 
 #[cfg(debug_assertions)]
-fn display_instruction_set() -> () {
-    use crate::processor::Instruction;
+fn display_instruction_set() {
+    use emulator_6502::processor::INSTRUCTIONS;
 
-    let vec = Instruction::create_instructions_table();
-    println!("length of vector: {}", vec.len());
+    println!("length of table: {}", INSTRUCTIONS.len());
 
     print!("\n{:8}", " ");
     for i in 0..16 {
         print!("{:^12}", format!("{:01x}", i));
     }
 
-    for (i, instruction) in vec.iter().enumerate() {
+    for (i, instruction) in INSTRUCTIONS.iter().enumerate() {
         if (i == 0) || i % 16 == 0 {
             print!("{:^12}", format!("\n {:01x}", (i + 1)/16));
         }
@@ -29,24 +297,25 @@ fn display_instruction_set() -> () {
 
 }
 
-fn main() -> () {
+fn main() {
     #[cfg(debug_assertions)] 
     {
         display_instruction_set();
     }
 
+    let args: Vec<String> = env::args().collect();
+
     // new processor instance
     let mut proc = Processor::new();
     
-    let mut current_dir = String::from("");
-    match env::current_dir() {
+    let current_dir = match env::current_dir() {
         Ok(current_dir_temp) => { 
-            current_dir = current_dir_temp.to_str().unwrap().to_owned();
+            current_dir_temp.to_str().unwrap().to_owned()
         },
         Err(_) => {
-            current_dir = String::from("None");
+            String::from("None")
         },
-    }
+    };
 
     let file_path = current_dir +  "/6502_functional_test.bin";
     // loading the rom file
@@ -55,17 +324,192 @@ fn main() -> () {
 
         let max_cycles = 10000000;
 
+        // labels for the backtraces and the monitor
+        if let Some(path) = arg_value(&args, "--symbols") {
+            match proc.symbols.load_file(path) {
+                Ok(count) => println!("Loaded {} symbols from {}", count, path),
+                Err(error) => println!("Failed to load symbols from {}: {}", path, error),
+            }
+        }
+
+        // source lines (and labels) from `ld65 --dbgfile`, the sources are looked up next to it
+        if let Some(path) = arg_value(&args, "--dbgfile") {
+            match DebugInfo::load_file(path) {
+                Ok(mut debug_info) => {
+                    let sources = debug_info.load_sources(Path::new(path).parent().unwrap_or(Path::new(".")));
+                    println!("Loaded debug info from {} ({} of {} source files found)", path, sources, debug_info.files().len());
+
+                    proc.debug_info = debug_info;
+                    proc.symbols.load_file(path).ok();
+                }
+                Err(error) => println!("Failed to load debug info from {}: {}", path, error),
+            }
+        }
+
+        // turning the machine on with a RAM pattern (zero, ff, alternating, random[:seed]) and starting from the reset vector
+        if let Some(pattern) = arg_value(&args, "--power-on") {
+            let pattern = pattern.parse::<PowerOnPattern>().unwrap_or_else(|error| panic!("{}", error));
+            proc.power_on(pattern);
+            println!("Powered on with {:?} RAM, starting at {:#06X}", pattern, proc.program_counter());
+        }
+
+        let mut scheduler = Scheduler::new(clock_rate_from_args(&args));
+
+        // connecting the terminal to stdin and stdout
+        let terminal = terminal_address_from_args(&args).map(|address| {
+            let terminal = Rc::new(RefCell::new(Terminal::new()));
+            proc.bus.attach_device(address, terminal.clone());
+            println!("Terminal attached at {:#06X}", address);
+
+            (address, terminal, spawn_stdin_reader())
+        });
+
+        // connecting the ACIA to a pseudo terminal or a TCP port
+        let acia = acia_from_args(&args).map(|(address, bridge)| {
+            // baud rates are timed against the nominal clock when running unlimited
+            let cpu_hz = match scheduler.clock_rate() {
+                ClockRate::Hz(hz) => hz,
+                ClockRate::Unlimited => scheduler::MHZ_1,
+            };
+
+            let acia = Rc::new(RefCell::new(Acia::new(bridge, cpu_hz)));
+            proc.bus.attach_device(address, acia.clone());
+            println!("ACIA attached at {:#06X}", address);
+
+            (address, acia)
+        });
+
+        // reporting stack wraps, and stack depths above the given number of bytes (0 for wraps only)
+        if let Some(depth) = arg_value(&args, "--stack-guard") {
+            let depth = depth.parse::<u16>().unwrap_or_else(|_| panic!("Invalid stack depth {}", depth));
+            proc.enable_stack_guard((depth > 0).then_some(depth));
+        }
+
+        // execution history for stepping back in the monitor or the debugger, capped to the given MB
+        if let Some(megabytes) = arg_value(&args, "--history") {
+            let megabytes = megabytes.parse::<usize>().unwrap_or_else(|_| panic!("Invalid history size {}", megabytes));
+            proc.enable_history(history::DEFAULT_SNAPSHOT_INTERVAL, megabytes * 1024 * 1024);
+        }
+
+        // external inputs are recorded to a file written when the program stops,
+        // or replayed from one in which case the live input is ignored
+        if arg_value(&args, "--record").is_some() {
+            proc.start_recording();
+        }
+        if let Some(path) = arg_value(&args, "--replay") {
+            match InputLog::load(path) {
+                Ok(log) => {
+                    println!("Replaying {} inputs from {}", log.len(), path);
+                    proc.start_replay(log);
+                }
+                Err(error) => println!("Failed to load the recording from {}: {}", path, error),
+            }
+        }
+
+        // profiling, the report is written when the program stops
+        if arg_value(&args, "--profile").is_some() {
+            proc.enable_profiling();
+        }
+
+        // coverage of the executed instructions and branches, written when the program stops
+        if arg_value(&args, "--coverage").is_some() {
+            proc.enable_coverage();
+        }
+
+        // static analysis of the ROM, written as Graphviz DOT (the call graph for `.calls.dot`) or annotated disassembly
+        if let Some(path) = arg_value(&args, "--analyze") {
+            write_analysis(path, &proc);
+            return;
+        }
+
+        // a debugger drives the processor through the GDB remote serial protocol
+        if let Some(port) = arg_value(&args, "--gdb") {
+            let port = port.parse::<u16>().unwrap_or_else(|_| panic!("Invalid port {}", port));
+            if let Err(error) = run_debugger(&mut proc, port) {
+                println!("Debugger connection failed: {}", error);
+            }
+
+            finish_session(&mut proc, &args);
+            return;
+        }
+
+        // the monitor drives the processor itself
+        if args.iter().any(|arg| arg == "--monitor") {
+            run_monitor(&mut proc);
+
+            finish_session(&mut proc, &args);
+            return;
+        }
+
         // running the cpu
-        let mut cycle_count = 0;
+        let mut last_update = Instant::now();
+        let mut since_serial_poll = SERIAL_POLL_INTERVAL;
         loop {
-            proc.clock();
+            if scheduler.is_throttled() {
+                thread::sleep(THROTTLE_SLICE);
+            }
+
+            // host input goes through the processor so that it can be recorded,
+            // it is read and dropped while a recording is replayed
+            let replaying = proc.is_replaying();
+            if let Some((address, _, keys)) = &terminal {
+                for key in keys.try_iter() {
+                    if !replaying {
+                        proc.input(InputEvent::Device { address: *address, port: 0, value: key });
+                    }
+                }
+            }
+
+            let now = Instant::now();
+            let elapsed = now - last_update;
+            last_update = now;
 
-            if cycle_count > max_cycles {
-                break
+            // the bridge is polled once per frame, the ACIA queues the bytes and receives them at its baud rate
+            since_serial_poll += elapsed;
+            if let Some((address, acia)) = &acia {
+                if since_serial_poll >= SERIAL_POLL_INTERVAL {
+                    since_serial_poll = Duration::ZERO;
+                    let received: Vec<u8> = std::iter::from_fn(|| acia.borrow_mut().bridge_mut().receive()).collect();
+                    if !replaying {
+                        for byte in received {
+                            proc.input(InputEvent::Device { address: *address, port: 0, value: byte });
+                        }
+                    }
+                }
+            }
+
+            let result = scheduler.advance(&mut proc, elapsed);
+
+            if let Some((_, terminal, _)) = &terminal {
+                let output = terminal.borrow_mut().take_output();
+                if !output.is_empty() {
+                    let mut stdout = io::stdout();
+                    stdout.write_all(&output).and_then(|_| stdout.flush()).expect("Failed to write to stdout");
+                }
+            }
+
+            if let Some(result) = result {
+                // stack guard events are reported without stopping the program
+                if let StopReason::StackGuard { event } = result.stop_reason {
+                    println!("Stack guard: {:?}", event);
+                    continue;
+                }
+
+                // an interactive session runs until the program stops
+                let out_of_cycles = terminal.is_none() && acia.is_none() && proc.total_cycles() >= max_cycles;
+                if result.stop_reason != StopReason::BudgetExhausted || out_of_cycles {
+                    println!("Program Complete: {:?} after {} cycles", result.stop_reason, proc.total_cycles());
+                    if result.stop_reason != StopReason::BudgetExhausted {
+                        print!("{}", proc.backtrace());
+                    }
+                    break;
+                }
             }
-            cycle_count += 1;
         }
-        println!("Program Complete");
+        println!("Achieved speed: {:.0} Hz", scheduler.achieved_hz());
+        println!("{}", proc.timing_stats());
+
+        finish_session(&mut proc, &args);
 
     } else {
         println!("Read File {} failed", file_path);
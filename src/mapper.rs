@@ -0,0 +1,704 @@
+use serde::Serialize;
+
+pub const KB_8: usize = 8 * 1024;
+pub const KB_16: usize = 16 * 1024;
+pub const KB_32: usize = 32 * 1024;
+
+/**
+ * A bank currently visible to the processor, reported by `getStorageLayout`
+ */
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct MappedBank {
+    /// Which image or memory the bank comes from (e.g. `prg`, `kernal`)
+    pub region: String,
+    /// Index of the bank inside the region
+    pub bank: usize,
+    /// First address the bank is visible at
+    pub start: u16,
+    /// Number of addresses the bank covers
+    pub size: usize,
+}
+
+impl MappedBank {
+    fn new(region: &str, bank: usize, start: u16, size: usize) -> Self {
+        Self {
+            region: region.to_owned(),
+            bank,
+            start,
+            size,
+        }
+    }
+}
+
+/**
+ * Bank switching logic sitting between the processor and the storages of the `Bus`
+ *
+ * Mappers see every access not claimed by a memory mapped device.
+ * Addresses they don't map fall through to the flat storages of the bus.
+ */
+pub trait Mapper {
+    /// Returns the byte at the address if the mapper maps it
+    fn read(&self, address: u16) -> Option<u8>;
+
+    /// Handles the write (bank latch, banked RAM or ignored ROM write), returns `false` to let it reach the storages
+    fn write(&mut self, address: u16, data: u8) -> bool;
+
+    /// The banks visible right now
+    fn banks(&self) -> Vec<MappedBank>;
+
+    /// The registers writes change (bank selection, shift register, ...), for save states and the execution history
+    fn save_registers(&self) -> Vec<u8>;
+
+    /// Restores registers returned by `save_registers()`
+    fn load_registers(&mut self, registers: &[u8]);
+
+    /// Banked RAM, empty when the mapper has none
+    fn ram(&self) -> &[u8] {
+        &[]
+    }
+
+    fn ram_mut(&mut self) -> &mut [u8] {
+        &mut []
+    }
+
+    /// Writes into the banked RAM visible at the address without touching the registers (debugger writes, undoing writes),
+    /// returns `false` when no RAM is mapped there
+    fn poke(&mut self, _address: u16, _data: u8) -> bool {
+        false
+    }
+}
+
+/**
+ * Splits the image into banks of the given size, the last bank is padded with 0xFF
+ */
+fn split_banks(image: &[u8], bank_size: usize) -> Vec<Vec<u8>> {
+    let mut banks: Vec<Vec<u8>> = image.chunks(bank_size).map(|bank| bank.to_vec()).collect();
+
+    if banks.is_empty() {
+        banks.push(Vec::new());
+    }
+    for bank in banks.iter_mut() {
+        bank.resize(bank_size, 0xFF);
+    }
+
+    banks
+}
+
+/**
+ * A single window swapping between banks of 16 KB or 32 KB of a larger image
+ *
+ * Writing the bank number to the latch register selects the bank shown in the window.
+ */
+pub struct SwapMapper {
+    banks: Vec<Vec<u8>>,
+    window_start: u16,
+    latch_address: u16,
+    selected: usize,
+}
+
+// Constructor like implementation
+impl SwapMapper {
+    /**
+     * Returns the mapper showing the first bank
+     *
+     # Arguments
+
+     * `image` - The whole ROM image
+     * `window_start` - First address of the window
+     * `bank_size` - `KB_16` or `KB_32`, the window must fit below 0x10000
+     * `latch_address` - Address of the bank select register
+     */
+    pub fn new(image: &[u8], window_start: u16, bank_size: usize, latch_address: u16) -> Self {
+        assert!(window_start as usize + bank_size <= 0x10000, "The bank window doesn't fit in the address space");
+
+        Self {
+            banks: split_banks(image, bank_size),
+            window_start,
+            latch_address,
+            selected: 0,
+        }
+    }
+}
+
+impl SwapMapper {
+    fn window_offset(&self, address: u16) -> Option<usize> {
+        let offset = (address as usize).checked_sub(self.window_start as usize)?;
+        (offset < self.banks[0].len()).then_some(offset)
+    }
+}
+
+impl Mapper for SwapMapper {
+    fn read(&self, address: u16) -> Option<u8> {
+        self.window_offset(address).map(|offset| self.banks[self.selected][offset])
+    }
+
+    fn write(&mut self, address: u16, data: u8) -> bool {
+        if address == self.latch_address {
+            self.selected = data as usize % self.banks.len();
+            true
+        } else {
+            // the window is read only
+            self.window_offset(address).is_some()
+        }
+    }
+
+    fn banks(&self) -> Vec<MappedBank> {
+        vec![MappedBank::new("rom", self.selected, self.window_start, self.banks[0].len())]
+    }
+
+    fn save_registers(&self) -> Vec<u8> {
+        (self.selected as u16).to_le_bytes().to_vec()
+    }
+
+    fn load_registers(&mut self, registers: &[u8]) {
+        if let &[low, high] = registers {
+            self.selected = u16::from_le_bytes([low, high]) as usize % self.banks.len();
+        }
+    }
+}
+
+/**
+ * NES mapper 0, 16 KB or 32 KB of PRG ROM at 0x8000 without any switching
+ *
+ * A 16 KB image is mirrored at 0xC000.
+ */
+pub struct Nrom {
+    prg: Vec<u8>,
+}
+
+// Constructor like implementation
+impl Nrom {
+    pub fn new(prg: &[u8]) -> Self {
+        let size = if prg.len() <= KB_16 { KB_16 } else { KB_32 };
+        let mut prg = prg[..prg.len().min(KB_32)].to_vec();
+        prg.resize(size, 0xFF);
+
+        Self { prg }
+    }
+}
+
+impl Mapper for Nrom {
+    fn read(&self, address: u16) -> Option<u8> {
+        (address >= 0x8000).then(|| self.prg[(address as usize - 0x8000) % self.prg.len()])
+    }
+
+    fn write(&mut self, address: u16, _data: u8) -> bool {
+        address >= 0x8000
+    }
+
+    fn banks(&self) -> Vec<MappedBank> {
+        if self.prg.len() == KB_16 {
+            vec![MappedBank::new("prg", 0, 0x8000, KB_16), MappedBank::new("prg", 0, 0xC000, KB_16)]
+        } else {
+            vec![MappedBank::new("prg", 0, 0x8000, KB_32)]
+        }
+    }
+
+    // nothing to switch
+    fn save_registers(&self) -> Vec<u8> {
+        Vec::new()
+    }
+
+    fn load_registers(&mut self, _registers: &[u8]) {}
+}
+
+/**
+ * NES mapper 2, a switchable 16 KB bank at 0x8000 and the last bank fixed at 0xC000
+ *
+ * Any write to 0x8000-0xFFFF selects the switchable bank.
+ */
+pub struct UxRom {
+    banks: Vec<Vec<u8>>,
+    selected: usize,
+}
+
+// Constructor like implementation
+impl UxRom {
+    pub fn new(prg: &[u8]) -> Self {
+        Self {
+            banks: split_banks(prg, KB_16),
+            selected: 0,
+        }
+    }
+}
+
+impl Mapper for UxRom {
+    fn read(&self, address: u16) -> Option<u8> {
+        match address {
+            0x8000..=0xBFFF => Some(self.banks[self.selected][address as usize - 0x8000]),
+            0xC000..=0xFFFF => Some(self.banks[self.banks.len() - 1][address as usize - 0xC000]),
+            _ => None,
+        }
+    }
+
+    fn write(&mut self, address: u16, data: u8) -> bool {
+        if address >= 0x8000 {
+            self.selected = data as usize % self.banks.len();
+            true
+        } else {
+            false
+        }
+    }
+
+    fn banks(&self) -> Vec<MappedBank> {
+        vec![
+            MappedBank::new("prg", self.selected, 0x8000, KB_16),
+            MappedBank::new("prg", self.banks.len() - 1, 0xC000, KB_16),
+        ]
+    }
+
+    fn save_registers(&self) -> Vec<u8> {
+        (self.selected as u16).to_le_bytes().to_vec()
+    }
+
+    fn load_registers(&mut self, registers: &[u8]) {
+        if let &[low, high] = registers {
+            self.selected = u16::from_le_bytes([low, high]) as usize % self.banks.len();
+        }
+    }
+}
+
+/**
+ * NES mapper 1 (MMC1), PRG banking through a 5 bit serial shift register and 8 KB of PRG RAM at 0x6000
+ *
+ * Writes to 0x8000-0xFFFF shift bit 0 in, the fifth write stores the value into the register selected by
+ * address bits 14-13 (control, CHR bank 0, CHR bank 1, PRG bank). A write with bit 7 set resets the shift register.
+ * There's no PPU, so the CHR registers are kept but not used.
+ */
+pub struct Mmc1 {
+    banks: Vec<Vec<u8>>,
+    prg_ram: Vec<u8>,
+
+    shift_register: u8,
+    shift_count: u8,
+
+    control: u8,
+    chr_bank_0: u8,
+    chr_bank_1: u8,
+    prg_bank: u8,
+}
+
+// Constructor like implementation
+impl Mmc1 {
+    /**
+     * Returns the mapper in its power on state, with the last bank fixed at 0xC000
+     */
+    pub fn new(prg: &[u8]) -> Self {
+        Self {
+            banks: split_banks(prg, KB_16),
+            prg_ram: vec![0x00; KB_8],
+            shift_register: 0x00,
+            shift_count: 0,
+            control: 0x0C,
+            chr_bank_0: 0x00,
+            chr_bank_1: 0x00,
+            prg_bank: 0x00,
+        }
+    }
+}
+
+impl Mmc1 {
+    /**
+     * Banks shown at 0x8000 and 0xC000, depending on the PRG mode (control bits 3-2)
+     */
+    fn prg_banks(&self) -> (usize, usize) {
+        let count = self.banks.len();
+        let selected = (self.prg_bank & 0x0F) as usize;
+
+        match (self.control >> 2) & 0b11 {
+            // 32 KB mode ignores the low bit of the bank number
+            0 | 1 => ((selected & !1) % count, ((selected & !1) + 1) % count),
+            2 => (0, selected % count),
+            _ => (selected % count, count - 1),
+        }
+    }
+
+    fn is_prg_ram_enabled(&self) -> bool {
+        (self.prg_bank & 0x10) == 0
+    }
+
+    fn store(&mut self, address: u16, value: u8) {
+        match (address >> 13) & 0b11 {
+            0 => self.control = value,
+            1 => self.chr_bank_0 = value,
+            2 => self.chr_bank_1 = value,
+            _ => self.prg_bank = value,
+        }
+    }
+}
+
+impl Mapper for Mmc1 {
+    fn read(&self, address: u16) -> Option<u8> {
+        let (low, high) = self.prg_banks();
+
+        match address {
+            0x6000..=0x7FFF if self.is_prg_ram_enabled() => Some(self.prg_ram[address as usize - 0x6000]),
+            0x8000..=0xBFFF => Some(self.banks[low][address as usize - 0x8000]),
+            0xC000..=0xFFFF => Some(self.banks[high][address as usize - 0xC000]),
+            _ => None,
+        }
+    }
+
+    fn write(&mut self, address: u16, data: u8) -> bool {
+        match address {
+            0x6000..=0x7FFF if self.is_prg_ram_enabled() => {
+                self.prg_ram[address as usize - 0x6000] = data;
+                true
+            }
+            0x8000..=0xFFFF => {
+                if (data & 0x80) != 0 {
+                    self.shift_register = 0x00;
+                    self.shift_count = 0;
+                    self.control |= 0x0C;
+                    return true;
+                }
+
+                // LSB first
+                self.shift_register |= (data & 0x01) << self.shift_count;
+                self.shift_count += 1;
+
+                if self.shift_count == 5 {
+                    self.store(address, self.shift_register);
+                    self.shift_register = 0x00;
+                    self.shift_count = 0;
+                }
+                true
+            }
+            _ => false,
+        }
+    }
+
+    fn banks(&self) -> Vec<MappedBank> {
+        let (low, high) = self.prg_banks();
+
+        let mut banks = vec![
+            MappedBank::new("prg", low, 0x8000, KB_16),
+            MappedBank::new("prg", high, 0xC000, KB_16),
+        ];
+        if self.is_prg_ram_enabled() {
+            banks.insert(0, MappedBank::new("prg_ram", 0, 0x6000, KB_8));
+        }
+
+        banks
+    }
+
+    fn save_registers(&self) -> Vec<u8> {
+        vec![self.shift_register, self.shift_count, self.control, self.chr_bank_0, self.chr_bank_1, self.prg_bank]
+    }
+
+    fn load_registers(&mut self, registers: &[u8]) {
+        if let &[shift_register, shift_count, control, chr_bank_0, chr_bank_1, prg_bank] = registers {
+            self.shift_register = shift_register;
+            self.shift_count = shift_count;
+            self.control = control;
+            self.chr_bank_0 = chr_bank_0;
+            self.chr_bank_1 = chr_bank_1;
+            self.prg_bank = prg_bank;
+        }
+    }
+
+    fn ram(&self) -> &[u8] {
+        &self.prg_ram
+    }
+
+    fn ram_mut(&mut self) -> &mut [u8] {
+        &mut self.prg_ram
+    }
+
+    fn poke(&mut self, address: u16, data: u8) -> bool {
+        match address {
+            0x6000..=0x7FFF if self.is_prg_ram_enabled() => {
+                self.prg_ram[address as usize - 0x6000] = data;
+                true
+            }
+            _ => false,
+        }
+    }
+}
+
+// 6510 processor port bits driving the PLA
+const LORAM: u8 = 1 << 0;
+const HIRAM: u8 = 1 << 1;
+const CHAREN: u8 = 1 << 2;
+
+/**
+ * Commodore 64 style banking, the 6510 processor port at 0x0000/0x0001 drives the PLA
+ *
+ * LORAM, HIRAM and CHAREN (port bits 0-2) decide whether BASIC (0xA000), the character ROM or the I/O area (0xD000)
+ * and the KERNAL (0xE000) are visible, RAM from the bus storages shows through otherwise.
+ * Writes always reach the RAM underneath, like on the real machine.
+ * The I/O area is left to the devices attached to the bus. The cartridge lines (GAME, EXROM) aren't modelled.
+ */
+pub struct C64Pla {
+    basic: Vec<u8>,
+    kernal: Vec<u8>,
+    characters: Vec<u8>,
+
+    port_direction: u8,
+    port_data: u8,
+}
+
+// Constructor like implementation
+impl C64Pla {
+    /**
+     * Returns the PLA with the KERNAL's power on configuration (BASIC, I/O and KERNAL visible)
+     *
+     # Arguments
+
+     * `basic` - 8 KB BASIC ROM image
+     * `kernal` - 8 KB KERNAL ROM image
+     * `characters` - 4 KB character ROM image
+     */
+    pub fn new(basic: &[u8], kernal: &[u8], characters: &[u8]) -> Self {
+        Self {
+            basic: split_banks(basic, KB_8).swap_remove(0),
+            kernal: split_banks(kernal, KB_8).swap_remove(0),
+            characters: split_banks(characters, 4 * 1024).swap_remove(0),
+            port_direction: 0x2F,
+            port_data: 0x37,
+        }
+    }
+}
+
+impl C64Pla {
+    /**
+     * Levels of the port pins, inputs are pulled high
+     */
+    fn port(&self) -> u8 {
+        (self.port_data & self.port_direction) | !self.port_direction
+    }
+
+    fn is_basic_visible(&self) -> bool {
+        (self.port() & (LORAM | HIRAM)) == (LORAM | HIRAM)
+    }
+
+    fn is_kernal_visible(&self) -> bool {
+        (self.port() & HIRAM) != 0
+    }
+
+    fn is_characters_visible(&self) -> bool {
+        (self.port() & (LORAM | HIRAM)) != 0 && (self.port() & CHAREN) == 0
+    }
+
+    fn is_io_visible(&self) -> bool {
+        (self.port() & (LORAM | HIRAM)) != 0 && (self.port() & CHAREN) != 0
+    }
+}
+
+impl Mapper for C64Pla {
+    fn read(&self, address: u16) -> Option<u8> {
+        match address {
+            0x0000 => Some(self.port_direction),
+            0x0001 => Some(self.port()),
+            0xA000..=0xBFFF if self.is_basic_visible() => Some(self.basic[address as usize - 0xA000]),
+            0xD000..=0xDFFF if self.is_characters_visible() => Some(self.characters[address as usize - 0xD000]),
+            0xE000..=0xFFFF if self.is_kernal_visible() => Some(self.kernal[address as usize - 0xE000]),
+            _ => None,
+        }
+    }
+
+    fn write(&mut self, address: u16, data: u8) -> bool {
+        match address {
+            0x0000 => self.port_direction = data,
+            0x0001 => self.port_data = data,
+            _ => (),
+        }
+
+        false
+    }
+
+    fn banks(&self) -> Vec<MappedBank> {
+        let mut banks = Vec::new();
+
+        if self.is_basic_visible() {
+            banks.push(MappedBank::new("basic", 0, 0xA000, KB_8));
+        }
+        if self.is_characters_visible() {
+            banks.push(MappedBank::new("characters", 0, 0xD000, 4 * 1024));
+        }
+        if self.is_io_visible() {
+            banks.push(MappedBank::new("io", 0, 0xD000, 4 * 1024));
+        }
+        if self.is_kernal_visible() {
+            banks.push(MappedBank::new("kernal", 0, 0xE000, KB_8));
+        }
+
+        banks
+    }
+
+    fn save_registers(&self) -> Vec<u8> {
+        vec![self.port_direction, self.port_data]
+    }
+
+    fn load_registers(&mut self, registers: &[u8]) {
+        if let &[port_direction, port_data] = registers {
+            self.port_direction = port_direction;
+            self.port_data = port_data;
+        }
+    }
+}
+
+/**
+ * Picks the mapper for an iNES (`.nes`) image from its header
+ *
+ * Only the PRG ROM is used, mappers 0 (NROM), 1 (MMC1) and 2 (UxROM) are supported.
+ */
+pub fn from_ines(image: &[u8]) -> Result<Box<dyn Mapper>, String> {
+    if image.len() < 16 || &image[0..4] != b"NES\x1A" {
+        return Err(String::from("Not an iNES image"));
+    }
+
+    let prg_size = image[4] as usize * KB_16;
+    // a 512 byte trainer may sit between the header and the PRG ROM
+    let prg_start = if (image[6] & 0x04) != 0 { 16 + 512 } else { 16 };
+    let prg = image
+        .get(prg_start..prg_start + prg_size)
+        .ok_or_else(|| String::from("The iNES image is shorter than its header says"))?;
+
+    match (image[7] & 0xF0) | (image[6] >> 4) {
+        0 => Ok(Box::new(Nrom::new(prg))),
+        1 => Ok(Box::new(Mmc1::new(prg))),
+        2 => Ok(Box::new(UxRom::new(prg))),
+        mapper => Err(format!("Unsupported iNES mapper {}", mapper)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    /**
+     * Returns an image whose banks are filled with their own bank number
+     */
+    fn numbered_banks(count: usize, bank_size: usize) -> Vec<u8> {
+        (0..count).flat_map(|bank| vec![bank as u8; bank_size]).collect()
+    }
+
+    /**
+     * Whether or not the latch register swaps the bank in the window
+     */
+    #[test]
+    fn swap_mapper() {
+        let mut mapper = SwapMapper::new(&numbered_banks(4, KB_16), 0x8000, KB_16, 0x7FFF);
+
+        assert_eq!(mapper.read(0x8000), Some(0));
+        assert_eq!(mapper.read(0xC000), None);
+
+        assert!(mapper.write(0x7FFF, 3));
+        assert_eq!(mapper.read(0xBFFF), Some(3));
+        assert_eq!(mapper.banks(), vec![MappedBank::new("rom", 3, 0x8000, KB_16)]);
+
+        // writes to the window don't change the ROM
+        assert!(mapper.write(0x8000, 0xAA));
+        assert_eq!(mapper.read(0x8000), Some(3));
+    }
+
+    /**
+     * Whether or not UxROM switches the low bank and keeps the last one fixed
+     */
+    #[test]
+    fn uxrom() {
+        let mut mapper = UxRom::new(&numbered_banks(8, KB_16));
+
+        assert_eq!(mapper.read(0xFFFC), Some(7));
+        mapper.write(0x8000, 5);
+        assert_eq!(mapper.read(0x8000), Some(5));
+        assert_eq!(mapper.read(0xC000), Some(7));
+    }
+
+    /**
+     * Whether or not MMC1 takes its registers serially and follows the PRG modes
+     */
+    #[test]
+    fn mmc1() {
+        let mut mapper = Mmc1::new(&numbered_banks(8, KB_16));
+
+        let write_register = |mapper: &mut Mmc1, address: u16, value: u8| {
+            for bit in 0..5 {
+                mapper.write(address, (value >> bit) & 0x01);
+            }
+        };
+
+        // power on: switchable bank at 0x8000, last bank fixed at 0xC000
+        assert_eq!(mapper.read(0xC000), Some(7));
+        write_register(&mut mapper, 0xE000, 3);
+        assert_eq!(mapper.read(0x8000), Some(3));
+
+        // 32 KB mode ignores the low bit
+        write_register(&mut mapper, 0x8000, 0x00);
+        assert_eq!((mapper.read(0x8000), mapper.read(0xC000)), (Some(2), Some(3)));
+
+        // a write with bit 7 set resets the shift register and the PRG mode
+        mapper.write(0x8000, 0x01);
+        mapper.write(0x8000, 0x80);
+        assert_eq!((mapper.read(0x8000), mapper.read(0xC000)), (Some(3), Some(7)));
+
+        // PRG RAM
+        assert!(mapper.write(0x6000, 0x42));
+        assert_eq!(mapper.read(0x6000), Some(0x42));
+    }
+
+    /**
+     * Whether or not the processor port banks the ROMs in and out
+     */
+    #[test]
+    fn c64_pla() {
+        let mut pla = C64Pla::new(&[0xBA; KB_8], &[0xEE; KB_8], &[0xCC; 4 * 1024]);
+
+        assert_eq!(pla.read(0xA000), Some(0xBA));
+        assert_eq!(pla.read(0xD000), None);
+        assert_eq!(pla.read(0xFFFF), Some(0xEE));
+
+        // writes under the ROMs go to the RAM
+        assert!(!pla.write(0xA000, 0x12));
+
+        // character ROM in, BASIC out
+        pla.write(0x0001, HIRAM);
+        assert_eq!(pla.read(0xA000), None);
+        assert_eq!(pla.read(0xD000), Some(0xCC));
+        assert_eq!(pla.read(0xE000), Some(0xEE));
+
+        // all RAM
+        pla.write(0x0001, 0x00);
+        assert!(pla.banks().is_empty());
+    }
+
+    /**
+     * Whether or not the bus goes through the mapper before its storages
+     */
+    #[test]
+    fn bus_with_mapper() {
+        let mut processor = crate::Processor::new();
+        processor.bus.set_mapper(Box::new(UxRom::new(&numbered_banks(4, KB_16))));
+
+        processor.bus.write(0x8000, 2);
+        assert_eq!(processor.bus.read(0x8000), 2);
+        assert_eq!(processor.bus.read(0xC000), 3);
+        assert_eq!(processor.bus.mapped_banks()[0], MappedBank::new("prg", 2, 0x8000, KB_16));
+
+        // unmapped addresses reach the RAM
+        processor.bus.write(0x0200, 0x42);
+        assert_eq!(processor.bus.read(0x0200), 0x42);
+
+        processor.bus.remove_mapper();
+        assert_eq!(processor.bus.read(0xC000), 0x00);
+    }
+
+    /**
+     * Whether or not the iNES header selects the mapper
+     */
+    #[test]
+    fn ines_header() {
+        let mut image = b"NES\x1A\x02\x00\x20\x00".to_vec();
+        image.resize(16, 0x00);
+        image.extend(numbered_banks(2, KB_16));
+
+        let mapper = from_ines(&image).unwrap();
+        assert_eq!(mapper.read(0xC000), Some(1));
+
+        image[6] = 0x30;
+        assert!(from_ines(&image).is_err());
+        assert!(from_ines(&image[..20]).is_err());
+    }
+}
@@ -1,13 +1,51 @@
-use num::traits::int::PrimInt;
+use std::str::FromStr;
 
 use serde::{Deserialize, Serialize};
 
-#[derive(Serialize, Deserialize)]
-pub struct Memory<T: PrimInt + std::convert::From<u8>> {
-    mem: Vec<T>,
+/**
+ * What the RAM contains when the machine is turned on
+ */
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum PowerOnPattern {
+    /// Every byte 0x00
+    #[default]
+    Zero,
+    /// Every byte 0xFF
+    Ones,
+    /// Pseudo random bytes, the same for the same seed
+    Random { seed: u64 },
+    /// 0x00 at even addresses, 0xFF at odd ones
+    Alternating,
 }
 
-impl<T: PrimInt + std::convert::From<u8>> Memory<T> {
+impl FromStr for PowerOnPattern {
+    type Err = String;
+
+    /**
+     * Accepts `zero`, `ff`, `alternating` and `random` with an optional seed (e.g. `random:42`)
+     */
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim().to_lowercase();
+
+        match s.as_str() {
+            "zero" | "00" => Ok(PowerOnPattern::Zero),
+            "ff" | "ones" => Ok(PowerOnPattern::Ones),
+            "alternating" => Ok(PowerOnPattern::Alternating),
+            "random" => Ok(PowerOnPattern::Random { seed: 0 }),
+            _ => match s.strip_prefix("random:").map(|seed| seed.parse::<u64>()) {
+                Some(Ok(seed)) => Ok(PowerOnPattern::Random { seed }),
+                _ => Err(format!("Invalid power on pattern: {}", s)),
+            },
+        }
+    }
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Memory {
+    mem: Vec<u8>,
+}
+
+impl Memory {
     /**
     Returns the memory with given size
 
@@ -20,17 +58,17 @@ impl<T: PrimInt + std::convert::From<u8>> Memory<T> {
     */
     pub fn new(size_b: usize) -> Self {
         Self {
-            mem: vec![T::zero(); size_b],
+            mem: vec![0x00; size_b],
         }
     }
 }
 
 // overloading [] for read access
-impl<T: PrimInt + std::convert::From<u8>> std::ops::Index<u16> for Memory<T> {
-    type Output = T;
+impl std::ops::Index<u16> for Memory {
+    type Output = u8;
 
-    fn index(&self, index: u16) -> &T {
-        return {
+    fn index(&self, index: u16) -> &u8 {
+        {
             if index < self.mem.len() as u16 {
                 let opt = self.mem.get(index as usize);
                 match opt {
@@ -40,26 +78,53 @@ impl<T: PrimInt + std::convert::From<u8>> std::ops::Index<u16> for Memory<T> {
             } else {
                 panic!("Invalid Read Address")
             }
-        };
+        }
     }
 }
 
 // overloading [] for read/write access
-impl<T: PrimInt + std::convert::From<u8>> std::ops::IndexMut<u16> for Memory<T> {
+impl std::ops::IndexMut<u16> for Memory {
     fn index_mut(&mut self, index: u16) -> &mut Self::Output {
-        return {
+        {
             if index < self.mem.len() as u16 {
                 self.mem.get_mut(index as usize).unwrap()
             } else {
                 panic!("Invalid write Address"); 
             }
-        };
+        }
         
     }
 }
 
 // helper functions
-impl<T: PrimInt + std::convert::From<u8>> Memory<T> {
+impl Memory {
+    /**
+     * Overwrites the whole memory with the pattern
+     */
+    pub fn fill(&mut self, pattern: PowerOnPattern) {
+        match pattern {
+            PowerOnPattern::Zero => self.mem.fill(0x00),
+            PowerOnPattern::Ones => self.mem.fill(0xFF),
+            PowerOnPattern::Alternating => {
+                for (address, byte) in self.mem.iter_mut().enumerate() {
+                    *byte = if address % 2 == 0 { 0x00 } else { 0xFF };
+                }
+            }
+            PowerOnPattern::Random { seed } => {
+                // splitmix64, good enough to look uninitialized and the same on every platform
+                let mut state = seed;
+                for chunk in self.mem.chunks_mut(8) {
+                    state = state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+                    let mut z = state;
+                    z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+                    z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+                    z ^= z >> 31;
+                    chunk.copy_from_slice(&z.to_le_bytes()[..chunk.len()]);
+                }
+            }
+        }
+    }
+
     pub fn len(&self) -> usize {
         self.mem.len()
     }
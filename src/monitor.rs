@@ -0,0 +1,390 @@
+use std::fmt::Write;
+
+use crate::disassembler::{disassemble, format_trace_entry};
+use crate::processor::Processor;
+
+/// Number of instructions `d` shows when no count is given
+const DISASSEMBLY_LINES: usize = 16;
+
+/// Number of bytes `m` shows when no length is given
+const MEMORY_DUMP_BYTES: u16 = 64;
+
+/// Number of addresses, the longest dump `m` shows
+const ADDRESS_SPACE: usize = 0x10000;
+
+/// Cycles `c` runs for when no budget is given, so a looping program can't hang the monitor
+const CONTINUE_CYCLES: u64 = 10_000_000;
+
+const HELP: &str = "\
+r                   registers
+s [count]           step instructions
+sl                  step a source line, into subroutines
+n                   step a source line, over subroutines
+l [address]         source line
+c [cycles]          continue until a breakpoint or the budget
+sb [count]          step back instructions
+rc                  continue backwards until a breakpoint
+goto cycle          move to a cycle of the history
+hist                execution history
+d [address] [count] disassemble
+m address [length]  memory dump
+b address           add a breakpoint
+bd address          delete a breakpoint
+bl                  list breakpoints
+bt                  backtrace
+t [count]           last traced instructions
+stats               cycles, instructions and interrupt latencies
+sym [name|address]  look up symbols
+h                   this help
+
+Addresses may be labels, label+offset, file:line, $hex, 0xhex, #decimal or plain hex";
+
+/**
+ * Text debugger commands, shared by the command line and the web front end
+ */
+#[derive(Debug, Default)]
+pub struct Monitor {
+    /// Where `d` continues from when no address is given
+    disassembly_address: Option<u16>,
+    /// Where `m` continues from when no address is given
+    memory_address: u16,
+}
+
+// Constructor like implementation
+impl Monitor {
+    pub fn new() -> Self {
+        Self {
+            ..Default::default()
+        }
+    }
+}
+
+impl Monitor {
+    /**
+     * Executes a command line
+     *
+     # Returns
+     the output of the command, errors included
+     */
+    pub fn execute(&mut self, processor: &mut Processor, line: &str) -> String {
+        let mut words = line.split_whitespace();
+        let command = match words.next() {
+            Some(command) => command,
+            None => return String::new(),
+        };
+        let arguments: Vec<&str> = words.collect();
+
+        let result = match command {
+            "r" => Ok(processor.registers().to_string()),
+            "s" => self.step(processor, &arguments),
+            "sl" => self.step_source_line(processor, false),
+            "n" => self.step_source_line(processor, true),
+            "l" => Self::source_line(processor, arguments.first()),
+            "c" => self.continue_execution(processor, &arguments),
+            "sb" => self.step_back(processor, &arguments),
+            "rc" => {
+                let result = processor.reverse_continue();
+                self.disassembly_address = None;
+                Ok(format!("{:?} after {} cycles back\n{}", result.stop_reason, result.cycles, Self::current_line(processor)))
+            }
+            "goto" => match arguments.first().map(|cycle| cycle.parse::<u64>()) {
+                Some(Ok(cycle)) if processor.goto_cycle(cycle) => {
+                    self.disassembly_address = None;
+                    Ok(Self::current_line(processor))
+                }
+                Some(Ok(cycle)) => Err(format!("cycle {} is out of the history", cycle)),
+                _ => Err(String::from("missing or invalid cycle")),
+            },
+            "hist" => match processor.history() {
+                Some(history) => Ok(format!(
+                    "cycle {}, back to cycle {} ({} instructions, {} snapshots, {} of {} bytes)",
+                    history.cycle(),
+                    history.oldest_cycle(),
+                    history.len(),
+                    history.snapshot_count(),
+                    history.memory_usage(),
+                    history.memory_cap()
+                )),
+                None => Err(String::from("the execution history isn't enabled")),
+            },
+            "d" => self.disassemble(processor, &arguments),
+            "m" => self.memory_dump(processor, &arguments),
+            "b" => Self::address(processor, arguments.first()).map(|address| {
+                processor.add_breakpoint(address);
+                format!("breakpoint at {}", processor.symbols.format_address(address))
+            }),
+            "bd" => Self::address(processor, arguments.first()).map(|address| {
+                processor.remove_breakpoint(address);
+                format!("removed breakpoint at {}", processor.symbols.format_address(address))
+            }),
+            "bl" => Ok(processor
+                .breakpoints()
+                .iter()
+                .map(|address| format!("${:04X} {}", address, processor.symbols.name_of(*address).unwrap_or("")).trim_end().to_owned())
+                .collect::<Vec<String>>()
+                .join("\n")),
+            "bt" => Ok(processor.backtrace().to_string().trim_end().to_owned()),
+            "t" => Self::count(arguments.first(), 16).map(|count| {
+                let entries: Vec<String> = processor.trace().map(|entry| format_trace_entry(processor, entry)).collect();
+                entries[entries.len().saturating_sub(count)..].join("\n")
+            }),
+            "sym" => Ok(Self::symbols(processor, arguments.first())),
+            "stats" => Ok(processor.timing_stats().to_string()),
+            "h" | "help" | "?" => Ok(HELP.to_owned()),
+            _ => Err(format!("unknown command '{}', h for help", command)),
+        };
+
+        result.unwrap_or_else(|error| format!("error: {}", error))
+    }
+
+    fn address(processor: &Processor, argument: Option<&&str>) -> Result<u16, String> {
+        let argument = argument.ok_or("missing address")?;
+        processor.resolve_address(argument).ok_or_else(|| format!("unknown address '{}'", argument))
+    }
+
+    fn count(argument: Option<&&str>, default: usize) -> Result<usize, String> {
+        match argument {
+            Some(argument) => argument.parse().map_err(|_| format!("invalid count '{}'", argument)),
+            None => Ok(default),
+        }
+    }
+
+    fn step(&mut self, processor: &mut Processor, arguments: &[&str]) -> Result<String, String> {
+        for _ in 0..Self::count(arguments.first(), 1)? {
+            processor.step();
+        }
+
+        self.disassembly_address = None;
+        Ok(Self::current_line(processor))
+    }
+
+    fn step_source_line(&mut self, processor: &mut Processor, step_over: bool) -> Result<String, String> {
+        if processor.debug_info.is_empty() {
+            return Err(String::from("no debug info loaded"));
+        }
+
+        processor.step_source_line(step_over);
+
+        self.disassembly_address = None;
+        Ok(Self::current_line(processor))
+    }
+
+    fn source_line(processor: &Processor, argument: Option<&&str>) -> Result<String, String> {
+        let address = match argument {
+            Some(_) => Self::address(processor, argument)?,
+            None => processor.program_counter(),
+        };
+
+        let location = processor
+            .debug_info
+            .location(address)
+            .ok_or_else(|| format!("no source line for ${:04X}", address))?;
+
+        match processor.debug_info.source_line(&location) {
+            Some(text) => Ok(format!("{}  {}", location, text)),
+            None => Ok(location.to_string()),
+        }
+    }
+
+    fn step_back(&mut self, processor: &mut Processor, arguments: &[&str]) -> Result<String, String> {
+        if processor.history().is_none() {
+            return Err(String::from("the execution history isn't enabled"));
+        }
+
+        for _ in 0..Self::count(arguments.first(), 1)? {
+            if !processor.step_back() {
+                break;
+            }
+        }
+
+        self.disassembly_address = None;
+        Ok(Self::current_line(processor))
+    }
+
+    fn continue_execution(&mut self, processor: &mut Processor, arguments: &[&str]) -> Result<String, String> {
+        let budget = match arguments.first() {
+            Some(argument) => argument.parse().map_err(|_| format!("invalid cycle count '{}'", argument))?,
+            None => CONTINUE_CYCLES,
+        };
+
+        let result = processor.run(budget);
+
+        self.disassembly_address = None;
+        Ok(format!("{:?} after {} cycles\n{}", result.stop_reason, result.cycles, Self::current_line(processor)))
+    }
+
+    fn disassemble(&mut self, processor: &Processor, arguments: &[&str]) -> Result<String, String> {
+        let address = match arguments.first() {
+            Some(_) => Self::address(processor, arguments.first())?,
+            None => self.disassembly_address.unwrap_or(processor.program_counter()),
+        };
+        let lines = disassemble(processor, address, Self::count(arguments.get(1), DISASSEMBLY_LINES)?);
+
+        self.disassembly_address = lines
+            .last()
+            .map(|line| line.address.wrapping_add(line.bytes.len() as u16));
+
+        Ok(lines.iter().map(ToString::to_string).collect::<Vec<String>>().join("\n"))
+    }
+
+    fn memory_dump(&mut self, processor: &Processor, arguments: &[&str]) -> Result<String, String> {
+        let address = match arguments.first() {
+            Some(_) => Self::address(processor, arguments.first())?,
+            None => self.memory_address,
+        };
+        // the whole address space at most, the dump would wrap around onto itself past it
+        let length = Self::count(arguments.get(1), MEMORY_DUMP_BYTES as usize)?;
+        if length > ADDRESS_SPACE {
+            return Err(format!("count {} is larger than the address space (65536 bytes)", length));
+        }
+
+        let mut output = String::new();
+        for row in (0..length).step_by(16) {
+            let row_address = address.wrapping_add(row as u16);
+            let bytes: Vec<u8> = (0..(length - row).min(16)).map(|i| processor.bus.peek(row_address.wrapping_add(i as u16))).collect();
+
+            let hex: Vec<String> = bytes.iter().map(|byte| format!("{:02X}", byte)).collect();
+            let ascii: String = bytes
+                .iter()
+                .map(|byte| if byte.is_ascii_graphic() || *byte == b' ' { *byte as char } else { '.' })
+                .collect();
+
+            let _ = writeln!(output, "{:04X}  {:<47}  {}", row_address, hex.join(" "), ascii);
+        }
+
+        self.memory_address = address.wrapping_add(length as u16);
+        Ok(output.trim_end().to_owned())
+    }
+
+    fn symbols(processor: &Processor, argument: Option<&&str>) -> String {
+        let argument = match argument {
+            Some(argument) => *argument,
+            None => return format!("{} symbols loaded", processor.symbols.len()),
+        };
+
+        if let Some(address) = processor.symbols.address_of(argument) {
+            return format!("{} = ${:04X}", argument, address);
+        }
+
+        match processor.symbols.resolve(argument).and_then(|address| processor.symbols.nearest(address)) {
+            Some((name, 0)) => name.to_owned(),
+            Some((name, offset)) => format!("{}+${:X}", name, offset),
+            None => format!("no symbol for '{}'", argument),
+        }
+    }
+
+    /**
+     * The instruction about to be executed with the registers, preceded by its source line if known
+     */
+    fn current_line(processor: &Processor) -> String {
+        let line = &disassemble(processor, processor.program_counter(), 1)[0];
+        let instruction = format!("{:<44} {}", line.to_string(), processor.registers());
+
+        match Self::source_line(processor, None) {
+            Ok(source) => format!("{}\n{}", source, instruction),
+            Err(_) => instruction,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use crate::debug_info::DebugInfo;
+
+    /**
+     * Whether or not the commands accept labels and step through a program
+     */
+    #[test]
+    fn commands() {
+        let mut processor = Processor::new();
+        // INX; INX; JSR $8010 ... $8010: INX; RTS
+        let program = [0xE8, 0xE8, 0x20, 0x10, 0x80];
+        for (i, byte) in program.iter().enumerate() {
+            processor.bus.write(0x8000 + i as u16, *byte);
+        }
+        processor.bus.write(0x8010, 0xE8);
+        processor.bus.write(0x8011, 0x60);
+
+        processor.reset();
+        // finishing the reset sequence
+        processor.step();
+        let mut registers = processor.registers();
+        registers.program_counter = 0x8000;
+        processor.set_registers(&registers);
+        processor.symbols.insert("main", 0x8000);
+        processor.symbols.insert("increment", 0x8010);
+
+        let mut monitor = Monitor::new();
+
+        assert_eq!(monitor.execute(&mut processor, "b increment"), "breakpoint at increment");
+        assert_eq!(monitor.execute(&mut processor, "bl"), "$8010 increment");
+
+        assert!(monitor.execute(&mut processor, "s 2").starts_with("8002  20 10 80"));
+        assert!(monitor.execute(&mut processor, "r").starts_with("PC=8002 A=00 X=02"));
+
+        assert!(monitor.execute(&mut processor, "c").starts_with("Breakpoint { address: 32784 }"));
+        assert_eq!(monitor.execute(&mut processor, "bt"), "#0   $8010 in increment\n#1   $8002");
+
+        assert_eq!(monitor.execute(&mut processor, "sym main+3"), "main+$3");
+        assert!(monitor.execute(&mut processor, "m main 4").starts_with("8000  E8 E8 20 10"));
+        assert_eq!(monitor.execute(&mut processor, "m 0 65536").lines().count(), 4096);
+        assert!(monitor.execute(&mut processor, "m 0 65537").starts_with("error: count 65537"));
+        assert_eq!(monitor.execute(&mut processor, "d nowhere"), "error: unknown address 'nowhere'");
+    }
+
+    /**
+     * Whether or not source lines are stepped into and over, and used as breakpoints
+     */
+    #[test]
+    fn source_lines() {
+        let mut processor = Processor::new();
+        // line 3: INX, line 4: INX; INX, line 5: JSR sub, line 6: INX, line 10 (sub): INX, line 11: RTS
+        let program = [(0x8000, 0xE8), (0x8001, 0xE8), (0x8002, 0xE8), (0x8003, 0x20), (0x8004, 0x10), (0x8005, 0x80), (0x8006, 0xE8), (0x8010, 0xE8), (0x8011, 0x60)];
+        for (address, byte) in program {
+            processor.bus.write(address, byte);
+        }
+
+        processor.debug_info = DebugInfo::parse(
+            "version\tmajor=2,minor=0\n\
+             file\tid=0,name=\"main.s\",size=100,mtime=0x60000000,mod=0\n\
+             line\tid=0,file=0,line=3,span=0\n\
+             line\tid=1,file=0,line=4,span=1\n\
+             line\tid=2,file=0,line=5,span=2\n\
+             line\tid=3,file=0,line=6,span=3\n\
+             line\tid=4,file=0,line=10,span=4\n\
+             line\tid=5,file=0,line=11,span=5\n\
+             seg\tid=0,name=\"CODE\",start=0x8000,size=0x12\n\
+             span\tid=0,seg=0,start=0,size=1\n\
+             span\tid=1,seg=0,start=1,size=2\n\
+             span\tid=2,seg=0,start=3,size=3\n\
+             span\tid=3,seg=0,start=6,size=1\n\
+             span\tid=4,seg=0,start=16,size=1\n\
+             span\tid=5,seg=0,start=17,size=1\n",
+        )
+        .unwrap();
+
+        processor.reset();
+        processor.step();
+        let mut registers = processor.registers();
+        registers.program_counter = 0x8000;
+        processor.set_registers(&registers);
+
+        let mut monitor = Monitor::new();
+
+        assert!(monitor.execute(&mut processor, "sl").starts_with("main.s:4\n8001"));
+        assert!(monitor.execute(&mut processor, "sl").starts_with("main.s:5\n8003"));
+        assert!(monitor.execute(&mut processor, "sl").starts_with("main.s:10\n8010"));
+        assert!(monitor.execute(&mut processor, "sl").starts_with("main.s:11\n8011"));
+        assert!(monitor.execute(&mut processor, "sl").starts_with("main.s:6\n8006"));
+
+        // stepping over the subroutine
+        registers.program_counter = 0x8003;
+        processor.set_registers(&registers);
+        assert!(monitor.execute(&mut processor, "n").starts_with("main.s:6\n8006"));
+
+        assert_eq!(monitor.execute(&mut processor, "b main.s:10"), "breakpoint at $8010");
+        assert_eq!(monitor.execute(&mut processor, "l 8002"), "main.s:4");
+    }
+}
@@ -1,6 +1,22 @@
-use crate::bus::Bus;
-use crate::memory::Memory;
+use std::collections::{HashSet, VecDeque};
+use std::fmt;
+
+use serde::Serialize;
+
+use crate::bus::{Bus, JournalEntry};
+use crate::debug_info::{parse_location, DebugInfo};
+use crate::call_stack::{Backtrace, CallFrame, CallStack, FrameKind};
+use crate::memory::{Memory, PowerOnPattern};
+use crate::coverage::Coverage;
+use crate::profiler::{AccessCounters, CycleCounters, ProfileReport, SubroutineCycles};
 use crate::rom::Rom;
+use crate::stack_guard::{StackEvent, StackGuard};
+use crate::history::History;
+use crate::replay::{InputEvent, InputLog, TimedInput};
+use crate::save_state::{CpuState, MachineState};
+use crate::symbols::SymbolTable;
+use crate::timing::{LatencyStats, TimingStats};
+use crate::watchpoints::{WatchHit, WatchKind, Watchpoints};
 
 // Status bits
 // representing the number of left shift required to get the bit from 0x01
@@ -35,16 +51,18 @@ const FIXED_READING_ADDRESS_FOR_BRK_AND_IRQ: u16 = 0xFFFE;     // 0xFFFE and 0xF
 /// valid for nmi
 const FIXED_READING_ADDRESS_FOR_NMI: u16 = 0xFFFA;
 
+// Cycles taken by the interrupt sequences, from the request to the first cycle of the handler
+const IRQ_CYCLES: u8 = 7;
+const NMI_CYCLES: u8 = 7;
+const RESET_CYCLES: u8 = 7;
+
 // 6502
 /**
 * 6502 is little endian, valid for 16 bit addresses
 * i.e. $LLHH
 */
 pub struct Processor {
-    pub bus: Bus<u8>,
-
-    // Instruction set
-    instructions: Vec<Instruction>,
+    pub bus: Bus,
 
     // CPU core registers
     accumulator: u8,
@@ -65,26 +83,79 @@ pub struct Processor {
     address_relative: u16,
     opcode: u8,
     cycles: u8,
+    /// Address of the instruction being executed, or of the instruction an interrupt came before
+    opcode_address: u16,
 
     // Variables denoting the stack location in RAM
     // stack (Reversed)
     /// The top of the stack
     stack_last_address: u16,
 
-    #[allow(dead_code)] // might be useful to detect stack overflow later
-    stack_first_address: u16
+    /// The bottom of the stack, where the first pushed byte goes
+    stack_first_address: u16,
+    /// Reports stack pointer wraps and deep stacks while enabled
+    stack_guard: Option<StackGuard>,
+
+    // Execution control
+    /// Set when the processor executes a `JAM` opcode, only `reset()` clears it
+    halted: bool,
+    /// Program counter values at which `run()` stops before executing the instruction
+    breakpoints: HashSet<u16>,
+
+    /// Per-opcode, per-PC and per-subroutine cycles, counted only while profiling
+    cycle_counters: Option<CycleCounters>,
+    /// Executed instructions and branch outcomes, recorded only while enabled
+    coverage: Option<Coverage>,
+
+    /// Calls and interrupts that haven't returned yet, for backtraces
+    call_stack: CallStack,
+
+    // Debugging
+    /// Labels shown by the backtraces, disassembly and monitor
+    pub symbols: SymbolTable,
+    /// Source lines of the program, for stepping and breaking by line
+    pub debug_info: DebugInfo,
+    /// The most recently executed instructions, recorded only while tracing
+    trace: Option<VecDeque<TraceEntry>>,
+    trace_capacity: usize,
+    /// Undo log and snapshots for reverse execution, recorded only while enabled
+    history: Option<History>,
+    /// Device states taken before the devices are clocked on an instruction boundary
+    devices_before_tick: Option<Vec<Vec<u8>>>,
+
+    // External inputs
+    /// Cycles clocked since the processor was created
+    total_cycles: u64,
+    /// Inputs given so far, recorded only while recording
+    input_log: Option<InputLog>,
+    /// Cycle the recording started at, the recorded cycles are relative to it
+    recording_start: u64,
+    /// Inputs left to give while replaying a log
+    replay: Option<VecDeque<TimedInput>>,
+
+    // Timing
+    /// Instructions executed since the processor was created
+    instructions: u64,
+    /// Level of the IRQ line on the previous cycle, `true` when pulled low
+    irq_line: bool,
+    /// Cycle the IRQ line was pulled low at, until the request is serviced or withdrawn
+    irq_requested_at: Option<u64>,
+    /// Cycle an IRQ was given through `input()` at, latched until it's serviced
+    irq_input_at: Option<u64>,
+    /// Cycle an NMI was given through `input()` at, the edge is latched until it's serviced
+    nmi_requested_at: Option<u64>,
+    irq_latency: LatencyStats,
+    nmi_latency: LatencyStats,
 }
 
 impl Default for Processor {
     /**
     * The default value for memory field is memory with given size (bytes)
-    * For the instruction set, the default value is the value returned by `create_instruction_table()` function of struct `Instruction`
     * Everything else defaults to zero for integers
     */
     fn default() -> Self {
         Self {
             bus: Bus::new(Memory::new(RAM), vec![0; OTHER], Rom::new(ROM)),
-            instructions: Instruction::create_instructions_table(),
             accumulator: 0x00,
             index_register_x: 0x00,
             index_register_y: 0x00,
@@ -98,9 +169,39 @@ impl Default for Processor {
             address_relative: 0x00,
             opcode: 0x00,
             cycles: 0x00,
+            opcode_address: 0x0000,
 
             stack_last_address: STACK_ADDRESS_RANGE.0,
             stack_first_address: STACK_ADDRESS_RANGE.1, 
+            stack_guard: None,
+
+            halted: false,
+            breakpoints: HashSet::new(),
+
+            cycle_counters: None,
+            coverage: None,
+
+            call_stack: CallStack::new(),
+
+            symbols: SymbolTable::new(),
+            debug_info: DebugInfo::new(),
+            trace: None,
+            trace_capacity: 0,
+            history: None,
+            devices_before_tick: None,
+
+            total_cycles: 0,
+            input_log: None,
+            recording_start: 0,
+            replay: None,
+
+            instructions: 0,
+            irq_line: false,
+            irq_requested_at: None,
+            irq_input_at: None,
+            nmi_requested_at: None,
+            irq_latency: LatencyStats::default(),
+            nmi_latency: LatencyStats::default(),
         }
     }
 }
@@ -237,6 +338,7 @@ impl Processor {
  * This is because i couldn't find a way to get function name from the function pointer
 */
 
+#[allow(clippy::upper_case_acronyms)]
 #[derive(Debug, PartialEq, PartialOrd)]
 pub enum AddressingMode {
     ABS,  /* absolute */
@@ -253,7 +355,8 @@ pub enum AddressingMode {
     IMM,  /* immediate */
 }
 
-#[derive(Debug, PartialEq, PartialOrd)]
+#[allow(clippy::upper_case_acronyms)]
+#[derive(Debug, Clone, Copy, PartialEq, PartialOrd)]
 pub enum Operation {
     ADC, // add with carry
     AND, // and (with accumulator)
@@ -336,337 +439,1509 @@ pub enum Operation {
     USBC,
 }
 
-// reset function implementation
-impl Processor {
-
+impl AddressingMode {
     /**
-     * Forces the CPU into known state
-     * 
-     # Operations
-     * Set the program counter to the address stored at `0xFFFD` | `0xFFFC` 
-     * Reset the internal registers 
+     * Number of bytes following the opcode
      */
-    pub fn reset(&mut self) -> () {
-        // set the next address for program counter
-        self.program_counter = self.bus.read(INITIAL_PROGRAM_COUNTER_ADDRESS + 1 as u16) as u16 
-                                | self.bus.read(INITIAL_PROGRAM_COUNTER_ADDRESS) as u16;
-        
-        // reset internal registers
-        self.accumulator = 0x00;
-        self.index_register_x = 0x00;
-        self.index_register_y = 0x00;
-        self.stack_pointer = 0xFF;
-        self.status = 0x00;
-
-        self.set_u(true); // always setting the unused state to true
+    pub const fn operand_length(&self) -> u16 {
+        match self {
+            AddressingMode::IMPL => 0,
+            AddressingMode::ABS | AddressingMode::ABSX | AddressingMode::ABSY | AddressingMode::IND => 2,
+            _ => 1,
+        }
+    }
+}
 
-        // clearing helper variables
-        self.fetched = 0x00;
-        self.address_absolute = 0x0000;
-        self.address_relative = 0x0000;
+/**
+ * The programmer visible registers
+ */
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+pub struct Registers {
+    pub accumulator: u8,
+    pub index_register_x: u8,
+    pub index_register_y: u8,
+    pub status: u8,
+    pub stack_pointer: u8,
+    pub program_counter: u16,
+}
 
-        self.cycles = 8; // reset takes time
+impl fmt::Display for Registers {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // set flags are shown by their letter, clear ones by a dot
+        let flags: String = "NV-BDIZC"
+            .chars()
+            .enumerate()
+            .map(|(i, flag)| if (self.status & (0x80 >> i)) != 0 { flag } else { '.' })
+            .collect();
 
+        write!(
+            f,
+            "PC={:04X} A={:02X} X={:02X} Y={:02X} SP={:02X} {}",
+            self.program_counter, self.accumulator, self.index_register_x, self.index_register_y, self.stack_pointer, flags
+        )
     }
 }
 
+/**
+ * An executed instruction, with the registers as they were before it ran
+ */
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct TraceEntry {
+    pub registers: Registers,
+    /// Opcode followed by the operand, unused bytes are zero
+    pub bytes: [u8; 3],
+}
 
-// Interrupts implementations 
+// Registers and stepping implementation
 impl Processor {
+    pub fn registers(&self) -> Registers {
+        Registers {
+            accumulator: self.accumulator,
+            index_register_x: self.index_register_x,
+            index_register_y: self.index_register_y,
+            status: self.status,
+            stack_pointer: self.stack_pointer,
+            program_counter: self.program_counter,
+        }
+    }
 
-    // can be ignored
-    fn irq(&mut self) {
-        // if interrupts are allowed // it might not be allowed when interrupt is ongoing
-        if !self.get_i() {
-
-            // pushing the current program counter to stack
-            self.bus.write(self.stack_last_address + self.stack_pointer as u16, ((self.program_counter >> 8) & 0x00FF) as u8 );
-            self.stack_pointer -= 1;
-            self.bus.write(self.stack_last_address + self.stack_pointer as u16, (self.program_counter & 0x00FF) as u8 );
-            self.stack_pointer -= 1;
-
-            // changing the status registers
-            self.set_b(false); self.set_u(true); self.set_i(true);
-
-            // pushing the processor status to stack
-            self.bus.write(self.stack_last_address + self.stack_pointer as u16, self.status);
-            self.stack_pointer -= 1;
-
-            // reading the new program counter from the fixed address
-            self.program_counter = (self.bus.read(FIXED_READING_ADDRESS_FOR_BRK_AND_IRQ + 1) as u16) << 8 
-                                    | (self.bus.read(FIXED_READING_ADDRESS_FOR_BRK_AND_IRQ)) as u16;
+    /**
+     * Overwrites the registers, meant to be used between instructions
+     */
+    pub fn set_registers(&mut self, registers: &Registers) {
+        self.accumulator = registers.accumulator;
+        self.index_register_x = registers.index_register_x;
+        self.index_register_y = registers.index_register_y;
+        self.status = registers.status;
+        self.stack_pointer = registers.stack_pointer;
+        self.program_counter = registers.program_counter;
+    }
 
-            // some time is required for irq
-            self.cycles = 7;
-        }
+    pub fn program_counter(&self) -> u16 {
+        self.program_counter
     }
 
-    // cannot be ignored
-    fn nmi(&mut self) {
-        // pushing the current program counter to stack
-        self.bus.write(self.stack_last_address + self.stack_pointer as u16, ((self.program_counter >> 8) & 0x00FF) as u8 );
-        self.stack_pointer -= 1;
-        self.bus.write(self.stack_last_address + self.stack_pointer as u16, (self.program_counter & 0x00FF) as u8 );
-        self.stack_pointer -= 1;
+    /**
+     * The instruction table, indexed by opcode
+     */
+    pub fn instructions(&self) -> &'static [Instruction] {
+        &INSTRUCTIONS
+    }
 
-        // changing the status registers
-        self.set_b(false); self.set_u(true); self.set_i(true);
+    /**
+     * Clocks the processor until the current (or next) instruction has completed
+     *
+     # Returns
+     the number of clock cycles that elapsed
+     */
+    pub fn step(&mut self) -> u64 {
+        let mut cycles = 0;
 
-        // pushing the processor status to stack
-        self.bus.write(self.stack_last_address + self.stack_pointer as u16, self.status);
-        self.stack_pointer -= 1;
+        loop {
+            if self.halted {
+                return cycles;
+            }
 
-        // reading the new program counter from the fixed address
-        self.program_counter = (self.bus.read(FIXED_READING_ADDRESS_FOR_NMI + 1) as u16) << 8 
-                                | (self.bus.read(FIXED_READING_ADDRESS_FOR_NMI)) as u16;
+            self.clock();
+            cycles += 1;
 
-        // some time is required for nmi
-        self.cycles = 8;
+            if self.cycles == 0 {
+                return cycles;
+            }
+        }
     }
+}
 
-} 
-
-// CPU clock implementation
-// typical fetch, decode, execute cycle
+// Address lookup implementation
 impl Processor {
+    /**
+     * Reads an address typed by the user
+     *
+     * Accepts everything `SymbolTable::resolve()` does, and `file:line` when debug info is loaded
+     */
+    pub fn resolve_address(&self, text: &str) -> Option<u16> {
+        // a label can't contain a colon, so labels are tried first
+        self.symbols.resolve(text).or_else(|| {
+            let (file, line) = parse_location(text)?;
+            self.debug_info.addresses_of(file, line).map(|(_, addresses)| addresses[0])
+        })
+    }
+}
 
-    pub fn clock(&mut self) {
-        /*
-            The instruction set is stored in such a way that it's index corresponds to the opcode.
-            Since, hex and decimal number are equivalent,
-            it doesn't matter if the opcode is represented in hex when storing in ROM or any other storage
-        */
+/// Instructions `step_source_line()` executes at most, so code without debug info can't hang it
+const MAX_SOURCE_LINE_INSTRUCTIONS: u32 = 1_000_000;
 
-        // if there are no other pending instruction (previous instruction's execution has completed)
-        if self.cycles == 0  {
+// Source level stepping implementation
+impl Processor {
 
-            // the next instruction byte (aka opcode)
-            self.opcode = self.bus.read(self.program_counter);
+    /**
+     * Executes instructions until the program counter is on another source line
+     *
+     # Arguments
 
-            // always set the unused falg to 1 
-            self.set_u(true);
+     * `step_over` - Whether subroutines called from the current line are run to completion instead of stepped into
 
-            // incrementing the program counter as this instruction is already read
-            // and instruction may not execute next one immediately ( turns out this is a standard practice)
-            // i.e fetch instruction -> increment program counter -> execute instruction
-            self.program_counter += 1;
+     # Description
+     * Instructions without a source line (e.g. ROM routines) are executed without stopping.
+     * A breakpoint or a `JAM` opcode stops the stepping early.
 
-            // get the starting number of cycles
-            self.cycles = self.instructions.get(self.opcode as usize).unwrap().cycles;
+     # Returns
+     the number of clock cycles that elapsed
+     */
+    pub fn step_source_line(&mut self, step_over: bool) -> u64 {
+        let start_line = self.debug_info.location(self.program_counter);
+        let start_depth = self.call_stack.depth();
+        let mut cycles = 0;
 
-            // performing the fetch operation
-            // and finding out if additional cycle is required by fetch
-            let additional_cycle_for_fetch = (self.instructions.get(self.opcode as usize).unwrap().addressing_mode)(self);
-            // performing the execute operation 
-            // and finding out if the operation has the potential to require additional cycle
-            let additional_cycle_for_execute = (self.instructions.get(self.opcode as usize).unwrap().addressing_mode)(self);
+        for _ in 0..MAX_SOURCE_LINE_INSTRUCTIONS {
+            cycles += self.step();
 
-            // if more additional cycle is required by particular operation
-            // then it should be incremented inside of the operation
-            
-            // incrementing cycle if the fetch operation required more cycle and execute operation had the potential to require more cycle
-            self.cycles += (additional_cycle_for_fetch && additional_cycle_for_execute) as u8;
+            if self.halted || self.breakpoints.contains(&self.program_counter) {
+                break;
+            }
 
-            // always set the unused falg to 1 
-            self.set_u(true);
-        }
+            if step_over && self.call_stack.depth() > start_depth {
+                continue;
+            }
 
-        if self.cycles == 0 {
-            return;
+            match self.debug_info.location(self.program_counter) {
+                Some(line) if Some(&line) != start_line.as_ref() => break,
+                _ => (),
+            }
         }
-        // decrementing the required cycle for the currently running instruction
-        // as one cycle has passed
-        self.cycles -= 1;
 
+        cycles
     }
 }
 
-/**
- * Addressing modes implementation
- *
- * These implementations make sure that the operand is available for a particular operation.
- * i.e. the instruction should utilize an addressing mode and an operation
- *
- * Addressing modes will return false for general cases and true for exceptions or unusual cases
- */
-#[allow(non_snake_case)]
+// Save state implementation
 impl Processor {
-    /**
-     # Description
-    * Absolute
-    * The data is present in
-      16 bit address present in program counter in the form of little endian $LLHH
-    */
-    fn ABS(&mut self) -> bool {
-        self.address_absolute = (self.bus.read(self.program_counter + 1) as u16) << 8
-            | self.bus.read(self.program_counter) as u16;
-        self.program_counter += 2;
+    pub fn cpu_state(&self) -> CpuState {
+        CpuState {
+            accumulator: self.accumulator,
+            index_register_x: self.index_register_x,
+            index_register_y: self.index_register_y,
+            status: self.status,
+            stack_pointer: self.stack_pointer,
+            program_counter: self.program_counter,
+            fetched: self.fetched,
+            temp: self.temp,
+            address_absolute: self.address_absolute,
+            address_relative: self.address_relative,
+            opcode: self.opcode,
+            cycles: self.cycles,
+            opcode_address: self.opcode_address,
+            halted: self.halted,
+            nmi_pending: self.nmi_requested_at.is_some(),
+            irq_pending: self.irq_input_at.is_some(),
+        }
+    }
 
-        false
+    fn restore_cpu_state(&mut self, cpu: &CpuState) {
+        self.accumulator = cpu.accumulator;
+        self.index_register_x = cpu.index_register_x;
+        self.index_register_y = cpu.index_register_y;
+        self.status = cpu.status;
+        self.stack_pointer = cpu.stack_pointer;
+        self.program_counter = cpu.program_counter;
+        self.fetched = cpu.fetched;
+        self.temp = cpu.temp;
+        self.address_absolute = cpu.address_absolute;
+        self.address_relative = cpu.address_relative;
+        self.opcode = cpu.opcode;
+        self.cycles = cpu.cycles;
+        self.opcode_address = cpu.opcode_address;
+        self.halted = cpu.halted;
+        self.nmi_requested_at = cpu.nmi_pending.then_some(self.total_cycles);
+        self.irq_input_at = cpu.irq_pending.then_some(self.total_cycles);
     }
 
     /**
-    * Returns `false` if page doesn't change `true` otherwise
-    # Description
-    *  Absolute X
-    *  Same as `ABS()` but the value of  `index_register_x` is added to the address and
-       if the page changes, then one more cycle is required so boolean value of `true` is returned
-    */
-    fn ABSX(&mut self) -> bool {
-        self.address_absolute = (self.bus.read(self.program_counter + 1) as u16) << 8
-            | self.bus.read(self.program_counter) as u16;
-        self.program_counter += 2;
+     * Returns the state of the processor and of the storages on the bus
+     */
+    pub fn save_state(&self) -> MachineState {
+        MachineState {
+            cpu: self.cpu_state(),
+            memory: self.bus.memory.clone(),
+            other: self.bus.other.clone(),
+            secondary_storage: self.bus.secondary_storage.clone(),
+            mapper_registers: self.bus.mapper.as_ref().map(|mapper| mapper.save_registers()).unwrap_or_default(),
+            mapper_ram: self.bus.mapper.as_ref().map(|mapper| mapper.ram().to_vec()).unwrap_or_default(),
+            devices: self.bus.save_device_states(),
+        }
+    }
 
-        let old_high_bits = 0xFF00 & self.address_absolute;
+    fn restore_machine_state(&mut self, state: &MachineState) {
+        self.bus.memory = state.memory.clone();
+        self.bus.other = state.other.clone();
+        self.bus.secondary_storage = state.secondary_storage.clone();
+        if let Some(mapper) = &mut self.bus.mapper {
+            mapper.load_registers(&state.mapper_registers);
+            if mapper.ram().len() == state.mapper_ram.len() {
+                mapper.ram_mut().copy_from_slice(&state.mapper_ram);
+            }
+        }
+        self.bus.load_device_states(&state.devices);
+        self.restore_cpu_state(&state.cpu);
+    }
 
-        self.address_absolute += self.index_register_x as u16;
+    /**
+     * Restores a state returned by `save_state()`
+     *
+     * The execution history (if enabled) starts over from the loaded state.
+     *
+     # Returns
+     an error if the storages of the state don't have the sizes of the bus ones
+     */
+    pub fn load_state(&mut self, state: &MachineState) -> Result<(), String> {
+        if state.memory.len() != self.bus.memory.len()
+            || state.other.len() != self.bus.other.len()
+            || state.secondary_storage.len() != self.bus.secondary_storage.len()
+        {
+            return Err(String::from("the state doesn't match the storage sizes of the bus"));
+        }
 
-        let new_high_bits = 0xFF00 & self.address_absolute;
+        self.restore_machine_state(state);
 
-        !(old_high_bits == new_high_bits)
+        if let Some(history) = &self.history {
+            let (snapshot_interval, memory_cap) = (history.snapshot_interval(), history.memory_cap());
+            self.enable_history(snapshot_interval, memory_cap);
+        }
+        Ok(())
     }
+}
 
+// Reverse execution implementation
+impl Processor {
     /**
-    * Returns `false` if page doesn't change `true` otherwise
-    # Description
-    *  Absolute Y
-    *  Same as `ABS()` but the value of  `index_register_y` is added to the address and
-       if the page changes, then one more cycle is required so boolean value of `true` is returned
-    */
-    fn ABSY(&mut self) -> bool {
-        self.address_absolute = (self.bus.read(self.program_counter + 1) as u16) << 8
-            | self.bus.read(self.program_counter) as u16;
-        self.program_counter += 2;
+     * Starts recording the execution history, so that it can be stepped back through
+     *
+     # Arguments
 
-        let old_high_bits = 0xFF00 & self.address_absolute;
+     * `snapshot_interval` - Cycles between two snapshots
+     * `memory_cap` - Bytes the history may take, the oldest instructions are forgotten past it
 
-        self.address_absolute += self.index_register_y as u16;
+     # Description
+     * Registers, storage bytes, the mapper (bank selection and banked RAM) and the devices are rewound exactly.
+       The call stack and the profiler counters aren't, they keep their current state.
+     * Devices add their state to every instruction of the history, so it reaches less far back with them.
+     */
+    pub fn enable_history(&mut self, snapshot_interval: u64, memory_cap: usize) {
+        self.history = Some(History::new(snapshot_interval, memory_cap));
+        self.bus.write_journal = Some(Vec::new());
+    }
 
-        let new_high_bits = 0xFF00 & self.address_absolute;
+    pub fn disable_history(&mut self) {
+        self.history = None;
+        self.bus.write_journal = None;
+    }
 
-        !(old_high_bits == new_high_bits)
+    pub fn history(&self) -> Option<&History> {
+        self.history.as_ref()
     }
 
     /**
-    # Description
-    * zeropage
+     * Records the processor as it is before an instruction (or interrupt) changes it
+     */
+    fn record_history(&mut self) {
+        if self.history.is_none() {
+            return;
+        }
 
-    * The address of the data is at the program counter address
-    * Data present at 0x00 - 0xFF
-    */
-    fn ZPG(&mut self) -> bool {
-        self.address_absolute = 0x00FF & self.bus.read(self.program_counter) as u16;
-        self.program_counter += 1;
+        // the devices have already been clocked once when an instruction starts, their state from before that is used
+        let cpu = self.cpu_state();
+        let devices = self.devices_before_tick.take().unwrap_or_else(|| self.bus.save_device_states());
+        let writes = self.take_write_journal();
+        let snapshot = self.history.as_ref().is_some_and(History::wants_snapshot).then(|| MachineState {
+            devices: devices.clone(),
+            ..self.save_state()
+        });
+
+        if let Some(history) = &mut self.history {
+            history.push(cpu, devices, writes);
+            if let Some(snapshot) = snapshot {
+                history.push_snapshot(snapshot);
+            }
+        }
+    }
 
-        false
+    fn take_write_journal(&mut self) -> Vec<JournalEntry> {
+        self.bus.write_journal.as_mut().map(std::mem::take).unwrap_or_default()
     }
 
     /**
-     *  zeropage, X-indexed
-     * Same as `ZPG()` but the address is present at offset `index_register_x` from `program_counter`
+     * Undoes the last instruction (or the current one, if it's still running)
+     *
+     # Returns
+     false if there is nothing left to undo
      */
-    fn ZPGX(&mut self) -> bool {
-        self.address_absolute = 0x00FF
-            & self
-                .bus
-                .read(self.program_counter + self.index_register_x as u16) as u16;
-        self.program_counter += 1;
+    pub fn step_back(&mut self) -> bool {
+        let writes = self.take_write_journal();
+
+        let entry = match &mut self.history {
+            Some(history) => {
+                history.attach_writes(writes);
+                match history.pop() {
+                    Some(entry) => entry,
+                    None => return false,
+                }
+            }
+            None => return false,
+        };
 
-        false
+        for write in entry.writes.iter().rev() {
+            self.bus.undo(write);
+        }
+        self.bus.load_device_states(&entry.devices);
+        self.restore_cpu_state(&entry.cpu);
+
+        true
     }
 
     /**
-    # Description
-    *  zeropage, Y-indexed
-    * Same as `ZPG()` but the address is present at offset `index_register_y` from `program_counter`
-    */
-    fn ZPGY(&mut self) -> bool {
-        self.address_absolute = 0x00FF
-            & self
-                .bus
-                .read(self.program_counter + self.index_register_y as u16) as u16;
-        self.program_counter += 1;
+     * Steps back until the program counter reaches a breakpoint or the history runs out
+     *
+     # Returns
+     why it stopped and the number of cycles rewound
+     */
+    pub fn reverse_continue(&mut self) -> RunResult {
+        let start = self.history.as_ref().map_or(0, History::cycle);
+        let rewound = |processor: &Self| start - processor.history.as_ref().map_or(0, History::cycle);
 
-        false
+        loop {
+            if !self.step_back() {
+                return RunResult { stop_reason: StopReason::HistoryStart, cycles: rewound(self) };
+            }
+
+            if self.breakpoints.contains(&self.program_counter) {
+                return RunResult { stop_reason: StopReason::Breakpoint { address: self.program_counter }, cycles: rewound(self) };
+            }
+        }
     }
 
     /**
+     * Moves to the given cycle of the history, backwards or forwards
+     *
      # Description
-    *  Indirect
-    *  In indirect modes, the provided 16 bit address is used to lookup the actual 16 bit address
-       . In a sense, this behaves like address pointers
+     * Going back uses the undo log while it reaches, older cycles are replayed from the closest snapshot.
+     * Going forward (and replaying) executes the program, devices included.
 
-    * There is a hardware bug in this mode, and we need to emulate that too
-    * */
-    fn IND(&mut self) -> bool {
-        let pointer_low = self.bus.read(self.program_counter) as u16;
-        self.program_counter += 1;
+     # Returns
+     false if the history isn't enabled or doesn't reach that far back
+     */
+    pub fn goto_cycle(&mut self, cycle: u64) -> bool {
+        let history = match &self.history {
+            Some(history) => history,
+            None => return false,
+        };
+
+        if cycle < history.cycle() {
+            let undo_reaches = !history.is_empty() && history.oldest_entry_cycle() <= cycle;
+
+            if undo_reaches {
+                while self.history.as_ref().is_some_and(|history| history.cycle() > cycle) {
+                    self.step_back();
+                }
+            } else {
+                self.take_write_journal();
+                match self.history.as_mut().and_then(|history| history.rewind_to_snapshot(cycle)) {
+                    Some(state) => self.restore_machine_state(&state),
+                    None => return false,
+                }
+            }
+        }
 
-        let pointer_high = self.bus.read(self.program_counter) as u16;
-        self.program_counter += 1;
+        // instructions are undone whole, the remaining cycles are clocked again
+        while self.history.as_ref().is_some_and(|history| history.cycle() < cycle) {
+            self.clock();
+        }
 
-        let pointer = pointer_high << 8 | pointer_low;
+        true
+    }
+}
 
-        // The high bits will be read from the start of the same page because of the hardware bug
+// Timing implementation
+impl Processor {
+    /**
+     * Cycles clocked since the processor was created
+     */
+    pub fn total_cycles(&self) -> u64 {
+        self.total_cycles
+    }
 
-        if pointer_low == 0x00FF {
-            // Simulate the page boundary hardware bug
-            self.address_absolute =
-                (self.bus.read(pointer_high) as u16) << 8 | self.bus.read(pointer + 0) as u16;
-        } else {
-            // behave normally
-            self.address_absolute =
-                ((self.bus.read(pointer + 1)) as u16) << 8 | self.bus.read(pointer + 0) as u16;
-        }
+    /**
+     * Instructions executed since the processor was created
+     */
+    pub fn instruction_count(&self) -> u64 {
+        self.instructions
+    }
 
-        false
+    pub fn timing_stats(&self) -> TimingStats {
+        TimingStats {
+            cycles: self.total_cycles,
+            instructions: self.instructions,
+            irq_latency: self.irq_latency,
+            nmi_latency: self.nmi_latency,
+        }
     }
+}
+
+// External input implementation
+impl Processor {
 
     /**
-     # Description
-    *  indirect, X-indexed, also utilizes zero page
+     * Gives an input from outside the machine, recording it along with the current cycle while recording
+     *
+     * Interrupts are latched and serviced once the current instruction has completed,
+     * an IRQ waits for the interrupts to be enabled.
+     *
+     # Returns
 
-    * The supplied 8 bit address is offset by the value in `index_register_x` to index a location in zero-page
-    * and the actual address is read from the given address and the consequent one
-    */
-    fn INDX(&mut self) -> bool {
-        let actual_pointer = (self.bus.read(self.program_counter) + self.index_register_x) as u16;
-        self.program_counter += 1;
+     * `false` if the input targets an address with no device mapped
+     */
+    pub fn input(&mut self, event: InputEvent) -> bool {
+        if let Some(log) = &mut self.input_log {
+            log.push(self.total_cycles - self.recording_start, event);
+        }
 
-        self.address_absolute = ((self.bus.read(actual_pointer + 1) as u16) << 8)
-            | (self.bus.read(actual_pointer) as u16) << 8;
+        match event {
+            InputEvent::Device { address, port, value } => self.bus.device_input(address, port, value),
+            InputEvent::Irq => {
+                self.irq_input_at.get_or_insert(self.total_cycles);
+                true
+            }
+            InputEvent::Nmi => {
+                self.nmi_requested_at.get_or_insert(self.total_cycles);
+                true
+            }
+            InputEvent::Reset => {
+                self.reset();
+                true
+            }
+        }
+    }
 
-        false
+    /**
+     * Starts recording the inputs given through `input()`, forgetting the previous recording
+     *
+     # Description
+     * The recording only reproduces the session when replayed from the state the processor is in now,
+       usually right after loading the program and resetting.
+     */
+    pub fn start_recording(&mut self) {
+        self.input_log = Some(InputLog::new());
+        self.recording_start = self.total_cycles;
     }
 
     /**
-    # Returns
-    false if offset causes page change and additional cycle is required
-    true otherwise
+     * Returns the inputs recorded since `start_recording()`
+     */
+    pub fn stop_recording(&mut self) -> Option<InputLog> {
+        self.input_log.take()
+    }
 
-    # Description
+    pub fn is_recording(&self) -> bool {
+        self.input_log.is_some()
+    }
+
+    /**
+     * Gives the inputs of the log at the cycles they were recorded at, counted from now
+     *
+     # Description
+     * Inputs the host gives through `input()` meanwhile are still applied, hosts should stop giving live
+       input until `is_replaying()` turns `false`.
+     */
+    pub fn start_replay(&mut self, log: InputLog) {
+        let start = self.total_cycles;
+        self.replay = Some(
+            log.events
+                .into_iter()
+                .map(|input| TimedInput { cycle: input.cycle + start, ..input })
+                .collect(),
+        );
+        self.replay_inputs();
+    }
+
+    pub fn stop_replay(&mut self) {
+        self.replay = None;
+    }
+
+    pub fn is_replaying(&self) -> bool {
+        self.replay.is_some()
+    }
+
+    /**
+     * Gives the replayed inputs that are due, ending the replay after the last one
+     */
+    fn replay_inputs(&mut self) {
+        loop {
+            let due = match &mut self.replay {
+                Some(replay) if replay.front().is_some_and(|input| input.cycle <= self.total_cycles) => replay.pop_front(),
+                _ => None,
+            };
+
+            match due {
+                Some(input) => {
+                    self.input(input.event);
+                }
+                None => break,
+            }
+        }
+
+        if self.replay.as_ref().is_some_and(|replay| replay.is_empty()) {
+            self.replay = None;
+        }
+    }
+}
+
+// Tracing implementation
+impl Processor {
+
+    /**
+     * Starts keeping the last `capacity` executed instructions
+     */
+    pub fn enable_trace(&mut self, capacity: usize) {
+        self.trace = Some(VecDeque::with_capacity(capacity));
+        self.trace_capacity = capacity;
+    }
+
+    pub fn disable_trace(&mut self) {
+        self.trace = None;
+    }
+
+    /**
+     * The traced instructions, oldest first
+     */
+    pub fn trace(&self) -> impl Iterator<Item = &TraceEntry> {
+        self.trace.iter().flatten()
+    }
+
+    /**
+     * Records the instruction about to be executed at `opcode_address`
+     */
+    fn trace_instruction(&mut self, opcode_address: u16) {
+        let mut registers = self.registers();
+        registers.program_counter = opcode_address;
+
+        let length = Instruction::decode(self.opcode).length as u16;
+        let mut bytes = [self.opcode, 0x00, 0x00];
+        for i in 1..length {
+            bytes[i as usize] = self.bus.peek(opcode_address.wrapping_add(i));
+        }
+
+        if let Some(trace) = &mut self.trace {
+            if trace.len() == self.trace_capacity {
+                trace.pop_front();
+            }
+            if self.trace_capacity > 0 {
+                trace.push_back(TraceEntry { registers, bytes });
+            }
+        }
+    }
+}
+
+// reset function implementation
+impl Processor {
+
+    /**
+     * Forces the CPU into known state, like pulling the RES line low
+     * 
+     # Operations
+     * Go through the 7 cycles of an interrupt sequence with the stack writes suppressed,
+       the stack pointer is decremented three times (from 0x00 at power on, it ends at 0xFD)
+     * Set the interrupt disable flag, A, X, Y and the other flags are left as they are
+     * Set the program counter to the address stored at `0xFFFD` (high) and `0xFFFC` (low)
+     */
+    pub fn reset(&mut self) {
+        self.record_history();
+
+        // set the next address for program counter
+        self.program_counter = (self.bus.read(INITIAL_PROGRAM_COUNTER_ADDRESS + 1_u16) as u16) << 8
+                                | self.bus.read(INITIAL_PROGRAM_COUNTER_ADDRESS) as u16;
+
+        // the return address and status would be pushed here, but the writes are turned into reads
+        self.stack_pointer = self.stack_pointer.wrapping_sub(3);
+
+        self.set_i(true);
+        self.set_u(true); // always setting the unused state to true
+
+        // clearing helper variables
+        self.fetched = 0x00;
+        self.address_absolute = 0x0000;
+        self.address_relative = 0x0000;
+
+        self.halted = false;
+        self.call_stack.clear();
+
+        // pending interrupts don't survive a reset
+        self.irq_input_at = None;
+        self.nmi_requested_at = None;
+
+        self.cycles = RESET_CYCLES; // reset takes time
+
+    }
+
+    /**
+     * Turns the machine on: fills the RAM with the pattern, clears the registers and resets
+     *
+     # Description
+     * Real RAM powers on with unpredictable contents, filling it with the different patterns flushes out
+       code reading memory it hasn't initialized. Registers are cleared, so the stack pointer ends at 0xFD.
+     */
+    pub fn power_on(&mut self, pattern: PowerOnPattern) {
+        self.bus.memory.fill(pattern);
+
+        self.accumulator = 0x00;
+        self.index_register_x = 0x00;
+        self.index_register_y = 0x00;
+        self.stack_pointer = 0x00;
+        self.status = 0x00;
+
+        self.reset();
+    }
+}
+
+
+// Interrupts implementations 
+impl Processor {
+
+    // can be ignored
+    pub fn irq(&mut self) {
+        // if interrupts are allowed // it might not be allowed when interrupt is ongoing
+        if !self.get_i() {
+            self.record_history();
+
+            let return_address = self.program_counter;
+            self.opcode_address = return_address;
+
+            // pushing the current program counter to stack
+            self.push_stack(((self.program_counter >> 8) & 0x00FF) as u8);
+            self.push_stack((self.program_counter & 0x00FF) as u8);
+
+            // pushing the processor status to stack, with the break flag clear
+            self.set_b(false); self.set_u(true);
+            self.push_stack(self.status);
+
+            // masking the other interrupts once the status is saved
+            self.set_i(true);
+
+            // reading the new program counter from the fixed address
+            self.program_counter = (self.bus.read(FIXED_READING_ADDRESS_FOR_BRK_AND_IRQ + 1) as u16) << 8 
+                                    | (self.bus.read(FIXED_READING_ADDRESS_FOR_BRK_AND_IRQ)) as u16;
+
+            // some time is required for irq
+            self.cycles = IRQ_CYCLES;
+
+            let requested_at = [self.irq_requested_at.take(), self.irq_input_at.take()]
+                .into_iter()
+                .flatten()
+                .min()
+                .unwrap_or(self.total_cycles);
+            self.irq_latency.record(self.total_cycles - requested_at + IRQ_CYCLES as u64);
+
+            // the sequence belongs to the interrupted code, the handler is entered after it
+            if let Some(counters) = &mut self.cycle_counters {
+                counters.record_interrupt(IRQ_CYCLES, self.call_stack.frames());
+                counters.enter(self.program_counter);
+            }
+
+            self.call_stack.push(CallFrame {
+                kind: FrameKind::Irq,
+                call_site: return_address,
+                target: self.program_counter,
+                return_address,
+                stack_pointer: self.stack_pointer,
+            });
+        }
+    }
+
+    // cannot be ignored
+    pub fn nmi(&mut self) {
+        self.record_history();
+
+        let return_address = self.program_counter;
+        self.opcode_address = return_address;
+
+        // pushing the current program counter to stack
+        self.push_stack(((self.program_counter >> 8) & 0x00FF) as u8);
+        self.push_stack((self.program_counter & 0x00FF) as u8);
+
+        // pushing the processor status to stack, with the break flag clear
+        self.set_b(false); self.set_u(true);
+        self.push_stack(self.status);
+
+        // masking the interrupts once the status is saved
+        self.set_i(true);
+
+        // reading the new program counter from the fixed address
+        self.program_counter = (self.bus.read(FIXED_READING_ADDRESS_FOR_NMI + 1) as u16) << 8 
+                                | (self.bus.read(FIXED_READING_ADDRESS_FOR_NMI)) as u16;
+
+        // some time is required for nmi
+        self.cycles = NMI_CYCLES;
+
+        let requested_at = self.nmi_requested_at.take().unwrap_or(self.total_cycles);
+        self.nmi_latency.record(self.total_cycles - requested_at + NMI_CYCLES as u64);
+
+        // the sequence belongs to the interrupted code, the handler is entered after it
+        if let Some(counters) = &mut self.cycle_counters {
+            counters.record_interrupt(NMI_CYCLES, self.call_stack.frames());
+            counters.enter(self.program_counter);
+        }
+
+        self.call_stack.push(CallFrame {
+            kind: FrameKind::Nmi,
+            call_site: return_address,
+            target: self.program_counter,
+            return_address,
+            stack_pointer: self.stack_pointer,
+        });
+    }
+
+} 
+
+// CPU clock implementation
+// typical fetch, decode, execute cycle
+impl Processor {
+
+    pub fn clock(&mut self) {
+        if self.replay.is_some() {
+            self.replay_inputs();
+        }
+
+        self.clock_cycle();
+        self.total_cycles += 1;
+
+        if let Some(history) = &mut self.history {
+            history.tick();
+        }
+    }
+
+    fn clock_cycle(&mut self) {
+        /*
+            The instruction set is stored in such a way that it's index corresponds to the opcode.
+            Since, hex and decimal number are equivalent,
+            it doesn't matter if the opcode is represented in hex when storing in ROM or any other storage
+        */
+
+        if self.history.is_some() && self.cycles == 0 && !self.halted {
+            self.devices_before_tick = Some(self.bus.save_device_states());
+        }
+
+        // the devices on the bus share the clock with the processor
+        self.bus.tick();
+
+        // a jammed processor doesn't fetch anything until it is reset
+        if self.halted {
+            return;
+        }
+
+        // timing the IRQ requests from the cycle the line is pulled low
+        let irq_line = self.bus.irq();
+        if irq_line && !self.irq_line {
+            self.irq_requested_at = Some(self.total_cycles);
+        } else if !irq_line {
+            self.irq_requested_at = None;
+        }
+        self.irq_line = irq_line;
+
+        // if there are no other pending instruction (previous instruction's execution has completed)
+        // a latched NMI, or an interrupt requested by a device or an input, is serviced instead of fetching the next instruction
+        if self.cycles == 0 && self.nmi_requested_at.is_some() {
+            self.nmi();
+        }
+        else if self.cycles == 0 && (irq_line || self.irq_input_at.is_some()) && !self.get_i() {
+            self.irq();
+        }
+        else if self.cycles == 0  {
+            self.record_history();
+
+            // the next instruction byte (aka opcode)
+            let opcode_address = self.program_counter;
+            let stack_pointer = self.stack_pointer;
+            self.opcode_address = opcode_address;
+            self.opcode = self.bus.fetch(self.program_counter);
+            self.instructions += 1;
+
+            // always set the unused falg to 1 
+            self.set_u(true);
+
+            if self.trace.is_some() {
+                self.trace_instruction(opcode_address);
+            }
+
+            // incrementing the program counter as this instruction is already read
+            // and instruction may not execute next one immediately ( turns out this is a standard practice)
+            // i.e fetch instruction -> increment program counter -> execute instruction
+            self.program_counter = self.program_counter.wrapping_add(1);
+
+            // get the starting number of cycles
+            let instruction = Instruction::decode(self.opcode);
+            self.cycles = instruction.cycles;
+
+            // performing the fetch operation
+            // and finding out if the indexing crossed a page
+            let page_crossed = (instruction.addressing_mode)(self);
+            // performing the execute operation 
+            (instruction.operation)(self);
+
+            // if more additional cycle is required by particular operation (e.g. a taken branch)
+            // then it should be incremented inside of the operation
+            
+            // incrementing cycle if the page crossing costs the instruction an extra cycle
+            self.cycles += (page_crossed && instruction.page_cross_penalty) as u8;
+
+            // profiled against the calls active before the instruction, a JSR belongs to the caller and an RTS to the callee
+            if self.cycle_counters.is_some() {
+                self.profile_instruction(opcode_address);
+            }
+
+            self.track_call_stack(opcode_address, stack_pointer);
+            if let Some(coverage) = &mut self.coverage {
+                coverage.record(opcode_address, self.opcode, self.program_counter);
+            }
+
+            // always set the unused falg to 1 
+            self.set_u(true);
+        }
+
+        if self.cycles == 0 {
+            return;
+        }
+        // decrementing the required cycle for the currently running instruction
+        // as one cycle has passed
+        self.cycles -= 1;
+
+    }
+}
+
+// Stack implementation
+impl Processor {
+
+    /**
+     * Pushes a byte, the stack pointer wraps around within page 1 like on the real chip
+     */
+    fn push_stack(&mut self, data: u8) {
+        let stack_pointer = self.stack_pointer;
+
+        self.bus.write(self.stack_last_address + stack_pointer as u16, data);
+        self.stack_pointer = stack_pointer.wrapping_sub(1);
+
+        if self.stack_guard.is_some() {
+            let depth = self.stack_depth();
+            if let Some(guard) = &mut self.stack_guard {
+                guard.check_push(self.opcode_address, stack_pointer, depth);
+            }
+        }
+    }
+
+    /**
+     * Pulls a byte, the stack pointer wraps around within page 1 like on the real chip
+     */
+    fn pull_stack(&mut self) -> u8 {
+        if let Some(guard) = &mut self.stack_guard {
+            guard.check_pull(self.opcode_address, self.stack_pointer);
+        }
+
+        self.stack_pointer = self.stack_pointer.wrapping_add(1);
+        self.bus.read(self.stack_last_address + self.stack_pointer as u16)
+    }
+
+    /**
+     * Number of bytes on the stack
+     */
+    pub fn stack_depth(&self) -> u16 {
+        self.stack_first_address - (self.stack_last_address + self.stack_pointer as u16)
+    }
+
+    /**
+     * Starts reporting stack pointer wraps, and stack depths above `depth_threshold` if given.
+     * `run()` stops with `StopReason::StackGuard` when the guard reports something.
+     */
+    pub fn enable_stack_guard(&mut self, depth_threshold: Option<u16>) {
+        self.stack_guard = Some(StackGuard::new(depth_threshold));
+    }
+
+    pub fn disable_stack_guard(&mut self) {
+        self.stack_guard = None;
+    }
+
+    pub fn stack_guard(&self) -> Option<&StackGuard> {
+        self.stack_guard.as_ref()
+    }
+
+    /**
+     * Returns the events reported by the stack guard since the previous call
+     */
+    pub fn take_stack_events(&mut self) -> Vec<StackEvent> {
+        self.stack_guard.as_mut().map_or_else(Vec::new, |guard| guard.take_events())
+    }
+}
+
+// Call stack implementation
+impl Processor {
+
+    /**
+     * Updates the shadow call stack after an instruction was executed
+     *
+     # Arguments
+
+     * `opcode_address` - Where the instruction was fetched from
+     * `stack_pointer` - The stack pointer before the instruction was executed
+     */
+    fn track_call_stack(&mut self, opcode_address: u16, stack_pointer: u8) {
+        let kind = match Instruction::decode(self.opcode).operation_enum {
+            Operation::JSR => FrameKind::Subroutine,
+            Operation::BRK => FrameKind::Break,
+            Operation::RTS => return self.call_stack.pop(opcode_address, true, stack_pointer, self.program_counter),
+            Operation::RTI => return self.call_stack.pop(opcode_address, false, stack_pointer, self.program_counter),
+            _ => return,
+        };
+
+        // JSR is 3 bytes long, BRK skips the byte after it
+        let length = if kind == FrameKind::Subroutine { 3 } else { 2 };
+
+        self.call_stack.push(CallFrame {
+            kind,
+            call_site: opcode_address,
+            target: self.program_counter,
+            return_address: opcode_address.wrapping_add(length),
+            stack_pointer: self.stack_pointer,
+        });
+    }
+
+    pub fn call_stack(&self) -> &CallStack {
+        &self.call_stack
+    }
+
+    pub fn call_stack_mut(&mut self) -> &mut CallStack {
+        &mut self.call_stack
+    }
+
+    /**
+     * Returns the backtrace from the current program counter, innermost call first
+     */
+    pub fn backtrace(&self) -> Backtrace {
+        self.call_stack.backtrace(self.program_counter, |address| self.symbols.name_of(address).map(str::to_owned))
+    }
+}
+
+// Profiling implementation
+impl Processor {
+
+    /**
+     * Starts counting memory accesses on the bus and cycles in the processor, from zero
+     */
+    pub fn enable_profiling(&mut self) {
+        self.bus.access_counters = Some(AccessCounters::new());
+        self.cycle_counters = Some(CycleCounters::new());
+    }
+
+    /**
+     * Stops profiling and drops the counters
+     */
+    pub fn disable_profiling(&mut self) {
+        self.bus.access_counters = None;
+        self.cycle_counters = None;
+    }
+
+    pub fn is_profiling(&self) -> bool {
+        self.cycle_counters.is_some()
+    }
+
+    /**
+     * Returns everything counted since profiling was enabled, `None` while not profiling
+     */
+    pub fn profile_report(&self) -> Option<ProfileReport> {
+        let counters = self.cycle_counters.as_ref()?;
+
+        Some(ProfileReport {
+            total_cycles: counters.total_cycles(),
+            memory: self.bus.access_counters.as_ref().map_or_else(Vec::new, |counters| counters.touched()),
+            opcodes: counters.opcodes(|opcode| Instruction::decode(opcode).name.to_string()),
+            program_counters: counters.program_counters(),
+            hot_subroutines: counters.hot_subroutines(),
+        })
+    }
+
+    /**
+     * Subroutines (JSR targets and interrupt handlers) sorted by the cycles spent in them, hottest first
+     */
+    pub fn hot_subroutines(&self) -> Vec<SubroutineCycles> {
+        self.cycle_counters.as_ref().map_or_else(Vec::new, |counters| counters.hot_subroutines())
+    }
+
+    /**
+     * Counts the instruction that was just executed, `self.cycles` holds all of its cycles at this point
+     */
+    fn profile_instruction(&mut self, opcode_address: u16) {
+        if let Some(counters) = &self.bus.access_counters {
+            counters.record_execute(opcode_address);
+        }
+
+        if let Some(counters) = &mut self.cycle_counters {
+            counters.record_instruction(opcode_address, self.opcode, self.cycles, self.call_stack.frames());
+
+            if let Operation::JSR | Operation::BRK = Instruction::decode(self.opcode).operation_enum {
+                counters.enter(self.program_counter);
+            }
+        }
+    }
+}
+
+// Coverage implementation
+impl Processor {
+
+    /**
+     * Starts recording the executed instructions and branch outcomes, from zero
+     */
+    pub fn enable_coverage(&mut self) {
+        self.coverage = Some(Coverage::new());
+    }
+
+    pub fn disable_coverage(&mut self) {
+        self.coverage = None;
+    }
+
+    /**
+     * Returns everything recorded since coverage was enabled, `None` while not enabled
+     */
+    pub fn coverage(&self) -> Option<&Coverage> {
+        self.coverage.as_ref()
+    }
+}
+
+/**
+ * Why `run()` returned control to the caller
+ */
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(tag = "reason")]
+pub enum StopReason {
+    /// The requested number of cycles has elapsed
+    BudgetExhausted,
+    /// The program counter reached a breakpoint, the instruction there hasn't been executed yet
+    Breakpoint { address: u16 },
+    /// A `JAM` opcode froze the processor
+    Halted,
+    /// An undocumented opcode was about to be executed, it's executed as stubbed when resuming
+    IllegalOpcode { opcode: u8, address: u16 },
+    /// The stack guard reported an event, the instruction causing it has been executed
+    StackGuard { event: StackEvent },
+    /// Reverse execution reached the oldest instruction of the history
+    HistoryStart,
+    /// A watched address was accessed, the instruction accessing it has been executed
+    Watchpoint { hit: WatchHit },
+}
+
+/**
+ * The outcome of a single `run()` call
+ */
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct RunResult {
+    #[serde(flatten)]
+    pub stop_reason: StopReason,
+    /// Number of clock cycles that elapsed during this call
+    pub cycles: u64,
+}
+
+// Batch run implementation
+impl Processor {
+
+    /**
+     * Clocks the processor until the budget is used up or something stops it
+     *
+     # Arguments
+
+     * `cycle_budget` - The maximum number of clock cycles to run
+
+     # Description
+     * Breakpoints and illegal opcodes are only checked on instruction boundaries.
+     * A breakpoint or an illegal opcode at the program counter we start from is ignored, so calling `run()` again
+       resumes execution (the illegal opcode is executed as stubbed).
+     */
+    pub fn run(&mut self, cycle_budget: u64) -> RunResult {
+        let mut cycles: u64 = 0;
+
+        // accesses made outside of `run()` (stepping, the host reading memory, ...) don't count
+        self.take_watch_hit();
+
+        while cycles < cycle_budget {
+            if self.halted {
+                return RunResult { stop_reason: StopReason::Halted, cycles };
+            }
+
+            // the previous instruction has completed, so the next one is about to be fetched
+            if self.cycles == 0 {
+                let address = self.program_counter;
+
+                if cycles > 0 && self.breakpoints.contains(&address) {
+                    return RunResult { stop_reason: StopReason::Breakpoint { address }, cycles };
+                }
+
+                // peeking, so that the check itself doesn't trigger watchpoints or count as a read
+                let opcode = self.bus.peek(address);
+                let instruction = Instruction::decode(opcode);
+                if cycles > 0 && instruction.undocumented && instruction.operation_enum != Operation::JAM {
+                    return RunResult { stop_reason: StopReason::IllegalOpcode { opcode, address }, cycles };
+                }
+            }
+
+            self.clock();
+            cycles += 1;
+
+            if let Some(event) = self.stack_guard.as_mut().and_then(|guard| guard.take_pending()) {
+                return RunResult { stop_reason: StopReason::StackGuard { event }, cycles };
+            }
+
+            // the instruction runs on its first cycle, stopping once its remaining cycles have elapsed
+            if self.cycles == 0 {
+                if let Some(hit) = self.take_watch_hit() {
+                    return RunResult { stop_reason: StopReason::Watchpoint { hit }, cycles };
+                }
+            }
+        }
+
+        RunResult { stop_reason: StopReason::BudgetExhausted, cycles }
+    }
+
+    pub fn is_halted(&self) -> bool {
+        self.halted
+    }
+
+    pub fn add_breakpoint(&mut self, address: u16) {
+        self.breakpoints.insert(address);
+    }
+
+    pub fn remove_breakpoint(&mut self, address: u16) {
+        self.breakpoints.remove(&address);
+    }
+
+    pub fn clear_breakpoints(&mut self) {
+        self.breakpoints.clear();
+    }
+
+    /**
+     * Stops `run()` after an instruction accesses one of the `length` addresses starting at `address`
+     */
+    pub fn add_watchpoint(&mut self, address: u16, length: u16, kind: WatchKind) {
+        self.bus.watchpoints.get_or_insert_with(Watchpoints::new).insert(address, length, kind);
+    }
+
+    /**
+     * Returns the first watched access since the previous call
+     */
+    pub fn take_watch_hit(&mut self) -> Option<WatchHit> {
+        self.bus.watchpoints.as_ref().and_then(|watchpoints| watchpoints.take_hit())
+    }
+
+    pub fn remove_watchpoint(&mut self, address: u16, length: u16, kind: WatchKind) {
+        if let Some(watchpoints) = &mut self.bus.watchpoints {
+            watchpoints.remove(address, length, kind);
+
+            if watchpoints.is_empty() {
+                self.bus.watchpoints = None;
+            }
+        }
+    }
+
+    /**
+     * The breakpoints in address order
+     */
+    pub fn breakpoints(&self) -> Vec<u16> {
+        let mut breakpoints: Vec<u16> = self.breakpoints.iter().copied().collect();
+        breakpoints.sort_unstable();
+        breakpoints
+    }
+}
+
+/// Address a subroutine called by `call()` returns to, nothing is executed there
+pub const CALL_RETURN_ADDRESS: u16 = 0xFFFF;
+
+/**
+ * The outcome of a subroutine that returned to `call()`
+ */
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct CallResult {
+    /// The registers once returned, the program counter being `CALL_RETURN_ADDRESS`
+    pub registers: Registers,
+    /// Number of clock cycles the subroutine took, its `RTS` included
+    pub cycles: u64,
+}
+
+/**
+ * Why a subroutine didn't return to `call()`
+ */
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct CallError {
+    /// What stopped it, `BudgetExhausted` if it was still running
+    #[serde(flatten)]
+    pub stop_reason: StopReason,
+    /// The registers where it stopped
+    pub registers: Registers,
+    pub cycles: u64,
+}
+
+impl fmt::Display for CallError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.stop_reason {
+            StopReason::BudgetExhausted => write!(f, "Didn't return within {} cycles", self.cycles)?,
+            StopReason::Halted => write!(f, "Halted")?,
+            StopReason::IllegalOpcode { opcode, .. } => write!(f, "Illegal opcode ${:02X}", opcode)?,
+            StopReason::Breakpoint { address } => write!(f, "Breakpoint at ${:04X}", address)?,
+            StopReason::StackGuard { event } => write!(f, "Stack guard reported {:?}", event)?,
+            stop_reason => write!(f, "Stopped by {:?}", stop_reason)?,
+        }
+        write!(f, ", at {}", self.registers)
+    }
+}
+
+// Subroutine call implementation
+impl Processor {
+
+    /**
+     * Calls a subroutine the way a `JSR` would and runs it until it returns
+     *
+     # Arguments
+
+     * `address` - Where the subroutine starts
+     * `registers` - The registers it's called with, the program counter aside
+     * `cycle_budget` - The maximum number of clock cycles it may take
+
+     # Description
+     * `CALL_RETURN_ADDRESS - 1` is pushed as the return address, the call completes once the matching `RTS`
+       gets there with the stack pointer back where it was. Everything else stopping `run()` stops the call, breakpoints
+       and stack guard reports included. An instruction still going on is completed first, without being counted.
+     * A call that stops without returning is dropped from the shadow call stack.
+
+     # Returns
+     the registers and cycles used, or where and why the subroutine stopped
+     */
+    pub fn call(&mut self, address: u16, registers: &Registers, cycle_budget: u64) -> Result<CallResult, CallError> {
+        if self.cycles > 0 {
+            self.step();
+        }
+
+        self.set_registers(registers);
+        let stack_pointer = self.stack_pointer;
+        let [low, high] = CALL_RETURN_ADDRESS.wrapping_sub(1).to_le_bytes();
+        self.push_stack(high);
+        self.push_stack(low);
+        self.program_counter = address;
+
+        // as if called by a JSR right before the return address, so that backtraces show the call
+        let depth = self.call_stack.depth();
+        self.call_stack.push(CallFrame {
+            kind: FrameKind::Subroutine,
+            call_site: CALL_RETURN_ADDRESS.wrapping_sub(3),
+            target: address,
+            return_address: CALL_RETURN_ADDRESS,
+            stack_pointer: self.stack_pointer,
+        });
+
+        let added_breakpoint = self.breakpoints.insert(CALL_RETURN_ADDRESS);
+        let mut cycles = 0;
+        let result = loop {
+            let result = self.run(cycle_budget - cycles);
+            cycles += result.cycles;
+
+            match result.stop_reason {
+                StopReason::Breakpoint { address: CALL_RETURN_ADDRESS } if self.stack_pointer == stack_pointer => {
+                    break Ok(CallResult { registers: self.registers(), cycles });
+                }
+                // reached by a nested call (e.g. a recursive one), the budget is checked by the next run
+                StopReason::Breakpoint { address: CALL_RETURN_ADDRESS } => (),
+                stop_reason => break Err(CallError { stop_reason, registers: self.registers(), cycles }),
+            }
+        };
+
+        if added_breakpoint {
+            self.breakpoints.remove(&CALL_RETURN_ADDRESS);
+        }
+        // a call that didn't return would leave its frame (and a phantom caller) behind
+        if result.is_err() {
+            self.call_stack.truncate(depth);
+        }
+        result
+    }
+}
+
+/**
+ * Addressing modes implementation
+ *
+ * These implementations make sure that the operand is available for a particular operation.
+ * i.e. the instruction should utilize an addressing mode and an operation
+ *
+ * Addressing modes will return false for general cases and true for exceptions or unusual cases
+ */
+#[allow(non_snake_case, clippy::upper_case_acronyms)]
+impl Processor {
+    /**
+     # Description
+    * Absolute
+    * The data is present in
+      16 bit address present in program counter in the form of little endian $LLHH
+    */
+    fn ABS(&mut self) -> bool {
+        self.address_absolute = (self.bus.fetch(self.program_counter.wrapping_add(1)) as u16) << 8
+            | self.bus.fetch(self.program_counter) as u16;
+        self.program_counter = self.program_counter.wrapping_add(2);
+
+        false
+    }
+
+    /**
+    * Returns `false` if page doesn't change `true` otherwise
+    # Description
+    *  Absolute X
+    *  Same as `ABS()` but the value of  `index_register_x` is added to the address and
+       if the page changes, then one more cycle is required so boolean value of `true` is returned
+    */
+    fn ABSX(&mut self) -> bool {
+        self.address_absolute = (self.bus.fetch(self.program_counter.wrapping_add(1)) as u16) << 8
+            | self.bus.fetch(self.program_counter) as u16;
+        self.program_counter = self.program_counter.wrapping_add(2);
+
+        let old_high_bits = 0xFF00 & self.address_absolute;
+
+        self.address_absolute = self.address_absolute.wrapping_add(self.index_register_x as u16);
+
+        let new_high_bits = 0xFF00 & self.address_absolute;
+
+        old_high_bits != new_high_bits
+    }
+
+    /**
+    * Returns `false` if page doesn't change `true` otherwise
+    # Description
+    *  Absolute Y
+    *  Same as `ABS()` but the value of  `index_register_y` is added to the address and
+       if the page changes, then one more cycle is required so boolean value of `true` is returned
+    */
+    fn ABSY(&mut self) -> bool {
+        self.address_absolute = (self.bus.fetch(self.program_counter.wrapping_add(1)) as u16) << 8
+            | self.bus.fetch(self.program_counter) as u16;
+        self.program_counter = self.program_counter.wrapping_add(2);
+
+        let old_high_bits = 0xFF00 & self.address_absolute;
+
+        self.address_absolute = self.address_absolute.wrapping_add(self.index_register_y as u16);
+
+        let new_high_bits = 0xFF00 & self.address_absolute;
+
+        old_high_bits != new_high_bits
+    }
+
+    /**
+    # Description
+    * zeropage
+
+    * The address of the data is at the program counter address
+    * Data present at 0x00 - 0xFF
+    */
+    fn ZPG(&mut self) -> bool {
+        self.address_absolute = 0x00FF & self.bus.fetch(self.program_counter) as u16;
+        self.program_counter = self.program_counter.wrapping_add(1);
+
+        false
+    }
+
+    /**
+     *  zeropage, X-indexed
+     * Same as `ZPG()` but `index_register_x` is added to the address, which wraps around within the zero page
+     */
+    fn ZPGX(&mut self) -> bool {
+        self.address_absolute = self.bus.fetch(self.program_counter).wrapping_add(self.index_register_x) as u16;
+        self.program_counter = self.program_counter.wrapping_add(1);
+
+        false
+    }
+
+    /**
+    # Description
+    *  zeropage, Y-indexed
+    * Same as `ZPG()` but `index_register_y` is added to the address, which wraps around within the zero page
+    */
+    fn ZPGY(&mut self) -> bool {
+        self.address_absolute = self.bus.fetch(self.program_counter).wrapping_add(self.index_register_y) as u16;
+        self.program_counter = self.program_counter.wrapping_add(1);
+
+        false
+    }
+
+    /**
+     # Description
+    *  Indirect
+    *  In indirect modes, the provided 16 bit address is used to lookup the actual 16 bit address
+       . In a sense, this behaves like address pointers
+
+    * There is a hardware bug in this mode, and we need to emulate that too
+    * */
+    fn IND(&mut self) -> bool {
+        let pointer_low = self.bus.fetch(self.program_counter) as u16;
+        self.program_counter = self.program_counter.wrapping_add(1);
+
+        let pointer_high = self.bus.fetch(self.program_counter) as u16;
+        self.program_counter = self.program_counter.wrapping_add(1);
+
+        let pointer = pointer_high << 8 | pointer_low;
+
+        // The high bits will be read from the start of the same page because of the hardware bug
+
+        if pointer_low == 0x00FF {
+            // Simulate the page boundary hardware bug
+            self.address_absolute =
+                (self.bus.read(pointer & 0xFF00) as u16) << 8 | self.bus.read(pointer) as u16;
+        } else {
+            // behave normally
+            self.address_absolute =
+                ((self.bus.read(pointer + 1)) as u16) << 8 | self.bus.read(pointer) as u16;
+        }
+
+        false
+    }
+
+    /**
+     # Description
+    *  indirect, X-indexed, also utilizes zero page
+
+    * The supplied 8 bit address is offset by the value in `index_register_x` to index a location in zero-page
+    * and the actual address is read from the given address and the consequent one (wrapping around within zero-page)
+    */
+    fn INDX(&mut self) -> bool {
+        let actual_pointer = self.bus.fetch(self.program_counter).wrapping_add(self.index_register_x);
+        self.program_counter = self.program_counter.wrapping_add(1);
+
+        self.address_absolute = ((self.bus.read(actual_pointer.wrapping_add(1) as u16) as u16) << 8)
+            | self.bus.read(actual_pointer as u16) as u16;
+
+        false
+    }
+
+    /**
+    # Returns
+    false if offset causes page change and additional cycle is required
+    true otherwise
+
+    # Description
     *  indirect, Y-indexed, also utilizes zero page
 
     * The supplied 8 bit address is used to lookup another address which is offset by the content of `index_register_y`
-     to get the final address
+      to get the final address
 
     * Here, first a pair of 8-bit addresses is found in zero-page to make the 16 bit address
-     which is then offset by the value in `index_register_y` to get the final address
+      which is then offset by the value in `index_register_y` to get the final address
 
     * If the addition of offset causes page change, then additional clock cycle is required
     */
     fn INDY(&mut self) -> bool {
-        let address_before_offset = (self
-            .bus
-            .read((self.bus.read((self.program_counter) as u16) + 1) as u16)
-            as u16)
-            << 8
-            | (self
-                .bus
-                .read(self.bus.read(self.program_counter as u16) as u16)) as u16;
-        self.program_counter += 1;
-
-        self.address_absolute = address_before_offset + self.index_register_y as u16;
+        let pointer = self.bus.fetch(self.program_counter);
+        self.program_counter = self.program_counter.wrapping_add(1);
+
+        // the high byte of a pointer at the end of zero-page is read from its start
+        let address_before_offset = (self.bus.read(pointer.wrapping_add(1) as u16) as u16) << 8
+            | self.bus.read(pointer as u16) as u16;
+
+        self.address_absolute = address_before_offset.wrapping_add(self.index_register_y as u16);
 
         address_before_offset & 0xFF00 != self.address_absolute & 0xFF00
     }
@@ -686,8 +1961,8 @@ impl Processor {
     # Description
      *  relative */
     fn REL(&mut self) -> bool {
-        self.address_relative = self.bus.read(self.program_counter) as u16;
-        self.program_counter += 1;
+        self.address_relative = self.bus.fetch(self.program_counter) as u16;
+        self.program_counter = self.program_counter.wrapping_add(1);
 
         // if the relative address is negative
         if (self.address_relative & 0x80) == 0x80 {
@@ -701,7 +1976,8 @@ impl Processor {
      * Data present on the next address of the opcode
      */
     fn IMM(&mut self) -> bool {
-        self.address_absolute = self.program_counter + 1;
+        self.address_absolute = self.program_counter;
+        self.program_counter = self.program_counter.wrapping_add(1);
 
         false
     }
@@ -718,9 +1994,13 @@ impl Processor {
     * Fetches either from accumulator or from the memory
     */
     fn fetch(&mut self) -> u8 {
-        if self.instructions[self.opcode as usize].addressing_mode_enum  == AddressingMode::IMPL { 
+        let addressing_mode = &Instruction::decode(self.opcode).addressing_mode_enum;
+        if *addressing_mode == AddressingMode::IMPL { 
             // if the data is present in the accumulator (i.e. in implied addressing mode)
             self.fetched = self.accumulator; 
+        } else if *addressing_mode == AddressingMode::IMM {
+            // the immediate operand is part of the instruction, not a data access
+            self.fetched = self.bus.fetch(self.address_absolute);
         } else {
             self.fetched = self.bus.read(self.address_absolute);
         }
@@ -731,11 +2011,12 @@ impl Processor {
 /*
  * OP Codes implementation
  */
-#[allow(non_snake_case)]
+#[allow(non_snake_case, clippy::upper_case_acronyms)]
 impl Processor {
     // add with carry
-    fn ADC(&mut self) -> bool {
+    fn ADC(&mut self) {
         self.fetch();
+        let (accumulator, carry) = (self.accumulator, self.get_c());
 
         self.temp = self.accumulator as u16 + self.fetched as u16 + self.get_c() as u16;
 
@@ -747,28 +2028,71 @@ impl Processor {
 
         // finding out if it has overflowed
         // and setting it as overflow flag
-        let sign_bit = 1 << 7;
+        let sign_bit: u8 = 1 << 7;
         self.set_o(
             (((self.accumulator & sign_bit) == 0)       // if the result is negative given both the operands are positive
                 && (self.fetched & sign_bit == 0)
-                && (self.temp & sign_bit as u16 == 1))
-                || (((self.accumulator & sign_bit) == 1)    // if the result is positive given both the operands are negative
-                    && (self.fetched & sign_bit == 1)
+                && (self.temp & sign_bit as u16 == sign_bit as u16))
+                || (((self.accumulator & sign_bit) == sign_bit)    // if the result is positive given both the operands are negative
+                    && (self.fetched & sign_bit == sign_bit)
                     && (self.temp & sign_bit as u16 == 0)),
         );
 
         // setting the negative flag
-        self.set_n(self.temp & sign_bit as u16 == 1);
+        self.set_n(self.temp & sign_bit as u16 == sign_bit as u16);
 
         // loading the result into accumulator (the part except carry, if any)
         self.accumulator = (self.temp & 0x00FF) as u8;
 
-        // has the potential to require additional clock cycle
-        true
+        if self.get_d() {
+            self.add_decimal(accumulator, self.fetched, carry);
+        }
+    }
+
+    /**
+     * Binary coded decimal addition, the way the NMOS 6502 does it
+     *
+     * The low digit is adjusted first, N and V are taken before the high digit is adjusted
+     * and Z is the one of the binary addition (already set by the caller).
+     */
+    fn add_decimal(&mut self, accumulator: u8, operand: u8, carry: bool) {
+        // low digit, carrying into the high one past 9
+        let mut low_digit = (accumulator & 0x0F) + (operand & 0x0F) + carry as u8;
+        if low_digit > 0x09 {
+            low_digit = ((low_digit + 0x06) & 0x0F) + 0x10;
+        }
+
+        let sum = (accumulator & 0xF0) as u16 + (operand & 0xF0) as u16 + low_digit as u16;
+        let signed_sum = (accumulator & 0xF0) as i8 as i16 + (operand & 0xF0) as i8 as i16 + low_digit as i16;
+        self.set_n(sum & 0x80 == 0x80);
+        self.set_o(!(-128..=127).contains(&signed_sum));
+
+        // high digit
+        let sum = if sum >= 0xA0 { sum + 0x60 } else { sum };
+        self.set_c(sum > 0xFF);
+        self.accumulator = (sum & 0x00FF) as u8;
+    }
+
+    /**
+     * Binary coded decimal subtraction, the way the NMOS 6502 does it
+     *
+     * Only the result is adjusted, the flags are the ones of the binary subtraction (already set by the caller).
+     */
+    fn subtract_decimal(&mut self, accumulator: u8, operand: u8, carry: bool) {
+        // low digit, borrowing from the high one below 0
+        let mut low_digit = (accumulator & 0x0F) as i16 - (operand & 0x0F) as i16 - !carry as i16;
+        if low_digit < 0 {
+            low_digit = ((low_digit - 0x06) & 0x0F) - 0x10;
+        }
+
+        // high digit
+        let difference = (accumulator & 0xF0) as i16 - (operand & 0xF0) as i16 + low_digit;
+        let difference = if difference < 0 { difference - 0x60 } else { difference };
+        self.accumulator = (difference & 0x00FF) as u8;
     }
 
     // and (with accumulator)
-    fn AND(&mut self) -> bool {
+    fn AND(&mut self) {
 
         // bitwise and with the value in memory
         self.accumulator &= self.fetch();
@@ -777,33 +2101,33 @@ impl Processor {
         self.set_z(self.accumulator == 0x00);
 
         // negative flag
-        self.set_n(self.accumulator & (1 << 7) == 0x01 );
-
-        true
+        self.set_n(self.accumulator & (1 << 7) == (1 << 7));
     }
 
     // arithmetic shift left
-    fn ASL(&mut self) -> bool {
+    fn ASL(&mut self) {
         let left_shifted_output = (self.fetch() as u16) << 1;
 
-        if self.instructions[self.opcode as usize].addressing_mode_enum == AddressingMode::IMPL {
+        // the bit shifted out goes to the carry
+        self.set_c(left_shifted_output & 0xFF00 != 0);
+        self.set_z(left_shifted_output & 0x00FF == 0x0000);
+        self.set_n(left_shifted_output & 0x0080 == 0x0080);
+
+        if Instruction::decode(self.opcode).addressing_mode_enum == AddressingMode::IMPL {
             self.accumulator = (left_shifted_output & 0x00FF) as u8;
         } else {
             self.bus.write(self.address_absolute, (left_shifted_output & 0x00FF) as u8)
         }
-
-        // doesn't require additional clock cycle
-        false
     }
 
     // branch on carry clear
-    fn BCC(&mut self) -> bool {
+    fn BCC(&mut self) {
         if !self.get_c() {
             // adding one cycle (due to branching)
             self.cycles += 1;
 
             // branching
-            self.address_absolute = self.program_counter + self.address_relative as u16;
+            self.address_absolute = self.program_counter.wrapping_add(self.address_relative);
 
             // adding one more cycle if branch occurs to different page
             if self.program_counter & 0xFF00 != self.address_absolute & 0xFF00 {
@@ -812,19 +2136,16 @@ impl Processor {
 
             self.program_counter = self.address_absolute;
         }
-
-        // cycles has already been incremented
-        false
     }
 
     // branch on carry set
-    fn BCS(&mut self) -> bool {
+    fn BCS(&mut self) {
         if self.get_c(){
             // adding one cycle (due to branching)
             self.cycles += 1;
 
             // branching
-            self.address_absolute = self.program_counter + self.address_relative as u16;
+            self.address_absolute = self.program_counter.wrapping_add(self.address_relative);
 
             // adding one more cycle if branch occurs to different page
             if self.program_counter & 0xFF00 != self.address_absolute & 0xFF00 {
@@ -833,19 +2154,16 @@ impl Processor {
 
             self.program_counter = self.address_absolute;
         }
-
-        // cycles has already been incremented
-        false
     }
 
     // branch on equal (zero set)
-    fn BEQ(&mut self) -> bool {
+    fn BEQ(&mut self) {
         if self.get_z() {
             // adding one cycle (due to branching)
             self.cycles += 1;
 
             // branching
-            self.address_absolute = self.program_counter + self.address_relative as u16;
+            self.address_absolute = self.program_counter.wrapping_add(self.address_relative);
 
             // adding one more cycle if branch occurs to different page
             if self.program_counter & 0xFF00 != self.address_absolute & 0xFF00 {
@@ -854,9 +2172,6 @@ impl Processor {
 
             self.program_counter = self.address_absolute;
         }
-
-        // cycles has already been incremented
-        false
     }
 
     /**
@@ -866,25 +2181,23 @@ impl Processor {
      * bits 7 and 6 of operand are transfered to bit 7 and 6 of status register (n,o) negative, overflow; BYTE: `7 6 5 4 3 2 1 0`
      * the zero-flag is set to the result of operand AND accumulator.
     */
-    fn BIT(&mut self) -> bool {
+    fn BIT(&mut self) {
         self.fetch();
 
         self.set_n(self.fetched & (1 << 7) == (1<<7));
         self.set_o(self.fetched & (1 << 6) == (1<<6));
 
         self.set_z((self.fetched & self.accumulator) == 0x00);
-
-        true
     }
 
     // branch on minus (negative set)
-    fn BMI(&mut self) -> bool {
+    fn BMI(&mut self) {
         if self.get_n() {
             // adding one cycle (due to branching)
             self.cycles += 1;
 
             // branching
-            self.address_absolute = self.program_counter + self.address_relative as u16;
+            self.address_absolute = self.program_counter.wrapping_add(self.address_relative);
 
             // adding one more cycle if branch occurs to different page
             if self.program_counter & 0xFF00 != self.address_absolute & 0xFF00 {
@@ -893,19 +2206,16 @@ impl Processor {
 
             self.program_counter = self.address_absolute;
         }
-
-        // cycles has already been incremented
-        false
     }
 
     // branch on not equal (zero clear)
-    fn BNE(&mut self) -> bool {
+    fn BNE(&mut self) {
         if !self.get_z() {
             // adding one cycle (due to branching)
             self.cycles += 1;
 
             // branching
-            self.address_absolute = self.program_counter + self.address_relative as u16;
+            self.address_absolute = self.program_counter.wrapping_add(self.address_relative);
 
             // adding one more cycle if branch occurs to different page
             if self.program_counter & 0xFF00 != self.address_absolute & 0xFF00 {
@@ -914,19 +2224,16 @@ impl Processor {
 
             self.program_counter = self.address_absolute;
         }
-
-        // cycles has already been incremented
-        false
     }
 
     // branch on plus (negative clear)
-    fn BPL(&mut self) -> bool {
+    fn BPL(&mut self) {
         if !self.get_n() {
             // adding one cycle (due to branching)
             self.cycles += 1;
 
             // branching
-            self.address_absolute = self.program_counter + self.address_relative as u16;
+            self.address_absolute = self.program_counter.wrapping_add(self.address_relative);
 
             // adding one more cycle if branch occurs to different page
             if self.program_counter & 0xFF00 != self.address_absolute & 0xFF00 {
@@ -935,49 +2242,36 @@ impl Processor {
 
             self.program_counter = self.address_absolute;
         }
-
-        // cycles has already been incremented
-        false
     }
 
     // break / interrupt
-    fn BRK(&mut self) -> bool {
-        self.program_counter += 1;
-
-        // setting interrupt inhibit flag
-        self.set_i(true);
+    fn BRK(&mut self) {
+        // the byte after BRK is skipped
+        self.program_counter = self.program_counter.wrapping_add(1);
 
         // pushing the program_counter to stack
-        self.bus.write(self.stack_last_address + self.stack_pointer as u16, ((self.program_counter >> 8) & 0x00FF) as u8);
-        self.stack_pointer -= 1;
-        self.bus.write(self.stack_last_address + self.stack_pointer as u16, (self.program_counter & 0x00FF) as u8);
-        self.stack_pointer -= 1;
-
-        // setting the break flag
-        self.set_b(true);
+        self.push_stack(((self.program_counter >> 8) & 0x00FF) as u8);
+        self.push_stack((self.program_counter & 0x00FF) as u8);
 
-        // pushing the status register to stack
-        self.bus.write(self.stack_last_address + self.stack_pointer as u16, self.status);
-        self.stack_pointer -= 1;
+        // pushing the status register to stack, the break flag only exists in the pushed copy
+        self.push_stack(self.status | (1 << B_FLAG_POS) | (1 << UNUSED_FLAG_POS));
 
-        // clearing the break flag
-        self.set_b(false);
+        // setting interrupt inhibit flag, once the status is saved
+        self.set_i(true);
 
         // setting the program counter to the value in final addresses (target addresses for break)
         self.program_counter = (self.bus.read(FIXED_READING_ADDRESS_FOR_BRK_AND_IRQ + 1) as u16) << 8 
                                 | self.bus.read(FIXED_READING_ADDRESS_FOR_BRK_AND_IRQ) as u16;
-
-        false
     }
 
     // branch on overflow clear
-    fn BVC(&mut self) -> bool {
+    fn BVC(&mut self) {
         if !self.get_o() {
             // adding one cycle (due to branching)
             self.cycles += 1;
 
             // branching
-            self.address_absolute = self.program_counter + self.address_relative as u16;
+            self.address_absolute = self.program_counter.wrapping_add(self.address_relative);
 
             // adding one more cycle if branch occurs to different page
             if self.program_counter & 0xFF00 != self.address_absolute & 0xFF00 {
@@ -986,19 +2280,16 @@ impl Processor {
 
             self.program_counter = self.address_absolute;
         }
-
-        // cycles has already been incremented
-        false
     }
 
     // branch on overflow set
-    fn BVS(&mut self) -> bool {
+    fn BVS(&mut self) {
         if self.get_o() {
             // adding one cycle (due to branching)
             self.cycles += 1;
 
             // branching
-            self.address_absolute = self.program_counter + self.address_relative as u16;
+            self.address_absolute = self.program_counter.wrapping_add(self.address_relative);
 
             // adding one more cycle if branch occurs to different page
             if self.program_counter & 0xFF00 != self.address_absolute & 0xFF00 {
@@ -1007,281 +2298,223 @@ impl Processor {
 
             self.program_counter = self.address_absolute;
         }
-
-        // cycles has already been incremented
-        false
     }
 
     // clear carry
-    fn CLC(&mut self) -> bool {
+    fn CLC(&mut self) {
         self.set_c(false);
-
-        false
     }
 
     // clear decimal
-    fn CLD(&mut self) -> bool {
+    fn CLD(&mut self) {
         self.set_d(false);
-
-        false
     }
 
     // clear interrupt disable
-    fn CLI(&mut self) -> bool {
+    fn CLI(&mut self) {
         self.set_i(false);
-
-        false
     }
 
     // clear overflow
-    fn CLV(&mut self) -> bool {
+    fn CLV(&mut self) {
         self.set_o(false);
-
-        false
     }
 
     // compare (with accumulator)
-    fn CMP(&mut self) -> bool {
+    fn CMP(&mut self) {
         self.fetch();
 
         self.set_c(self.accumulator >= self.fetched); // carry flag if accumulator is greater than or equal to the fetched value
-        self.set_z((self.accumulator as u16 - self.fetched as u16) == 0x0000); // zero flag if accumulator and fetched both are same (except the carry bit)
-        self.set_n((self.accumulator as u16 - self.fetched as u16) & (1 << 7) == (1 << 7)); // negative flag if the result is negative
-
-        // may require on additional clock cycle
-        true
+        self.set_z(self.accumulator == self.fetched); // zero flag if accumulator and fetched both are same
+        self.set_n(self.accumulator.wrapping_sub(self.fetched) & (1 << 7) == (1 << 7)); // negative flag if bit 7 of the difference is set
     }
 
     // compare with X
-    fn CPX(&mut self) -> bool {
+    fn CPX(&mut self) {
         self.fetch();
 
         self.set_c(self.index_register_x >= self.fetched); // carry flag if index_register_x is greater than or equal to the fetched value
-        self.set_z((self.index_register_x as u16 - self.fetched as u16) == 0x0000); // zero flag if index_register_x and fetched both are same (except the carry bit)
-        self.set_n((self.index_register_x as u16 - self.fetched as u16) & (1 << 7) == (1 << 7)); // negative flag if the result is negative
-
-       false        
+        self.set_z(self.index_register_x == self.fetched); // zero flag if index_register_x and fetched both are same
+        self.set_n(self.index_register_x.wrapping_sub(self.fetched) & (1 << 7) == (1 << 7)); // negative flag if bit 7 of the difference is set
     }
 
     // compare with Y
-    fn CPY(&mut self) -> bool {
+    fn CPY(&mut self) {
         self.fetch();
 
         self.set_c(self.index_register_y >= self.fetched); // carry flag if index_register_y is greater than or equal to the fetched value
-        self.set_z((self.index_register_y as u16 - self.fetched as u16) == 0x0000); // zero flag if index_register_y and fetched both are same (except the carry bit)
-        self.set_n((self.index_register_y as u16 - self.fetched as u16) & (1 << 7) == (1 << 7)); // negative flag if the result is negative
-
-        false
+        self.set_z(self.index_register_y == self.fetched); // zero flag if index_register_y and fetched both are same
+        self.set_n(self.index_register_y.wrapping_sub(self.fetched) & (1 << 7) == (1 << 7)); // negative flag if bit 7 of the difference is set
     }
 
     // decrement the value at memory location
-    fn DEC(&mut self) -> bool {
-        self.temp = self.fetch() as u16 - 1 as u16;
+    fn DEC(&mut self) {
+        self.temp = self.fetch().wrapping_sub(1) as u16;
         self.bus.write(self.address_absolute, (self.temp & 0x00FF) as u8);
 
         // setting the flags
         self.set_z((self.temp & 0x00FF) == 0x0000);
         self.set_n((self.temp & 0x80) == 0x80);
-
-        false
     }
 
     // decrement index_register_x
-    fn DEX(&mut self) -> bool {
-        self.index_register_x -= 1;
+    fn DEX(&mut self) {
+        self.index_register_x = self.index_register_x.wrapping_sub(1);
         
         // setting the flags
         self.set_z(self.index_register_x == 0x00);
         self.set_n((self.index_register_x & 0x80) == 0x80);
-
-        false
     }
 
     // decrement index_register_y
-    fn DEY(&mut self) -> bool {
-        self.index_register_y -= 1;
+    fn DEY(&mut self) {
+        self.index_register_y = self.index_register_y.wrapping_sub(1);
         
         // setting the flags
         self.set_z(self.index_register_y == 0x00);
         self.set_n((self.index_register_y & 0x80) == 0x80);
-
-        false
     }
 
     // exclusive or (with accumulator)
-    fn EOR(&mut self) -> bool {
-        self.accumulator = self.accumulator ^ self.fetch();
+    fn EOR(&mut self) {
+        self.accumulator ^= self.fetch();
 
         // setting the flags
         self.set_z(self.accumulator == 0x00);
         self.set_n((self.accumulator & (1 << 7)) == (1 << 7));
-
-        true
     }
 
     // increment
-    fn INC(&mut self) -> bool {
-        self.temp = self.fetch() as u16 + 1 as u16;
+    fn INC(&mut self) {
+        self.temp = self.fetch().wrapping_add(1) as u16;
         self.bus.write(self.address_absolute, (self.temp & 0x00FF) as u8);
 
         // setting the flags
         self.set_z((self.temp & 0x00FF) == 0x0000);
         self.set_n((self.temp & 0x80) == 0x80);
-
-        false
     }
 
     // increment X
-    fn INX(&mut self) -> bool {
-        self.index_register_x += 1;
+    fn INX(&mut self) {
+        self.index_register_x = self.index_register_x.wrapping_add(1);
         
         // setting the flags
         self.set_z(self.index_register_x == 0x00);
         self.set_n((self.index_register_x & 0x80) == 0x80);
-
-        false
     }
 
     // increment Y
-    fn INY(&mut self) -> bool {
-        self.index_register_y += 1;
+    fn INY(&mut self) {
+        self.index_register_y = self.index_register_y.wrapping_add(1);
         
         // setting the flags
         self.set_z(self.index_register_y == 0x00);
         self.set_n((self.index_register_y & 0x80) == 0x80);
-
-        false
     }
 
     // jump
-    fn JMP(&mut self) -> bool {
+    fn JMP(&mut self) {
         self.program_counter = self.address_absolute;
-
-        false
     }
 
     // jump subroutine
-    fn JSR(&mut self) -> bool {
-        true
+    fn JSR(&mut self) {
+        // the address pushed is the last byte of the JSR instruction, RTS adds one to it
+        self.program_counter = self.program_counter.wrapping_sub(1);
+
+        // pushing the program_counter to stack
+        self.push_stack(((self.program_counter >> 8) & 0x00FF) as u8);
+        self.push_stack((self.program_counter & 0x00FF) as u8);
+
+        self.program_counter = self.address_absolute;
     }
 
     // load accumulator
-    fn LDA(&mut self) -> bool {
+    fn LDA(&mut self) {
         self.accumulator = self.fetch();
 
         // setting the flags
         self.set_z(self.accumulator == 0x00);
         self.set_n((self.accumulator & 0x80) == 0x80);
-
-        true
     }
 
     // load X
-    fn LDX(&mut self) -> bool {
+    fn LDX(&mut self) {
         self.index_register_x = self.fetch();
 
         // setting the flags
         self.set_z(self.index_register_x == 0x00);
         self.set_n((self.index_register_x & 0x80) == 0x80);
-
-        true
     }
 
     // load Y
-    fn LDY(&mut self) -> bool {
+    fn LDY(&mut self) {
         self.index_register_y = self.fetch();
 
         // setting the flags
         self.set_z(self.index_register_y == 0x00);
         self.set_n((self.index_register_y & 0x80) == 0x80);
-
-        true
     }
 
     // logical shift right
-    fn LSR(&mut self) -> bool {
+    fn LSR(&mut self) {
 
         let right_shifted_output = (self.fetch() as u16) >> 1;
 
         self.set_c(self.fetched & 0x01 == 0x01 );
+        self.set_z(right_shifted_output == 0x0000);
+        self.set_n(false);
 
-        if self.instructions[self.opcode as usize].addressing_mode_enum == AddressingMode::IMPL {
+        if Instruction::decode(self.opcode).addressing_mode_enum == AddressingMode::IMPL {
             self.accumulator = (right_shifted_output & 0x00FF) as u8;
         } else {
             self.bus.write(self.address_absolute, (right_shifted_output & 0x00FF) as u8)
         }
-
-        // doesn't require additional clock cycle
-        false
     }
 
     // no operation
-    fn NOP(&mut self) -> bool {
-        true
-    }
+    fn NOP(&mut self) {}
 
     // or with accumulator
-    fn ORA(&mut self) -> bool {
-        self.accumulator = self.accumulator | self.fetch();
+    fn ORA(&mut self) {
+        self.accumulator |= self.fetch();
 
         // setting the flags
         self.set_z(self.accumulator == 0x00);
         self.set_n((self.accumulator & (1 << 7)) == (1 << 7));
-
-        true
     }
 
     // push accumulator to stack
-    fn PHA(&mut self) -> bool {
-        self.bus.write(self.stack_last_address + self.stack_pointer as u16, self.accumulator);
-        self.stack_pointer -= 1;
-
-        false
+    fn PHA(&mut self) {
+        self.push_stack(self.accumulator);
     }
 
     // push processor status (`self.status`) to stack
-    // Break flag is set to 1 before push
-    fn PHP(&mut self) -> bool {
-
-        self.bus.write(self.stack_last_address + self.stack_pointer as u16,
-             self.status 
-             | ((self.get_u() as u8) << UNUSED_FLAG_POS) 
-             | (((self.get_b() as u8) <<  B_FLAG_POS)));
-
-        self.set_b(false);
-        self.set_u(false);
-
-        self.stack_pointer -= 1;
-
-        false
+    // Break and unused flags are set in the pushed copy
+    fn PHP(&mut self) {
+        self.push_stack(self.status | (1 << UNUSED_FLAG_POS) | (1 << B_FLAG_POS));
     }
 
     // pull accumulator from stack (pop accumulator off stack)
-    fn PLA(&mut self) -> bool {
-        self.stack_pointer += 1;
-
-        self.accumulator = self.bus.read(self.stack_last_address + self.stack_pointer as u16);
+    fn PLA(&mut self) {
+        self.accumulator = self.pull_stack();
 
         // setting the flags depending upon the new accumulator value
         self.set_z(self.accumulator == 0x00);
         self.set_n((self.accumulator & (1 << 7)) == (1 << 7));
-
-        false
     }
 
     // pull processor status (`self.status`) (pop status register off stack)
-    fn PLP(&mut self) -> bool {
-        self.stack_pointer += 1;
-
-        self.status = self.bus.read(self.stack_last_address + self.stack_pointer as u16);
+    fn PLP(&mut self) {
+        self.status = self.pull_stack();
 
+        // the break flag only exists on the stack
+        self.set_b(false);
         self.set_u(true);
-
-        false
     }
 
     // rotate left
-    fn ROL(&mut self) -> bool {
+    fn ROL(&mut self) {
         self.temp = ((self.fetch() as u16) << 1) | self.get_c() as u16;
 
         // setting the flags
@@ -1290,18 +2523,15 @@ impl Processor {
         self.set_n((self.temp & 0x0080) == 0x0080);
 
         // writing to accumulatro or to the memory
-        if self.instructions[self.opcode as usize].addressing_mode_enum == AddressingMode::IMPL {
+        if Instruction::decode(self.opcode).addressing_mode_enum == AddressingMode::IMPL {
             self.accumulator = (self.temp & 0x00FF) as u8;
         } else {
             self.bus.write(self.address_absolute, (self.temp & 0x00FF) as u8)
         }
-
-        // no additional clock cycle required
-        false
     }
 
     // rotate right
-    fn ROR(&mut self) -> bool {
+    fn ROR(&mut self) {
         
         // shifting one bit to right and setting the carry bit in the leftmost bit
         self.temp = ((self.get_c() as u8) << 7) as u16 | ((self.fetch()) >> 1) as u16; 
@@ -1316,55 +2546,41 @@ impl Processor {
         self.set_n((self.temp & (1 << 7)) == (1 << 7));
 
         // writing to accumulatro or to the memory
-        if self.instructions[self.opcode as usize].addressing_mode_enum == AddressingMode::IMPL {
+        if Instruction::decode(self.opcode).addressing_mode_enum == AddressingMode::IMPL {
             self.accumulator = (self.temp & 0x00FF) as u8;
         } else {
             self.bus.write(self.address_absolute, (self.temp & 0x00FF) as u8)
         }
-
-        // no additional clock cycle required
-        false
     }
 
     // return from interrupt
-    fn RTI(&mut self) -> bool {
-        self.stack_pointer += 1;
-
+    fn RTI(&mut self) {
         // getting the status from stack
-        self.status = self.bus.read(self.stack_last_address + self.stack_pointer as u16);
+        self.status = self.pull_stack();
 
-        // changing the break and unused flags
-        self.set_b(!self.get_b());
-        self.set_u(!self.get_u());
-
-        self.stack_pointer += 1;
+        // the break flag only exists on the stack
+        self.set_b(false);
+        self.set_u(true);
 
         // getting the program counter from stack
-        self.program_counter = (self.bus.read(self.stack_last_address + self.stack_pointer as u16 + 1) as u16) << 8 
-                                | self.bus.read(self.stack_last_address + self.stack_pointer as u16) as u16;
-        self.stack_pointer += 1;
-
-        false
+        self.program_counter = self.pull_stack() as u16;
+        self.program_counter |= (self.pull_stack() as u16) << 8;
     }
 
     // return from subroutine
-    fn RTS(&mut self) -> bool {
-        self.stack_pointer += 1;
-
+    fn RTS(&mut self) {
         // getting the program counter from stack
-        self.program_counter = (self.bus.read(self.stack_last_address + self.stack_pointer as u16 + 1) as u16) << 8 
-                                | self.bus.read(self.stack_last_address + self.stack_pointer as u16) as u16;
-        self.stack_pointer += 1;
+        self.program_counter = self.pull_stack() as u16;
+        self.program_counter |= (self.pull_stack() as u16) << 8;
 
-        self.program_counter += 1;
-
-        false
+        self.program_counter = self.program_counter.wrapping_add(1);
     }
 
     // subtract with carry (burrow)
-    fn SBC(&mut self) -> bool {
+    fn SBC(&mut self) {
 
         self.fetch();
+        let (accumulator, carry) = (self.accumulator, self.get_c());
 
         // inverting the fetched value (1's complement)
         let inverted_fetched = !self.fetched;
@@ -1381,227 +2597,174 @@ impl Processor {
 
         // finding out if it has overflowed
         // and setting it as overflow flag
-        let sign_bit = (1 as u8) << 7;
+        let sign_bit = 1_u8 << 7;
         self.set_o(
             (((self.accumulator & sign_bit) == 0)       // if the result is negative given both the operands are positive
                 && (inverted_fetched & sign_bit == 0)
-                && (self.temp & sign_bit as u16 == 1))
-                || (((self.accumulator & sign_bit) == 1) // if the result is positive given both the operands are negative
-                    && (inverted_fetched & sign_bit == 1)
+                && (self.temp & sign_bit as u16 == sign_bit as u16))
+                || (((self.accumulator & sign_bit) == sign_bit) // if the result is positive given both the operands are negative
+                    && (inverted_fetched & sign_bit == sign_bit)
                     && (self.temp & sign_bit as u16 == 0)),
         );
 
         // setting the negative flag
-        self.set_n(self.temp & sign_bit as u16 == 1);
+        self.set_n(self.temp & sign_bit as u16 == sign_bit as u16);
 
         // loading the result into accumulator (the part except carry, if any)
         self.accumulator = (self.temp & 0x00FF) as u8;
 
-        true
+        if self.get_d() {
+            self.subtract_decimal(accumulator, self.fetched, carry);
+        }
     }
 
     // set carry
-    fn SEC(&mut self) -> bool {
+    fn SEC(&mut self) {
         self.set_c(true);
-
-        false
     }
 
     // set decimal
-    fn SED(&mut self) -> bool {
+    fn SED(&mut self) {
         self.set_d(true);
-
-        false
     }
 
     // set interrupt disable
-    fn SEI(&mut self) -> bool {
+    fn SEI(&mut self) {
         self.set_i(true);
-
-        false
     }
 
     // store accumulator at address
-    fn STA(&mut self) -> bool {
+    fn STA(&mut self) {
         self.bus.write(self.address_absolute, self.accumulator);
-
-        false
     }
 
     // store X at address
-    fn STX(&mut self) -> bool {
+    fn STX(&mut self) {
         self.bus.write(self.address_absolute, self.index_register_x);
-
-        false
     }
 
     // store Y at address
-    fn STY(&mut self) -> bool {
+    fn STY(&mut self) {
         self.bus.write(self.address_absolute, self.index_register_y);
-
-        false
     }
 
     // transfer accumulator to X register
-    fn TAX(&mut self) -> bool {
+    fn TAX(&mut self) {
         self.index_register_x = self.accumulator;
 
         self.set_z(self.index_register_x == 0x00);
         self.set_n((self.index_register_x & 0x80) == 0x80);
-
-        false
     }
 
     // transfer accumulator to Y
-    fn TAY(&mut self) -> bool {
+    fn TAY(&mut self) {
         self.index_register_y = self.accumulator;
 
         self.set_z(self.index_register_y == 0x00);
         self.set_n((self.index_register_y & 0x80) == 0x80);
-
-        false
     }
 
     // transfer stack pointer to X
-    fn TSX(&mut self) -> bool {
+    fn TSX(&mut self) {
         self.index_register_x = self.stack_pointer;
 
         self.set_z(self.index_register_x == 0x00);
         self.set_n((self.index_register_x & 0x80) == 0x80);
-
-        false
     }
 
     // transfer X to accumulator
-    fn TXA(&mut self) -> bool {
+    fn TXA(&mut self) {
         self.accumulator = self.index_register_x;
 
         self.set_z(self.accumulator == 0x00);
         self.set_n((self.accumulator & 0x80) == 0x80);
-
-        false
     }
 
     // transfer X to stack pointer
-    fn TXS(&mut self) -> bool {
+    fn TXS(&mut self) {
         self.stack_pointer = self.index_register_x;
-    
-        false    
     }
 
     // transfer Y to accumulator
-    fn TYA(&mut self) -> bool {
+    fn TYA(&mut self) {
         self.accumulator = self.index_register_y;
 
         self.set_z(self.accumulator == 0x00);
         self.set_n((self.accumulator & 0x80) == 0x80);
-
-        false
     }
 
     // illegal opcodes
 
     // ASL oper + ORA oper
-    fn SLO(&mut self) -> bool {
-        true
-    }
+    fn SLO(&mut self) {}
 
     // Freeze the CPU
-    fn JAM(&mut self) -> bool {
-        true
+    fn JAM(&mut self) {
+        self.halted = true;
     }
 
     // AND oper + set C as ASL
-    fn ANC(&mut self) -> bool {
-        true
-    }
+    fn ANC(&mut self) {}
 
     // ROL oper + AND oper
-    fn RLA(&mut self) -> bool {
-        true
-    }
+    fn RLA(&mut self) {}
 
     // (LSE)
     // LSR oper + EOR oper
-    fn SRE(&mut self) -> bool {
-        true
-    }
+    fn SRE(&mut self) {}
 
     // (ASR)
     // AND oper + LSR
-    fn ALR(&mut self) -> bool {
-        true
-    }
+    fn ALR(&mut self) {}
 
     // ROR oper + ADC oper
-    fn RRA(&mut self) -> bool {
-        true
-    }
+    fn RRA(&mut self) {}
 
-    fn SAX(&mut self) -> bool {
-        true
-    }
+    fn SAX(&mut self) {}
 
-    fn ANE(&mut self) -> bool {
-        true
-    }
+    fn ANE(&mut self) {}
 
-    fn SHA(&mut self) -> bool {
-        true
-    }
+    fn SHA(&mut self) {}
 
-    fn SHX(&mut self) -> bool {
-        true
-    }
+    fn SHX(&mut self) {}
 
-    fn SHY(&mut self) -> bool {
-        true
-    }
+    fn SHY(&mut self) {}
 
-    fn ARR(&mut self) -> bool {
-        true
-    }
+    fn ARR(&mut self) {}
 
-    fn TAS(&mut self) -> bool {
-        true
-    }
+    fn TAS(&mut self) {}
 
-    fn LAS(&mut self) -> bool {
-        true
-    }
+    fn LAS(&mut self) {}
 
-    fn LAX(&mut self) -> bool {
-        true
-    }
+    fn LAX(&mut self) {}
 
-    fn LXA(&mut self) -> bool {
-        true
-    }
+    fn LXA(&mut self) {}
 
-    fn DCP(&mut self) -> bool {
-        true
-    }
+    fn DCP(&mut self) {}
 
-    fn SBX(&mut self) -> bool {
-        true
-    }
+    fn SBX(&mut self) {}
 
-    fn ISC(&mut self) -> bool {
-        true
-    }
+    fn ISC(&mut self) {}
 
-    fn USBC(&mut self) -> bool {
-        true
-    }
+    fn USBC(&mut self) {}
 }
+/**
+ * An entry of the decode table
+ */
 pub struct Instruction {
-    pub name: String,
-    pub human_readable_form: String,
-    pub operation: fn(&mut Processor) -> bool,
+    pub name: &'static str,
+    pub operation: fn(&mut Processor),
     pub operation_enum: Operation,
     pub addressing_mode: fn(&mut Processor) -> bool,
     pub addressing_mode_enum: AddressingMode,
+    /// Cycles taken without the page crossing and branch penalties
     pub cycles: u8,
+    /// Bytes taken by the opcode and its operand
+    pub length: u8,
+    /// Whether or not crossing a page while indexing (or taking a branch) costs extra cycles
+    pub page_cross_penalty: bool,
+    /// Whether or not the opcode is missing from the data sheet, the NOPs other than `0xEA` included
+    pub undocumented: bool,
 }
 
 impl std::fmt::Display for Instruction {
@@ -1617,292 +2780,337 @@ impl std::fmt::Display for Instruction {
 }
 
 impl Instruction {
-    pub fn new(
-        name: &str,
-        human_readable_form: &str,
-        operation: fn(&mut Processor) -> bool,
+    pub const fn new(
+        name: &'static str,
+        operation: fn(&mut Processor),
         operation_enum: Operation,
         addressing_mode: fn(&mut Processor) -> bool,
         addressing_mode_enum: AddressingMode,
         cycles: u8,
     ) -> Self {
+        let length = 1 + addressing_mode_enum.operand_length() as u8;
+        let page_cross_penalty = match addressing_mode_enum {
+            AddressingMode::REL => true,
+            // stores and read-modify-write instructions always take the extra cycle
+            AddressingMode::ABSX | AddressingMode::ABSY | AddressingMode::INDY => matches!(
+                operation_enum,
+                Operation::ADC | Operation::AND | Operation::CMP | Operation::EOR | Operation::LDA | Operation::LDX
+                    | Operation::LDY | Operation::ORA | Operation::SBC | Operation::NOP | Operation::LAX
+                    | Operation::LAS | Operation::USBC
+            ),
+            _ => false,
+        };
+        // the undocumented operations are declared after the documented ones
+        let undocumented = operation_enum as u8 >= Operation::SLO as u8;
+
         Self {
-            name: String::from(name),
-            human_readable_form: String::from(human_readable_form),
+            name,
             operation,
             operation_enum,
             addressing_mode,
             addressing_mode_enum,
             cycles,
+            length,
+            page_cross_penalty,
+            undocumented,
         }
     }
 
-    pub fn create_instructions_table() -> Vec<Instruction> {
-        let n_c_nop: u8 = 0;
-        let n_c_jam: u8 = 0;
-
-        // creating instruction set
-        vec![
-            Instruction::new(r#"BRK"#, r#""#, Processor::BRK,  Operation::BRK, Processor::IMPL, AddressingMode::IMPL, 7),
-            Instruction::new(r#"ORA"#, r#""#, Processor::ORA,  Operation::ORA, Processor::INDX, AddressingMode::INDX, 6),
-            Instruction::new(r#"JAM"#, r#""#, Processor::JAM,  Operation::JAM, Processor::IMPL, AddressingMode::IMPL, n_c_jam),
-            Instruction::new(r#"SLO"#, r#""#, Processor::SLO,  Operation::SLO, Processor::INDX, AddressingMode::INDX, 8),
-            Instruction::new(r#"NOP"#, r#""#, Processor::NOP,  Operation::NOP, Processor::ZPG,  AddressingMode::ZPG, n_c_nop),
-            Instruction::new(r#"ORA"#, r#""#, Processor::ORA,  Operation::ORA, Processor::ZPG,  AddressingMode::ZPG, 3),
-            Instruction::new(r#"ASL"#, r#""#, Processor::ASL,  Operation::ASL, Processor::ZPG,  AddressingMode::ZPG, 5),
-            Instruction::new(r#"SLO"#, r#""#, Processor::SLO,  Operation::SLO, Processor::ZPG,  AddressingMode::ZPG, 5),
-            Instruction::new(r#"PHP"#, r#""#, Processor::PHP,  Operation::PHP, Processor::IMPL, AddressingMode::IMPL, 3),
-            Instruction::new(r#"ORA"#, r#""#, Processor::ORA,  Operation::ORA, Processor::IMM,  AddressingMode::IMM, 2),
-            Instruction::new(r#"ASL"#, r#""#, Processor::ASL,  Operation::ASL, Processor::IMPL, AddressingMode::IMPL, 2),
-            Instruction::new(r#"ANC"#, r#""#, Processor::ANC,  Operation::ANC, Processor::IMM,  AddressingMode::IMM, 2),
-            Instruction::new(r#"NOP"#, r#""#, Processor::NOP,  Operation::NOP, Processor::ABS,  AddressingMode::ABS, n_c_nop),
-            Instruction::new(r#"ORA"#, r#""#, Processor::ORA,  Operation::ORA, Processor::ABS,  AddressingMode::ABS, 4),
-            Instruction::new(r#"ASL"#, r#""#, Processor::ASL,  Operation::ASL, Processor::ABS,  AddressingMode::ABS, 6),
-            Instruction::new(r#"SLO"#, r#""#, Processor::SLO,  Operation::SLO, Processor::ABS,  AddressingMode::ABS, 6),
-            Instruction::new(r#"BPL"#, r#""#, Processor::BPL,  Operation::BPL, Processor::IMPL, AddressingMode::IMPL, 2),
-            Instruction::new(r#"ORA"#, r#""#, Processor::ORA,  Operation::ORA, Processor::INDY, AddressingMode::INDY, 5),
-            Instruction::new(r#"JAM"#, r#""#, Processor::JAM,  Operation::JAM, Processor::IMPL, AddressingMode::IMPL, n_c_jam),
-            Instruction::new(r#"SLO"#, r#""#, Processor::SLO,  Operation::SLO, Processor::INDX, AddressingMode::INDX, 8),
-            Instruction::new(r#"NOP"#, r#""#, Processor::NOP,  Operation::NOP, Processor::ZPGX, AddressingMode::ZPGX, n_c_nop),
-            Instruction::new(r#"ORA"#, r#""#, Processor::ORA,  Operation::ORA, Processor::ZPGX, AddressingMode::ZPGX, 4),
-            Instruction::new(r#"ASL"#, r#""#, Processor::ASL,  Operation::ASL, Processor::ZPGX, AddressingMode::ZPGX, 6),
-            Instruction::new(r#"SLO"#, r#""#, Processor::SLO,  Operation::SLO, Processor::ZPGX, AddressingMode::ZPGX, 6),
-            Instruction::new(r#"CLC"#, r#""#, Processor::CLC,  Operation::CLC, Processor::IMPL, AddressingMode::IMPL, 2),
-            Instruction::new(r#"ORA"#, r#""#, Processor::ORA,  Operation::ORA, Processor::ABSY, AddressingMode::ABSY, 4),
-            Instruction::new(r#"NOP"#, r#""#, Processor::NOP,  Operation::NOP, Processor::IMPL, AddressingMode::IMPL, n_c_nop),
-            Instruction::new(r#"SLO"#, r#""#, Processor::SLO,  Operation::SLO, Processor::ABSY, AddressingMode::ABSY, 7),
-            Instruction::new(r#"NOP"#, r#""#, Processor::NOP,  Operation::NOP, Processor::ABSX, AddressingMode::ABSX, n_c_nop),
-            Instruction::new(r#"ORA"#, r#""#, Processor::ORA,  Operation::ORA, Processor::ABSX, AddressingMode::ABSX, 4),
-            Instruction::new(r#"ASL"#, r#""#, Processor::ASL,  Operation::ASL, Processor::ABSX, AddressingMode::ABSX, 7),
-            Instruction::new(r#"SLO"#, r#""#, Processor::SLO,  Operation::SLO, Processor::ABSX, AddressingMode::ABSX, 7),
-            Instruction::new(r#"JSR"#, r#""#, Processor::JSR,  Operation::JSR, Processor::ABS,  AddressingMode::ABS, 6),
-            Instruction::new(r#"AND"#, r#""#, Processor::AND,  Operation::AND, Processor::INDX, AddressingMode::INDX, 6),
-            Instruction::new(r#"JAM"#, r#""#, Processor::JAM,  Operation::JAM, Processor::IMPL, AddressingMode::IMPL, n_c_jam),
-            Instruction::new(r#"RLA"#, r#""#, Processor::RLA,  Operation::RLA, Processor::INDX, AddressingMode::INDX, 8),
-            Instruction::new(r#"BIT"#, r#""#, Processor::BIT,  Operation::BIT, Processor::ZPG,  AddressingMode::ZPG, 3),
-            Instruction::new(r#"AND"#, r#""#, Processor::AND,  Operation::AND, Processor::ZPG,  AddressingMode::ZPG, 3),
-            Instruction::new(r#"ROL"#, r#""#, Processor::ROL,  Operation::ROL, Processor::ZPG,  AddressingMode::ZPG, 5),
-            Instruction::new(r#"RLA"#, r#""#, Processor::RLA,  Operation::RLA, Processor::ZPG,  AddressingMode::ZPG, 5),
-            Instruction::new(r#"PLP"#, r#""#, Processor::PLP,  Operation::PLP, Processor::IMPL, AddressingMode::IMPL, 4),
-            Instruction::new(r#"AND"#, r#""#, Processor::AND,  Operation::AND, Processor::IMM,  AddressingMode::IMM, 2),
-            Instruction::new(r#"ROL"#, r#""#, Processor::ROL,  Operation::ROL, Processor::IMPL, AddressingMode::IMPL, 2),
-            Instruction::new(r#"ANC"#, r#""#, Processor::ANC,  Operation::ANC, Processor::IMM,  AddressingMode::IMM, 2),
-            Instruction::new(r#"BIT"#, r#""#, Processor::BIT,  Operation::BIT, Processor::ABS,  AddressingMode::ABS, 4),
-            Instruction::new(r#"AND"#, r#""#, Processor::AND,  Operation::AND, Processor::ABS,  AddressingMode::ABS, 4),
-            Instruction::new(r#"ROL"#, r#""#, Processor::ROL,  Operation::ROL, Processor::ABS,  AddressingMode::ABS, 6),
-            Instruction::new(r#"RLA"#, r#""#, Processor::RLA,  Operation::RLA, Processor::ABS,  AddressingMode::ABS, 6),
-            Instruction::new(r#"BMI"#, r#""#, Processor::BMI,  Operation::BMI, Processor::REL,  AddressingMode::REL, 2),
-            Instruction::new(r#"AND"#, r#""#, Processor::AND,  Operation::AND, Processor::INDY, AddressingMode::INDY, 5),
-            Instruction::new(r#"JAM"#, r#""#, Processor::JAM,  Operation::JAM, Processor::IMPL, AddressingMode::IMPL, n_c_jam),
-            Instruction::new(r#"RLA"#, r#""#, Processor::RLA,  Operation::RLA, Processor::INDY, AddressingMode::INDY, 8),
-            Instruction::new(r#"NOP"#, r#""#, Processor::NOP,  Operation::NOP, Processor::ZPGX, AddressingMode::ZPGX, n_c_nop),
-            Instruction::new(r#"AND"#, r#""#, Processor::AND,  Operation::AND, Processor::ZPGX, AddressingMode::ZPGX, 4),
-            Instruction::new(r#"ROL"#, r#""#, Processor::ROL,  Operation::ROL, Processor::ZPGX, AddressingMode::ZPGX, 6),
-            Instruction::new(r#"RLA"#, r#""#, Processor::RLA,  Operation::RLA, Processor::ZPGX, AddressingMode::ZPGX, 6),
-            Instruction::new(r#"SEC"#, r#""#, Processor::SEC,  Operation::SEC, Processor::IMPL, AddressingMode::IMPL, 2),
-            Instruction::new(r#"AND"#, r#""#, Processor::AND,  Operation::AND, Processor::ABSY, AddressingMode::ABSY, 4),
-            Instruction::new(r#"NOP"#, r#""#, Processor::NOP,  Operation::NOP, Processor::IMPL, AddressingMode::IMPL, n_c_nop),
-            Instruction::new(r#"RLA"#, r#""#, Processor::RLA,  Operation::RLA, Processor::ABSY, AddressingMode::ABSY, 7),
-            Instruction::new(r#"NOP"#, r#""#, Processor::NOP,  Operation::NOP, Processor::ABSX, AddressingMode::ABSX, n_c_nop),
-            Instruction::new(r#"AND"#, r#""#, Processor::AND,  Operation::AND, Processor::ABSX, AddressingMode::ABSX, 4),
-            Instruction::new(r#"ROL"#, r#""#, Processor::ROL,  Operation::ROL, Processor::ABSX, AddressingMode::ABSX, 7),
-            Instruction::new(r#"RLA"#, r#""#, Processor::RLA,  Operation::RLA, Processor::ABSX, AddressingMode::ABSX, 7),
-            Instruction::new(r#"RTI"#, r#""#, Processor::RTI,  Operation::RTI, Processor::IMPL, AddressingMode::IMPL, 6),
-            Instruction::new(r#"EOR"#, r#""#, Processor::EOR,  Operation::EOR, Processor::INDX, AddressingMode::INDX, 6),
-            Instruction::new(r#"JAM"#, r#""#, Processor::JAM,  Operation::JAM, Processor::IMPL, AddressingMode::IMPL, n_c_jam),
-            Instruction::new(r#"SRE"#, r#""#, Processor::SRE,  Operation::SRE, Processor::INDX, AddressingMode::INDX, 8),
-            Instruction::new(r#"NOP"#, r#""#, Processor::NOP,  Operation::NOP, Processor::ZPG,  AddressingMode::ZPG, n_c_nop),
-            Instruction::new(r#"EOR"#, r#""#, Processor::EOR,  Operation::EOR, Processor::ZPG,  AddressingMode::ZPG, 3),
-            Instruction::new(r#"LSR"#, r#""#, Processor::LSR,  Operation::LSR, Processor::ZPG,  AddressingMode::ZPG, 5),
-            Instruction::new(r#"SRE"#, r#""#, Processor::SRE,  Operation::SRE, Processor::ZPG,  AddressingMode::ZPG, 5),
-            Instruction::new(r#"PHA"#, r#""#, Processor::PHA,  Operation::PHA, Processor::IMPL, AddressingMode::IMPL, 3),
-            Instruction::new(r#"EOR"#, r#""#, Processor::EOR,  Operation::EOR, Processor::IMM,  AddressingMode::IMM, 2),
-            Instruction::new(r#"LSR"#, r#""#, Processor::LSR,  Operation::LSR, Processor::IMPL, AddressingMode::IMPL, 2),
-            Instruction::new(r#"ALR"#, r#""#, Processor::ALR,  Operation::ALR, Processor::IMM,  AddressingMode::IMM, 2),
-            Instruction::new(r#"JMP"#, r#""#, Processor::JMP,  Operation::JMP, Processor::ABS,  AddressingMode::ABS, 3),
-            Instruction::new(r#"EOR"#, r#""#, Processor::EOR,  Operation::EOR, Processor::ABS,  AddressingMode::ABS, 4),
-            Instruction::new(r#"LSR"#, r#""#, Processor::LSR,  Operation::LSR, Processor::ABS,  AddressingMode::ABS, 6),
-            Instruction::new(r#"SRE"#, r#""#, Processor::SRE,  Operation::SRE, Processor::ABS,  AddressingMode::ABS, 6),
-            Instruction::new(r#"BVC"#, r#""#, Processor::BVC,  Operation::BVC, Processor::REL,  AddressingMode::REL, 2),
-            Instruction::new(r#"EOR"#, r#""#, Processor::EOR,  Operation::EOR, Processor::INDY, AddressingMode::INDY, 5),
-            Instruction::new(r#"JAM"#, r#""#, Processor::JAM,  Operation::JAM, Processor::INDY, AddressingMode::INDY, n_c_jam),
-            Instruction::new(r#"SRE"#, r#""#, Processor::SRE,  Operation::SRE, Processor::INDY, AddressingMode::INDY, 8),
-            Instruction::new(r#"NOP"#, r#""#, Processor::NOP,  Operation::NOP, Processor::ZPGX, AddressingMode::ZPGX, n_c_nop),
-            Instruction::new(r#"EOR"#, r#""#, Processor::EOR,  Operation::EOR, Processor::ZPGX, AddressingMode::ZPGX, 4),
-            Instruction::new(r#"LSR"#, r#""#, Processor::LSR,  Operation::LSR, Processor::ZPGX, AddressingMode::ZPGX, 6),
-            Instruction::new(r#"SRE"#, r#""#, Processor::SRE,  Operation::SRE, Processor::ZPGX, AddressingMode::ZPGX, 6),
-            Instruction::new(r#"CLI"#, r#""#, Processor::CLI,  Operation::CLI, Processor::IMPL, AddressingMode::IMPL, 2),
-            Instruction::new(r#"EOR"#, r#""#, Processor::EOR,  Operation::EOR, Processor::ABSY, AddressingMode::ABSY, 4),
-            Instruction::new(r#"NOP"#, r#""#, Processor::NOP,  Operation::NOP, Processor::IMPL, AddressingMode::IMPL, n_c_nop),
-            Instruction::new(r#"SRE"#, r#""#, Processor::SRE,  Operation::SRE, Processor::ABSY, AddressingMode::ABSY, 7),
-            Instruction::new(r#"NOP"#, r#""#, Processor::NOP,  Operation::NOP, Processor::ABSX, AddressingMode::ABSX, n_c_nop),
-            Instruction::new(r#"EOR"#, r#""#, Processor::EOR,  Operation::EOR, Processor::ABSX, AddressingMode::ABSX, 4),
-            Instruction::new(r#"LSR"#, r#""#, Processor::LSR,  Operation::LSR, Processor::ABSX, AddressingMode::ABSX, 7),
-            Instruction::new(r#"SRE"#, r#""#, Processor::SRE,  Operation::SRE, Processor::ABSX, AddressingMode::ABSX, 7),
-            Instruction::new(r#"RTS"#, r#""#, Processor::RTS,  Operation::RTS, Processor::IMPL, AddressingMode::IMPL, 6),
-            Instruction::new(r#"ADC"#, r#""#, Processor::ADC,  Operation::ADC, Processor::INDX, AddressingMode::INDX, 6),
-            Instruction::new(r#"JAM"#, r#""#, Processor::JAM,  Operation::JAM, Processor::INDX, AddressingMode::INDX, n_c_jam),
-            Instruction::new(r#"RRA"#, r#""#, Processor::RRA,  Operation::RRA, Processor::INDX, AddressingMode::INDX, 8),
-            Instruction::new(r#"NOP"#, r#""#, Processor::NOP,  Operation::NOP, Processor::ZPG,  AddressingMode::ZPG, n_c_nop),
-            Instruction::new(r#"ADC"#, r#""#, Processor::ADC,  Operation::ADC, Processor::ZPG,  AddressingMode::ZPG, 3),
-            Instruction::new(r#"ROR"#, r#""#, Processor::ROR,  Operation::ROR, Processor::ZPG,  AddressingMode::ZPG, 5),
-            Instruction::new(r#"RRA"#, r#""#, Processor::RRA,  Operation::RRA, Processor::ZPG,  AddressingMode::ZPG, 5),
-            Instruction::new(r#"PLA"#, r#""#, Processor::PLA,  Operation::PLA, Processor::IMPL, AddressingMode::IMPL, 4),
-            Instruction::new(r#"ADC"#, r#""#, Processor::ADC,  Operation::ADC, Processor::IMM,  AddressingMode::IMM, 2),
-            Instruction::new(r#"ROR"#, r#""#, Processor::ROR,  Operation::ROR, Processor::IMPL, AddressingMode::IMPL, 2),
-            Instruction::new(r#"ARR"#, r#""#, Processor::ARR,  Operation::ARR, Processor::IMM,  AddressingMode::IMM, 2),
-            Instruction::new(r#"JMP"#, r#""#, Processor::JMP,  Operation::JMP, Processor::IND,  AddressingMode::IND, 5),
-            Instruction::new(r#"ADC"#, r#""#, Processor::ADC,  Operation::ADC, Processor::ABS,  AddressingMode::ABS, 4),
-            Instruction::new(r#"ROR"#, r#""#, Processor::ROR,  Operation::ROR, Processor::ABS,  AddressingMode::ABS, 6),
-            Instruction::new(r#"RRA"#, r#""#, Processor::RRA,  Operation::RRA, Processor::ABS,  AddressingMode::ABS, 6),
-            Instruction::new(r#"BVS"#, r#""#, Processor::BVS,  Operation::BVS, Processor::REL,  AddressingMode::REL, 2),
-            Instruction::new(r#"ADC"#, r#""#, Processor::ADC,  Operation::ADC, Processor::INDY, AddressingMode::INDY, 5),
-            Instruction::new(r#"JAM"#, r#""#, Processor::JAM,  Operation::JAM, Processor::INDY, AddressingMode::INDY, n_c_jam),
-            Instruction::new(r#"RRA"#, r#""#, Processor::RRA,  Operation::RRA, Processor::INDY, AddressingMode::INDY, 8),
-            Instruction::new(r#"NOP"#, r#""#, Processor::NOP,  Operation::NOP, Processor::ZPGX, AddressingMode::ZPGX, n_c_nop),
-            Instruction::new(r#"ADC"#, r#""#, Processor::ADC,  Operation::ADC, Processor::ZPGX, AddressingMode::ZPGX, 4),
-            Instruction::new(r#"ROR"#, r#""#, Processor::ROR,  Operation::ROR, Processor::ZPGX, AddressingMode::ZPGX, 6),
-            Instruction::new(r#"RRA"#, r#""#, Processor::RRA,  Operation::RRA, Processor::ZPGX, AddressingMode::ZPGX, 6),
-            Instruction::new(r#"SEI"#, r#""#, Processor::SEI,  Operation::SEI, Processor::IMPL, AddressingMode::IMPL, 2),
-            Instruction::new(r#"ADC"#, r#""#, Processor::ADC,  Operation::ADC, Processor::ABSY, AddressingMode::ABSY, 4),
-            Instruction::new(r#"NOP"#, r#""#, Processor::NOP,  Operation::NOP, Processor::IMPL, AddressingMode::IMPL, n_c_nop),
-            Instruction::new(r#"RRA"#, r#""#, Processor::RRA,  Operation::RRA, Processor::ABSY, AddressingMode::ABSY, 7),
-            Instruction::new(r#"NOP"#, r#""#, Processor::NOP,  Operation::NOP, Processor::ABSX, AddressingMode::ABSX, n_c_nop),
-            Instruction::new(r#"ADC"#, r#""#, Processor::ADC,  Operation::ADC, Processor::ABSX, AddressingMode::ABSX, 4),
-            Instruction::new(r#"ROR"#, r#""#, Processor::ROR,  Operation::ROR, Processor::ABSX, AddressingMode::ABSX, 7),
-            Instruction::new(r#"RRA"#, r#""#, Processor::RRA,  Operation::RRA, Processor::ABSX, AddressingMode::ABSX, 7),
-            Instruction::new(r#"NOP"#, r#""#, Processor::NOP,  Operation::NOP, Processor::IMM,  AddressingMode::IMM, n_c_nop),
-            Instruction::new(r#"STA"#, r#""#, Processor::STA,  Operation::STA, Processor::INDX, AddressingMode::INDX, 6),
-            Instruction::new(r#"NOP"#, r#""#, Processor::NOP,  Operation::NOP, Processor::IMM,  AddressingMode::IMM, n_c_nop),
-            Instruction::new(r#"SAX"#, r#""#, Processor::SAX,  Operation::SAX, Processor::INDX, AddressingMode::INDX, 6),
-            Instruction::new(r#"STY"#, r#""#, Processor::STY,  Operation::STY, Processor::ZPG,  AddressingMode::ZPG, 3),
-            Instruction::new(r#"STA"#, r#""#, Processor::STA,  Operation::STA, Processor::ZPG,  AddressingMode::ZPG, 3),
-            Instruction::new(r#"STX"#, r#""#, Processor::STX,  Operation::STX, Processor::ZPG,  AddressingMode::ZPG, 3),
-            Instruction::new(r#"SAX"#, r#""#, Processor::SAX,  Operation::SAX, Processor::ZPG,  AddressingMode::ZPG, 3),
-            Instruction::new(r#"DEY"#, r#""#, Processor::DEY,  Operation::DEY, Processor::IMPL, AddressingMode::IMPL, 2),
-            Instruction::new(r#"NOP"#, r#""#, Processor::NOP,  Operation::NOP, Processor::IMM,  AddressingMode::IMM, n_c_nop),
-            Instruction::new(r#"TXA"#, r#""#, Processor::TXA,  Operation::TXA, Processor::IMPL, AddressingMode::IMPL, 2),
-            Instruction::new(r#"ANE"#, r#""#, Processor::ANE,  Operation::ANE, Processor::IMM,  AddressingMode::IMM, 2),
-            Instruction::new(r#"STY"#, r#""#, Processor::STY,  Operation::STY, Processor::ABS,  AddressingMode::ABS, 4),
-            Instruction::new(r#"STA"#, r#""#, Processor::STA,  Operation::STA, Processor::ABS,  AddressingMode::ABS, 4),
-            Instruction::new(r#"STX"#, r#""#, Processor::STX,  Operation::STX, Processor::ABS,  AddressingMode::ABS, 4),
-            Instruction::new(r#"SAX"#, r#""#, Processor::SAX,  Operation::SAX, Processor::ABS,  AddressingMode::ABS, 4),
-            Instruction::new(r#"BCC"#, r#""#, Processor::BCC,  Operation::BCC, Processor::REL,  AddressingMode::REL, 2),
-            Instruction::new(r#"STA"#, r#""#, Processor::STA,  Operation::STA, Processor::INDY, AddressingMode::INDY, 6),
-            Instruction::new(r#"JAM"#, r#""#, Processor::JAM,  Operation::JAM, Processor::INDY, AddressingMode::INDY, n_c_jam),
-            Instruction::new(r#"SHA"#, r#""#, Processor::SHA,  Operation::SHA, Processor::INDY, AddressingMode::INDY, 6),
-            Instruction::new(r#"STY"#, r#""#, Processor::STY,  Operation::STY, Processor::ZPGX, AddressingMode::ZPGX, 4),
-            Instruction::new(r#"STA"#, r#""#, Processor::STA,  Operation::STA, Processor::ZPGX, AddressingMode::ZPGX, 4),
-            Instruction::new(r#"STX"#, r#""#, Processor::STX,  Operation::STX, Processor::ZPGY, AddressingMode::ZPGY, 4),
-            Instruction::new(r#"SAX"#, r#""#, Processor::SAX,  Operation::SAX, Processor::ZPGY, AddressingMode::ZPGY, 4),
-            Instruction::new(r#"TYA"#, r#""#, Processor::TYA,  Operation::TYA, Processor::IMPL, AddressingMode::IMPL, 2),
-            Instruction::new(r#"STA"#, r#""#, Processor::STA,  Operation::STA, Processor::ABSY, AddressingMode::ABSY, 5),
-            Instruction::new(r#"TXS"#, r#""#, Processor::TXS,  Operation::TXS, Processor::IMPL, AddressingMode::IMPL, 2),
-            Instruction::new(r#"TAS"#, r#""#, Processor::TAS,  Operation::TAS, Processor::ABSY, AddressingMode::ABSY, 5),
-            Instruction::new(r#"SHY"#, r#""#, Processor::SHY,  Operation::SHY, Processor::ABSX, AddressingMode::ABSX, 5),
-            Instruction::new(r#"STA"#, r#""#, Processor::STA,  Operation::STA, Processor::ABSX, AddressingMode::ABSX, 5),
-            Instruction::new(r#"SHX"#, r#""#, Processor::SHX,  Operation::SHX, Processor::ABSY, AddressingMode::ABSY, 5),
-            Instruction::new(r#"SHA"#, r#""#, Processor::SHA,  Operation::SHA, Processor::ABSY, AddressingMode::ABSY, 5),
-            Instruction::new(r#"LDY"#, r#""#, Processor::LDY,  Operation::LDY, Processor::IMM,  AddressingMode::IMM, 2),
-            Instruction::new(r#"LDA"#, r#""#, Processor::LDA,  Operation::LDA, Processor::INDX, AddressingMode::INDX, 6),
-            Instruction::new(r#"LDX"#, r#""#, Processor::LDX,  Operation::LDX, Processor::IMM,  AddressingMode::IMM, 2),
-            Instruction::new(r#"LAX"#, r#""#, Processor::LAX,  Operation::LAX, Processor::INDX, AddressingMode::INDX, 6),
-            Instruction::new(r#"LDY"#, r#""#, Processor::LDY,  Operation::LDY, Processor::ZPG,  AddressingMode::ZPG, 3),
-            Instruction::new(r#"LDA"#, r#""#, Processor::LDA,  Operation::LDA, Processor::ZPG,  AddressingMode::ZPG, 3),
-            Instruction::new(r#"LDX"#, r#""#, Processor::LDX,  Operation::LDX, Processor::ZPG,  AddressingMode::ZPG, 3),
-            Instruction::new(r#"LAX"#, r#""#, Processor::LAX,  Operation::LAX, Processor::ZPG,  AddressingMode::ZPG, 3),
-            Instruction::new(r#"TAY"#, r#""#, Processor::TAY,  Operation::TAY, Processor::IMPL, AddressingMode::IMPL, 2),
-            Instruction::new(r#"LDA"#, r#""#, Processor::LDA,  Operation::LDA, Processor::IMM,  AddressingMode::IMM, 2),
-            Instruction::new(r#"TAX"#, r#""#, Processor::TAX,  Operation::TAX, Processor::IMPL, AddressingMode::IMPL, 2),
-            Instruction::new(r#"LXA"#, r#""#, Processor::LXA,  Operation::LXA, Processor::IMM,  AddressingMode::IMM, 2),
-            Instruction::new(r#"LDY"#, r#""#, Processor::LDY,  Operation::LDY, Processor::ABS,  AddressingMode::ABS, 4),
-            Instruction::new(r#"LDA"#, r#""#, Processor::LDA,  Operation::LDA, Processor::ABS,  AddressingMode::ABS, 4),
-            Instruction::new(r#"LDX"#, r#""#, Processor::LDX,  Operation::LDX, Processor::ABS,  AddressingMode::ABS, 4),
-            Instruction::new(r#"LAX"#, r#""#, Processor::LAX,  Operation::LAX, Processor::ABS,  AddressingMode::ABS, 4),
-            Instruction::new(r#"BCS"#, r#""#, Processor::BCS,  Operation::BCS, Processor::REL,  AddressingMode::REL, 2),
-            Instruction::new(r#"LDA"#, r#""#, Processor::LDA,  Operation::LDA, Processor::INDY, AddressingMode::INDY, 5),
-            Instruction::new(r#"JAM"#, r#""#, Processor::JAM,  Operation::JAM, Processor::INDY, AddressingMode::INDY, n_c_jam),
-            Instruction::new(r#"LAX"#, r#""#, Processor::LAX,  Operation::LAX, Processor::INDY, AddressingMode::INDY, 5),
-            Instruction::new(r#"LDY"#, r#""#, Processor::LDY,  Operation::LDY, Processor::ZPGX, AddressingMode::ZPGX, 4),
-            Instruction::new(r#"LDA"#, r#""#, Processor::LDA,  Operation::LDA, Processor::ZPGX, AddressingMode::ZPGX, 4),
-            Instruction::new(r#"LDX"#, r#""#, Processor::LDX,  Operation::LDX, Processor::ZPGY, AddressingMode::ZPGY, 4),
-            Instruction::new(r#"LAX"#, r#""#, Processor::LAX,  Operation::LAX, Processor::ZPGY, AddressingMode::ZPGY, 4),
-            Instruction::new(r#"CLV"#, r#""#, Processor::CLV,  Operation::CLV, Processor::IMPL, AddressingMode::IMPL, 2),
-            Instruction::new(r#"LDA"#, r#""#, Processor::LDA,  Operation::LDA, Processor::ABSY, AddressingMode::ABSY, 4),
-            Instruction::new(r#"TSX"#, r#""#, Processor::TSX,  Operation::TSX, Processor::IMPL, AddressingMode::IMPL, 2),
-            Instruction::new(r#"LAS"#, r#""#, Processor::LAS,  Operation::LAS, Processor::ABSY, AddressingMode::ABSY, 4),
-            Instruction::new(r#"LDY"#, r#""#, Processor::LDY,  Operation::LDY, Processor::ABSX, AddressingMode::ABSX, 4),
-            Instruction::new(r#"LDA"#, r#""#, Processor::LDA,  Operation::LDA, Processor::ABSX, AddressingMode::ABSX, 4),
-            Instruction::new(r#"LDX"#, r#""#, Processor::LDX,  Operation::LDX, Processor::ABSY, AddressingMode::ABSY, 4),
-            Instruction::new(r#"LAX"#, r#""#, Processor::LAX,  Operation::LAX, Processor::ABSY, AddressingMode::ABSY, 4),
-            Instruction::new(r#"CPY"#, r#""#, Processor::CPY,  Operation::CPY, Processor::IMM,  AddressingMode::IMM, 2),
-            Instruction::new(r#"CMP"#, r#""#, Processor::CMP,  Operation::CMP, Processor::INDX, AddressingMode::INDX, 6),
-            Instruction::new(r#"NOP"#, r#""#, Processor::NOP,  Operation::NOP, Processor::IMM,  AddressingMode::IMM, n_c_nop),
-            Instruction::new(r#"DCP"#, r#""#, Processor::DCP,  Operation::DCP, Processor::INDX, AddressingMode::INDX, 8),
-            Instruction::new(r#"CPY"#, r#""#, Processor::CPY,  Operation::CPY, Processor::ZPG,  AddressingMode::ZPG, 3),
-            Instruction::new(r#"CMP"#, r#""#, Processor::CMP,  Operation::CMP, Processor::ZPG,  AddressingMode::ZPG, 3),
-            Instruction::new(r#"DEC"#, r#""#, Processor::DEC,  Operation::DEC, Processor::ZPG,  AddressingMode::ZPG, 5),
-            Instruction::new(r#"DCP"#, r#""#, Processor::DCP,  Operation::DCP, Processor::ZPG,  AddressingMode::ZPG, 5),
-            Instruction::new(r#"INY"#, r#""#, Processor::INY,  Operation::INY, Processor::IMPL, AddressingMode::IMPL, 2),
-            Instruction::new(r#"CMP"#, r#""#, Processor::CMP,  Operation::CMP, Processor::IMM,  AddressingMode::IMM, 2),
-            Instruction::new(r#"DEX"#, r#""#, Processor::DEX,  Operation::DEX, Processor::IMPL, AddressingMode::IMPL, 2),
-            Instruction::new(r#"SBX"#, r#""#, Processor::SBX,  Operation::SBX, Processor::IMM,  AddressingMode::IMM, 2),
-            Instruction::new(r#"CPY"#, r#""#, Processor::CPY,  Operation::CPY, Processor::ABS,  AddressingMode::ABS, 4),
-            Instruction::new(r#"CMP"#, r#""#, Processor::CMP,  Operation::CMP, Processor::ABS,  AddressingMode::ABS, 4),
-            Instruction::new(r#"DEC"#, r#""#, Processor::DEC,  Operation::DEC, Processor::ABS,  AddressingMode::ABS, 6),
-            Instruction::new(r#"DCP"#, r#""#, Processor::DCP,  Operation::DCP, Processor::ABS,  AddressingMode::ABS, 6),
-            Instruction::new(r#"BNE"#, r#""#, Processor::BNE,  Operation::BNE, Processor::REL,  AddressingMode::REL, 2),
-            Instruction::new(r#"CMP"#, r#""#, Processor::CMP,  Operation::CMP, Processor::INDY, AddressingMode::INDY, 5),
-            Instruction::new(r#"JAM"#, r#""#, Processor::JAM,  Operation::JAM, Processor::INDY, AddressingMode::INDY, n_c_jam),
-            Instruction::new(r#"DCP"#, r#""#, Processor::DCP,  Operation::DCP, Processor::INDY, AddressingMode::INDY, 8),
-            Instruction::new(r#"NOP"#, r#""#, Processor::NOP,  Operation::NOP, Processor::ZPGX, AddressingMode::ZPGX, n_c_nop),
-            Instruction::new(r#"CMP"#, r#""#, Processor::CMP,  Operation::CMP, Processor::ZPGX, AddressingMode::ZPGX, 4),
-            Instruction::new(r#"DEC"#, r#""#, Processor::DEC,  Operation::DEC, Processor::ZPGX, AddressingMode::ZPGX, 6),
-            Instruction::new(r#"DCP"#, r#""#, Processor::DCP,  Operation::DCP, Processor::ZPGX, AddressingMode::ZPGX, 6),
-            Instruction::new(r#"CLD"#, r#""#, Processor::CLD,  Operation::CLD, Processor::IMPL, AddressingMode::IMPL, 2),
-            Instruction::new(r#"CMP"#, r#""#, Processor::CMP,  Operation::CMP, Processor::ABSY, AddressingMode::ABSY, 4),
-            Instruction::new(r#"NOP"#, r#""#, Processor::NOP,  Operation::NOP, Processor::IMPL, AddressingMode::IMPL, n_c_nop),
-            Instruction::new(r#"DCP"#, r#""#, Processor::DCP,  Operation::DCP, Processor::ABSY, AddressingMode::ABSY, 7),
-            Instruction::new(r#"NOP"#, r#""#, Processor::NOP,  Operation::NOP, Processor::ABSX, AddressingMode::ABSX, n_c_nop),
-            Instruction::new(r#"CMP"#, r#""#, Processor::CMP,  Operation::CMP, Processor::ABSX, AddressingMode::ABSX, 4),
-            Instruction::new(r#"DEC"#, r#""#, Processor::DEC,  Operation::DEC, Processor::ABSX, AddressingMode::ABSX, 7),
-            Instruction::new(r#"DCP"#, r#""#, Processor::DCP,  Operation::DCP, Processor::ABSX, AddressingMode::ABSX, 7),
-            Instruction::new(r#"CPX"#, r#""#, Processor::CPX,  Operation::CPX, Processor::IMM,  AddressingMode::IMM, 2),
-            Instruction::new(r#"SBC"#, r#""#, Processor::SBC,  Operation::SBC, Processor::INDX, AddressingMode::INDX, 6),
-            Instruction::new(r#"NOP"#, r#""#, Processor::NOP,  Operation::NOP, Processor::IMM,  AddressingMode::IMM, n_c_nop),
-            Instruction::new(r#"ISC"#, r#""#, Processor::ISC,  Operation::ISC, Processor::INDX, AddressingMode::INDX, 8),
-            Instruction::new(r#"CPX"#, r#""#, Processor::CPX,  Operation::CPX, Processor::ZPG,  AddressingMode::ZPG, 3),
-            Instruction::new(r#"SBC"#, r#""#, Processor::SBC,  Operation::SBC, Processor::ZPG,  AddressingMode::ZPG, 3),
-            Instruction::new(r#"INC"#, r#""#, Processor::INC,  Operation::INC, Processor::ZPG,  AddressingMode::ZPG, 5),
-            Instruction::new(r#"ISC"#, r#""#, Processor::ISC,  Operation::ISC, Processor::ZPG,  AddressingMode::ZPG, 5),
-            Instruction::new(r#"INX"#, r#""#, Processor::INX,  Operation::INX, Processor::IMPL, AddressingMode::IMPL, 2),
-            Instruction::new(r#"SBC"#, r#""#, Processor::SBC,  Operation::SBC, Processor::IMM,  AddressingMode::IMM, 2),
-            Instruction::new(r#"NOP"#, r#""#, Processor::NOP,  Operation::NOP, Processor::IMPL, AddressingMode::IMPL, n_c_nop),
-            Instruction::new(r#"USBC"#, r#""#, Processor::USBC, Operation::USBC, Processor::IMM,AddressingMode::IMM, 2),
-            Instruction::new(r#"CPX"#, r#""#, Processor::CPX,  Operation::CPX, Processor::ABS,  AddressingMode::ABS, 4),
-            Instruction::new(r#"SBC"#, r#""#, Processor::SBC,  Operation::SBC, Processor::ABS,  AddressingMode::ABS, 4),
-            Instruction::new(r#"INC"#, r#""#, Processor::INC,  Operation::INC, Processor::ABS,  AddressingMode::ABS, 6),
-            Instruction::new(r#"ISC"#, r#""#, Processor::ISC,  Operation::ISC, Processor::ABS,  AddressingMode::ABS, 6),
-            Instruction::new(r#"BEQ"#, r#""#, Processor::BEQ,  Operation::BEQ, Processor::REL,  AddressingMode::REL, 2),
-            Instruction::new(r#"SBC"#, r#""#, Processor::SBC,  Operation::SBC, Processor::INDY, AddressingMode::INDY, 5),
-            Instruction::new(r#"JAM"#, r#""#, Processor::JAM,  Operation::JAM, Processor::INDY, AddressingMode::INDY, n_c_jam),
-            Instruction::new(r#"ISC"#, r#""#, Processor::ISC,  Operation::ISC, Processor::INDY, AddressingMode::INDY, 8),
-            Instruction::new(r#"NOP"#, r#""#, Processor::NOP,  Operation::NOP, Processor::ZPGX, AddressingMode::ZPGX, n_c_nop),
-            Instruction::new(r#"SBC"#, r#""#, Processor::SBC,  Operation::SBC, Processor::ZPGX, AddressingMode::ZPGX, 4),
-            Instruction::new(r#"INC"#, r#""#, Processor::INC,  Operation::INC, Processor::ZPGX, AddressingMode::ZPGX, 6),
-            Instruction::new(r#"ISC"#, r#""#, Processor::ISC,  Operation::ISC, Processor::ZPGX, AddressingMode::ZPGX, 6),
-            Instruction::new(r#"SED"#, r#""#, Processor::SED,  Operation::SED, Processor::IMPL, AddressingMode::IMPL, 2),
-            Instruction::new(r#"SBC"#, r#""#, Processor::SBC,  Operation::SBC, Processor::ABSY, AddressingMode::ABSY, 4),
-            Instruction::new(r#"NOP"#, r#""#, Processor::NOP,  Operation::NOP, Processor::IMPL, AddressingMode::IMPL, n_c_nop),
-            Instruction::new(r#"ISC"#, r#""#, Processor::ISC,  Operation::ISC, Processor::ABSY, AddressingMode::ABSY, 7),
-            Instruction::new(r#"NOP"#, r#""#, Processor::NOP,  Operation::NOP, Processor::ABSX, AddressingMode::ABSX, n_c_nop),
-            Instruction::new(r#"SBC"#, r#""#, Processor::SBC,  Operation::SBC, Processor::ABSX, AddressingMode::ABSX, 4),
-            Instruction::new(r#"INC"#, r#""#, Processor::INC,  Operation::INC, Processor::ABSX, AddressingMode::ABSX, 7),
-            Instruction::new(r#"ISC"#, r#""#, Processor::ISC,  Operation::ISC, Processor::ABSX, AddressingMode::ABSX, 7),
-        ]
+    /**
+     * Marks a decode table entry as undocumented, for the opcodes sharing their operation with a documented one
+     */
+    pub const fn undocumented(self) -> Self {
+        Self { undocumented: true, ..self }
+    }
+
+    /**
+     * Returns the decode table entry of the opcode
+     */
+    pub fn decode(opcode: u8) -> &'static Instruction {
+        &INSTRUCTIONS[opcode as usize]
     }
 }
 
+/**
+ * The decode table, indexed by opcode
+ *
+ * Built at compile time and shared by the executor, the disassembler and the analysis tools.
+ */
+pub static INSTRUCTIONS: [Instruction; 256] = [
+    // 0x00
+    Instruction::new("BRK", Processor::BRK, Operation::BRK, Processor::IMPL, AddressingMode::IMPL, 7),
+    Instruction::new("ORA", Processor::ORA, Operation::ORA, Processor::INDX, AddressingMode::INDX, 6),
+    Instruction::new("JAM", Processor::JAM, Operation::JAM, Processor::IMPL, AddressingMode::IMPL, 2),
+    Instruction::new("SLO", Processor::SLO, Operation::SLO, Processor::INDX, AddressingMode::INDX, 8),
+    Instruction::new("NOP", Processor::NOP, Operation::NOP, Processor::ZPG, AddressingMode::ZPG, 3).undocumented(),
+    Instruction::new("ORA", Processor::ORA, Operation::ORA, Processor::ZPG, AddressingMode::ZPG, 3),
+    Instruction::new("ASL", Processor::ASL, Operation::ASL, Processor::ZPG, AddressingMode::ZPG, 5),
+    Instruction::new("SLO", Processor::SLO, Operation::SLO, Processor::ZPG, AddressingMode::ZPG, 5),
+    Instruction::new("PHP", Processor::PHP, Operation::PHP, Processor::IMPL, AddressingMode::IMPL, 3),
+    Instruction::new("ORA", Processor::ORA, Operation::ORA, Processor::IMM, AddressingMode::IMM, 2),
+    Instruction::new("ASL", Processor::ASL, Operation::ASL, Processor::IMPL, AddressingMode::IMPL, 2),
+    Instruction::new("ANC", Processor::ANC, Operation::ANC, Processor::IMM, AddressingMode::IMM, 2),
+    Instruction::new("NOP", Processor::NOP, Operation::NOP, Processor::ABS, AddressingMode::ABS, 4).undocumented(),
+    Instruction::new("ORA", Processor::ORA, Operation::ORA, Processor::ABS, AddressingMode::ABS, 4),
+    Instruction::new("ASL", Processor::ASL, Operation::ASL, Processor::ABS, AddressingMode::ABS, 6),
+    Instruction::new("SLO", Processor::SLO, Operation::SLO, Processor::ABS, AddressingMode::ABS, 6),
+    // 0x10
+    Instruction::new("BPL", Processor::BPL, Operation::BPL, Processor::REL, AddressingMode::REL, 2),
+    Instruction::new("ORA", Processor::ORA, Operation::ORA, Processor::INDY, AddressingMode::INDY, 5),
+    Instruction::new("JAM", Processor::JAM, Operation::JAM, Processor::IMPL, AddressingMode::IMPL, 2),
+    Instruction::new("SLO", Processor::SLO, Operation::SLO, Processor::INDY, AddressingMode::INDY, 8),
+    Instruction::new("NOP", Processor::NOP, Operation::NOP, Processor::ZPGX, AddressingMode::ZPGX, 4).undocumented(),
+    Instruction::new("ORA", Processor::ORA, Operation::ORA, Processor::ZPGX, AddressingMode::ZPGX, 4),
+    Instruction::new("ASL", Processor::ASL, Operation::ASL, Processor::ZPGX, AddressingMode::ZPGX, 6),
+    Instruction::new("SLO", Processor::SLO, Operation::SLO, Processor::ZPGX, AddressingMode::ZPGX, 6),
+    Instruction::new("CLC", Processor::CLC, Operation::CLC, Processor::IMPL, AddressingMode::IMPL, 2),
+    Instruction::new("ORA", Processor::ORA, Operation::ORA, Processor::ABSY, AddressingMode::ABSY, 4),
+    Instruction::new("NOP", Processor::NOP, Operation::NOP, Processor::IMPL, AddressingMode::IMPL, 2).undocumented(),
+    Instruction::new("SLO", Processor::SLO, Operation::SLO, Processor::ABSY, AddressingMode::ABSY, 7),
+    Instruction::new("NOP", Processor::NOP, Operation::NOP, Processor::ABSX, AddressingMode::ABSX, 4).undocumented(),
+    Instruction::new("ORA", Processor::ORA, Operation::ORA, Processor::ABSX, AddressingMode::ABSX, 4),
+    Instruction::new("ASL", Processor::ASL, Operation::ASL, Processor::ABSX, AddressingMode::ABSX, 7),
+    Instruction::new("SLO", Processor::SLO, Operation::SLO, Processor::ABSX, AddressingMode::ABSX, 7),
+    // 0x20
+    Instruction::new("JSR", Processor::JSR, Operation::JSR, Processor::ABS, AddressingMode::ABS, 6),
+    Instruction::new("AND", Processor::AND, Operation::AND, Processor::INDX, AddressingMode::INDX, 6),
+    Instruction::new("JAM", Processor::JAM, Operation::JAM, Processor::IMPL, AddressingMode::IMPL, 2),
+    Instruction::new("RLA", Processor::RLA, Operation::RLA, Processor::INDX, AddressingMode::INDX, 8),
+    Instruction::new("BIT", Processor::BIT, Operation::BIT, Processor::ZPG, AddressingMode::ZPG, 3),
+    Instruction::new("AND", Processor::AND, Operation::AND, Processor::ZPG, AddressingMode::ZPG, 3),
+    Instruction::new("ROL", Processor::ROL, Operation::ROL, Processor::ZPG, AddressingMode::ZPG, 5),
+    Instruction::new("RLA", Processor::RLA, Operation::RLA, Processor::ZPG, AddressingMode::ZPG, 5),
+    Instruction::new("PLP", Processor::PLP, Operation::PLP, Processor::IMPL, AddressingMode::IMPL, 4),
+    Instruction::new("AND", Processor::AND, Operation::AND, Processor::IMM, AddressingMode::IMM, 2),
+    Instruction::new("ROL", Processor::ROL, Operation::ROL, Processor::IMPL, AddressingMode::IMPL, 2),
+    Instruction::new("ANC", Processor::ANC, Operation::ANC, Processor::IMM, AddressingMode::IMM, 2),
+    Instruction::new("BIT", Processor::BIT, Operation::BIT, Processor::ABS, AddressingMode::ABS, 4),
+    Instruction::new("AND", Processor::AND, Operation::AND, Processor::ABS, AddressingMode::ABS, 4),
+    Instruction::new("ROL", Processor::ROL, Operation::ROL, Processor::ABS, AddressingMode::ABS, 6),
+    Instruction::new("RLA", Processor::RLA, Operation::RLA, Processor::ABS, AddressingMode::ABS, 6),
+    // 0x30
+    Instruction::new("BMI", Processor::BMI, Operation::BMI, Processor::REL, AddressingMode::REL, 2),
+    Instruction::new("AND", Processor::AND, Operation::AND, Processor::INDY, AddressingMode::INDY, 5),
+    Instruction::new("JAM", Processor::JAM, Operation::JAM, Processor::IMPL, AddressingMode::IMPL, 2),
+    Instruction::new("RLA", Processor::RLA, Operation::RLA, Processor::INDY, AddressingMode::INDY, 8),
+    Instruction::new("NOP", Processor::NOP, Operation::NOP, Processor::ZPGX, AddressingMode::ZPGX, 4).undocumented(),
+    Instruction::new("AND", Processor::AND, Operation::AND, Processor::ZPGX, AddressingMode::ZPGX, 4),
+    Instruction::new("ROL", Processor::ROL, Operation::ROL, Processor::ZPGX, AddressingMode::ZPGX, 6),
+    Instruction::new("RLA", Processor::RLA, Operation::RLA, Processor::ZPGX, AddressingMode::ZPGX, 6),
+    Instruction::new("SEC", Processor::SEC, Operation::SEC, Processor::IMPL, AddressingMode::IMPL, 2),
+    Instruction::new("AND", Processor::AND, Operation::AND, Processor::ABSY, AddressingMode::ABSY, 4),
+    Instruction::new("NOP", Processor::NOP, Operation::NOP, Processor::IMPL, AddressingMode::IMPL, 2).undocumented(),
+    Instruction::new("RLA", Processor::RLA, Operation::RLA, Processor::ABSY, AddressingMode::ABSY, 7),
+    Instruction::new("NOP", Processor::NOP, Operation::NOP, Processor::ABSX, AddressingMode::ABSX, 4).undocumented(),
+    Instruction::new("AND", Processor::AND, Operation::AND, Processor::ABSX, AddressingMode::ABSX, 4),
+    Instruction::new("ROL", Processor::ROL, Operation::ROL, Processor::ABSX, AddressingMode::ABSX, 7),
+    Instruction::new("RLA", Processor::RLA, Operation::RLA, Processor::ABSX, AddressingMode::ABSX, 7),
+    // 0x40
+    Instruction::new("RTI", Processor::RTI, Operation::RTI, Processor::IMPL, AddressingMode::IMPL, 6),
+    Instruction::new("EOR", Processor::EOR, Operation::EOR, Processor::INDX, AddressingMode::INDX, 6),
+    Instruction::new("JAM", Processor::JAM, Operation::JAM, Processor::IMPL, AddressingMode::IMPL, 2),
+    Instruction::new("SRE", Processor::SRE, Operation::SRE, Processor::INDX, AddressingMode::INDX, 8),
+    Instruction::new("NOP", Processor::NOP, Operation::NOP, Processor::ZPG, AddressingMode::ZPG, 3).undocumented(),
+    Instruction::new("EOR", Processor::EOR, Operation::EOR, Processor::ZPG, AddressingMode::ZPG, 3),
+    Instruction::new("LSR", Processor::LSR, Operation::LSR, Processor::ZPG, AddressingMode::ZPG, 5),
+    Instruction::new("SRE", Processor::SRE, Operation::SRE, Processor::ZPG, AddressingMode::ZPG, 5),
+    Instruction::new("PHA", Processor::PHA, Operation::PHA, Processor::IMPL, AddressingMode::IMPL, 3),
+    Instruction::new("EOR", Processor::EOR, Operation::EOR, Processor::IMM, AddressingMode::IMM, 2),
+    Instruction::new("LSR", Processor::LSR, Operation::LSR, Processor::IMPL, AddressingMode::IMPL, 2),
+    Instruction::new("ALR", Processor::ALR, Operation::ALR, Processor::IMM, AddressingMode::IMM, 2),
+    Instruction::new("JMP", Processor::JMP, Operation::JMP, Processor::ABS, AddressingMode::ABS, 3),
+    Instruction::new("EOR", Processor::EOR, Operation::EOR, Processor::ABS, AddressingMode::ABS, 4),
+    Instruction::new("LSR", Processor::LSR, Operation::LSR, Processor::ABS, AddressingMode::ABS, 6),
+    Instruction::new("SRE", Processor::SRE, Operation::SRE, Processor::ABS, AddressingMode::ABS, 6),
+    // 0x50
+    Instruction::new("BVC", Processor::BVC, Operation::BVC, Processor::REL, AddressingMode::REL, 2),
+    Instruction::new("EOR", Processor::EOR, Operation::EOR, Processor::INDY, AddressingMode::INDY, 5),
+    Instruction::new("JAM", Processor::JAM, Operation::JAM, Processor::IMPL, AddressingMode::IMPL, 2),
+    Instruction::new("SRE", Processor::SRE, Operation::SRE, Processor::INDY, AddressingMode::INDY, 8),
+    Instruction::new("NOP", Processor::NOP, Operation::NOP, Processor::ZPGX, AddressingMode::ZPGX, 4).undocumented(),
+    Instruction::new("EOR", Processor::EOR, Operation::EOR, Processor::ZPGX, AddressingMode::ZPGX, 4),
+    Instruction::new("LSR", Processor::LSR, Operation::LSR, Processor::ZPGX, AddressingMode::ZPGX, 6),
+    Instruction::new("SRE", Processor::SRE, Operation::SRE, Processor::ZPGX, AddressingMode::ZPGX, 6),
+    Instruction::new("CLI", Processor::CLI, Operation::CLI, Processor::IMPL, AddressingMode::IMPL, 2),
+    Instruction::new("EOR", Processor::EOR, Operation::EOR, Processor::ABSY, AddressingMode::ABSY, 4),
+    Instruction::new("NOP", Processor::NOP, Operation::NOP, Processor::IMPL, AddressingMode::IMPL, 2).undocumented(),
+    Instruction::new("SRE", Processor::SRE, Operation::SRE, Processor::ABSY, AddressingMode::ABSY, 7),
+    Instruction::new("NOP", Processor::NOP, Operation::NOP, Processor::ABSX, AddressingMode::ABSX, 4).undocumented(),
+    Instruction::new("EOR", Processor::EOR, Operation::EOR, Processor::ABSX, AddressingMode::ABSX, 4),
+    Instruction::new("LSR", Processor::LSR, Operation::LSR, Processor::ABSX, AddressingMode::ABSX, 7),
+    Instruction::new("SRE", Processor::SRE, Operation::SRE, Processor::ABSX, AddressingMode::ABSX, 7),
+    // 0x60
+    Instruction::new("RTS", Processor::RTS, Operation::RTS, Processor::IMPL, AddressingMode::IMPL, 6),
+    Instruction::new("ADC", Processor::ADC, Operation::ADC, Processor::INDX, AddressingMode::INDX, 6),
+    Instruction::new("JAM", Processor::JAM, Operation::JAM, Processor::IMPL, AddressingMode::IMPL, 2),
+    Instruction::new("RRA", Processor::RRA, Operation::RRA, Processor::INDX, AddressingMode::INDX, 8),
+    Instruction::new("NOP", Processor::NOP, Operation::NOP, Processor::ZPG, AddressingMode::ZPG, 3).undocumented(),
+    Instruction::new("ADC", Processor::ADC, Operation::ADC, Processor::ZPG, AddressingMode::ZPG, 3),
+    Instruction::new("ROR", Processor::ROR, Operation::ROR, Processor::ZPG, AddressingMode::ZPG, 5),
+    Instruction::new("RRA", Processor::RRA, Operation::RRA, Processor::ZPG, AddressingMode::ZPG, 5),
+    Instruction::new("PLA", Processor::PLA, Operation::PLA, Processor::IMPL, AddressingMode::IMPL, 4),
+    Instruction::new("ADC", Processor::ADC, Operation::ADC, Processor::IMM, AddressingMode::IMM, 2),
+    Instruction::new("ROR", Processor::ROR, Operation::ROR, Processor::IMPL, AddressingMode::IMPL, 2),
+    Instruction::new("ARR", Processor::ARR, Operation::ARR, Processor::IMM, AddressingMode::IMM, 2),
+    Instruction::new("JMP", Processor::JMP, Operation::JMP, Processor::IND, AddressingMode::IND, 5),
+    Instruction::new("ADC", Processor::ADC, Operation::ADC, Processor::ABS, AddressingMode::ABS, 4),
+    Instruction::new("ROR", Processor::ROR, Operation::ROR, Processor::ABS, AddressingMode::ABS, 6),
+    Instruction::new("RRA", Processor::RRA, Operation::RRA, Processor::ABS, AddressingMode::ABS, 6),
+    // 0x70
+    Instruction::new("BVS", Processor::BVS, Operation::BVS, Processor::REL, AddressingMode::REL, 2),
+    Instruction::new("ADC", Processor::ADC, Operation::ADC, Processor::INDY, AddressingMode::INDY, 5),
+    Instruction::new("JAM", Processor::JAM, Operation::JAM, Processor::IMPL, AddressingMode::IMPL, 2),
+    Instruction::new("RRA", Processor::RRA, Operation::RRA, Processor::INDY, AddressingMode::INDY, 8),
+    Instruction::new("NOP", Processor::NOP, Operation::NOP, Processor::ZPGX, AddressingMode::ZPGX, 4).undocumented(),
+    Instruction::new("ADC", Processor::ADC, Operation::ADC, Processor::ZPGX, AddressingMode::ZPGX, 4),
+    Instruction::new("ROR", Processor::ROR, Operation::ROR, Processor::ZPGX, AddressingMode::ZPGX, 6),
+    Instruction::new("RRA", Processor::RRA, Operation::RRA, Processor::ZPGX, AddressingMode::ZPGX, 6),
+    Instruction::new("SEI", Processor::SEI, Operation::SEI, Processor::IMPL, AddressingMode::IMPL, 2),
+    Instruction::new("ADC", Processor::ADC, Operation::ADC, Processor::ABSY, AddressingMode::ABSY, 4),
+    Instruction::new("NOP", Processor::NOP, Operation::NOP, Processor::IMPL, AddressingMode::IMPL, 2).undocumented(),
+    Instruction::new("RRA", Processor::RRA, Operation::RRA, Processor::ABSY, AddressingMode::ABSY, 7),
+    Instruction::new("NOP", Processor::NOP, Operation::NOP, Processor::ABSX, AddressingMode::ABSX, 4).undocumented(),
+    Instruction::new("ADC", Processor::ADC, Operation::ADC, Processor::ABSX, AddressingMode::ABSX, 4),
+    Instruction::new("ROR", Processor::ROR, Operation::ROR, Processor::ABSX, AddressingMode::ABSX, 7),
+    Instruction::new("RRA", Processor::RRA, Operation::RRA, Processor::ABSX, AddressingMode::ABSX, 7),
+    // 0x80
+    Instruction::new("NOP", Processor::NOP, Operation::NOP, Processor::IMM, AddressingMode::IMM, 2).undocumented(),
+    Instruction::new("STA", Processor::STA, Operation::STA, Processor::INDX, AddressingMode::INDX, 6),
+    Instruction::new("NOP", Processor::NOP, Operation::NOP, Processor::IMM, AddressingMode::IMM, 2).undocumented(),
+    Instruction::new("SAX", Processor::SAX, Operation::SAX, Processor::INDX, AddressingMode::INDX, 6),
+    Instruction::new("STY", Processor::STY, Operation::STY, Processor::ZPG, AddressingMode::ZPG, 3),
+    Instruction::new("STA", Processor::STA, Operation::STA, Processor::ZPG, AddressingMode::ZPG, 3),
+    Instruction::new("STX", Processor::STX, Operation::STX, Processor::ZPG, AddressingMode::ZPG, 3),
+    Instruction::new("SAX", Processor::SAX, Operation::SAX, Processor::ZPG, AddressingMode::ZPG, 3),
+    Instruction::new("DEY", Processor::DEY, Operation::DEY, Processor::IMPL, AddressingMode::IMPL, 2),
+    Instruction::new("NOP", Processor::NOP, Operation::NOP, Processor::IMM, AddressingMode::IMM, 2).undocumented(),
+    Instruction::new("TXA", Processor::TXA, Operation::TXA, Processor::IMPL, AddressingMode::IMPL, 2),
+    Instruction::new("ANE", Processor::ANE, Operation::ANE, Processor::IMM, AddressingMode::IMM, 2),
+    Instruction::new("STY", Processor::STY, Operation::STY, Processor::ABS, AddressingMode::ABS, 4),
+    Instruction::new("STA", Processor::STA, Operation::STA, Processor::ABS, AddressingMode::ABS, 4),
+    Instruction::new("STX", Processor::STX, Operation::STX, Processor::ABS, AddressingMode::ABS, 4),
+    Instruction::new("SAX", Processor::SAX, Operation::SAX, Processor::ABS, AddressingMode::ABS, 4),
+    // 0x90
+    Instruction::new("BCC", Processor::BCC, Operation::BCC, Processor::REL, AddressingMode::REL, 2),
+    Instruction::new("STA", Processor::STA, Operation::STA, Processor::INDY, AddressingMode::INDY, 6),
+    Instruction::new("JAM", Processor::JAM, Operation::JAM, Processor::IMPL, AddressingMode::IMPL, 2),
+    Instruction::new("SHA", Processor::SHA, Operation::SHA, Processor::INDY, AddressingMode::INDY, 6),
+    Instruction::new("STY", Processor::STY, Operation::STY, Processor::ZPGX, AddressingMode::ZPGX, 4),
+    Instruction::new("STA", Processor::STA, Operation::STA, Processor::ZPGX, AddressingMode::ZPGX, 4),
+    Instruction::new("STX", Processor::STX, Operation::STX, Processor::ZPGY, AddressingMode::ZPGY, 4),
+    Instruction::new("SAX", Processor::SAX, Operation::SAX, Processor::ZPGY, AddressingMode::ZPGY, 4),
+    Instruction::new("TYA", Processor::TYA, Operation::TYA, Processor::IMPL, AddressingMode::IMPL, 2),
+    Instruction::new("STA", Processor::STA, Operation::STA, Processor::ABSY, AddressingMode::ABSY, 5),
+    Instruction::new("TXS", Processor::TXS, Operation::TXS, Processor::IMPL, AddressingMode::IMPL, 2),
+    Instruction::new("TAS", Processor::TAS, Operation::TAS, Processor::ABSY, AddressingMode::ABSY, 5),
+    Instruction::new("SHY", Processor::SHY, Operation::SHY, Processor::ABSX, AddressingMode::ABSX, 5),
+    Instruction::new("STA", Processor::STA, Operation::STA, Processor::ABSX, AddressingMode::ABSX, 5),
+    Instruction::new("SHX", Processor::SHX, Operation::SHX, Processor::ABSY, AddressingMode::ABSY, 5),
+    Instruction::new("SHA", Processor::SHA, Operation::SHA, Processor::ABSY, AddressingMode::ABSY, 5),
+    // 0xA0
+    Instruction::new("LDY", Processor::LDY, Operation::LDY, Processor::IMM, AddressingMode::IMM, 2),
+    Instruction::new("LDA", Processor::LDA, Operation::LDA, Processor::INDX, AddressingMode::INDX, 6),
+    Instruction::new("LDX", Processor::LDX, Operation::LDX, Processor::IMM, AddressingMode::IMM, 2),
+    Instruction::new("LAX", Processor::LAX, Operation::LAX, Processor::INDX, AddressingMode::INDX, 6),
+    Instruction::new("LDY", Processor::LDY, Operation::LDY, Processor::ZPG, AddressingMode::ZPG, 3),
+    Instruction::new("LDA", Processor::LDA, Operation::LDA, Processor::ZPG, AddressingMode::ZPG, 3),
+    Instruction::new("LDX", Processor::LDX, Operation::LDX, Processor::ZPG, AddressingMode::ZPG, 3),
+    Instruction::new("LAX", Processor::LAX, Operation::LAX, Processor::ZPG, AddressingMode::ZPG, 3),
+    Instruction::new("TAY", Processor::TAY, Operation::TAY, Processor::IMPL, AddressingMode::IMPL, 2),
+    Instruction::new("LDA", Processor::LDA, Operation::LDA, Processor::IMM, AddressingMode::IMM, 2),
+    Instruction::new("TAX", Processor::TAX, Operation::TAX, Processor::IMPL, AddressingMode::IMPL, 2),
+    Instruction::new("LXA", Processor::LXA, Operation::LXA, Processor::IMM, AddressingMode::IMM, 2),
+    Instruction::new("LDY", Processor::LDY, Operation::LDY, Processor::ABS, AddressingMode::ABS, 4),
+    Instruction::new("LDA", Processor::LDA, Operation::LDA, Processor::ABS, AddressingMode::ABS, 4),
+    Instruction::new("LDX", Processor::LDX, Operation::LDX, Processor::ABS, AddressingMode::ABS, 4),
+    Instruction::new("LAX", Processor::LAX, Operation::LAX, Processor::ABS, AddressingMode::ABS, 4),
+    // 0xB0
+    Instruction::new("BCS", Processor::BCS, Operation::BCS, Processor::REL, AddressingMode::REL, 2),
+    Instruction::new("LDA", Processor::LDA, Operation::LDA, Processor::INDY, AddressingMode::INDY, 5),
+    Instruction::new("JAM", Processor::JAM, Operation::JAM, Processor::IMPL, AddressingMode::IMPL, 2),
+    Instruction::new("LAX", Processor::LAX, Operation::LAX, Processor::INDY, AddressingMode::INDY, 5),
+    Instruction::new("LDY", Processor::LDY, Operation::LDY, Processor::ZPGX, AddressingMode::ZPGX, 4),
+    Instruction::new("LDA", Processor::LDA, Operation::LDA, Processor::ZPGX, AddressingMode::ZPGX, 4),
+    Instruction::new("LDX", Processor::LDX, Operation::LDX, Processor::ZPGY, AddressingMode::ZPGY, 4),
+    Instruction::new("LAX", Processor::LAX, Operation::LAX, Processor::ZPGY, AddressingMode::ZPGY, 4),
+    Instruction::new("CLV", Processor::CLV, Operation::CLV, Processor::IMPL, AddressingMode::IMPL, 2),
+    Instruction::new("LDA", Processor::LDA, Operation::LDA, Processor::ABSY, AddressingMode::ABSY, 4),
+    Instruction::new("TSX", Processor::TSX, Operation::TSX, Processor::IMPL, AddressingMode::IMPL, 2),
+    Instruction::new("LAS", Processor::LAS, Operation::LAS, Processor::ABSY, AddressingMode::ABSY, 4),
+    Instruction::new("LDY", Processor::LDY, Operation::LDY, Processor::ABSX, AddressingMode::ABSX, 4),
+    Instruction::new("LDA", Processor::LDA, Operation::LDA, Processor::ABSX, AddressingMode::ABSX, 4),
+    Instruction::new("LDX", Processor::LDX, Operation::LDX, Processor::ABSY, AddressingMode::ABSY, 4),
+    Instruction::new("LAX", Processor::LAX, Operation::LAX, Processor::ABSY, AddressingMode::ABSY, 4),
+    // 0xC0
+    Instruction::new("CPY", Processor::CPY, Operation::CPY, Processor::IMM, AddressingMode::IMM, 2),
+    Instruction::new("CMP", Processor::CMP, Operation::CMP, Processor::INDX, AddressingMode::INDX, 6),
+    Instruction::new("NOP", Processor::NOP, Operation::NOP, Processor::IMM, AddressingMode::IMM, 2).undocumented(),
+    Instruction::new("DCP", Processor::DCP, Operation::DCP, Processor::INDX, AddressingMode::INDX, 8),
+    Instruction::new("CPY", Processor::CPY, Operation::CPY, Processor::ZPG, AddressingMode::ZPG, 3),
+    Instruction::new("CMP", Processor::CMP, Operation::CMP, Processor::ZPG, AddressingMode::ZPG, 3),
+    Instruction::new("DEC", Processor::DEC, Operation::DEC, Processor::ZPG, AddressingMode::ZPG, 5),
+    Instruction::new("DCP", Processor::DCP, Operation::DCP, Processor::ZPG, AddressingMode::ZPG, 5),
+    Instruction::new("INY", Processor::INY, Operation::INY, Processor::IMPL, AddressingMode::IMPL, 2),
+    Instruction::new("CMP", Processor::CMP, Operation::CMP, Processor::IMM, AddressingMode::IMM, 2),
+    Instruction::new("DEX", Processor::DEX, Operation::DEX, Processor::IMPL, AddressingMode::IMPL, 2),
+    Instruction::new("SBX", Processor::SBX, Operation::SBX, Processor::IMM, AddressingMode::IMM, 2),
+    Instruction::new("CPY", Processor::CPY, Operation::CPY, Processor::ABS, AddressingMode::ABS, 4),
+    Instruction::new("CMP", Processor::CMP, Operation::CMP, Processor::ABS, AddressingMode::ABS, 4),
+    Instruction::new("DEC", Processor::DEC, Operation::DEC, Processor::ABS, AddressingMode::ABS, 6),
+    Instruction::new("DCP", Processor::DCP, Operation::DCP, Processor::ABS, AddressingMode::ABS, 6),
+    // 0xD0
+    Instruction::new("BNE", Processor::BNE, Operation::BNE, Processor::REL, AddressingMode::REL, 2),
+    Instruction::new("CMP", Processor::CMP, Operation::CMP, Processor::INDY, AddressingMode::INDY, 5),
+    Instruction::new("JAM", Processor::JAM, Operation::JAM, Processor::IMPL, AddressingMode::IMPL, 2),
+    Instruction::new("DCP", Processor::DCP, Operation::DCP, Processor::INDY, AddressingMode::INDY, 8),
+    Instruction::new("NOP", Processor::NOP, Operation::NOP, Processor::ZPGX, AddressingMode::ZPGX, 4).undocumented(),
+    Instruction::new("CMP", Processor::CMP, Operation::CMP, Processor::ZPGX, AddressingMode::ZPGX, 4),
+    Instruction::new("DEC", Processor::DEC, Operation::DEC, Processor::ZPGX, AddressingMode::ZPGX, 6),
+    Instruction::new("DCP", Processor::DCP, Operation::DCP, Processor::ZPGX, AddressingMode::ZPGX, 6),
+    Instruction::new("CLD", Processor::CLD, Operation::CLD, Processor::IMPL, AddressingMode::IMPL, 2),
+    Instruction::new("CMP", Processor::CMP, Operation::CMP, Processor::ABSY, AddressingMode::ABSY, 4),
+    Instruction::new("NOP", Processor::NOP, Operation::NOP, Processor::IMPL, AddressingMode::IMPL, 2).undocumented(),
+    Instruction::new("DCP", Processor::DCP, Operation::DCP, Processor::ABSY, AddressingMode::ABSY, 7),
+    Instruction::new("NOP", Processor::NOP, Operation::NOP, Processor::ABSX, AddressingMode::ABSX, 4).undocumented(),
+    Instruction::new("CMP", Processor::CMP, Operation::CMP, Processor::ABSX, AddressingMode::ABSX, 4),
+    Instruction::new("DEC", Processor::DEC, Operation::DEC, Processor::ABSX, AddressingMode::ABSX, 7),
+    Instruction::new("DCP", Processor::DCP, Operation::DCP, Processor::ABSX, AddressingMode::ABSX, 7),
+    // 0xE0
+    Instruction::new("CPX", Processor::CPX, Operation::CPX, Processor::IMM, AddressingMode::IMM, 2),
+    Instruction::new("SBC", Processor::SBC, Operation::SBC, Processor::INDX, AddressingMode::INDX, 6),
+    Instruction::new("NOP", Processor::NOP, Operation::NOP, Processor::IMM, AddressingMode::IMM, 2).undocumented(),
+    Instruction::new("ISC", Processor::ISC, Operation::ISC, Processor::INDX, AddressingMode::INDX, 8),
+    Instruction::new("CPX", Processor::CPX, Operation::CPX, Processor::ZPG, AddressingMode::ZPG, 3),
+    Instruction::new("SBC", Processor::SBC, Operation::SBC, Processor::ZPG, AddressingMode::ZPG, 3),
+    Instruction::new("INC", Processor::INC, Operation::INC, Processor::ZPG, AddressingMode::ZPG, 5),
+    Instruction::new("ISC", Processor::ISC, Operation::ISC, Processor::ZPG, AddressingMode::ZPG, 5),
+    Instruction::new("INX", Processor::INX, Operation::INX, Processor::IMPL, AddressingMode::IMPL, 2),
+    Instruction::new("SBC", Processor::SBC, Operation::SBC, Processor::IMM, AddressingMode::IMM, 2),
+    Instruction::new("NOP", Processor::NOP, Operation::NOP, Processor::IMPL, AddressingMode::IMPL, 2),
+    Instruction::new("USBC", Processor::USBC, Operation::USBC, Processor::IMM, AddressingMode::IMM, 2),
+    Instruction::new("CPX", Processor::CPX, Operation::CPX, Processor::ABS, AddressingMode::ABS, 4),
+    Instruction::new("SBC", Processor::SBC, Operation::SBC, Processor::ABS, AddressingMode::ABS, 4),
+    Instruction::new("INC", Processor::INC, Operation::INC, Processor::ABS, AddressingMode::ABS, 6),
+    Instruction::new("ISC", Processor::ISC, Operation::ISC, Processor::ABS, AddressingMode::ABS, 6),
+    // 0xF0
+    Instruction::new("BEQ", Processor::BEQ, Operation::BEQ, Processor::REL, AddressingMode::REL, 2),
+    Instruction::new("SBC", Processor::SBC, Operation::SBC, Processor::INDY, AddressingMode::INDY, 5),
+    Instruction::new("JAM", Processor::JAM, Operation::JAM, Processor::IMPL, AddressingMode::IMPL, 2),
+    Instruction::new("ISC", Processor::ISC, Operation::ISC, Processor::INDY, AddressingMode::INDY, 8),
+    Instruction::new("NOP", Processor::NOP, Operation::NOP, Processor::ZPGX, AddressingMode::ZPGX, 4).undocumented(),
+    Instruction::new("SBC", Processor::SBC, Operation::SBC, Processor::ZPGX, AddressingMode::ZPGX, 4),
+    Instruction::new("INC", Processor::INC, Operation::INC, Processor::ZPGX, AddressingMode::ZPGX, 6),
+    Instruction::new("ISC", Processor::ISC, Operation::ISC, Processor::ZPGX, AddressingMode::ZPGX, 6),
+    Instruction::new("SED", Processor::SED, Operation::SED, Processor::IMPL, AddressingMode::IMPL, 2),
+    Instruction::new("SBC", Processor::SBC, Operation::SBC, Processor::ABSY, AddressingMode::ABSY, 4),
+    Instruction::new("NOP", Processor::NOP, Operation::NOP, Processor::IMPL, AddressingMode::IMPL, 2).undocumented(),
+    Instruction::new("ISC", Processor::ISC, Operation::ISC, Processor::ABSY, AddressingMode::ABSY, 7),
+    Instruction::new("NOP", Processor::NOP, Operation::NOP, Processor::ABSX, AddressingMode::ABSX, 4).undocumented(),
+    Instruction::new("SBC", Processor::SBC, Operation::SBC, Processor::ABSX, AddressingMode::ABSX, 4),
+    Instruction::new("INC", Processor::INC, Operation::INC, Processor::ABSX, AddressingMode::ABSX, 7),
+    Instruction::new("ISC", Processor::ISC, Operation::ISC, Processor::ABSX, AddressingMode::ABSX, 7),
+];
+
 #[cfg(test)]
 mod tests {
 
@@ -1917,7 +3125,7 @@ mod tests {
         test_processor.reset();
 
         // testing with values from 0 to 255
-        for value in 0..=255 as u8 {
+        for value in 0..=255_u8 {
 
             // testing get
             test_processor.status = value;
@@ -1971,5 +3179,627 @@ mod tests {
         
     }
 
+    /**
+     * Whether or not the static decode table gives lengths and page crossing penalties
+     */
+    #[test]
+    fn decode_table() {
+        assert_eq!(INSTRUCTIONS.len(), 256);
+
+        let lda = Instruction::decode(0xBD); // LDA abs,X
+        assert_eq!((lda.name, lda.length, lda.cycles, lda.page_cross_penalty), ("LDA", 3, 4, true));
+
+        let sta = Instruction::decode(0x9D); // STA abs,X
+        assert_eq!((sta.name, sta.length, sta.cycles, sta.page_cross_penalty), ("STA", 3, 5, false));
+
+        assert_eq!(Instruction::decode(0xE8).length, 1); // INX
+        assert_eq!(Instruction::decode(0xA9).length, 2); // LDA #
+    }
+
+    /**
+     * Whether or not the undocumented NOPs take the cycles of their addressing mode and JAM halts after its fetch
+     */
+    #[test]
+    fn undocumented_nop_and_jam_cycles() {
+        let mut test_processor = processor_with_program(&[
+            0x1A,             // NOP
+            0x80, 0x00,       // NOP #$00
+            0x04, 0x00,       // NOP $00
+            0x14, 0x00,       // NOP $00,X
+            0x0C, 0x00, 0x00, // NOP $0000
+            0x1C, 0x00, 0x80, // NOP $8000,X
+            0x1C, 0xFF, 0x80, // NOP $80FF,X
+            0x02,             // JAM
+        ]);
+        test_processor.step();
+        test_processor.index_register_x = 1;
+
+        let cycles: Vec<u64> = (0..8).map(|_| test_processor.step()).collect();
+        assert_eq!(cycles, [2, 2, 3, 4, 4, 4, 5, 1]);
+        assert!(test_processor.is_halted());
+        assert_eq!(test_processor.step(), 0);
+    }
+
+    /**
+     * Places the program at the start of ROM (0x8000) and starts executing from there after reset
+     */
+    fn processor_with_program(program: &[u8]) -> Processor {
+        let mut test_processor = Processor::new();
+
+        for (i, byte) in program.iter().enumerate() {
+            test_processor.bus.write(0x8000 + i as u16, *byte);
+        }
+
+        test_processor.reset();
+        test_processor.program_counter = 0x8000;
+        test_processor
+    }
+
+    /**
+     * Clocks the processor until the program counter reaches `end`, every instruction before it has been executed by then
+     */
+    fn execute_until(test_processor: &mut Processor, end: u16) {
+        for _ in 0..1000 {
+            if test_processor.program_counter == end {
+                return;
+            }
+            test_processor.clock();
+        }
+
+        panic!("program counter never reached {:04X}", end);
+    }
+
+    /**
+     * Whether or not `clock()` executes the operation of the fetched instruction
+     */
+    #[test]
+    fn clock_executes_operation() {
+        // INX, INX, INX
+        let mut test_processor = processor_with_program(&[0xE8, 0xE8, 0xE8]);
+        execute_until(&mut test_processor, 0x8003);
+
+        assert_eq!(test_processor.index_register_x, 3);
+    }
+
+    /**
+     * Runs a single instruction with a zero page operand, returns the accumulator and the N, V and C flags
+     */
+    fn execute_with_operand(opcode: u8, accumulator: u8, operand: u8, carry: bool) -> (u8, bool, bool, bool) {
+        let mut test_processor = processor_with_program(&[opcode, 0x10]);
+        test_processor.bus.write(0x0010, operand);
+        test_processor.accumulator = accumulator;
+        test_processor.set_c(carry);
+
+        execute_until(&mut test_processor, 0x8002);
+        (test_processor.accumulator, test_processor.get_n(), test_processor.get_o(), test_processor.get_c())
+    }
+
+    /**
+     * Whether or not ADC, SBC and AND set the negative and overflow flags from the sign bit
+     */
+    #[test]
+    fn sign_and_overflow_flags() {
+        // ADC $10: two positives overflowing into a negative, two negatives overflowing into a positive, no overflow
+        assert_eq!(execute_with_operand(0x65, 0x50, 0x50, false), (0xA0, true, true, false));
+        assert_eq!(execute_with_operand(0x65, 0xD0, 0x90, false), (0x60, false, true, true));
+        assert_eq!(execute_with_operand(0x65, 0xF0, 0x01, true), (0xF2, true, false, false));
+
+        // SBC $10: positive minus negative overflowing, negative minus positive overflowing, no overflow
+        assert_eq!(execute_with_operand(0xE5, 0x50, 0xB0, true), (0xA0, true, true, false));
+        assert_eq!(execute_with_operand(0xE5, 0xD0, 0x70, true), (0x60, false, true, true));
+        assert_eq!(execute_with_operand(0xE5, 0xF0, 0x01, true), (0xEF, true, false, true));
+
+        // AND $10
+        assert_eq!(execute_with_operand(0x25, 0xF0, 0x80, false), (0x80, true, false, false));
+        assert_eq!(execute_with_operand(0x25, 0xF0, 0x0F, false), (0x00, false, false, false));
+    }
+
+    /**
+     * Whether or not reset loads the vector, takes 7 cycles and moves the stack pointer like the real chip
+     */
+    #[test]
+    fn reset_and_power_on() {
+        let mut test_processor = Processor::new();
+        test_processor.bus.write(INITIAL_PROGRAM_COUNTER_ADDRESS, 0x34);
+        test_processor.bus.write(INITIAL_PROGRAM_COUNTER_ADDRESS + 1, 0x92);
+        test_processor.bus.write(0x0200, 0x42);
+
+        test_processor.power_on(PowerOnPattern::Alternating);
+        assert_eq!((test_processor.program_counter, test_processor.stack_pointer), (0x9234, 0xFD));
+        assert!(test_processor.get_i());
+        assert_eq!(test_processor.step(), RESET_CYCLES as u64);
+        assert_eq!((test_processor.bus.peek(0x0200), test_processor.bus.peek(0x0201)), (0x00, 0xFF));
+
+        // the registers survive a reset, the stack pointer keeps moving down
+        test_processor.accumulator = 0x12;
+        test_processor.reset();
+        assert_eq!((test_processor.accumulator, test_processor.stack_pointer), (0x12, 0xFA));
+
+        test_processor.power_on(PowerOnPattern::Random { seed: 7 });
+        let memory: Vec<u8> = (0..0x100).map(|address| test_processor.bus.peek(address)).collect();
+        test_processor.power_on("random:7".parse().unwrap());
+        assert!((0..0x100).all(|address| test_processor.bus.peek(address) == memory[address as usize]));
+        test_processor.power_on(PowerOnPattern::Random { seed: 8 });
+        assert!((0..0x100).any(|address| test_processor.bus.peek(address) != memory[address as usize]));
+        assert_eq!(test_processor.accumulator, 0x00);
+
+        assert!("checkerboard".parse::<PowerOnPattern>().is_err());
+    }
+
+    /**
+     * Whether or not `run()` stops for the expected reasons with the expected cycle counts
+     */
+    #[test]
+    fn run_stop_reasons() {
+        // INX, INX, INX, JAM
+        let mut test_processor = processor_with_program(&[0xE8, 0xE8, 0xE8, 0x02]);
+        test_processor.add_breakpoint(0x8002);
+
+        // the reset sequence takes 7 cycles
+        let result = test_processor.run(7);
+        assert_eq!(result, RunResult { stop_reason: StopReason::BudgetExhausted, cycles: 7 });
+
+        let result = test_processor.run(100);
+        assert_eq!(result, RunResult { stop_reason: StopReason::Breakpoint { address: 0x8002 }, cycles: 4 });
+        assert_eq!(test_processor.index_register_x, 2);
+
+        // resuming from the breakpoint
+        let result = test_processor.run(100);
+        assert_eq!(result.stop_reason, StopReason::Halted);
+        assert_eq!(test_processor.index_register_x, 3);
+        assert!(test_processor.is_halted());
+
+        // INX, SLO ($00,X)
+        let mut test_processor = processor_with_program(&[0xE8, 0x03, 0x00]);
+        let result = test_processor.run(100);
+        assert_eq!(result.stop_reason, StopReason::IllegalOpcode { opcode: 0x03, address: 0x8001 });
+
+        // resuming executes the illegal opcode
+        let result = test_processor.run(1);
+        assert_eq!(result, RunResult { stop_reason: StopReason::BudgetExhausted, cycles: 1 });
+        assert_eq!(test_processor.program_counter, 0x8003);
+
+        // NOP, NOP (undocumented 0x1A)
+        let mut test_processor = processor_with_program(&[0xEA, 0x1A]);
+        let result = test_processor.run(100);
+        assert_eq!(result.stop_reason, StopReason::IllegalOpcode { opcode: 0x1A, address: 0x8001 });
+    }
+
+    /**
+     * Whether or not read watchpoints only fire on data reads, not on the opcode and operand fetches
+     */
+    #[test]
+    fn watchpoints_ignore_fetches() {
+        // LDA #$42, LDA $8001
+        let mut test_processor = processor_with_program(&[0xA9, 0x42, 0xAD, 0x01, 0x80]);
+        test_processor.step();
+        test_processor.add_watchpoint(0x8000, 5, WatchKind::Read);
+
+        let result = test_processor.run(100);
+        assert_eq!(result.stop_reason, StopReason::Watchpoint { hit: WatchHit { kind: WatchKind::Read, address: 0x8001 } });
+        assert_eq!(result.cycles, 2 + 4);
+        assert_eq!(test_processor.accumulator, 0x42);
+    }
+
+    /**
+     * Whether or not an interrupt requested by a device on the bus is serviced
+     */
+    #[test]
+    fn device_irq() {
+        use crate::devices::via::{self, Via};
+        use crate::devices::BusDevice;
+        use std::cell::RefCell;
+        use std::rc::Rc;
+
+        // CLI, JMP $8001
+        let mut test_processor = processor_with_program(&[0x58, 0x4C, 0x01, 0x80]);
+
+        // the interrupt handler at 0x9000 jams the processor
+        test_processor.bus.write(0x9000, 0x02);
+        test_processor.bus.write(FIXED_READING_ADDRESS_FOR_BRK_AND_IRQ, 0x00);
+        test_processor.bus.write(FIXED_READING_ADDRESS_FOR_BRK_AND_IRQ + 1, 0x90);
+
+        let via = Rc::new(RefCell::new(Via::new()));
+        test_processor.bus.attach_device(via::DEFAULT_ADDRESS, via.clone());
+
+        // timer 1 one-shot of 100 cycles with its interrupt enabled
+        test_processor.bus.write(via::DEFAULT_ADDRESS + 0xE, 0x80 | via::IRQ_T1);
+        test_processor.bus.write(via::DEFAULT_ADDRESS + 0x4, 100);
+        test_processor.bus.write(via::DEFAULT_ADDRESS + 0x5, 0x00);
+
+        let result = test_processor.run(50);
+        assert_eq!(result.stop_reason, StopReason::BudgetExhausted);
+
+        let result = test_processor.run(1000);
+        assert_eq!(result.stop_reason, StopReason::Halted);
+        assert!(test_processor.get_i());
+        assert!(via.borrow().irq());
+    }
+
+    /**
+     * Whether or not cycles, instructions and interrupt latencies are counted
+     */
+    #[test]
+    fn timing_stats() {
+        use crate::devices::via::{self, Via};
+        use std::cell::RefCell;
+        use std::rc::Rc;
+
+        // INX, INX, INX, JAM
+        let mut test_processor = processor_with_program(&[0xE8, 0xE8, 0xE8, 0x02]);
+        test_processor.run(100);
+        let stats = test_processor.timing_stats();
+        assert_eq!((stats.cycles, stats.instructions), (test_processor.total_cycles(), 4));
+        assert_eq!(stats.irq_latency.mean(), None);
+
+        // SEI, JMP $8001
+        let mut test_processor = processor_with_program(&[0x78, 0x4C, 0x01, 0x80]);
+        test_processor.step();
+
+        // the interrupt handler at 0x9000 jams the processor
+        test_processor.bus.write(0x9000, 0x02);
+        test_processor.bus.write(FIXED_READING_ADDRESS_FOR_BRK_AND_IRQ, 0x00);
+        test_processor.bus.write(FIXED_READING_ADDRESS_FOR_BRK_AND_IRQ + 1, 0x90);
+
+        // timer 1 one-shot of 10 cycles with its interrupt enabled, while interrupts are disabled
+        let via = Rc::new(RefCell::new(Via::new()));
+        test_processor.bus.attach_device(via::DEFAULT_ADDRESS, via.clone());
+        test_processor.bus.write(via::DEFAULT_ADDRESS + 0xE, 0x80 | via::IRQ_T1);
+        test_processor.bus.write(via::DEFAULT_ADDRESS + 0x4, 10);
+        test_processor.bus.write(via::DEFAULT_ADDRESS + 0x5, 0x00);
+        test_processor.run(100);
+        let requested_at = test_processor.irq_requested_at.unwrap();
+
+        // the request waits for the interrupts to be enabled, then for the JMP going on to complete
+        test_processor.set_i(false);
+        let enabled_at = test_processor.total_cycles();
+        let result = test_processor.run(100);
+        assert_eq!(result.stop_reason, StopReason::Halted);
+
+        let latency = test_processor.timing_stats().irq_latency;
+        assert_eq!(latency.count, 1);
+        assert!((enabled_at - requested_at + IRQ_CYCLES as u64..=enabled_at - requested_at + 2 + IRQ_CYCLES as u64).contains(&latency.max));
+    }
+
+    /**
+     * Whether or not interrupts given as inputs wait for the current instruction to complete
+     */
+    #[test]
+    fn input_interrupts_at_instruction_boundary() {
+        // INC $0200, NOP, JAM
+        let mut test_processor = processor_with_program(&[0xEE, 0x00, 0x02, 0xEA, 0x02]);
+        test_processor.bus.write(FIXED_READING_ADDRESS_FOR_NMI, 0x00);
+        test_processor.bus.write(FIXED_READING_ADDRESS_FOR_NMI + 1, 0x90);
+        test_processor.bus.write(FIXED_READING_ADDRESS_FOR_BRK_AND_IRQ, 0x00);
+        test_processor.bus.write(FIXED_READING_ADDRESS_FOR_BRK_AND_IRQ + 1, 0xA0);
+
+        // requested on the first of the 6 cycles of INC, once the reset sequence is over
+        test_processor.step();
+        test_processor.clock();
+        let requested_at = test_processor.total_cycles();
+        test_processor.input(InputEvent::Nmi);
+        assert_eq!(test_processor.step(), 5);
+        assert_eq!(test_processor.bus.peek(0x0200), 1);
+        assert_eq!(test_processor.program_counter, 0x8003);
+
+        let serviced_at = test_processor.total_cycles();
+        assert_eq!(test_processor.step(), NMI_CYCLES as u64);
+        assert_eq!(test_processor.program_counter, 0x9000);
+        assert_eq!(
+            test_processor.timing_stats().nmi_latency.max,
+            serviced_at - requested_at + NMI_CYCLES as u64
+        );
+
+        // an IRQ waits for the interrupts to be enabled
+        test_processor.input(InputEvent::Irq);
+        test_processor.program_counter = 0x8003;
+        test_processor.step();
+        assert_eq!(test_processor.program_counter, 0x8004);
+        test_processor.set_i(false);
+        test_processor.step();
+        assert_eq!(test_processor.program_counter, 0xA000);
+        assert_eq!(test_processor.timing_stats().irq_latency.count, 1);
+    }
+
+    /**
+     * Whether or not the stack pointer wraps around and the stack guard stops `run()`
+     */
+    #[test]
+    fn stack_guard() {
+        // PHA, JMP $8000
+        let mut test_processor = processor_with_program(&[0x48, 0x4C, 0x00, 0x80]);
+        test_processor.enable_stack_guard(Some(16));
+
+        let result = test_processor.run(100_000);
+        assert_eq!(result.stop_reason, StopReason::StackGuard { event: StackEvent::DepthExceeded { address: 0x8000, depth: 17 } });
+
+        let result = test_processor.run(100_000);
+        assert_eq!(result.stop_reason, StopReason::StackGuard { event: StackEvent::Overflow { address: 0x8000 } });
+        assert_eq!(test_processor.stack_pointer, 0xFF);
+        assert_eq!(test_processor.take_stack_events().len(), 2);
+
+        // without the guard the stack keeps wrapping silently
+        test_processor.disable_stack_guard();
+        assert_eq!(test_processor.run(10_000).stop_reason, StopReason::BudgetExhausted);
+    }
+
+    /**
+     * Whether or not instructions are undone exactly and reverse execution stops at breakpoints
+     */
+    #[test]
+    fn step_back_and_reverse_continue() {
+        // INX, STX $0200, INX, STX $0200, JAM
+        let mut test_processor = processor_with_program(&[0xE8, 0x8E, 0x00, 0x02, 0xE8, 0x8E, 0x00, 0x02, 0x02]);
+        test_processor.enable_history(4, usize::MAX);
+
+        assert_eq!(test_processor.run(1000).stop_reason, StopReason::Halted);
+        let end = test_processor.history().unwrap().cycle();
+        assert_eq!(test_processor.bus.peek(0x0200), 2);
+
+        // JAM, STX
+        assert!(test_processor.step_back() && test_processor.step_back());
+        assert!(!test_processor.is_halted());
+        assert_eq!((test_processor.program_counter, test_processor.index_register_x), (0x8005, 2));
+        assert_eq!(test_processor.bus.peek(0x0200), 1);
+
+        test_processor.add_breakpoint(0x8001);
+        let result = test_processor.reverse_continue();
+        assert_eq!(result.stop_reason, StopReason::Breakpoint { address: 0x8001 });
+        assert_eq!(test_processor.bus.peek(0x0200), 0);
+
+        assert_eq!(test_processor.reverse_continue().stop_reason, StopReason::HistoryStart);
+
+        // forward again
+        assert!(test_processor.goto_cycle(end));
+        assert!(test_processor.is_halted());
+        assert_eq!(test_processor.bus.peek(0x0200), 2);
+    }
+
+    /**
+     * Whether or not cycles older than the undo log are reached through the snapshots under the memory cap
+     */
+    #[test]
+    fn goto_cycle_from_snapshot() {
+        // INC $0200, JMP $8000
+        let program = [0xEE, 0x00, 0x02, 0x4C, 0x00, 0x80];
+        let memory_cap = 400_000;
+
+        let mut test_processor = processor_with_program(&program);
+        test_processor.enable_history(10_000, memory_cap);
+        test_processor.run(100_000);
+
+        let history = test_processor.history().unwrap();
+        assert!(history.memory_usage() <= memory_cap);
+        assert!(history.oldest_cycle() < history.oldest_entry_cycle());
+        let target = history.oldest_cycle() + 7;
+
+        let mut reference = processor_with_program(&program);
+        for _ in 0..target {
+            reference.clock();
+        }
+
+        assert!(test_processor.goto_cycle(target));
+        assert_eq!(test_processor.cpu_state(), reference.cpu_state());
+        assert_eq!(test_processor.bus.peek(0x0200), reference.bus.peek(0x0200));
+        assert!(!test_processor.goto_cycle(0));
+    }
+
+    /**
+     * Whether or not bank switches and device registers are rewound and saved along with the processor
+     */
+    #[test]
+    fn history_with_mapper_and_devices() {
+        use crate::devices::{via::Via, BusDevice};
+        use crate::mapper::{UxRom, KB_16};
+        use std::cell::RefCell;
+        use std::rc::Rc;
+
+        // 4 banks starting with their number, the last one is fixed at $C000
+        let mut prg = vec![0x00; 4 * KB_16];
+        for bank in 0..4 {
+            prg[bank * KB_16] = bank as u8;
+        }
+        // LDA #$02, STA $8000 (bank 2), STA $6000 (VIA port B), JAM
+        let program = [0xA9, 0x02, 0x8D, 0x00, 0x80, 0x8D, 0x00, 0x60, 0x02];
+        prg[3 * KB_16 + 1..3 * KB_16 + 1 + program.len()].copy_from_slice(&program);
+
+        let mut test_processor = Processor::new();
+        test_processor.bus.set_mapper(Box::new(UxRom::new(&prg)));
+        let via = Rc::new(RefCell::new(Via::new()));
+        test_processor.bus.attach_device(0x6000, via.clone());
+        let mut registers = test_processor.registers();
+        registers.program_counter = 0xC001;
+        test_processor.set_registers(&registers);
+
+        let start = test_processor.save_state();
+        let via_start = via.borrow().save_state();
+        test_processor.enable_history(4, usize::MAX);
+
+        assert_eq!(test_processor.run(1000).stop_reason, StopReason::Halted);
+        assert_eq!(test_processor.bus.peek(0x8000), 2);
+        assert_ne!(via.borrow().save_state(), via_start);
+
+        assert!(test_processor.goto_cycle(0));
+        assert_eq!(test_processor.bus.peek(0x8000), 0);
+        assert_eq!(via.borrow().save_state(), via_start);
+
+        // and through a save state
+        assert!(test_processor.goto_cycle(12));
+        assert_eq!(test_processor.bus.peek(0x8000), 2);
+        test_processor.load_state(&start).unwrap();
+        assert_eq!(test_processor.bus.peek(0x8000), 0);
+        assert_eq!(via.borrow().save_state(), via_start);
+    }
+
+    /**
+     * Whether or not the shadow call stack follows JSR into a subroutine that never returns
+     */
+    #[test]
+    fn backtrace_from_subroutine() {
+        // JSR $8010, ..., $8010: JAM
+        let mut program = vec![0x20, 0x10, 0x80];
+        program.resize(0x10, 0xEA);
+        program.push(0x02);
+
+        let mut test_processor = processor_with_program(&program);
+        assert_eq!(test_processor.run(1000).stop_reason, StopReason::Halted);
+
+        let backtrace = test_processor.backtrace();
+        assert_eq!(backtrace.entries.len(), 2);
+        assert_eq!(backtrace.entries[0].function, Some(0x8010));
+        assert_eq!(backtrace.entries[1].address, 0x8000);
+        assert_eq!(test_processor.call_stack().frames()[0].return_address, 0x8003);
+    }
+
+    /**
+     * Whether or not the profiler attributes the cycles of a subroutine to its JSR target
+     */
+    #[test]
+    fn profile_subroutines() {
+        // JSR $8010, JSR $8010, JAM, ..., $8010: INX, RTS
+        let mut program = vec![0x20, 0x10, 0x80, 0x20, 0x10, 0x80, 0x02];
+        program.resize(0x10, 0xEA);
+        program.extend([0xE8, 0x60]);
+
+        let mut test_processor = processor_with_program(&program);
+        test_processor.run(7);
+        test_processor.enable_profiling();
+
+        let result = test_processor.run(1000);
+        assert_eq!(result.stop_reason, StopReason::Halted);
+        assert_eq!(test_processor.index_register_x, 2);
+
+        let report = test_processor.profile_report().unwrap();
+        assert_eq!(report.hot_subroutines[0], SubroutineCycles { address: 0x8010, calls: 2, inclusive_cycles: 16, exclusive_cycles: 16 });
+        // the two JSRs, the subroutine and the JAM
+        assert_eq!(report.total_cycles, 6 * 2 + 16 + 2);
+
+        let counters = test_processor.bus.access_counters.as_ref().unwrap();
+        assert_eq!(counters.executes(0x8010), 2);
+        // the stack pointer is at 0xFD after the reset
+        assert_eq!(counters.writes(0x01FD), 2);
+
+        test_processor.disable_profiling();
+        assert!(test_processor.profile_report().is_none());
+    }
+
+    /**
+     * Whether or not replaying the recorded terminal input reproduces the session exactly
+     */
+    #[test]
+    fn record_and_replay_inputs() {
+        use std::cell::RefCell;
+        use std::rc::Rc;
+
+        use crate::devices::terminal::Terminal;
+
+        // counts every key read (0 when there is none) at 0x0200 + key
+        let program = [
+            0xAD, 0x10, 0xD0, // LDA $D010
+            0xAA,             // TAX
+            0xFE, 0x00, 0x02, // INC $0200,X
+            0x4C, 0x00, 0x80, // JMP $8000
+        ];
+        let session = || {
+            let mut test_processor = processor_with_program(&program);
+            test_processor.bus.attach_device(0xD010, Rc::new(RefCell::new(Terminal::new())));
+            test_processor.bus.write(0xD011, 0x04); // selecting the keyboard data register
+            test_processor.step();
+            test_processor
+        };
+
+        let mut recorded = session();
+        recorded.start_recording();
+        for (wait, key) in [(150, b'H'), (7, b'I'), (300, b'!')] {
+            for _ in 0..wait {
+                recorded.clock();
+            }
+            assert!(recorded.input(InputEvent::Device { address: 0xD010, port: 0, value: key }));
+        }
+        for _ in 0..200 {
+            recorded.clock();
+        }
+        let log = recorded.stop_recording().unwrap();
+        assert_eq!(log.len(), 3);
+        assert_eq!(log.events[1].cycle, 157);
+
+        let mut replayed = session();
+        replayed.start_replay(InputLog::from_json(&log.to_json()).unwrap());
+        for _ in 0..657 {
+            replayed.clock();
+        }
+        assert!(!replayed.is_replaying());
+
+        assert_eq!(replayed.cpu_state(), recorded.cpu_state());
+        for key in [b'H', b'I', b'!'] {
+            assert_eq!(replayed.bus.peek(0x0200 + (key | 0x80) as u16), 1);
+        }
+        for address in 0x0200..0x0300 {
+            assert_eq!(replayed.bus.peek(address), recorded.bus.peek(address));
+        }
+    }
+
+    /**
+     * Whether or not a called subroutine runs through its nested calls until it returns, within the budget
+     */
+    #[test]
+    fn call_subroutine() {
+        let mut test_processor = processor_with_program(&[
+            0x20, 0x06, 0x80, // 8000 JSR $8006
+            0xE8,             // 8003 INX
+            0x60,             // 8004 RTS
+            0x00,             // 8005
+            0xC8,             // 8006 INY
+            0x60,             // 8007 RTS
+            0x4C, 0x08, 0x80, // 8008 JMP $8008
+        ]);
+        test_processor.step();
+
+        let registers = Registers { index_register_x: 1, index_register_y: 1, stack_pointer: 0xFD, ..Default::default() };
+        let result = test_processor.call(0x8000, &registers, 1000).unwrap();
+        assert_eq!(result.cycles, 6 + 2 + 6 + 2 + 6);
+        assert_eq!((result.registers.index_register_x, result.registers.index_register_y), (2, 2));
+        assert_eq!((result.registers.stack_pointer, result.registers.program_counter), (0xFD, CALL_RETURN_ADDRESS));
+        assert_eq!(test_processor.call_stack().depth(), 0);
+        assert!(test_processor.breakpoints().is_empty());
+
+        let error = test_processor.call(0x8008, &registers, 30).unwrap_err();
+        assert_eq!((error.stop_reason, error.cycles), (StopReason::BudgetExhausted, 30));
+        assert_eq!(error.registers.program_counter, 0x8008);
+        assert_eq!(test_processor.call_stack().depth(), 0);
+        assert_eq!(test_processor.backtrace().entries.len(), 1);
+    }
 
+    /**
+     * Whether or not a breakpoint or a stack guard report stops a call with that reason
+     */
+    #[test]
+    fn call_stop_reasons() {
+        let program = [
+            0x20, 0x06, 0x80, // 8000 JSR $8006
+            0xE8,             // 8003 INX
+            0x60,             // 8004 RTS
+            0x00,             // 8005
+            0xC8,             // 8006 INY
+            0x60,             // 8007 RTS
+        ];
+        let registers = Registers { stack_pointer: 0xFD, ..Default::default() };
+
+        let mut test_processor = processor_with_program(&program);
+        test_processor.step();
+        test_processor.add_breakpoint(0x8006);
+        let error = test_processor.call(0x8000, &registers, 1000).unwrap_err();
+        assert_eq!((error.stop_reason, error.cycles), (StopReason::Breakpoint { address: 0x8006 }, 6));
+        assert_eq!(error.to_string(), format!("Breakpoint at $8006, at {}", error.registers));
+        assert_eq!(test_processor.call_stack().depth(), 0);
+        assert_eq!(test_processor.breakpoints().len(), 1);
+
+        // the call itself leaves 4 bytes on the stack, the JSR goes past them
+        let mut test_processor = processor_with_program(&program);
+        test_processor.step();
+        test_processor.enable_stack_guard(Some(4));
+        let error = test_processor.call(0x8000, &registers, 1000).unwrap_err();
+        assert_eq!(error.stop_reason, StopReason::StackGuard { event: StackEvent::DepthExceeded { address: 0x8000, depth: 5 } });
+        assert_eq!(test_processor.call_stack().depth(), 0);
+    }
 }
\ No newline at end of file
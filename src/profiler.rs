@@ -0,0 +1,410 @@
+use std::cell::Cell;
+use std::collections::HashMap;
+use std::fmt::Write;
+
+use serde::Serialize;
+
+use crate::call_stack::CallFrame;
+
+/// Number of addresses in the 6502 address space
+const ADDRESS_SPACE: usize = 0x10000;
+
+/**
+ * Per-address read, write and execute counters kept by the `Bus`
+ *
+ * `Bus::read()` only borrows the bus, so the counters are cells.
+ */
+pub struct AccessCounters {
+    reads: Vec<Cell<u64>>,
+    writes: Vec<Cell<u64>>,
+    executes: Vec<Cell<u64>>,
+}
+
+impl Default for AccessCounters {
+    fn default() -> Self {
+        Self {
+            reads: vec![Cell::new(0); ADDRESS_SPACE],
+            writes: vec![Cell::new(0); ADDRESS_SPACE],
+            executes: vec![Cell::new(0); ADDRESS_SPACE],
+        }
+    }
+}
+
+// Constructor like implementation
+impl AccessCounters {
+    pub fn new() -> Self {
+        Self {
+            ..Default::default()
+        }
+    }
+}
+
+impl AccessCounters {
+    pub fn record_read(&self, address: u16) {
+        let counter = &self.reads[address as usize];
+        counter.set(counter.get() + 1);
+    }
+
+    pub fn record_write(&self, address: u16) {
+        let counter = &self.writes[address as usize];
+        counter.set(counter.get() + 1);
+    }
+
+    /**
+     * Counts an opcode fetch, the fetch itself is also counted as a read
+     */
+    pub fn record_execute(&self, address: u16) {
+        let counter = &self.executes[address as usize];
+        counter.set(counter.get() + 1);
+    }
+
+    pub fn reads(&self, address: u16) -> u64 {
+        self.reads[address as usize].get()
+    }
+
+    pub fn writes(&self, address: u16) -> u64 {
+        self.writes[address as usize].get()
+    }
+
+    pub fn executes(&self, address: u16) -> u64 {
+        self.executes[address as usize].get()
+    }
+
+    /**
+     * Returns the counters of every address, indexed by address, for rendering a heatmap
+     */
+    pub fn heatmap(&self) -> MemoryHeatmap {
+        let values = |counters: &[Cell<u64>]| counters.iter().map(Cell::get).collect();
+
+        MemoryHeatmap {
+            reads: values(&self.reads),
+            writes: values(&self.writes),
+            executes: values(&self.executes),
+        }
+    }
+
+    /**
+     * Returns the addresses that were touched at least once, in address order
+     */
+    pub fn touched(&self) -> Vec<AddressAccesses> {
+        (0..ADDRESS_SPACE)
+            .map(|address| AddressAccesses {
+                address: address as u16,
+                reads: self.reads[address].get(),
+                writes: self.writes[address].get(),
+                executes: self.executes[address].get(),
+            })
+            .filter(|accesses| accesses.reads + accesses.writes + accesses.executes > 0)
+            .collect()
+    }
+}
+
+/**
+ * Every counter of the address space, as three arrays of 65536 values
+ */
+#[derive(Debug, Serialize)]
+pub struct MemoryHeatmap {
+    pub reads: Vec<u64>,
+    pub writes: Vec<u64>,
+    pub executes: Vec<u64>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct AddressAccesses {
+    pub address: u16,
+    pub reads: u64,
+    pub writes: u64,
+    pub executes: u64,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct OpcodeCycles {
+    pub opcode: u8,
+    pub mnemonic: String,
+    /// Number of times the opcode was executed
+    pub count: u64,
+    pub cycles: u64,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct AddressCycles {
+    pub address: u16,
+    pub cycles: u64,
+}
+
+/**
+ * Cycles spent in a subroutine, identified by its JSR target (or interrupt handler address)
+ */
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct SubroutineCycles {
+    pub address: u16,
+    pub calls: u64,
+    /// Cycles spent in the subroutine and everything it called
+    pub inclusive_cycles: u64,
+    /// Cycles spent in the subroutine's own instructions
+    pub exclusive_cycles: u64,
+}
+
+/**
+ * Per-opcode, per-PC and per-subroutine cycle counters kept by the `Processor`
+ *
+ * Subroutine cycles are attributed from the frames of the processor's shadow call stack.
+ */
+pub struct CycleCounters {
+    opcode_counts: Vec<u64>,
+    opcode_cycles: Vec<u64>,
+    pc_cycles: Vec<u64>,
+    subroutines: HashMap<u16, SubroutineCycles>,
+    total_cycles: u64,
+}
+
+impl Default for CycleCounters {
+    fn default() -> Self {
+        Self {
+            opcode_counts: vec![0; 256],
+            opcode_cycles: vec![0; 256],
+            pc_cycles: vec![0; ADDRESS_SPACE],
+            subroutines: HashMap::new(),
+            total_cycles: 0,
+        }
+    }
+}
+
+// Constructor like implementation
+impl CycleCounters {
+    pub fn new() -> Self {
+        Self {
+            ..Default::default()
+        }
+    }
+}
+
+impl CycleCounters {
+    /**
+     * Attributes the cycles of an executed instruction
+     *
+     # Arguments
+
+     * `address` - Where the opcode was fetched from
+     * `opcode` - The opcode
+     * `cycles` - All the cycles the instruction takes, including page crossings and taken branches
+     * `frames` - The active calls before the instruction, outermost first
+     */
+    pub fn record_instruction(&mut self, address: u16, opcode: u8, cycles: u8, frames: &[CallFrame]) {
+        let cycles = cycles as u64;
+
+        self.opcode_counts[opcode as usize] += 1;
+        self.opcode_cycles[opcode as usize] += cycles;
+        self.pc_cycles[address as usize] += cycles;
+        self.attribute(cycles, frames);
+    }
+
+    /**
+     * Attributes the cycles of an interrupt sequence (IRQ or NMI) to the code it interrupted,
+       like those of a `JSR` belong to the caller
+     *
+     # Arguments
+
+     * `cycles` - The cycles of the sequence
+     * `frames` - The active calls before the interrupt, outermost first
+     */
+    pub fn record_interrupt(&mut self, cycles: u8, frames: &[CallFrame]) {
+        self.attribute(cycles as u64, frames);
+    }
+
+    fn attribute(&mut self, cycles: u64, frames: &[CallFrame]) {
+        self.total_cycles += cycles;
+
+        if let Some(innermost) = frames.last() {
+            self.subroutine(innermost.target).exclusive_cycles += cycles;
+
+            // recursive calls are only counted once, by the outermost one
+            for (depth, frame) in frames.iter().enumerate() {
+                if frames[..depth].iter().all(|caller| caller.target != frame.target) {
+                    self.subroutine(frame.target).inclusive_cycles += cycles;
+                }
+            }
+        }
+    }
+
+    fn subroutine(&mut self, address: u16) -> &mut SubroutineCycles {
+        self.subroutines.entry(address).or_insert_with(|| SubroutineCycles {
+            address,
+            ..Default::default()
+        })
+    }
+
+    /**
+     * Counts a call to a subroutine (JSR) or an interrupt handler
+     */
+    pub fn enter(&mut self, address: u16) {
+        self.subroutine(address).calls += 1;
+    }
+
+    /**
+     * Subroutines sorted by the cycles spent in them, hottest first
+     */
+    pub fn hot_subroutines(&self) -> Vec<SubroutineCycles> {
+        let mut subroutines: Vec<SubroutineCycles> = self.subroutines.values().cloned().collect();
+        subroutines.sort_by(|a, b| {
+            b.inclusive_cycles
+                .cmp(&a.inclusive_cycles)
+                .then(b.exclusive_cycles.cmp(&a.exclusive_cycles))
+                .then(a.address.cmp(&b.address))
+        });
+
+        subroutines
+    }
+
+    /**
+     * Returns the executed opcodes in opcode order
+     *
+     # Arguments
+
+     * `mnemonic` - Names the opcode (e.g. from the instruction table)
+     */
+    pub fn opcodes(&self, mnemonic: impl Fn(u8) -> String) -> Vec<OpcodeCycles> {
+        (0..=0xFF_u8)
+            .filter(|opcode| self.opcode_counts[*opcode as usize] > 0)
+            .map(|opcode| OpcodeCycles {
+                opcode,
+                mnemonic: mnemonic(opcode),
+                count: self.opcode_counts[opcode as usize],
+                cycles: self.opcode_cycles[opcode as usize],
+            })
+            .collect()
+    }
+
+    /**
+     * Returns the cycles spent at every executed program counter, in address order
+     */
+    pub fn program_counters(&self) -> Vec<AddressCycles> {
+        (0..ADDRESS_SPACE)
+            .filter(|address| self.pc_cycles[*address] > 0)
+            .map(|address| AddressCycles { address: address as u16, cycles: self.pc_cycles[address] })
+            .collect()
+    }
+
+    pub fn total_cycles(&self) -> u64 {
+        self.total_cycles
+    }
+}
+
+/**
+ * Everything the profiler counted, without the untouched addresses and opcodes
+ */
+#[derive(Debug, Serialize)]
+pub struct ProfileReport {
+    pub total_cycles: u64,
+    pub memory: Vec<AddressAccesses>,
+    pub opcodes: Vec<OpcodeCycles>,
+    pub program_counters: Vec<AddressCycles>,
+    pub hot_subroutines: Vec<SubroutineCycles>,
+}
+
+// exporting
+impl ProfileReport {
+    pub fn to_json(&self) -> String {
+        serde_json::to_string(self).unwrap()
+    }
+
+    pub fn memory_csv(&self) -> String {
+        let mut csv = String::from("address,reads,writes,executes\n");
+        for row in self.memory.iter() {
+            writeln!(csv, "{:04X},{},{},{}", row.address, row.reads, row.writes, row.executes).unwrap();
+        }
+        csv
+    }
+
+    pub fn opcodes_csv(&self) -> String {
+        let mut csv = String::from("opcode,mnemonic,count,cycles\n");
+        for row in self.opcodes.iter() {
+            writeln!(csv, "{:02X},{},{},{}", row.opcode, row.mnemonic, row.count, row.cycles).unwrap();
+        }
+        csv
+    }
+
+    pub fn program_counters_csv(&self) -> String {
+        let mut csv = String::from("address,cycles\n");
+        for row in self.program_counters.iter() {
+            writeln!(csv, "{:04X},{}", row.address, row.cycles).unwrap();
+        }
+        csv
+    }
+
+    pub fn hot_subroutines_csv(&self) -> String {
+        let mut csv = String::from("address,calls,inclusive_cycles,exclusive_cycles\n");
+        for row in self.hot_subroutines.iter() {
+            writeln!(csv, "{:04X},{},{},{}", row.address, row.calls, row.inclusive_cycles, row.exclusive_cycles).unwrap();
+        }
+        csv
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use crate::call_stack::FrameKind;
+
+    /**
+     * Whether or not cycles are attributed to the subroutines, inclusively and exclusively
+     */
+    #[test]
+    fn subroutine_attribution() {
+        let frame = |target: u16, stack_pointer: u8| CallFrame {
+            kind: FrameKind::Subroutine,
+            call_site: 0x0000,
+            target,
+            return_address: 0x0000,
+            stack_pointer,
+        };
+        let sub = [frame(0x1000, 0xFD)];
+        let inner = [frame(0x1000, 0xFD), frame(0x2000, 0xFB)];
+        let mut counters = CycleCounters::new();
+
+        // main: JSR $1000, sub: JSR $2000 ... RTS, inner: NOP, RTS
+        counters.record_instruction(0x0200, 0x20, 6, &[]);
+        counters.enter(0x1000);
+        counters.record_instruction(0x1000, 0x20, 6, &sub);
+        counters.enter(0x2000);
+        counters.record_instruction(0x2000, 0xEA, 2, &inner);
+        counters.record_instruction(0x2001, 0x60, 6, &inner);
+        counters.record_instruction(0x1003, 0x60, 6, &sub);
+
+        let hot = counters.hot_subroutines();
+        assert_eq!(hot[0], SubroutineCycles { address: 0x1000, calls: 1, inclusive_cycles: 20, exclusive_cycles: 12 });
+        assert_eq!(hot[1], SubroutineCycles { address: 0x2000, calls: 1, inclusive_cycles: 8, exclusive_cycles: 8 });
+
+        assert_eq!(counters.total_cycles(), 26);
+        let opcodes = counters.opcodes(|opcode| format!("{:02X}", opcode));
+        assert_eq!(opcodes[0], OpcodeCycles { opcode: 0x20, mnemonic: String::from("20"), count: 2, cycles: 12 });
+
+        // a recursive call is counted once inclusively, an interrupt sequence goes to the interrupted code
+        counters.record_instruction(0x1000, 0xEA, 2, &[frame(0x1000, 0xFD), frame(0x1000, 0xFB)]);
+        counters.record_interrupt(7, &sub);
+        assert_eq!(counters.hot_subroutines()[0], SubroutineCycles { address: 0x1000, calls: 1, inclusive_cycles: 29, exclusive_cycles: 21 });
+        assert_eq!(counters.total_cycles(), 35);
+    }
+
+    /**
+     * Whether or not only the touched addresses are exported
+     */
+    #[test]
+    fn memory_csv() {
+        let counters = AccessCounters::new();
+        counters.record_read(0x0010);
+        counters.record_read(0x0010);
+        counters.record_write(0xFFFF);
+
+        let report = ProfileReport {
+            total_cycles: 0,
+            memory: counters.touched(),
+            opcodes: Vec::new(),
+            program_counters: Vec::new(),
+            hot_subroutines: Vec::new(),
+        };
+        assert_eq!(report.memory_csv(), "address,reads,writes,executes\n0010,2,0,0\nFFFF,0,1,0\n");
+        assert_eq!(counters.heatmap().writes[0xFFFF], 1);
+    }
+}
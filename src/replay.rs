@@ -0,0 +1,102 @@
+use serde::{Deserialize, Serialize};
+
+/**
+ * Something happening to the machine from outside of it
+ */
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "kind")]
+pub enum InputEvent {
+    /// Input given to the device mapped at `address`, see `BusDevice::input()`
+    Device { address: u16, port: u8, value: u8 },
+    Irq,
+    Nmi,
+    Reset,
+}
+
+/**
+ * An input along with the processor cycle it happened at
+ */
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TimedInput {
+    pub cycle: u64,
+    #[serde(flatten)]
+    pub event: InputEvent,
+}
+
+/**
+ * Recorded inputs of a session, in cycle order
+ *
+ * Replaying them from the same starting state (program, reset) reproduces the session exactly,
+ * the log is JSON so that sessions recorded by the browser frontend can be replayed natively.
+ */
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct InputLog {
+    pub events: Vec<TimedInput>,
+}
+
+// Constructor like implementation
+impl InputLog {
+    pub fn new() -> Self {
+        Self {
+            ..Default::default()
+        }
+    }
+}
+
+impl InputLog {
+    pub fn push(&mut self, cycle: u64, event: InputEvent) {
+        self.events.push(TimedInput { cycle, event });
+    }
+
+    pub fn len(&self) -> usize {
+        self.events.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.events.is_empty()
+    }
+
+    pub fn to_json(&self) -> String {
+        serde_json::to_string(self).unwrap()
+    }
+
+    pub fn from_json(json: &str) -> Result<Self, String> {
+        let mut log: Self = serde_json::from_str(json).map_err(|error| error.to_string())?;
+        // replaying relies on the order, a stable sort keeps same cycle events as recorded
+        log.events.sort_by_key(|input| input.cycle);
+        Ok(log)
+    }
+
+    pub fn save(&self, path: &str) -> Result<(), String> {
+        std::fs::write(path, self.to_json()).map_err(|error| error.to_string())
+    }
+
+    pub fn load(path: &str) -> Result<Self, String> {
+        let json = std::fs::read_to_string(path).map_err(|error| error.to_string())?;
+        Self::from_json(&json)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    /**
+     * Whether or not a log survives a JSON round trip, events being put back in cycle order
+     */
+    #[test]
+    fn json_round_trip() {
+        let json = r#"{"events":[{"cycle":40,"kind":"Nmi"},{"cycle":12,"kind":"Device","address":53264,"port":0,"value":65}]}"#;
+        let log = InputLog::from_json(json).unwrap();
+
+        assert_eq!(
+            log.events,
+            vec![
+                TimedInput { cycle: 12, event: InputEvent::Device { address: 0xD010, port: 0, value: b'A' } },
+                TimedInput { cycle: 40, event: InputEvent::Nmi },
+            ]
+        );
+        assert_eq!(InputLog::from_json(&log.to_json()).unwrap(), log);
+    }
+}
@@ -1,16 +1,14 @@
 use std::io::{BufReader, Read};
 use std::fs::File;
 
-use num::traits::int::PrimInt;
-
 use serde::{Deserialize, Serialize};
 
-#[derive(Deserialize, Serialize)]
-pub struct Rom<T: PrimInt + std::convert::From<u8>> {
-    pub rom: Vec<T>,
+#[derive(Clone, Deserialize, Serialize)]
+pub struct Rom {
+    pub rom: Vec<u8>,
 }
 
-impl<T: PrimInt + std::convert::From<u8>> Rom<T> {
+impl Rom {
     /**
     Returns the Rom with given size
 
@@ -23,13 +21,13 @@ impl<T: PrimInt + std::convert::From<u8>> Rom<T> {
     */
     pub fn new(size_b: usize) -> Self {
         Self {
-            rom: vec![T::zero(); size_b],
+            rom: vec![0x00; size_b],
         }
     }
 }
 
-impl<T: PrimInt + std::convert::From<u8>> From<Vec<T>> for Rom<T> {
-    fn from(vector: Vec<T>) -> Self {
+impl From<Vec<u8>> for Rom {
+    fn from(vector: Vec<u8>) -> Self {
         Self {
             rom: vector,
         }
@@ -37,11 +35,11 @@ impl<T: PrimInt + std::convert::From<u8>> From<Vec<T>> for Rom<T> {
 }
 
 // overloading [] for read access
-impl<T: PrimInt + std::convert::From<u8>> std::ops::Index<u16> for Rom<T> {
-    type Output = T;
+impl std::ops::Index<u16> for Rom {
+    type Output = u8;
 
-    fn index(&self, index: u16) -> &T {
-        return {
+    fn index(&self, index: u16) -> &u8 {
+        {
             if index < self.rom.len() as u16 {
                 let opt = self.rom.get(index as usize);
                 match opt {
@@ -51,12 +49,12 @@ impl<T: PrimInt + std::convert::From<u8>> std::ops::Index<u16> for Rom<T> {
             } else {
                panic!("Invalid Read Address");
             }
-        };
+        }
     }
 }
 
 // loading data into Rom
-impl<T: PrimInt + std::convert::From<u8>> Rom<T> {
+impl Rom {
 
     pub fn load(&mut self, filepath: &str) -> bool {
 
@@ -64,17 +62,17 @@ impl<T: PrimInt + std::convert::From<u8>> Rom<T> {
         let file = File::open(filepath);
 
         // to place the read file
-        let mut buffer_for_rom = vec![0 as u8; self.rom.len()];
+        let mut buffer_for_rom = vec![0_u8; self.rom.len()];
         let buffer_size = buffer_for_rom.len();
 
         match file {
             Ok(opened_file) => {
                     let mut buffered_reader = BufReader::new(opened_file);
-                    if let Ok(()) = buffered_reader.read_exact(&mut buffer_for_rom[0..0 + buffer_size]) {
+                    if let Ok(()) = buffered_reader.read_exact(&mut buffer_for_rom[0..buffer_size]) {
 
                         // copying the value from buffer to rom
                         for (i, value) in self.rom.iter_mut().enumerate() {
-                            *value = buffer_for_rom[i].into();
+                            *value = buffer_for_rom[i];
                         }
                         true
                     } else {
@@ -87,22 +85,22 @@ impl<T: PrimInt + std::convert::From<u8>> Rom<T> {
 }
 
 // overloading [] for read/write access
-impl<T: PrimInt + std::convert::From<u8>> std::ops::IndexMut<u16> for Rom<T> {
+impl std::ops::IndexMut<u16> for Rom {
 
-    fn index_mut(&mut self, index: u16) -> &mut T {
-        return {
+    fn index_mut(&mut self, index: u16) -> &mut u8 {
+        {
             if index < self.rom.len() as u16 {
                 self.rom.get_mut(index as usize).unwrap()
             } else {
                 panic!("Invalid write Address"); 
             }
-        };
+        }
         
     }
 }
 
 // helper functions
-impl<T: PrimInt + std::convert::From<u8>> Rom<T> {
+impl Rom {
     pub fn len(&self) -> usize {
         self.rom.len()
     }
@@ -0,0 +1,76 @@
+use serde::{Deserialize, Serialize};
+
+use crate::memory::Memory;
+use crate::rom::Rom;
+
+/**
+ * Everything the processor needs to resume exactly where it was, mid-instruction included
+ */
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CpuState {
+    pub accumulator: u8,
+    pub index_register_x: u8,
+    pub index_register_y: u8,
+    pub status: u8,
+    pub stack_pointer: u8,
+    pub program_counter: u16,
+
+    pub fetched: u8,
+    pub temp: u16,
+    pub address_absolute: u16,
+    pub address_relative: u16,
+    pub opcode: u8,
+    pub cycles: u8,
+    pub opcode_address: u16,
+    pub halted: bool,
+    /// An NMI edge was latched and waits for the current instruction to complete
+    #[serde(default)]
+    pub nmi_pending: bool,
+    /// An IRQ was given through `Processor::input()` and waits to be serviced
+    #[serde(default)]
+    pub irq_pending: bool,
+}
+
+/**
+ * Save state of the processor and everything on its bus: storages, mapper and devices
+ *
+ * The mapper and device states are opaque bytes given by `Mapper::save_registers()` and `BusDevice::save_state()`,
+ * they are only restored onto the same kind of mapper and the same devices attached in the same order.
+ */
+#[derive(Clone, Serialize, Deserialize)]
+pub struct MachineState {
+    pub cpu: CpuState,
+    pub memory: Memory,
+    pub other: Vec<u8>,
+    pub secondary_storage: Rom,
+    #[serde(default)]
+    pub mapper_registers: Vec<u8>,
+    #[serde(default)]
+    pub mapper_ram: Vec<u8>,
+    /// States of the attached devices, in attach order
+    #[serde(default)]
+    pub devices: Vec<Vec<u8>>,
+}
+
+impl MachineState {
+    /**
+     * Approximate number of bytes the state takes in memory
+     */
+    pub fn size(&self) -> usize {
+        std::mem::size_of::<Self>()
+            + self.memory.len()
+            + self.other.len()
+            + self.secondary_storage.len()
+            + self.mapper_registers.len()
+            + self.mapper_ram.len()
+            + self.devices.iter().map(Vec::len).sum::<usize>()
+    }
+
+    pub fn to_json(&self) -> String {
+        serde_json::to_string(self).unwrap()
+    }
+
+    pub fn from_json(json: &str) -> Result<Self, String> {
+        serde_json::from_str(json).map_err(|error| error.to_string())
+    }
+}
@@ -0,0 +1,265 @@
+use std::str::FromStr;
+use std::time::Duration;
+
+use crate::processor::{Processor, RunResult};
+
+/// Clock of the original 6502 boards (Apple-1, KIM-1, ...)
+pub const MHZ_1: u32 = 1_000_000;
+/// NTSC NES clock (2A03)
+pub const MHZ_1_79: u32 = 1_789_773;
+/// Clock of the later 6502 systems (BBC Micro, ...)
+pub const MHZ_2: u32 = 2_000_000;
+
+/// Number of cycles run per `advance()` call when the speed isn't limited
+const UNLIMITED_CYCLES_PER_ADVANCE: u64 = 100_000;
+
+/// Elapsed time longer than this isn't caught up on (e.g. a backgrounded browser tab)
+const MAX_CATCH_UP: Duration = Duration::from_millis(100);
+
+/// Achieved speed is measured over windows of this length
+const SPEED_MEASUREMENT_WINDOW: Duration = Duration::from_secs(1);
+
+/**
+ * The speed the processor should run at
+ */
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ClockRate {
+    /// Cycles per second
+    Hz(u32),
+    /// As fast as the host allows
+    Unlimited,
+}
+
+impl FromStr for ClockRate {
+    type Err = String;
+
+    /**
+     * Accepts `unlimited`, a frequency with a `mhz`/`khz`/`hz` suffix (e.g. `1.79mhz`) or plain hertz
+     */
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim().to_lowercase();
+
+        if s == "unlimited" {
+            return Ok(ClockRate::Unlimited);
+        }
+
+        let (number, multiplier) = if let Some(number) = s.strip_suffix("mhz") {
+            (number, 1_000_000.0)
+        } else if let Some(number) = s.strip_suffix("khz") {
+            (number, 1_000.0)
+        } else if let Some(number) = s.strip_suffix("hz") {
+            (number, 1.0)
+        } else {
+            (s.as_str(), 1.0)
+        };
+
+        match number.trim().parse::<f64>() {
+            Ok(value) if value > 0.0 && value * multiplier <= u32::MAX as f64 => {
+                Ok(ClockRate::Hz((value * multiplier).round() as u32))
+            }
+            _ => Err(format!("Invalid clock rate: {}", s)),
+        }
+    }
+}
+
+/**
+ * Relates processor cycles to wall time
+ *
+ * The scheduler doesn't read the time itself, the caller passes the elapsed time to `advance()`.
+ * That way the same scheduler works natively (`std::time::Instant`) and in the browser (`performance.now()`).
+ */
+pub struct Scheduler {
+    clock_rate: ClockRate,
+    /// Runs unthrottled while set, without forgetting the configured clock rate
+    warp: bool,
+    paused: bool,
+
+    /// Fraction of a cycle carried over between `advance()` calls, so that rounding doesn't drift
+    cycle_remainder: f64,
+
+    // achieved speed measurement
+    window_cycles: u64,
+    window_time: Duration,
+    achieved_hz: f64,
+}
+
+impl Default for Scheduler {
+    fn default() -> Self {
+        Self::new(ClockRate::Hz(MHZ_1))
+    }
+}
+
+// Constructor like implementation
+impl Scheduler {
+    pub fn new(clock_rate: ClockRate) -> Self {
+        Self {
+            clock_rate,
+            warp: false,
+            paused: false,
+            cycle_remainder: 0.0,
+            window_cycles: 0,
+            window_time: Duration::ZERO,
+            achieved_hz: 0.0,
+        }
+    }
+}
+
+// getters and setters
+impl Scheduler {
+    pub fn clock_rate(&self) -> ClockRate {
+        self.clock_rate
+    }
+
+    pub fn set_clock_rate(&mut self, clock_rate: ClockRate) {
+        self.clock_rate = clock_rate;
+        self.cycle_remainder = 0.0;
+    }
+
+    pub fn is_warp(&self) -> bool {
+        self.warp
+    }
+
+    pub fn set_warp(&mut self, warp: bool) {
+        self.warp = warp;
+        self.cycle_remainder = 0.0;
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.paused
+    }
+
+    pub fn set_paused(&mut self, paused: bool) {
+        self.paused = paused;
+        self.cycle_remainder = 0.0;
+    }
+
+    /**
+     * Returns `true` if the caller should wait between `advance()` calls
+     */
+    pub fn is_throttled(&self) -> bool {
+        !self.warp && self.clock_rate != ClockRate::Unlimited
+    }
+
+    /**
+     * Cycles per second actually achieved, measured over the last complete window
+     */
+    pub fn achieved_hz(&self) -> f64 {
+        self.achieved_hz
+    }
+}
+
+impl Scheduler {
+    /**
+     * Returns the number of cycles the processor owes after `elapsed` time has passed
+     */
+    fn cycle_budget(&mut self, elapsed: Duration) -> u64 {
+        match self.clock_rate {
+            ClockRate::Hz(hz) if !self.warp => {
+                let elapsed = elapsed.min(MAX_CATCH_UP);
+                let cycles = hz as f64 * elapsed.as_secs_f64() + self.cycle_remainder;
+
+                self.cycle_remainder = cycles.fract();
+                cycles as u64
+            }
+            _ => UNLIMITED_CYCLES_PER_ADVANCE,
+        }
+    }
+
+    /**
+     * Runs the processor for the time that has passed since the previous call
+     *
+     # Arguments
+
+     * `processor` - The processor to clock
+     * `elapsed` - Wall time since the previous `advance()` call
+
+     # Returns
+     `None` while paused, the result of `Processor::run()` otherwise
+     */
+    pub fn advance(&mut self, processor: &mut Processor, elapsed: Duration) -> Option<RunResult> {
+        if self.paused {
+            return None;
+        }
+
+        let cycle_budget = self.cycle_budget(elapsed);
+        let result = processor.run(cycle_budget);
+
+        // anything still owed is dropped when the run was cut short
+        if result.cycles < cycle_budget {
+            self.cycle_remainder = 0.0;
+        }
+
+        self.window_cycles += result.cycles;
+        self.window_time += elapsed;
+        if self.window_time >= SPEED_MEASUREMENT_WINDOW {
+            self.achieved_hz = self.window_cycles as f64 / self.window_time.as_secs_f64();
+            self.window_cycles = 0;
+            self.window_time = Duration::ZERO;
+        }
+
+        Some(result)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    /**
+     * Whether or not clock rates are parsed from the command line format
+     */
+    #[test]
+    fn parse_clock_rate() {
+        assert_eq!("1mhz".parse::<ClockRate>(), Ok(ClockRate::Hz(MHZ_1)));
+        assert_eq!("1.789773MHz".parse::<ClockRate>(), Ok(ClockRate::Hz(MHZ_1_79)));
+        assert_eq!("2000khz".parse::<ClockRate>(), Ok(ClockRate::Hz(MHZ_2)));
+        assert_eq!("500".parse::<ClockRate>(), Ok(ClockRate::Hz(500)));
+        assert_eq!("unlimited".parse::<ClockRate>(), Ok(ClockRate::Unlimited));
+        assert!("fast".parse::<ClockRate>().is_err());
+        assert!("0mhz".parse::<ClockRate>().is_err());
+    }
+
+    /**
+     * Whether or not the cycle budget follows the clock rate without drifting
+     */
+    #[test]
+    fn budget_follows_clock_rate() {
+        let mut scheduler = Scheduler::new(ClockRate::Hz(MHZ_1_79));
+
+        // 60 frames of 1/60th of a second should be exactly one second worth of cycles
+        let frame = Duration::from_secs(1) / 60;
+        let total: u64 = (0..60).map(|_| scheduler.cycle_budget(frame)).sum();
+        assert!((total as i64 - MHZ_1_79 as i64).abs() <= 1);
+
+        // long pauses aren't caught up on
+        scheduler.set_clock_rate(ClockRate::Hz(MHZ_1));
+        assert_eq!(scheduler.cycle_budget(Duration::from_secs(10)), MHZ_1 as u64 / 10);
+
+        scheduler.set_warp(true);
+        assert!(!scheduler.is_throttled());
+        assert_eq!(scheduler.cycle_budget(frame), UNLIMITED_CYCLES_PER_ADVANCE);
+    }
+
+    /**
+     * Whether or not pausing stops the processor and the achieved speed is measured
+     */
+    #[test]
+    fn pause_and_achieved_speed() {
+        // JMP $0000 at the reset address
+        let mut processor = Processor::new();
+        processor.bus.write(0x0000, 0x4C);
+        processor.reset();
+
+        let mut scheduler = Scheduler::new(ClockRate::Hz(1000));
+
+        scheduler.set_paused(true);
+        assert_eq!(scheduler.advance(&mut processor, Duration::from_millis(10)), None);
+        scheduler.set_paused(false);
+
+        for _ in 0..10 {
+            scheduler.advance(&mut processor, Duration::from_millis(100));
+        }
+        assert!((scheduler.achieved_hz() - 1000.0).abs() < 1.0);
+    }
+}
@@ -0,0 +1,146 @@
+use std::collections::VecDeque;
+
+use serde::Serialize;
+
+/// Only the most recent events are kept
+const MAX_EVENTS: usize = 64;
+
+/**
+ * Something the stack guard noticed, `address` is the instruction (or interrupt) that caused it
+ */
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(tag = "kind")]
+pub enum StackEvent {
+    /// A push wrapped the stack pointer from 0x00 to 0xFF, overwriting the bottom of the stack
+    Overflow { address: u16 },
+    /// A pull wrapped the stack pointer from 0xFF to 0x00, reading below the bottom of the stack
+    Underflow { address: u16 },
+    /// The stack grew past the configured depth
+    DepthExceeded { address: u16, depth: u16 },
+}
+
+/**
+ * Optional watch over the stack pointer
+ *
+ * The stack pointer wraps around like on the real chip, the guard only reports it.
+ */
+#[derive(Debug, Default)]
+pub struct StackGuard {
+    /// Number of bytes on the stack above which `DepthExceeded` is reported
+    depth_threshold: Option<u16>,
+    events: VecDeque<StackEvent>,
+    /// Reported by `Processor::run()`, cleared once it has been
+    pending: Option<StackEvent>,
+}
+
+// Constructor like implementation
+impl StackGuard {
+    /**
+     * Returns a guard reporting wraps, and depths above `depth_threshold` if given
+     */
+    pub fn new(depth_threshold: Option<u16>) -> Self {
+        Self {
+            depth_threshold,
+            ..Default::default()
+        }
+    }
+}
+
+impl StackGuard {
+    pub fn depth_threshold(&self) -> Option<u16> {
+        self.depth_threshold
+    }
+
+    pub fn set_depth_threshold(&mut self, depth_threshold: Option<u16>) {
+        self.depth_threshold = depth_threshold;
+    }
+
+    /**
+     * Checks a push
+     *
+     # Arguments
+
+     * `address` - The instruction pushing
+     * `stack_pointer` - The stack pointer before the push
+     * `depth` - Number of bytes on the stack after the push
+     */
+    pub fn check_push(&mut self, address: u16, stack_pointer: u8, depth: u16) {
+        if stack_pointer == 0x00 {
+            self.report(StackEvent::Overflow { address });
+        }
+
+        // only the push crossing the threshold is reported
+        if let Some(threshold) = self.depth_threshold {
+            if depth == threshold + 1 {
+                self.report(StackEvent::DepthExceeded { address, depth });
+            }
+        }
+    }
+
+    /**
+     * Checks a pull, `stack_pointer` is the stack pointer before the pull
+     */
+    pub fn check_pull(&mut self, address: u16, stack_pointer: u8) {
+        if stack_pointer == 0xFF {
+            self.report(StackEvent::Underflow { address });
+        }
+    }
+
+    fn report(&mut self, event: StackEvent) {
+        if self.events.len() == MAX_EVENTS {
+            self.events.pop_front();
+        }
+        self.events.push_back(event);
+
+        // the first event of an instruction is the interesting one
+        self.pending.get_or_insert(event);
+    }
+
+    /**
+     * Returns the event not reported by `run()` yet
+     */
+    pub fn take_pending(&mut self) -> Option<StackEvent> {
+        self.pending.take()
+    }
+
+    /**
+     * Returns the events since the previous call, oldest first
+     */
+    pub fn take_events(&mut self) -> Vec<StackEvent> {
+        self.events.drain(..).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    /**
+     * Whether or not wraps and the threshold crossing are reported once
+     */
+    #[test]
+    fn reports() {
+        let mut guard = StackGuard::new(Some(2));
+
+        guard.check_push(0x8000, 0xFF, 1);
+        guard.check_push(0x8000, 0xFE, 2);
+        assert_eq!(guard.take_pending(), None);
+
+        guard.check_push(0x8001, 0xFD, 3);
+        guard.check_push(0x8001, 0xFC, 4);
+        assert_eq!(guard.take_pending(), Some(StackEvent::DepthExceeded { address: 0x8001, depth: 3 }));
+
+        guard.check_push(0x8002, 0x00, 0);
+        guard.check_pull(0x8003, 0xFF);
+        assert_eq!(
+            guard.take_events(),
+            vec![
+                StackEvent::DepthExceeded { address: 0x8001, depth: 3 },
+                StackEvent::Overflow { address: 0x8002 },
+                StackEvent::Underflow { address: 0x8003 },
+            ]
+        );
+        assert_eq!(guard.take_pending(), Some(StackEvent::Overflow { address: 0x8002 }));
+    }
+}
@@ -0,0 +1,313 @@
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+
+/**
+ * Labels for addresses, loaded from the assembler or emulator symbol files
+ *
+ * An address may have several labels, the first one loaded is the one shown.
+ */
+#[derive(Debug, Default, Clone)]
+pub struct SymbolTable {
+    names: BTreeMap<u16, Vec<String>>,
+    addresses: HashMap<String, u16>,
+}
+
+// Constructor like implementation
+impl SymbolTable {
+    pub fn new() -> Self {
+        Self {
+            ..Default::default()
+        }
+    }
+}
+
+impl SymbolTable {
+    pub fn insert(&mut self, name: &str, address: u16) {
+        if let Some(old_address) = self.addresses.insert(name.to_owned(), address) {
+            if let Some(names) = self.names.get_mut(&old_address) {
+                names.retain(|old_name| old_name != name);
+            }
+        }
+        self.names.entry(address).or_default().push(name.to_owned());
+    }
+
+    pub fn len(&self) -> usize {
+        self.addresses.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.addresses.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.names.clear();
+        self.addresses.clear();
+    }
+
+    pub fn address_of(&self, name: &str) -> Option<u16> {
+        self.addresses.get(name).copied()
+    }
+
+    /**
+     * Returns the label shown for the address
+     */
+    pub fn name_of(&self, address: u16) -> Option<&str> {
+        self.names.get(&address).and_then(|names| names.first()).map(String::as_str)
+    }
+
+    /**
+     * Returns the closest label at or below the address along with the distance to it
+     */
+    pub fn nearest(&self, address: u16) -> Option<(&str, u16)> {
+        self.names
+            .range(..=address)
+            .rev()
+            .find_map(|(label_address, names)| names.first().map(|name| (name.as_str(), address - label_address)))
+    }
+
+    /**
+     * Iterates over the labels in address order
+     */
+    pub fn iter(&self) -> impl Iterator<Item = (u16, &str)> {
+        self.names
+            .iter()
+            .flat_map(|(address, names)| names.iter().map(move |name| (*address, name.as_str())))
+    }
+
+    /**
+     * Returns the label of the address, or the address in hexadecimal (`$XXXX`)
+     */
+    pub fn format_address(&self, address: u16) -> String {
+        match self.name_of(address) {
+            Some(name) => name.to_owned(),
+            None => format!("${:04X}", address),
+        }
+    }
+
+    /**
+     * Reads an address typed by the user
+     *
+     * Accepts labels, `label+offset`, `$hex`, `0xhex`, `#decimal` and plain hexadecimal
+     */
+    pub fn resolve(&self, text: &str) -> Option<u16> {
+        let text = text.trim();
+
+        if let Some(address) = self.address_of(text) {
+            return Some(address);
+        }
+
+        if let Some((base, offset)) = text.split_once('+') {
+            return self.resolve(base)?.checked_add(self.resolve(offset)?);
+        }
+
+        // plain numbers are hexadecimal, like in every other monitor
+        if text.starts_with(['$', '%', '#']) || text.starts_with("0x") {
+            parse_number(text)
+        } else {
+            u16::from_str_radix(text, 16).ok()
+        }
+    }
+
+    /**
+     * Adds the labels of every `(name, address)` pair
+     */
+    pub fn extend<'a>(&mut self, symbols: impl IntoIterator<Item = (&'a str, u16)>) {
+        for (name, address) in symbols {
+            self.insert(name, address);
+        }
+    }
+}
+
+/**
+ * Parses `$hex`, `0xhex`, `%binary` and decimal numbers (`#` prefixed or not)
+ */
+pub fn parse_number(text: &str) -> Option<u16> {
+    let text = text.trim();
+
+    if let Some(hex) = text.strip_prefix('$').or_else(|| text.strip_prefix("0x")).or_else(|| text.strip_prefix("0X")) {
+        u16::from_str_radix(hex, 16).ok()
+    } else if let Some(binary) = text.strip_prefix('%') {
+        u16::from_str_radix(binary, 2).ok()
+    } else {
+        text.strip_prefix('#').unwrap_or(text).parse::<u16>().ok()
+    }
+}
+
+/**
+ * Splits a line of an ld65 debug file (`kind<TAB>key=value,key="value",...`) into its kind and fields
+ */
+pub fn parse_dbg_record(line: &str) -> Option<(&str, HashMap<&str, &str>)> {
+    let (kind, fields) = line.split_once('\t')?;
+
+    // commas inside a quoted value (e.g. a file name) don't separate fields
+    let mut quoted = false;
+    let fields = fields
+        .split(|character| {
+            if character == '"' {
+                quoted = !quoted;
+            }
+            character == ',' && !quoted
+        })
+        .filter_map(|field| field.split_once('='))
+        .map(|(key, value)| (key, value.trim_matches('"')))
+        .collect();
+
+    Some((kind, fields))
+}
+
+// loading symbol files
+impl SymbolTable {
+    /**
+     * Loads the labels of a ca65/ld65 debug file (`ld65 --dbgfile`)
+     *
+     * Only labels (`type=lab`) are loaded, constants aren't addresses.
+     */
+    pub fn load_ld65_dbg(&mut self, text: &str) -> usize {
+        let before = self.len();
+
+        for (kind, fields) in text.lines().filter_map(parse_dbg_record) {
+            if kind != "sym" || fields.get("type") != Some(&"lab") {
+                continue;
+            }
+
+            if let (Some(name), Some(address)) = (fields.get("name"), fields.get("val").and_then(|value| parse_number(value))) {
+                self.insert(name, address);
+            }
+        }
+
+        self.len() - before
+    }
+
+    /**
+     * Loads a VICE label file, lines of the form `al C:8000 .main`
+     */
+    pub fn load_vice_labels(&mut self, text: &str) -> usize {
+        let before = self.len();
+
+        for line in text.lines() {
+            let mut words = line.split_whitespace();
+            if words.next() != Some("al") {
+                continue;
+            }
+
+            let address = words.next().map(|address| address.rsplit(':').next().unwrap_or(address));
+            let name = words.next().map(|name| name.trim_start_matches('.'));
+
+            if let (Some(address), Some(name)) = (address.and_then(|address| u16::from_str_radix(address, 16).ok()), name) {
+                self.insert(name, address);
+            }
+        }
+
+        self.len() - before
+    }
+
+    /**
+     * Loads `name = $addr` lines (`:=` works too), comments start with `;` or `#`
+     */
+    pub fn load_assignments(&mut self, text: &str) -> usize {
+        let before = self.len();
+
+        for line in text.lines() {
+            let line = line.split([';', '#']).next().unwrap_or("");
+
+            if let Some((name, value)) = line.split_once('=') {
+                let name = name.trim().trim_end_matches(':').trim();
+                if let (false, Some(address)) = (name.is_empty() || name.contains(char::is_whitespace), parse_number(value)) {
+                    self.insert(name, address);
+                }
+            }
+        }
+
+        self.len() - before
+    }
+
+    /**
+     * Loads a symbol file of any of the supported formats, guessing the format from its content
+     *
+     # Returns
+     the number of labels loaded
+     */
+    pub fn load_str(&mut self, text: &str) -> usize {
+        let first_line = text.lines().find(|line| !line.trim().is_empty()).unwrap_or("");
+
+        if first_line.starts_with("version\t") {
+            self.load_ld65_dbg(text)
+        } else if first_line.starts_with("al ") {
+            self.load_vice_labels(text)
+        } else {
+            self.load_assignments(text)
+        }
+    }
+
+    pub fn load_file(&mut self, filepath: &str) -> std::io::Result<usize> {
+        Ok(self.load_str(&fs::read_to_string(filepath)?))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    /**
+     * Whether or not each file format is recognized and loaded
+     */
+    #[test]
+    fn load_formats() {
+        let mut symbols = SymbolTable::new();
+
+        let dbg = "version\tmajor=2,minor=0\n\
+                   sym\tid=0,name=\"reset\",addrsize=absolute,scope=0,def=3,ref=7,val=0x8000,seg=0,type=lab\n\
+                   sym\tid=1,name=\"SCREEN\",addrsize=absolute,scope=0,def=1,val=0x400,type=equ\n";
+        assert_eq!(symbols.load_str(dbg), 1);
+        assert_eq!(symbols.address_of("reset"), Some(0x8000));
+
+        assert_eq!(symbols.load_str("al C:9000 .print\nal C:9010 .print_loop\n"), 2);
+        assert_eq!(symbols.name_of(0x9010), Some("print_loop"));
+
+        assert_eq!(symbols.load_str("; constants\nCHROUT = $FFD2\nbuffer := 0x0200 # input\nnot a label\n"), 2);
+        assert_eq!(symbols.address_of("CHROUT"), Some(0xFFD2));
+        assert_eq!(symbols.address_of("buffer"), Some(0x0200));
+    }
+
+    /**
+     * Whether or not the fields of a debug file record are only split on the commas outside quotes
+     */
+    #[test]
+    fn dbg_record_with_quoted_commas() {
+        let (kind, fields) = parse_dbg_record("file\tid=0,name=\"src/a,b.s\",size=120,mtime=0x5F5E1000,mod=0").unwrap();
+        assert_eq!(kind, "file");
+        assert_eq!(fields["name"], "src/a,b.s");
+        assert_eq!((fields["size"], fields["mod"]), ("120", "0"));
+        assert_eq!(fields.len(), 5);
+
+        assert!(parse_dbg_record("no tab").is_none());
+    }
+
+    /**
+     * Whether or not addresses are resolved from labels and numbers and shown as labels
+     */
+    #[test]
+    fn resolve_and_format() {
+        let mut symbols = SymbolTable::new();
+        symbols.insert("main", 0x8000);
+        symbols.insert("start", 0x8000);
+
+        assert_eq!(symbols.resolve("main"), Some(0x8000));
+        assert_eq!(symbols.resolve("main+$10"), Some(0x8010));
+        assert_eq!(symbols.resolve("$C000"), Some(0xC000));
+        assert_eq!(symbols.resolve("fffc"), Some(0xFFFC));
+        assert_eq!(symbols.resolve("1000"), Some(0x1000));
+        assert_eq!(symbols.resolve("#256"), Some(0x0100));
+        assert_eq!(symbols.resolve("nowhere"), None);
+
+        assert_eq!(symbols.format_address(0x8000), "main");
+        assert_eq!(symbols.format_address(0x8001), "$8001");
+        assert_eq!(symbols.nearest(0x8003), Some(("main", 3)));
+
+        // moving a label
+        symbols.insert("main", 0x9000);
+        assert_eq!(symbols.name_of(0x8000), Some("start"));
+        assert_eq!(symbols.len(), 2);
+    }
+}
@@ -0,0 +1,673 @@
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use serde::Deserialize;
+
+use crate::processor::{Processor, Registers};
+use crate::symbols::SymbolTable;
+
+/// Cycles a test may take when its script doesn't say otherwise
+pub const DEFAULT_MAX_CYCLES: u64 = 1_000_000;
+
+/// Status register of a test that sets none, the unused and interrupt disable bits like after a reset
+const DEFAULT_STATUS: u8 = 0x24;
+
+/**
+ * An address in a script, either a number or text resolved like in the monitor (`label`, `label+2`, `$0200`, ...)
+ */
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(untagged)]
+pub enum Location {
+    Address(u16),
+    Name(String),
+}
+
+/**
+ * Bytes loaded before every test, from a binary file or given inline
+ */
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Segment {
+    pub address: Location,
+    /// Binary file, relative to the script
+    pub file: Option<String>,
+    #[serde(default)]
+    pub bytes: Vec<u8>,
+}
+
+/**
+ * Register values, the ones left out aren't set (or checked)
+ */
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct RegisterValues {
+    pub a: Option<u8>,
+    pub x: Option<u8>,
+    pub y: Option<u8>,
+    pub sp: Option<u8>,
+    pub status: Option<u8>,
+}
+
+/**
+ * What a test checks once the routine has returned
+ */
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Expectations {
+    #[serde(default)]
+    pub registers: RegisterValues,
+    /// Flags by letter (`n`, `v`, `u`, `b`, `d`, `i`, `z`, `c`)
+    #[serde(default)]
+    pub flags: BTreeMap<String, bool>,
+    /// Bytes expected from each address on
+    #[serde(default)]
+    pub memory: BTreeMap<String, Vec<u8>>,
+}
+
+/**
+ * A single test: set registers and memory, call a routine and check the outcome
+ */
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct TestCase {
+    pub name: String,
+    /// Routine called with `Processor::call()`, the test runs until it returns
+    pub call: Location,
+    pub max_cycles: Option<u64>,
+    #[serde(default)]
+    pub registers: RegisterValues,
+    /// Bytes written from each address on before the call
+    #[serde(default)]
+    pub memory: BTreeMap<String, Vec<u8>>,
+    #[serde(default)]
+    pub expect: Expectations,
+}
+
+/**
+ * A test script, written in TOML or JSON
+ *
+ # Description
+ * Every test runs on a machine of its own, with the segments loaded and the labels of the symbol file
+   (any format `SymbolTable::load_file()` reads) known.
+ */
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct TestScript {
+    /// Name of the suite in the reports, the file name by default
+    pub name: Option<String>,
+    /// Symbol file, relative to the script
+    pub symbols: Option<String>,
+    #[serde(default)]
+    pub segments: Vec<Segment>,
+    #[serde(default)]
+    pub tests: Vec<TestCase>,
+
+    /// Where the files named by the script are looked up
+    #[serde(skip)]
+    pub base_directory: PathBuf,
+}
+
+/**
+ * How a test went
+ */
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Outcome {
+    Passed,
+    /// The routine returned, but some expectations weren't met
+    Failed(Vec<String>),
+    /// The test couldn't be carried out (unknown label, routine not returning, ...)
+    Error(String),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TestResult {
+    pub name: String,
+    pub outcome: Outcome,
+    /// Cycles the routine took, until it returned or the test stopped
+    pub cycles: u64,
+}
+
+/**
+ * The results of the tests of a script, in order
+ */
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TestReport {
+    pub suite: String,
+    pub results: Vec<TestResult>,
+}
+
+// Loading implementation
+impl TestScript {
+    pub fn from_toml(text: &str) -> Result<Self, String> {
+        toml::from_str(text).map_err(|error| error.to_string())
+    }
+
+    pub fn from_json(text: &str) -> Result<Self, String> {
+        serde_json::from_str(text).map_err(|error| error.to_string())
+    }
+
+    /**
+     * Reads a script, as JSON for a `.json` file and as TOML otherwise
+     */
+    pub fn load(filepath: &str) -> Result<Self, String> {
+        let text = fs::read_to_string(filepath).map_err(|error| error.to_string())?;
+        let path = Path::new(filepath);
+
+        let mut script = match path.extension().and_then(|extension| extension.to_str()) {
+            Some("json") => Self::from_json(&text)?,
+            _ => Self::from_toml(&text)?,
+        };
+
+        script.base_directory = path.parent().map_or_else(PathBuf::new, Path::to_path_buf);
+        if script.name.is_none() {
+            script.name = path.file_stem().map(|stem| stem.to_string_lossy().into_owned());
+        }
+        Ok(script)
+    }
+}
+
+// Running implementation
+impl TestScript {
+    /**
+     * Runs every test of the script
+     *
+     # Returns
+
+     * An error if the segments or the symbols can't be loaded, in which case no test has run
+     */
+    pub fn run(&self) -> Result<TestReport, String> {
+        let mut symbols = SymbolTable::new();
+        if let Some(file) = &self.symbols {
+            let path = self.base_directory.join(file);
+            symbols.load_file(&path.to_string_lossy()).map_err(|error| format!("{}: {}", path.display(), error))?;
+        }
+
+        let mut segments = Vec::new();
+        for segment in self.segments.iter() {
+            let mut bytes = segment.bytes.clone();
+            if let Some(file) = &segment.file {
+                let path = self.base_directory.join(file);
+                bytes.extend(fs::read(&path).map_err(|error| format!("{}: {}", path.display(), error))?);
+            }
+            segments.push((&segment.address, bytes));
+        }
+
+        let mut results = Vec::new();
+        for test in self.tests.iter() {
+            let mut proc = Processor::new();
+            proc.symbols = symbols.clone();
+            for (address, bytes) in segments.iter() {
+                let address = resolve(&proc, address)?;
+                write_bytes(&mut proc, address, bytes)?;
+            }
+
+            results.push(run_test(&mut proc, test));
+        }
+
+        Ok(TestReport {
+            suite: self.name.clone().unwrap_or_else(|| String::from("6502")),
+            results,
+        })
+    }
+}
+
+/**
+ * Writes the bytes from the address on the way a debugger would, so that the devices and the mapper aren't disturbed
+ */
+fn write_bytes(proc: &mut Processor, address: u16, bytes: &[u8]) -> Result<(), String> {
+    for (offset, byte) in bytes.iter().enumerate() {
+        let address = address.wrapping_add(offset as u16);
+        if !proc.bus.debug_write(address, *byte) {
+            return Err(format!("Nothing writable at ${:04X}", address));
+        }
+    }
+    Ok(())
+}
+
+fn resolve(proc: &Processor, location: &Location) -> Result<u16, String> {
+    match location {
+        Location::Address(address) => Ok(*address),
+        Location::Name(name) => proc.resolve_address(name).ok_or_else(|| format!("Unknown address {}", name)),
+    }
+}
+
+fn run_test(proc: &mut Processor, test: &TestCase) -> TestResult {
+    let mut result = TestResult { name: test.name.clone(), outcome: Outcome::Passed, cycles: 0 };
+
+    let (target, registers) = match set_up(proc, test) {
+        Ok(call) => call,
+        Err(error) => {
+            result.outcome = Outcome::Error(error);
+            return result;
+        }
+    };
+
+    result.outcome = match proc.call(target, &registers, test.max_cycles.unwrap_or(DEFAULT_MAX_CYCLES)) {
+        Ok(call) => {
+            result.cycles = call.cycles;
+            match check(proc, &test.expect) {
+                Ok(failures) if failures.is_empty() => Outcome::Passed,
+                Ok(failures) => Outcome::Failed(failures),
+                Err(error) => Outcome::Error(error),
+            }
+        }
+        Err(error) => {
+            result.cycles = error.cycles;
+            Outcome::Error(error.to_string())
+        }
+    };
+    result
+}
+
+/**
+ * Writes the memory of the test
+ *
+ # Returns
+
+ * The address of the routine to call and the registers to call it with
+ */
+fn set_up(proc: &mut Processor, test: &TestCase) -> Result<(u16, Registers), String> {
+    let values = &test.registers;
+    let registers = Registers {
+        accumulator: values.a.unwrap_or(0),
+        index_register_x: values.x.unwrap_or(0),
+        index_register_y: values.y.unwrap_or(0),
+        status: values.status.unwrap_or(DEFAULT_STATUS),
+        stack_pointer: values.sp.unwrap_or(0xFF),
+        program_counter: 0x0000,
+    };
+
+    for (address, bytes) in test.memory.iter() {
+        let address = resolve(proc, &Location::Name(address.clone()))?;
+        write_bytes(proc, address, bytes)?;
+    }
+
+    Ok((resolve(proc, &test.call)?, registers))
+}
+
+/**
+ * Returns a message for every expectation that isn't met
+ */
+fn check(proc: &Processor, expect: &Expectations) -> Result<Vec<String>, String> {
+    let registers = proc.registers();
+    let mut failures = Vec::new();
+
+    let expected = &expect.registers;
+    for (name, expected, actual) in [
+        ("A", expected.a, registers.accumulator),
+        ("X", expected.x, registers.index_register_x),
+        ("Y", expected.y, registers.index_register_y),
+        ("SP", expected.sp, registers.stack_pointer),
+        ("status", expected.status, registers.status),
+    ] {
+        if let Some(expected) = expected.filter(|expected| *expected != actual) {
+            failures.push(format!("{} is ${:02X}, expected ${:02X}", name, actual, expected));
+        }
+    }
+
+    for (flag, expected) in expect.flags.iter() {
+        // status bits from bit 0 on, `u` being the unused bit
+        let position = match "czidbuvn".find(flag.to_lowercase().as_str()).filter(|_| flag.len() == 1) {
+            Some(position) => position,
+            None => return Err(format!("Unknown flag {}", flag)),
+        };
+        let actual = registers.status & (1 << position) != 0;
+        if actual != *expected {
+            let state = |set: bool| if set { "set" } else { "clear" };
+            failures.push(format!("flag {} is {}, expected {}", flag.to_uppercase(), state(actual), state(*expected)));
+        }
+    }
+
+    for (address, expected) in expect.memory.iter() {
+        let start = resolve(proc, &Location::Name(address.clone()))?;
+        let actual: Vec<u8> = (0..expected.len()).map(|offset| proc.bus.peek(start.wrapping_add(offset as u16))).collect();
+        if actual != *expected {
+            failures.push(format!("memory at {} (${:04X}) is {}, expected {}", address, start, hex_bytes(&actual), hex_bytes(expected)));
+        }
+    }
+
+    Ok(failures)
+}
+
+fn hex_bytes(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02X}", byte)).collect::<Vec<String>>().join(" ")
+}
+
+// Reporting implementation
+impl TestReport {
+    pub fn passed(&self) -> usize {
+        self.count(|outcome| matches!(outcome, Outcome::Passed))
+    }
+
+    pub fn failed(&self) -> usize {
+        self.count(|outcome| matches!(outcome, Outcome::Failed(_)))
+    }
+
+    pub fn errors(&self) -> usize {
+        self.count(|outcome| matches!(outcome, Outcome::Error(_)))
+    }
+
+    /**
+     * Whether or not every test passed
+     */
+    pub fn success(&self) -> bool {
+        self.passed() == self.results.len()
+    }
+
+    fn count(&self, predicate: impl Fn(&Outcome) -> bool) -> usize {
+        self.results.iter().filter(|result| predicate(&result.outcome)).count()
+    }
+
+    /**
+     * Returns the report in the Test Anything Protocol (version 13), see `to_tap()`
+     */
+    pub fn to_tap(&self) -> String {
+        to_tap(std::slice::from_ref(self))
+    }
+
+    /**
+     * Returns the report as a JUnit XML document, see `to_junit()`
+     */
+    pub fn to_junit(&self) -> String {
+        to_junit(std::slice::from_ref(self))
+    }
+
+    /**
+     * Appends the `<testsuite>` element of the report
+     */
+    fn write_junit_suite(&self, output: &mut String) {
+        let _ = writeln!(
+            output,
+            "  <testsuite name=\"{}\" tests=\"{}\" failures=\"{}\" errors=\"{}\">",
+            xml_escape(&self.suite),
+            self.results.len(),
+            self.failed(),
+            self.errors()
+        );
+
+        for result in self.results.iter() {
+            let _ = write!(
+                output,
+                "    <testcase classname=\"{}\" name=\"{}\">",
+                xml_escape(&self.suite),
+                xml_escape(&result.name)
+            );
+
+            match &result.outcome {
+                Outcome::Passed => (),
+                Outcome::Failed(failures) => {
+                    let _ = write!(
+                        output,
+                        "\n      <failure message=\"{}\">{}</failure>\n    ",
+                        xml_escape(&failures[0]),
+                        xml_escape(&failures.join("\n"))
+                    );
+                }
+                Outcome::Error(error) => {
+                    let _ = write!(output, "\n      <error message=\"{}\"/>\n    ", xml_escape(error));
+                }
+            }
+            let _ = writeln!(output, "<system-out>{} cycles</system-out></testcase>", result.cycles);
+        }
+
+        output.push_str("  </testsuite>\n");
+    }
+}
+
+/**
+ * Returns the reports as a single Test Anything Protocol (version 13) stream, failures are detailed as YAML
+ *
+ # Description
+ * Tests are numbered across the reports, their names are prefixed with the suite when there are several.
+ */
+pub fn to_tap(reports: &[TestReport]) -> String {
+    let count: usize = reports.iter().map(|report| report.results.len()).sum();
+    let mut output = format!("TAP version 13\n1..{}\n", count);
+
+    let results = reports.iter().flat_map(|report| report.results.iter().map(move |result| (&report.suite, result)));
+    for (number, (suite, result)) in results.enumerate() {
+        let status = if result.outcome == Outcome::Passed { "ok" } else { "not ok" };
+        let _ = match reports.len() {
+            1 => writeln!(output, "{} {} - {}", status, number + 1, result.name),
+            _ => writeln!(output, "{} {} - {}: {}", status, number + 1, suite, result.name),
+        };
+
+        let messages = match &result.outcome {
+            Outcome::Passed => continue,
+            Outcome::Failed(failures) => failures.clone(),
+            Outcome::Error(error) => vec![error.clone()],
+        };
+        output.push_str("  ---\n  messages:\n");
+        for message in messages {
+            let _ = writeln!(output, "    - \"{}\"", message.replace('\\', "\\\\").replace('"', "\\\""));
+        }
+        let _ = writeln!(output, "  cycles: {}\n  ...", result.cycles);
+    }
+
+    output
+}
+
+/**
+ * Returns the reports as a JUnit XML document, one `<testsuite>` per report
+ *
+ # Description
+ * The emulated cycles of every test go to its `<system-out>`, as there's no wall clock time to report.
+ */
+pub fn to_junit(reports: &[TestReport]) -> String {
+    let mut output = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+    let _ = writeln!(
+        output,
+        "<testsuites tests=\"{}\" failures=\"{}\" errors=\"{}\">",
+        reports.iter().map(|report| report.results.len()).sum::<usize>(),
+        reports.iter().map(TestReport::failed).sum::<usize>(),
+        reports.iter().map(TestReport::errors).sum::<usize>()
+    );
+    for report in reports.iter() {
+        report.write_junit_suite(&mut output);
+    }
+    output.push_str("</testsuites>\n");
+    output
+}
+
+fn xml_escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&apos;")
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    const SCRIPT: &str = r#"
+        name = "math"
+
+        [[segments]]
+        address = "$0300"
+        # add16: ($10) + ($12) -> ($14)
+        bytes = [
+            0x18,             # CLC
+            0xA5, 0x10,       # LDA $10
+            0x65, 0x12,       # ADC $12
+            0x85, 0x14,       # STA $14
+            0xA5, 0x11,       # LDA $11
+            0x65, 0x13,       # ADC $13
+            0x85, 0x15,       # STA $15
+            0x60,             # RTS
+        ]
+
+        [[segments]]
+        address = 0x0320
+        bytes = [0x4C, 0x20, 0x03] # JMP $0320
+
+        [[tests]]
+        name = "adds with carry between the bytes"
+        call = "$0300"
+        memory = { "10" = [0xFF, 0x01], "12" = [0x01, 0x02] }
+        expect = { registers = { a = 0x04, sp = 0xFF }, flags = { c = false, z = false }, memory = { "$14" = [0x00, 0x04] } }
+
+        [[tests]]
+        name = "reports wrong results"
+        call = "$0300"
+        memory = { "10" = [0x01, 0x00], "12" = [0x01, 0x00] }
+        expect = { flags = { c = true }, memory = { "$14" = [0x03, 0x00] } }
+
+        [[tests]]
+        name = "never returns"
+        call = "$0320"
+        max_cycles = 100
+    "#;
+
+    /**
+     * Whether or not the tests of a script pass, fail and error as expected, and are reported as TAP and JUnit
+     */
+    #[test]
+    fn run_script() {
+        let report = TestScript::from_toml(SCRIPT).unwrap().run().unwrap();
+
+        assert_eq!(report.results[0].outcome, Outcome::Passed);
+        assert_eq!(report.results[0].cycles, 2 + 3 * 6 + 6);
+        assert_eq!(
+            report.results[1].outcome,
+            Outcome::Failed(vec![
+                String::from("flag C is clear, expected set"),
+                String::from("memory at $14 ($0014) is 02 00, expected 03 00"),
+            ])
+        );
+        assert!(matches!(&report.results[2].outcome, Outcome::Error(error) if error.starts_with("Didn't return within 100 cycles")));
+        assert_eq!((report.passed(), report.failed(), report.errors()), (1, 1, 1));
+
+        let tap = report.to_tap();
+        assert!(tap.starts_with("TAP version 13\n1..3\nok 1 - adds with carry between the bytes\nnot ok 2 - reports wrong results\n  ---\n"));
+
+        let junit = report.to_junit();
+        assert!(junit.contains("<testsuite name=\"math\" tests=\"3\" failures=\"1\" errors=\"1\">"));
+        assert!(junit.contains("<failure message=\"flag C is clear, expected set\">"));
+
+        assert!(TestScript::from_toml("[[tests]]\nname = \"no call\"").is_err());
+    }
+
+    /**
+     * Whether or not a JSON script loads its segments and symbols from files next to it
+     */
+    #[test]
+    fn json_script_with_files() {
+        let directory = std::env::temp_dir().join(format!("emulator-6502-test-runner-{}", std::process::id()));
+        fs::create_dir_all(&directory).unwrap();
+
+        // increment: INC counter, RTS
+        fs::write(directory.join("increment.bin"), [0xE6, 0x10, 0x60]).unwrap();
+        fs::write(directory.join("labels.txt"), "al C:0400 .increment\nal C:0010 .counter\n").unwrap();
+        let script = r#"{
+            "symbols": "labels.txt",
+            "segments": [{ "address": "increment", "file": "increment.bin" }],
+            "tests": [
+                { "name": "increments", "call": "increment", "memory": { "counter": [65] }, "expect": { "memory": { "counter": [66] } } }
+            ]
+        }"#;
+        fs::write(directory.join("counter.json"), script).unwrap();
+        fs::write(directory.join("missing.json"), r#"{ "segments": [{ "address": 1024, "file": "missing.bin" }] }"#).unwrap();
+
+        let script = TestScript::load(&directory.join("counter.json").to_string_lossy()).unwrap();
+        let report = script.run().unwrap();
+        assert_eq!(report.suite, "counter");
+        assert_eq!(report.results[0].outcome, Outcome::Passed);
+        assert_eq!(report.results[0].cycles, 5 + 6);
+
+        let script = TestScript::load(&directory.join("missing.json").to_string_lossy()).unwrap();
+        assert!(script.run().unwrap_err().contains("missing.bin"));
+
+        fs::remove_dir_all(&directory).unwrap();
+    }
+
+    /**
+     * Whether or not unmet register expectations fail the test and unusable ones are errors
+     */
+    #[test]
+    fn failing_expectations() {
+        let script = r#"
+            [[segments]]
+            address = 0x0300
+            bytes = [0xE8, 0x60] # INX, RTS
+
+            [[tests]]
+            name = "registers"
+            call = 0x0300
+            registers = { x = 1, status = 0x25 }
+            expect = { registers = { a = 0x02, x = 0x02, status = 0x24 } }
+
+            [[tests]]
+            name = "unknown flag"
+            call = 0x0300
+            expect = { flags = { q = true } }
+
+            [[tests]]
+            name = "unknown routine"
+            call = "nowhere"
+        "#;
+        let report = TestScript::from_toml(script).unwrap().run().unwrap();
+
+        assert_eq!(
+            report.results[0].outcome,
+            Outcome::Failed(vec![String::from("A is $00, expected $02"), String::from("status is $25, expected $24")])
+        );
+        assert_eq!(report.results[1].outcome, Outcome::Error(String::from("Unknown flag q")));
+        assert_eq!(report.results[2].outcome, Outcome::Error(String::from("Unknown address nowhere")));
+        assert!(!report.success());
+    }
+
+    /**
+     * Whether or not several reports are written as a single TAP stream and a single JUnit document
+     */
+    #[test]
+    fn tap_and_junit() {
+        let reports = [
+            TestReport {
+                suite: String::from("one"),
+                results: vec![
+                    TestResult { name: String::from("first"), outcome: Outcome::Passed, cycles: 10 },
+                    TestResult {
+                        name: String::from("second"),
+                        outcome: Outcome::Failed(vec![String::from("A is $01, expected $02"), String::from("say \"hi\"")]),
+                        cycles: 20,
+                    },
+                ],
+            },
+            TestReport {
+                suite: String::from("two"),
+                results: vec![TestResult { name: String::from("a < b & c"), outcome: Outcome::Error(String::from("Halted")), cycles: 0 }],
+            },
+        ];
+
+        assert_eq!(
+            to_tap(&reports),
+            "TAP version 13\n1..3\n\
+             ok 1 - one: first\n\
+             not ok 2 - one: second\n  ---\n  messages:\n    - \"A is $01, expected $02\"\n    - \"say \\\"hi\\\"\"\n  cycles: 20\n  ...\n\
+             not ok 3 - two: a < b & c\n  ---\n  messages:\n    - \"Halted\"\n  cycles: 0\n  ...\n"
+        );
+
+        assert_eq!(
+            to_junit(&reports),
+            "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n\
+             <testsuites tests=\"3\" failures=\"1\" errors=\"1\">\n\
+             \x20 <testsuite name=\"one\" tests=\"2\" failures=\"1\" errors=\"0\">\n\
+             \x20   <testcase classname=\"one\" name=\"first\"><system-out>10 cycles</system-out></testcase>\n\
+             \x20   <testcase classname=\"one\" name=\"second\">\n\
+             \x20     <failure message=\"A is $01, expected $02\">A is $01, expected $02\nsay &quot;hi&quot;</failure>\n\
+             \x20   <system-out>20 cycles</system-out></testcase>\n\
+             \x20 </testsuite>\n\
+             \x20 <testsuite name=\"two\" tests=\"1\" failures=\"0\" errors=\"1\">\n\
+             \x20   <testcase classname=\"two\" name=\"a &lt; b &amp; c\">\n\
+             \x20     <error message=\"Halted\"/>\n\
+             \x20   <system-out>0 cycles</system-out></testcase>\n\
+             \x20 </testsuite>\n\
+             </testsuites>\n"
+        );
+    }
+}
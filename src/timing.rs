@@ -0,0 +1,79 @@
+use std::fmt;
+
+use serde::Serialize;
+
+/**
+ * Cycles from interrupt requests to their handlers
+ *
+ * A request is timed from the cycle the IRQ line went low (or the interrupt was given to the processor)
+ * to the first cycle of the handler, the cycles pushing the state and reading the vector included.
+ */
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+pub struct LatencyStats {
+    /// Number of interrupts serviced
+    pub count: u64,
+    pub min: u64,
+    pub max: u64,
+    /// Sum of the latencies, for the mean
+    pub total: u64,
+}
+
+impl LatencyStats {
+    pub fn record(&mut self, latency: u64) {
+        self.min = if self.count == 0 { latency } else { self.min.min(latency) };
+        self.max = self.max.max(latency);
+        self.total += latency;
+        self.count += 1;
+    }
+
+    /**
+     * Returns the mean latency in cycles, `None` if no interrupt was serviced
+     */
+    pub fn mean(&self) -> Option<f64> {
+        (self.count > 0).then(|| self.total as f64 / self.count as f64)
+    }
+}
+
+impl fmt::Display for LatencyStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.mean() {
+            Some(mean) => write!(f, "{} serviced, {} / {:.1} / {} cycles (min / mean / max)", self.count, self.min, mean, self.max),
+            None => write!(f, "none serviced"),
+        }
+    }
+}
+
+/**
+ * Cycle and instruction counts of the processor since it was created, along with the interrupt latencies
+ */
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+pub struct TimingStats {
+    pub cycles: u64,
+    /// Instructions executed, interrupt sequences aside
+    pub instructions: u64,
+    pub irq_latency: LatencyStats,
+    pub nmi_latency: LatencyStats,
+}
+
+impl TimingStats {
+    /**
+     * Returns the mean cycles per instruction, `None` before the first one
+     */
+    pub fn cycles_per_instruction(&self) -> Option<f64> {
+        (self.instructions > 0).then(|| self.cycles as f64 / self.instructions as f64)
+    }
+
+    pub fn to_json(&self) -> String {
+        serde_json::to_string(self).unwrap()
+    }
+}
+
+impl fmt::Display for TimingStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} cycles, {} instructions", self.cycles, self.instructions)?;
+        if let Some(cycles_per_instruction) = self.cycles_per_instruction() {
+            write!(f, " ({:.2} cycles per instruction)", cycles_per_instruction)?;
+        }
+        write!(f, "\nIRQ latency: {}\nNMI latency: {}", self.irq_latency, self.nmi_latency)
+    }
+}
@@ -1,16 +1,47 @@
-mod bus;
-mod memory;
-mod processor;
-mod rom;
+pub mod analysis;
+pub mod bus;
+pub mod call_stack;
+pub mod coverage;
+pub mod debug_info;
+#[cfg(test)]
+mod differential;
+pub mod devices;
+pub mod history;
+pub mod instruction_info;
+pub mod disassembler;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod gdb;
+pub mod mapper;
+pub mod memory;
+pub mod monitor;
+pub mod processor;
+pub mod profiler;
+pub mod replay;
+pub mod rom;
+pub mod save_state;
+pub mod scheduler;
+pub mod stack_guard;
+pub mod symbols;
+pub mod test_runner;
+pub mod timing;
+pub mod watchpoints;
 
 pub use bus::Bus;
 pub use memory::Memory;
-pub use processor::Processor;
+pub use processor::{Processor, RunResult, StopReason};
+pub use scheduler::{ClockRate, Scheduler};
+
+use devices::terminal::Terminal;
+use analysis::Analysis;
+use debug_info::DebugInfo;
+use memory::PowerOnPattern;
+use monitor::Monitor;
+use replay::{InputEvent, InputLog};
+use save_state::MachineState;
 
 use wasm_bindgen::prelude::*;
 
-use serde_json;
-use std::{cell::RefCell, collections::HashMap, u8};
+use std::{cell::RefCell, rc::Rc, time::Duration};
 
 #[wasm_bindgen]
 extern "C" {
@@ -28,7 +59,10 @@ extern "C" {
 
 struct Instance {
     processor: Option<Processor>,
-    total_clock_cycle: u16,
+    scheduler: Option<Scheduler>,
+    terminal: Option<Rc<RefCell<Terminal>>>,
+    terminal_address: u16,
+    monitor: Option<Monitor>,
 }
 
 /*
@@ -36,7 +70,7 @@ struct Instance {
  * As JS is single threaded, this won't be a problem
  */
 thread_local! (
-    static INSTANCE: RefCell<Instance> = RefCell::new( Instance{processor: None, total_clock_cycle: 0,} )
+    static INSTANCE: RefCell<Instance> = const { RefCell::new( Instance{processor: None, scheduler: None, terminal: None, terminal_address: 0, monitor: None,} ) }
 );
 
 #[wasm_bindgen(js_name = createProcessor)]
@@ -48,7 +82,9 @@ pub fn create_processor() {
         let mut instance = ins.borrow_mut();
 
         instance.processor = Some(Processor::new());
-        instance.total_clock_cycle = 0;
+        instance.scheduler = Some(Scheduler::default());
+        instance.terminal = None;
+        instance.monitor = Some(Monitor::new());
     });
 
     log("An instance of processor created");
@@ -63,30 +99,721 @@ pub fn clear_processor_instance() {
         let mut instance = ins.borrow_mut();
 
         instance.processor = None;
-        instance.total_clock_cycle = 0;
+        instance.scheduler = None;
+        instance.terminal = None;
+        instance.monitor = None;
     });
 
     log("An instance of processor created");
 }
 
+#[wasm_bindgen(js_name=powerOn)]
+/**
+ * Fills the RAM with `pattern` ("zero", "ff", "alternating", "random" or "random:seed"), clears the registers
+   and resets the processor, which then starts from the reset vector.
+ * Returns false for an unknown pattern
+ */
+pub fn power_on(pattern: &str) -> bool {
+    INSTANCE.with(|ins| match (&mut ins.borrow_mut().processor, pattern.parse::<PowerOnPattern>()) {
+        (Some(proc), Ok(pattern)) => {
+            proc.power_on(pattern);
+            true
+        }
+        _ => false,
+    })
+}
+
 #[wasm_bindgen(js_name=tickClock)]
 /**
- * Performs one fecth, decode and execute cycle.
+ * Advances the processor by a single clock cycle.
  */
 pub fn tick_clock() {
+    INSTANCE.with(|ins| {
+        if let Some(proc) = &mut ins.borrow_mut().processor {
+            proc.clock();
+        }
+    })
+}
+
+#[wasm_bindgen(js_name=run)]
+/**
+ * Runs the processor for at most `cycle_budget` clock cycles.
+ * Returns serialized result of the form {reason, cycles} where reason is one of
+   "BudgetExhausted", "Breakpoint" (with address), "Halted", "IllegalOpcode" (with opcode and address),
+   "StackGuard" (with event, see `takeStackEvents`), "Watchpoint" (with hit, i.e. the kind and address of the access)
+   or "HistoryStart" (only returned when running backwards, see `reverseContinue`)
+ */
+pub fn run(cycle_budget: u64) -> std::string::String {
+    INSTANCE.with(|ins| {
+        match &mut ins.borrow_mut().processor {
+            Some(proc) => serde_json::to_string(&proc.run(cycle_budget)).unwrap(),
+            None => "{}".to_owned(),
+        }
+    })
+}
+
+#[wasm_bindgen(js_name=runFrame)]
+/**
+ * Runs as many cycles as a processor clocked at `hz` would in `ms` milliseconds.
+ * Meant to be called once per animation frame. Returns the same result as `run`
+ * Doesn't go through the scheduler, see `advanceFrame` for the throttled clock
+ */
+pub fn run_frame(hz: u32, ms: f64) -> std::string::String {
+    let cycle_budget = (hz as f64 * ms / 1000.0) as u64;
+    run(cycle_budget)
+}
+
+#[wasm_bindgen(js_name=advanceFrame)]
+/**
+ * Runs as many cycles as the configured clock rate allows in `ms` milliseconds.
+ * Meant to be called once per animation frame with the time since the previous frame.
+ * Returns the same result as `run`, or {} while paused
+ */
+pub fn advance_frame(ms: f64) -> std::string::String {
     INSTANCE.with(|ins| {
         let mut instance = ins.borrow_mut();
-        match &mut instance.processor {
-            Some(proc) => {
-                proc.reset();
-                instance.total_clock_cycle += 1;
-                log("Called tick_clock through wasm");
+        let Instance { processor, scheduler, .. } = &mut *instance;
+
+        match (processor, scheduler) {
+            (Some(proc), Some(scheduler)) => {
+                match scheduler.advance(proc, Duration::from_secs_f64(ms.max(0.0) / 1000.0)) {
+                    Some(result) => serde_json::to_string(&result).unwrap(),
+                    None => "{}".to_owned(),
+                }
+            }
+            _ => "{}".to_owned(),
+        }
+    })
+}
+
+#[wasm_bindgen(js_name=setClockRate)]
+/**
+ * Sets the clock rate used by `advanceFrame` in hertz, 0 means unlimited.
+ */
+pub fn set_clock_rate(hz: u32) {
+    INSTANCE.with(|ins| {
+        if let Some(scheduler) = &mut ins.borrow_mut().scheduler {
+            scheduler.set_clock_rate(match hz {
+                0 => ClockRate::Unlimited,
+                hz => ClockRate::Hz(hz),
+            });
+        }
+    })
+}
+
+#[wasm_bindgen(js_name=setWarp)]
+/**
+ * While warp is on, `advanceFrame` ignores the clock rate and runs as fast as possible.
+ */
+pub fn set_warp(warp: bool) {
+    INSTANCE.with(|ins| {
+        if let Some(scheduler) = &mut ins.borrow_mut().scheduler {
+            scheduler.set_warp(warp);
+        }
+    })
+}
+
+#[wasm_bindgen(js_name=setPaused)]
+/**
+ * While paused, `advanceFrame` doesn't clock the processor.
+ */
+pub fn set_paused(paused: bool) {
+    INSTANCE.with(|ins| {
+        if let Some(scheduler) = &mut ins.borrow_mut().scheduler {
+            scheduler.set_paused(paused);
+        }
+    })
+}
+
+#[wasm_bindgen(js_name=getAchievedSpeed)]
+/**
+ * Returns the clock rate actually achieved by `advanceFrame` in hertz.
+ */
+pub fn get_achieved_speed() -> f64 {
+    INSTANCE.with(|ins| match &ins.borrow().scheduler {
+        Some(scheduler) => scheduler.achieved_hz(),
+        None => 0.0,
+    })
+}
+
+#[wasm_bindgen(js_name=getTotalClockCycle)]
+/**
+ * Returns the number of clock cycles run since the processor was created.
+ */
+pub fn get_total_clock_cycle() -> u64 {
+    INSTANCE.with(|ins| ins.borrow().processor.as_ref().map_or(0, |proc| proc.total_cycles()))
+}
+
+#[wasm_bindgen(js_name=getTimingStats)]
+/**
+ * Returns serialized timing statistics of the form {cycles, instructions, irq_latency, nmi_latency},
+   each latency being {count, min, max, total} in cycles.
+ * Returns {} if there is no processor
+ */
+pub fn get_timing_stats() -> std::string::String {
+    INSTANCE.with(|ins| match &ins.borrow().processor {
+        Some(proc) => proc.timing_stats().to_json(),
+        None => "{}".to_owned(),
+    })
+}
+
+#[wasm_bindgen(js_name=addBreakpoint)]
+/**
+ * `run`, `runFrame` and `advanceFrame` stop before executing the instruction at the given address.
+ */
+pub fn add_breakpoint(address: u16) {
+    INSTANCE.with(|ins| {
+        if let Some(proc) = &mut ins.borrow_mut().processor {
+            proc.add_breakpoint(address);
+        }
+    })
+}
+
+#[wasm_bindgen(js_name=addBreakpointAt)]
+/**
+ * Same as `addBreakpoint`, the location may be a label, `label+offset`, `file:line` or a number ($hex, 0xhex, #decimal or plain hex).
+ * Returns false if the location can't be resolved
+ */
+pub fn add_breakpoint_at(location: &str) -> bool {
+    INSTANCE.with(|ins| match &mut ins.borrow_mut().processor {
+        Some(proc) => match proc.resolve_address(location) {
+            Some(address) => {
+                proc.add_breakpoint(address);
+                true
+            }
+            None => false,
+        },
+        None => false,
+    })
+}
+
+#[wasm_bindgen(js_name=removeBreakpoint)]
+/**
+ * Removes the breakpoint at the given address, if any.
+ */
+pub fn remove_breakpoint(address: u16) {
+    INSTANCE.with(|ins| {
+        if let Some(proc) = &mut ins.borrow_mut().processor {
+            proc.remove_breakpoint(address);
+        }
+    })
+}
+
+#[wasm_bindgen(js_name=setStackGuard)]
+/**
+ * Turns the stack guard on or off, `run`, `runFrame` and `advanceFrame` stop when it reports something.
+ * Stack depths above `depth_threshold` bytes are reported as well, 0 only reports wraps
+ */
+pub fn set_stack_guard(enabled: bool, depth_threshold: u16) {
+    INSTANCE.with(|ins| {
+        if let Some(proc) = &mut ins.borrow_mut().processor {
+            match (enabled, depth_threshold) {
+                (false, _) => proc.disable_stack_guard(),
+                (true, 0) => proc.enable_stack_guard(None),
+                (true, threshold) => proc.enable_stack_guard(Some(threshold)),
             }
-            None => (),
         }
     })
 }
 
+#[wasm_bindgen(js_name=takeStackEvents)]
+/**
+ * Returns serialized array of the stack guard events since the previous call, each of the form {kind, address}
+   where kind is one of "Overflow", "Underflow" or "DepthExceeded" (with depth)
+ */
+pub fn take_stack_events() -> std::string::String {
+    INSTANCE.with(|ins| match &mut ins.borrow_mut().processor {
+        Some(proc) => serde_json::to_string(&proc.take_stack_events()).unwrap(),
+        None => "[]".to_owned(),
+    })
+}
+
+#[wasm_bindgen(js_name=getBacktrace)]
+/**
+ * Returns serialized backtrace of the form {entries: [{kind, address, function, symbol}]}, innermost call first.
+ * The first entry is the current program counter, the others are the call sites
+ */
+pub fn get_backtrace() -> std::string::String {
+    INSTANCE.with(|ins| match &ins.borrow().processor {
+        Some(proc) => serde_json::to_string(&proc.backtrace()).unwrap(),
+        None => "{}".to_owned(),
+    })
+}
+
+#[wasm_bindgen(js_name=takeCallStackMismatches)]
+/**
+ * Returns serialized array of the call stack mismatches flagged since the previous call,
+   each of the form {mismatch, address, ...} where mismatch is one of
+   "ReturnWithoutCall", "ModifiedReturnAddress", "WrongReturnInstruction" or "DiscardedFrames"
+ */
+pub fn take_call_stack_mismatches() -> std::string::String {
+    INSTANCE.with(|ins| match &mut ins.borrow_mut().processor {
+        Some(proc) => serde_json::to_string(&proc.call_stack_mut().take_mismatches()).unwrap(),
+        None => "[]".to_owned(),
+    })
+}
+
+#[wasm_bindgen(js_name=setHistory)]
+/**
+ * Turns the execution history on or off, needed by `stepBack`, `reverseContinue` and `gotoCycle`.
+ * A snapshot is taken every `snapshot_interval` cycles, the history takes at most `memory_cap` bytes
+ */
+pub fn set_history(enabled: bool, snapshot_interval: u64, memory_cap: usize) {
+    INSTANCE.with(|ins| {
+        if let Some(proc) = &mut ins.borrow_mut().processor {
+            if enabled {
+                proc.enable_history(snapshot_interval, memory_cap);
+            } else {
+                proc.disable_history();
+            }
+        }
+    })
+}
+
+#[wasm_bindgen(js_name=stepBack)]
+/**
+ * Undoes the last instruction, returns false when the history has nothing left
+ */
+pub fn step_back() -> bool {
+    INSTANCE.with(|ins| match &mut ins.borrow_mut().processor {
+        Some(proc) => proc.step_back(),
+        None => false,
+    })
+}
+
+#[wasm_bindgen(js_name=reverseContinue)]
+/**
+ * Steps back until a breakpoint or the start of the history.
+ * Returns serialized result of the form {reason, cycles, ...} like `run`, reason being "Breakpoint" or "HistoryStart"
+ */
+pub fn reverse_continue() -> std::string::String {
+    INSTANCE.with(|ins| match &mut ins.borrow_mut().processor {
+        Some(proc) => serde_json::to_string(&proc.reverse_continue()).unwrap(),
+        None => "{}".to_owned(),
+    })
+}
+
+#[wasm_bindgen(js_name=gotoCycle)]
+/**
+ * Moves to a cycle of the history (counted from when it was enabled), backwards or forwards.
+ * Returns false if the history doesn't reach it
+ */
+pub fn goto_cycle(cycle: u64) -> bool {
+    INSTANCE.with(|ins| match &mut ins.borrow_mut().processor {
+        Some(proc) => proc.goto_cycle(cycle),
+        None => false,
+    })
+}
+
+#[wasm_bindgen(js_name=getHistory)]
+/**
+ * Returns serialized history status of the form {cycle, oldest_cycle, instructions, snapshots, memory_usage, memory_cap}
+ */
+pub fn get_history() -> std::string::String {
+    INSTANCE.with(|ins| match ins.borrow().processor.as_ref().and_then(|proc| proc.history()) {
+        Some(history) => serde_json::json!({
+            "cycle": history.cycle(),
+            "oldest_cycle": history.oldest_cycle(),
+            "instructions": history.len(),
+            "snapshots": history.snapshot_count(),
+            "memory_usage": history.memory_usage(),
+            "memory_cap": history.memory_cap(),
+        })
+        .to_string(),
+        None => "{}".to_owned(),
+    })
+}
+
+#[wasm_bindgen(js_name=saveState)]
+/**
+ * Returns the serialized state of the processor and of the storages on the bus
+ */
+pub fn save_state() -> std::string::String {
+    INSTANCE.with(|ins| match &ins.borrow().processor {
+        Some(proc) => proc.save_state().to_json(),
+        None => "{}".to_owned(),
+    })
+}
+
+#[wasm_bindgen(js_name=loadState)]
+/**
+ * Restores a state returned by `saveState`, returns false if it can't be loaded
+ */
+pub fn load_state(state: &str) -> bool {
+    INSTANCE.with(|ins| match &mut ins.borrow_mut().processor {
+        Some(proc) => match MachineState::from_json(state).and_then(|state| proc.load_state(&state)) {
+            Ok(()) => true,
+            Err(error) => {
+                log(&error);
+                false
+            }
+        },
+        None => false,
+    })
+}
+
+#[wasm_bindgen(js_name=deviceInput)]
+/**
+ * Gives an input to the device mapped at the address (see `BusDevice::input`), recorded while recording.
+ * Returns false if no device is mapped there
+ */
+pub fn device_input(address: u16, port: u8, value: u8) -> bool {
+    INSTANCE.with(|ins| match &mut ins.borrow_mut().processor {
+        Some(proc) => proc.input(InputEvent::Device { address, port, value }),
+        None => false,
+    })
+}
+
+#[wasm_bindgen(js_name=startRecording)]
+/**
+ * Starts recording the external inputs (keys, device inputs) along with the cycle they happen at
+ */
+pub fn start_recording() {
+    INSTANCE.with(|ins| {
+        if let Some(proc) = &mut ins.borrow_mut().processor {
+            proc.start_recording();
+        }
+    })
+}
+
+#[wasm_bindgen(js_name=stopRecording)]
+/**
+ * Returns the serialized inputs recorded since `startRecording`, which `cargo test` or `--replay` can replay
+ */
+pub fn stop_recording() -> std::string::String {
+    INSTANCE.with(|ins| match &mut ins.borrow_mut().processor {
+        Some(proc) => proc.stop_recording().unwrap_or_default().to_json(),
+        None => "{}".to_owned(),
+    })
+}
+
+#[wasm_bindgen(js_name=startReplay)]
+/**
+ * Replays inputs returned by `stopRecording` from now on, returns false if they can't be parsed
+ */
+pub fn start_replay(inputs: &str) -> bool {
+    INSTANCE.with(|ins| match &mut ins.borrow_mut().processor {
+        Some(proc) => match InputLog::from_json(inputs) {
+            Ok(inputs) => {
+                proc.start_replay(inputs);
+                true
+            }
+            Err(error) => {
+                log(&error);
+                false
+            }
+        },
+        None => false,
+    })
+}
+
+#[wasm_bindgen(js_name=isReplaying)]
+pub fn is_replaying() -> bool {
+    INSTANCE.with(|ins| match &ins.borrow().processor {
+        Some(proc) => proc.is_replaying(),
+        None => false,
+    })
+}
+
+#[wasm_bindgen(js_name=loadSymbols)]
+/**
+ * Loads labels from the text of an ld65 debug file, a VICE label file or `name = $addr` lines.
+ * Returns the number of labels loaded
+ */
+pub fn load_symbols(text: &str) -> usize {
+    INSTANCE.with(|ins| match &mut ins.borrow_mut().processor {
+        Some(proc) => proc.symbols.load_str(text),
+        None => 0,
+    })
+}
+
+#[wasm_bindgen(js_name=loadDebugInfo)]
+/**
+ * Loads the text of an ld65 debug file (`ld65 --dbgfile`), for the source lines and the labels.
+ * Returns false if the text isn't a debug file
+ */
+pub fn load_debug_info(text: &str) -> bool {
+    INSTANCE.with(|ins| match &mut ins.borrow_mut().processor {
+        Some(proc) => match DebugInfo::parse(text) {
+            Ok(debug_info) => {
+                proc.debug_info = debug_info;
+                proc.symbols.load_ld65_dbg(text);
+                true
+            }
+            Err(error) => {
+                log(&error);
+                false
+            }
+        },
+        None => false,
+    })
+}
+
+#[wasm_bindgen(js_name=setSourceFile)]
+/**
+ * Sets the contents of a source file named in the debug info, so `getSourceLocation` includes the line's text.
+ * Returns false if the debug info doesn't know the file
+ */
+pub fn set_source_file(name: &str, text: &str) -> bool {
+    INSTANCE.with(|ins| match &mut ins.borrow_mut().processor {
+        Some(proc) => proc.debug_info.set_source(name, text),
+        None => false,
+    })
+}
+
+#[wasm_bindgen(js_name=getSourceLocation)]
+/**
+ * Returns serialized source line of the given address of the form {file, line, text},
+   text being null when the source file wasn't set. Returns {} for addresses without a source line
+ */
+pub fn get_source_location(address: u16) -> std::string::String {
+    INSTANCE.with(|ins| match &ins.borrow().processor {
+        Some(proc) => match proc.debug_info.location(address) {
+            Some(location) => serde_json::json!({
+                "file": location.file,
+                "line": location.line,
+                "text": proc.debug_info.source_line(&location),
+            })
+            .to_string(),
+            None => "{}".to_owned(),
+        },
+        None => "{}".to_owned(),
+    })
+}
+
+#[wasm_bindgen(js_name=getCurrentSourceLocation)]
+/**
+ * Same as `getSourceLocation` for the program counter
+ */
+pub fn get_current_source_location() -> std::string::String {
+    let program_counter = INSTANCE.with(|ins| ins.borrow().processor.as_ref().map(|proc| proc.program_counter()));
+
+    match program_counter {
+        Some(program_counter) => get_source_location(program_counter),
+        None => "{}".to_owned(),
+    }
+}
+
+#[wasm_bindgen(js_name=stepLine)]
+/**
+ * Executes until the program counter is on another source line, stepping over subroutine calls if `step_over` is set.
+ * Returns the number of clock cycles that elapsed
+ */
+pub fn step_line(step_over: bool) -> u64 {
+    INSTANCE.with(|ins| {
+        match &mut ins.borrow_mut().processor {
+            Some(proc) => proc.step_source_line(step_over),
+            None => 0,
+        }
+    })
+}
+
+#[wasm_bindgen(js_name=disassemble)]
+/**
+ * Returns serialized array of `count` instructions starting at `address`,
+   each of the form {address, bytes, label, text}
+ */
+pub fn disassemble(address: u16, count: usize) -> std::string::String {
+    INSTANCE.with(|ins| match &ins.borrow().processor {
+        Some(proc) => serde_json::to_string(&disassembler::disassemble(proc, address, count)).unwrap(),
+        None => "[]".to_owned(),
+    })
+}
+
+#[wasm_bindgen(js_name=analyzeCode)]
+/**
+ * Follows the code from the reset, NMI and IRQ vectors and returns, depending on `format`,
+   the control flow graph ("dot"), the call graph ("calls") or the annotated disassembly of the ROM ("asm")
+ */
+pub fn analyze_code(format: &str) -> std::string::String {
+    INSTANCE.with(|ins| match &ins.borrow().processor {
+        Some(proc) => {
+            let analysis = Analysis::new(&proc.bus);
+            match format {
+                "dot" => analysis.to_dot(&proc.symbols),
+                "calls" => analysis.call_graph_dot(&proc.symbols),
+                _ => {
+                    let rom_start = (proc.bus.memory.len() + proc.bus.other.len()) as u16;
+                    analysis.to_disassembly(&proc.symbols, rom_start, 0xFFFF)
+                }
+            }
+        }
+        None => String::new(),
+    })
+}
+
+#[wasm_bindgen(js_name=getInstructionInfo)]
+/**
+ * Returns serialized metadata of the opcode of the form
+   {name, length, cycles, page_cross_penalty, operand_type, flags_read, flags_written, reads_memory, writes_memory, control_flow}
+ */
+pub fn get_instruction_info(opcode: u8) -> std::string::String {
+    serde_json::to_string(&processor::Instruction::decode(opcode).info()).unwrap()
+}
+
+#[wasm_bindgen(js_name=monitorCommand)]
+/**
+ * Executes a monitor command (`h` lists them) and returns its output
+ */
+pub fn monitor_command(line: &str) -> std::string::String {
+    INSTANCE.with(|ins| {
+        let instance = &mut *ins.borrow_mut();
+        match (&mut instance.processor, &mut instance.monitor) {
+            (Some(proc), Some(monitor)) => monitor.execute(proc, line),
+            _ => "no processor".to_owned(),
+        }
+    })
+}
+
+#[wasm_bindgen(js_name=setProfiling)]
+/**
+ * Starts (from zero) or stops counting memory accesses and cycles for the profiling views.
+ */
+pub fn set_profiling(enabled: bool) {
+    INSTANCE.with(|ins| {
+        if let Some(proc) = &mut ins.borrow_mut().processor {
+            match enabled {
+                true => proc.enable_profiling(),
+                false => proc.disable_profiling(),
+            }
+        }
+    })
+}
+
+#[wasm_bindgen(js_name=getMemoryHeatmap)]
+/**
+ * Returns serialized access counters of the whole address space.
+ * Upon deserialization a Object of {reads, writes, executes} is obtained, each an array of 65536 counts indexed by address.
+ * Returns {} while not profiling
+ */
+pub fn get_memory_heatmap() -> std::string::String {
+    INSTANCE.with(|ins| {
+        let instance = ins.borrow();
+        match instance.processor.as_ref().and_then(|proc| proc.bus.access_counters.as_ref()) {
+            Some(counters) => serde_json::to_string(&counters.heatmap()).unwrap(),
+            None => "{}".to_owned(),
+        }
+    })
+}
+
+#[wasm_bindgen(js_name=getProfileReport)]
+/**
+ * Returns serialized profile of the form {total_cycles, memory, opcodes, program_counters, hot_subroutines},
+ * only listing the addresses and opcodes that were used.
+ * Returns {} while not profiling
+ */
+pub fn get_profile_report() -> std::string::String {
+    INSTANCE.with(|ins| {
+        match ins.borrow().processor.as_ref().and_then(|proc| proc.profile_report()) {
+            Some(report) => report.to_json(),
+            None => "{}".to_owned(),
+        }
+    })
+}
+
+#[wasm_bindgen(js_name=getProfileCsv)]
+/**
+ * Returns one table of the profile as CSV, `table` is one of
+   "memory", "opcodes", "program_counters" or "hot_subroutines".
+ * Returns an empty string while not profiling or for an unknown table
+ */
+pub fn get_profile_csv(table: &str) -> std::string::String {
+    INSTANCE.with(|ins| {
+        match ins.borrow().processor.as_ref().and_then(|proc| proc.profile_report()) {
+            Some(report) => match table {
+                "memory" => report.memory_csv(),
+                "opcodes" => report.opcodes_csv(),
+                "program_counters" => report.program_counters_csv(),
+                "hot_subroutines" => report.hot_subroutines_csv(),
+                _ => String::new(),
+            },
+            None => String::new(),
+        }
+    })
+}
+
+#[wasm_bindgen(js_name=setCoverage)]
+/**
+ * Starts (from zero) or stops recording the executed instructions and branch outcomes.
+ */
+pub fn set_coverage(enabled: bool) {
+    INSTANCE.with(|ins| {
+        if let Some(proc) = &mut ins.borrow_mut().processor {
+            match enabled {
+                true => proc.enable_coverage(),
+                false => proc.disable_coverage(),
+            }
+        }
+    })
+}
+
+#[wasm_bindgen(js_name=getCoverage)]
+/**
+ * Returns the coverage report, `format` is "lcov" for an lcov tracefile (lines of the loaded debug info only),
+   anything else gives the per-address text report.
+ * Returns an empty string while coverage isn't recorded
+ */
+pub fn get_coverage(format: &str) -> std::string::String {
+    INSTANCE.with(|ins| {
+        let instance = ins.borrow();
+        match instance.processor.as_ref() {
+            Some(proc) => match (proc.coverage(), format) {
+                (Some(coverage), "lcov") => coverage.to_lcov(proc),
+                (Some(coverage), _) => coverage.to_text(proc),
+                (None, _) => String::new(),
+            },
+            None => String::new(),
+        }
+    })
+}
+
+#[wasm_bindgen(js_name=attachTerminal)]
+/**
+ * Maps an Apple-1 style terminal at the given address (0xD010 on the Apple-1).
+ * Keys are fed with `terminalInput` and printed characters are collected with `terminalOutput`
+ */
+pub fn attach_terminal(address: u16) {
+    INSTANCE.with(|ins| {
+        let mut instance = ins.borrow_mut();
+        let Instance { processor, terminal, terminal_address, .. } = &mut *instance;
+
+        if let Some(proc) = processor {
+            let new_terminal = Rc::new(RefCell::new(Terminal::new()));
+            proc.bus.attach_device(address, new_terminal.clone());
+            *terminal = Some(new_terminal);
+            *terminal_address = address;
+        }
+    })
+}
+
+#[wasm_bindgen(js_name=terminalInput)]
+/**
+ * Queues the given keys on the terminal keyboard, they are part of the recording while recording.
+ */
+pub fn terminal_input(keys: &str) {
+    INSTANCE.with(|ins| {
+        let mut instance = ins.borrow_mut();
+        let Instance { processor, terminal, terminal_address, .. } = &mut *instance;
+
+        if let (Some(proc), Some(_)) = (processor, terminal) {
+            for key in keys.bytes() {
+                proc.input(InputEvent::Device { address: *terminal_address, port: 0, value: key });
+            }
+        }
+    })
+}
+
+#[wasm_bindgen(js_name=terminalOutput)]
+/**
+ * Returns the characters printed on the terminal since the previous call.
+ */
+pub fn terminal_output() -> std::string::String {
+    INSTANCE.with(|ins| match &ins.borrow().terminal {
+        Some(terminal) => String::from_utf8_lossy(&terminal.borrow_mut().take_output()).into_owned(),
+        None => String::new(),
+    })
+}
+
 #[wasm_bindgen(js_name=loadRomFromFilepath)]
 /**
  * Load the rom contents from the given file
@@ -94,11 +821,8 @@ pub fn tick_clock() {
 pub fn load_rom_from_filepath(filepath: &str) {
     INSTANCE.with(|ins| {
         let mut instance = ins.borrow_mut();
-        match &mut instance.processor {
-            Some(proc) => {
-                proc.load_rom(filepath);
-            }
-            None => (),
+        if let Some(proc) = &mut instance.processor {
+            proc.load_rom(filepath);
         }
     })
 }
@@ -155,40 +879,60 @@ pub fn get_processor_status() -> std::string::String {
 pub fn load_rom(bytes: String) {
     INSTANCE.with(|ins| {
         let mut instance = ins.borrow_mut();
-        match &mut instance.processor {
-            Some(proc) => {
-                
-                // Spilliting into the groups of 2 as two hexadecimal bits = 8 binary bits
-                let chars: Vec<char> = bytes.chars().collect();
-                let split = &chars
-                    .chunks(2)
-                    .map(|chunk| chunk.iter().collect::<String>())
-                    .collect::<Vec<_>>();
-
-                let nums = split
-                    .into_iter()
-                    .map(|ch| u8::from_str_radix(ch, 16).expect("Converstion Error"))
-                    .collect::<Vec<u8>>();
+        if let Some(proc) = &mut instance.processor {
+            
+            // Spilliting into the groups of 2 as two hexadecimal bits = 8 binary bits
+            let chars: Vec<char> = bytes.chars().collect();
+            let split = &chars
+                .chunks(2)
+                .map(|chunk| chunk.iter().collect::<String>())
+                .collect::<Vec<_>>();
+
+            let nums = split
+                .iter()
+                .map(|ch| u8::from_str_radix(ch, 16).expect("Converstion Error"))
+                .collect::<Vec<u8>>();
 
-                log(format!("Loaded {:?}", nums).as_str());
+            log(format!("Loaded {:?}", nums).as_str());
 
-                // changing the rom
-                for (i, val) in nums.iter().enumerate() {
-                    if i < proc.bus.secondary_storage.len() {
-                        proc.bus.secondary_storage[i as u16] = *val;
-                    }
+            // changing the rom
+            for (i, val) in nums.iter().enumerate() {
+                if i < proc.bus.secondary_storage.len() {
+                    proc.bus.secondary_storage[i as u16] = *val;
                 }
             }
-            None => {}
+        }
+    })
+}
+
+#[wasm_bindgen(js_name=loadCartridge)]
+/**
+ * Loads an iNES (`.nes`) image through the mapper named in its header
+ *
+ * Returns `false` if the image or its mapper isn't supported
+ */
+pub fn load_cartridge(image: Vec<u8>) -> bool {
+    INSTANCE.with(|ins| {
+        let mut instance = ins.borrow_mut();
+        match (&mut instance.processor, mapper::from_ines(&image)) {
+            (Some(proc), Ok(mapper)) => {
+                proc.bus.set_mapper(mapper);
+                true
+            }
+            (_, Err(error)) => {
+                log(error.as_str());
+                false
+            }
+            _ => false,
         }
     })
 }
 
 #[wasm_bindgen(js_name=getStorageLayout)]
 /**
- *   Returns serialized HashMap of [ storage : (start_index, end_index) ]
- * Upon deserialization a Object of {storage : (start_index, end_index)}
-    is obtained
+ *   Returns serialized list of [ {region, bank, start, end} ]
+ * The storages come first (`memory`, `other` and `secondary_storage` as bank 0, indexed from the start of the bus),
+    followed by the banks mapped in by the mapper (indexed by address, they shadow the storages they are mapped over)
  */
 pub fn get_storage_layout() -> std::string::String {
     INSTANCE.with(|ins| {
@@ -201,21 +945,35 @@ pub fn get_storage_layout() -> std::string::String {
                 let other_len: usize = proc.bus.other.len();
                 let secondary_storage_len: usize = proc.bus.secondary_storage.len();
 
-                let storage_to_location: HashMap<String, (usize, usize)> = HashMap::from([
-                    (String::from("memory"), (0, memory_len)),
-                    (String::from("other"), (memory_len, memory_len + other_len)),
+                let storages = [
+                    (String::from("memory"), 0, 0, memory_len),
+                    (String::from("other"), 0, memory_len, memory_len + other_len),
                     (
                         String::from("secondary_storage"),
-                        (
-                            memory_len + other_len,
-                            memory_len + other_len + secondary_storage_len,
-                        ),
+                        0,
+                        memory_len + other_len,
+                        memory_len + other_len + secondary_storage_len,
                     ),
-                ]);
+                ];
+                let banks = proc.bus.mapped_banks().into_iter().map(|bank| {
+                    let start = bank.start as usize;
+                    (bank.region, bank.bank, start, start + bank.size)
+                });
 
-                serde_json::to_string(&storage_to_location).unwrap()
+                let layout: Vec<serde_json::Value> = storages
+                    .into_iter()
+                    .chain(banks)
+                    .map(|(region, bank, start, end)| serde_json::json!({
+                        "region": region,
+                        "bank": bank,
+                        "start": start,
+                        "end": end,
+                    }))
+                    .collect();
+
+                serde_json::to_string(&layout).unwrap()
             }
-            None => "{}".to_owned(),
+            None => "[]".to_owned(),
         }
     })
 }
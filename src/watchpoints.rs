@@ -0,0 +1,150 @@
+use std::cell::Cell;
+use std::collections::HashSet;
+
+use serde::Serialize;
+
+/**
+ * Which accesses a watchpoint stops on
+ */
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub enum WatchKind {
+    Read,
+    Write,
+    /// Reads and writes
+    Access,
+}
+
+/**
+ * A watched access, `kind` is the one the watchpoint was set with
+ */
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct WatchHit {
+    pub kind: WatchKind,
+    pub address: u16,
+}
+
+/**
+ * Addresses whose reads or writes stop `Processor::run()`
+ *
+ * The bus records the first hit, the processor stops once the instruction causing it has completed.
+ * Like the access counters, hits are recorded through a `Cell` as bus reads only borrow the bus.
+ */
+#[derive(Debug, Default)]
+pub struct Watchpoints {
+    reads: HashSet<u16>,
+    writes: HashSet<u16>,
+    accesses: HashSet<u16>,
+    hit: Cell<Option<WatchHit>>,
+}
+
+// Constructor like implementation
+impl Watchpoints {
+    pub fn new() -> Self {
+        Self {
+            ..Default::default()
+        }
+    }
+}
+
+impl Watchpoints {
+    /**
+     * Watches `length` addresses starting at `address`
+     */
+    pub fn insert(&mut self, address: u16, length: u16, kind: WatchKind) {
+        let addresses = self.addresses_mut(kind);
+        for offset in 0..length.max(1) {
+            addresses.insert(address.wrapping_add(offset));
+        }
+    }
+
+    /**
+     * Stops watching `length` addresses starting at `address` with a watchpoint of the given kind
+     */
+    pub fn remove(&mut self, address: u16, length: u16, kind: WatchKind) {
+        let addresses = self.addresses_mut(kind);
+        for offset in 0..length.max(1) {
+            addresses.remove(&address.wrapping_add(offset));
+        }
+    }
+
+    fn addresses_mut(&mut self, kind: WatchKind) -> &mut HashSet<u16> {
+        match kind {
+            WatchKind::Read => &mut self.reads,
+            WatchKind::Write => &mut self.writes,
+            WatchKind::Access => &mut self.accesses,
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.reads.is_empty() && self.writes.is_empty() && self.accesses.is_empty()
+    }
+
+    pub fn record_read(&self, address: u16) {
+        self.record(address, &self.reads, WatchKind::Read);
+    }
+
+    pub fn record_write(&self, address: u16) {
+        self.record(address, &self.writes, WatchKind::Write);
+    }
+
+    /// Access watchpoints are reported as such, whichever access hit them
+    fn record(&self, address: u16, addresses: &HashSet<u16>, kind: WatchKind) {
+        if self.hit.get().is_some() {
+            return;
+        }
+
+        if self.accesses.contains(&address) {
+            self.hit.set(Some(WatchHit { kind: WatchKind::Access, address }));
+        } else if addresses.contains(&address) {
+            self.hit.set(Some(WatchHit { kind, address }));
+        }
+    }
+
+    /**
+     * Returns the first hit since the previous call
+     */
+    pub fn take_hit(&self) -> Option<WatchHit> {
+        self.hit.take()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    /**
+     * Whether or not only the watched kind of access is recorded, first hit first
+     */
+    #[test]
+    fn hits() {
+        let mut watchpoints = Watchpoints::new();
+        watchpoints.insert(0x0200, 2, WatchKind::Write);
+        watchpoints.insert(0x0300, 1, WatchKind::Access);
+
+        watchpoints.record_read(0x0201);
+        assert_eq!(watchpoints.take_hit(), None);
+
+        watchpoints.record_write(0x0201);
+        watchpoints.record_read(0x0300);
+        assert_eq!(watchpoints.take_hit(), Some(WatchHit { kind: WatchKind::Write, address: 0x0201 }));
+        assert_eq!(watchpoints.take_hit(), None);
+
+        // an access watchpoint keeps its kind
+        watchpoints.record_read(0x0300);
+        assert_eq!(watchpoints.take_hit(), Some(WatchHit { kind: WatchKind::Access, address: 0x0300 }));
+        watchpoints.record_write(0x0300);
+        assert_eq!(watchpoints.take_hit(), Some(WatchHit { kind: WatchKind::Access, address: 0x0300 }));
+
+        // only a watchpoint of the same kind is removed
+        watchpoints.remove(0x0300, 1, WatchKind::Read);
+        watchpoints.record_read(0x0300);
+        assert_eq!(watchpoints.take_hit(), Some(WatchHit { kind: WatchKind::Access, address: 0x0300 }));
+        watchpoints.remove(0x0300, 1, WatchKind::Access);
+        watchpoints.record_write(0x0300);
+        assert_eq!(watchpoints.take_hit(), None);
+        assert!(!watchpoints.is_empty());
+        watchpoints.remove(0x0200, 2, WatchKind::Write);
+        assert!(watchpoints.is_empty());
+    }
+}